@@ -0,0 +1,123 @@
+use std::time::{Duration, Instant};
+
+use futures::{stream::FuturesUnordered, Stream, StreamExt};
+use greptime_proto::v1::RowInsertRequests;
+use tokio::sync::Semaphore;
+
+use super::{greptime::InsertOutcome, retry::RetryPolicy, writer::GreptimeWriter};
+use crate::common::error::{Error, Result};
+
+/// How `DatabaseClient::bulk_insert_with` treats a failed request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BulkErrorMode {
+    // Fail with the first failed request, requests in flight are cancelled
+    #[default]
+    FailFast,
+    // Send every request and collect the failures in the outcome
+    CollectAll,
+}
+
+/// Options of `DatabaseClient::bulk_insert_with`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BulkInsertOptions {
+    // Requests in flight at the same time
+    pub concurrency: usize,
+    pub mode: BulkErrorMode,
+    // Attempts of every request, see `DatabaseClient::row_insert_with_retry`
+    pub retry: RetryPolicy,
+}
+
+impl BulkInsertOptions {
+    /// Fail fast without retrying
+    pub fn new(concurrency: usize) -> Self {
+        BulkInsertOptions {
+            concurrency,
+            mode: BulkErrorMode::FailFast,
+            retry: RetryPolicy::none(),
+        }
+    }
+}
+
+/// Requests sent by a bulk insert, indexed in the order they were given
+#[derive(Debug, Default)]
+pub struct BulkInsertOutcome {
+    // Rows written by each request, `None` for a failed one
+    pub rows: Vec<Option<u32>>,
+    // Failed requests by index, only collected under `BulkErrorMode::CollectAll`
+    pub errors: Vec<(usize, Error)>,
+}
+
+impl BulkInsertOutcome {
+    /// Rows written by all requests that succeeded
+    pub fn affected_rows(&self) -> u32 {
+        self.rows.iter().flatten().sum()
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Held by a bulk insert for as long as the request it came with is in
+/// flight, e.g. a share of a budget the caller admits requests by
+pub trait InFlight {
+    /// The request ended after `latency`, retries included
+    fn finished(self, latency: Duration, result: &Result<InsertOutcome>);
+}
+
+impl InFlight for () {
+    fn finished(self, _latency: Duration, _result: &Result<InsertOutcome>) {}
+}
+
+/// Send the requests `requests` yields through `row_insert_with_retry` of
+/// `writer`, with up to `options.concurrency` of them in flight. The next
+/// request is taken once one may be sent, its `InFlight` is held until it
+/// ends. An error yielded by `requests` fails the bulk insert in either mode.
+pub async fn bulk_insert_with<W, G>(
+    writer: &W,
+    requests: impl Stream<Item = Result<(RowInsertRequests, G)>>,
+    options: &BulkInsertOptions,
+) -> Result<BulkInsertOutcome>
+where
+    W: GreptimeWriter + ?Sized,
+    G: InFlight,
+{
+    let semaphore = Semaphore::new(options.concurrency.max(1));
+    let mut requests = std::pin::pin!(requests);
+    let mut in_flight = FuturesUnordered::new();
+    let mut outcome = BulkInsertOutcome::default();
+    let mut exhausted = false;
+    loop {
+        tokio::select! {
+            next = async {
+                let permit = semaphore
+                    .acquire()
+                    .await
+                    .expect("bulk insert semaphore is never closed");
+                (permit, requests.next().await)
+            }, if !exhausted => match next {
+                (_, None) => exhausted = true,
+                (_, Some(Err(e))) => return Err(e),
+                (permit, Some(Ok((request, guard)))) => {
+                    let index = outcome.rows.len();
+                    outcome.rows.push(None);
+                    in_flight.push(async move {
+                        let _permit = permit;
+                        let start = Instant::now();
+                        let result = writer.row_insert_with_retry(request, &options.retry).await;
+                        guard.finished(start.elapsed(), &result);
+                        (index, result)
+                    });
+                }
+            },
+            Some((index, result)) = in_flight.next(), if !in_flight.is_empty() => match result {
+                Ok(inserted) => outcome.rows[index] = Some(inserted.affected_rows),
+                Err(e) if options.mode == BulkErrorMode::FailFast => return Err(e),
+                Err(e) => outcome.errors.push((index, e)),
+            },
+            else => break,
+        }
+    }
+    outcome.errors.sort_by_key(|(index, _)| *index);
+    Ok(outcome)
+}
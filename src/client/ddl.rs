@@ -0,0 +1,126 @@
+use greptime_proto::v1::{ColumnDef, ColumnSchema, CreateTableExpr, SemanticType};
+
+/// Table to create from the `ColumnSchema`s of a use case. The timestamp
+/// column becomes the TIME INDEX and tag columns become the PRIMARY KEY.
+/// Creating a table that exists fails with `TableAlreadyExists`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTableSpec {
+    pub table_name: String,
+    pub columns: Vec<ColumnSchema>,
+    pub engine: String,
+}
+
+impl CreateTableSpec {
+    pub fn new(table_name: impl Into<String>, columns: Vec<ColumnSchema>) -> Self {
+        CreateTableSpec {
+            table_name: table_name.into(),
+            columns,
+            engine: "mito".to_string(),
+        }
+    }
+
+    /// The DDL expression creating the table in `schema_name`. Only fields
+    /// are nullable.
+    pub fn to_expr(&self, schema_name: &str) -> CreateTableExpr {
+        let column_defs = self
+            .columns
+            .iter()
+            .map(|col| ColumnDef {
+                name: col.column_name.clone(),
+                data_type: col.datatype,
+                is_nullable: col.semantic_type == SemanticType::Field as i32,
+                semantic_type: col.semantic_type,
+                ..Default::default()
+            })
+            .collect();
+        let time_index = self
+            .columns
+            .iter()
+            .find(|col| col.semantic_type == SemanticType::Timestamp as i32)
+            .map(|col| col.column_name.clone())
+            .unwrap_or_default();
+        let primary_keys = self
+            .columns
+            .iter()
+            .filter(|col| col.semantic_type == SemanticType::Tag as i32)
+            .map(|col| col.column_name.clone())
+            .collect();
+
+        CreateTableExpr {
+            schema_name: schema_name.to_string(),
+            table_name: self.table_name.clone(),
+            column_defs,
+            time_index,
+            primary_keys,
+            engine: self.engine.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use greptime_proto::v1::ColumnDataType;
+
+    use super::*;
+    use crate::{
+        generator::{data_generator::DataGenerator, log_data_generator::LogDataGenerator},
+        loader::{field, tag, timestamp},
+    };
+
+    #[test]
+    fn test_create_table_expr() {
+        let schema = LogDataGenerator::schema("web_logs");
+        let expr = CreateTableSpec::new("web_logs", schema.clone()).to_expr("public");
+
+        assert_eq!(expr.schema_name, "public");
+        assert_eq!(expr.table_name, "web_logs");
+        assert_eq!(expr.time_index, "timestamp");
+        assert_eq!(expr.primary_keys, vec!["log_id".to_string()]);
+        assert!(!expr.create_if_not_exists);
+        assert_eq!(expr.engine, "mito");
+        assert_eq!(expr.column_defs.len(), schema.len());
+        assert!(!expr.column_defs[0].is_nullable);
+        assert!(expr.column_defs[1].is_nullable);
+        assert_eq!(
+            expr.column_defs[6].data_type,
+            ColumnDataType::TimestampMicrosecond as i32
+        );
+    }
+
+    #[test]
+    fn test_create_table_expr_keys_in_order() {
+        let columns = vec![
+            field("value", ColumnDataType::Float64),
+            tag("region", ColumnDataType::String),
+            timestamp("ts", ColumnDataType::TimestampMillisecond),
+            tag("host", ColumnDataType::String),
+        ];
+        let expr = CreateTableSpec::new("cpu", columns).to_expr("metrics");
+
+        assert_eq!(expr.time_index, "ts");
+        assert_eq!(
+            expr.primary_keys,
+            vec!["region".to_string(), "host".to_string()]
+        );
+        let defs = expr
+            .column_defs
+            .iter()
+            .map(|def| (def.name.as_str(), def.semantic_type, def.is_nullable))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            defs,
+            [
+                ("value", SemanticType::Field as i32, true),
+                ("region", SemanticType::Tag as i32, false),
+                ("ts", SemanticType::Timestamp as i32, false),
+                ("host", SemanticType::Tag as i32, false),
+            ]
+        );
+
+        // Without a timestamp column there is no time index to declare
+        let expr = CreateTableSpec::new("t", vec![field("v", ColumnDataType::Int64)]).to_expr("p");
+        assert!(expr.time_index.is_empty());
+        assert!(expr.primary_keys.is_empty());
+    }
+}
@@ -0,0 +1,443 @@
+use std::{collections::BTreeMap, fmt, sync::Mutex, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::error::{Error, Result};
+
+// Upper bounds of the latency buckets in milliseconds, slower requests fall
+// into a last bucket without a bound
+const BUCKET_BOUNDS_MS: [u64; 13] = [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000, 10000];
+
+/// How a unary request ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+    Succeeded,
+    Failed,
+    TimedOut,
+    Cancelled,
+}
+
+impl RequestOutcome {
+    pub fn of(result: &Result<u32>) -> Self {
+        match result {
+            Ok(_) => RequestOutcome::Succeeded,
+            Err(Error::RequestTimeout { .. }) => RequestOutcome::TimedOut,
+            Err(Error::RequestCancelled { .. }) => RequestOutcome::Cancelled,
+            Err(_) => RequestOutcome::Failed,
+        }
+    }
+}
+
+/// A unary request sent by a `DatabaseClient`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestMetrics {
+    // Rows the request writes, none for DDL
+    pub rows: usize,
+    // Encoded size of the request before compression
+    pub bytes: usize,
+    pub latency: Duration,
+    pub outcome: RequestOutcome,
+}
+
+/// Called by a `DatabaseClient` once for every unary request it sent, when
+/// the request ended. Requests of insert streams aren't recorded.
+pub trait MetricsHook: Send + Sync {
+    fn record(&self, metrics: &RequestMetrics);
+}
+
+/// Latencies of requests counted into fixed buckets
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    // One count per bound of `BUCKET_BOUNDS_MS` and one for slower requests
+    pub counts: Vec<u64>,
+    pub requests: u64,
+    // Requests that didn't succeed, timed out and cancelled ones included
+    pub failed: u64,
+    pub rows: u64,
+    pub bytes: u64,
+    pub max: Duration,
+    // The same latencies at a fixed precision, for percentiles
+    pub distribution: LatencyDistribution,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, metrics: &RequestMetrics) {
+        if self.counts.is_empty() {
+            self.counts = vec![0; BUCKET_BOUNDS_MS.len() + 1];
+        }
+        let bucket = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|&bound| metrics.latency <= Duration::from_millis(bound))
+            .unwrap_or(BUCKET_BOUNDS_MS.len());
+        self.counts[bucket] += 1;
+        self.requests += 1;
+        if metrics.outcome != RequestOutcome::Succeeded {
+            self.failed += 1;
+        }
+        self.rows += metrics.rows as u64;
+        self.bytes += metrics.bytes as u64;
+        self.max = self.max.max(metrics.latency);
+        self.distribution.record(metrics.latency);
+    }
+
+    /// Latency `quantile` of the requests were within, the bound of the
+    /// bucket it falls into. Requests slower than the last bound report the
+    /// slowest latency.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.requests == 0 {
+            return None;
+        }
+        let rank = ((self.requests as f64 * quantile).ceil() as u64).clamp(1, self.requests);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = BUCKET_BOUNDS_MS
+                    .get(bucket)
+                    .map_or(self.max, |&ms| Duration::from_millis(ms));
+                return Some(bound.min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    // Bucket bounds with their counts, `None` for the unbounded bucket
+    fn buckets(&self) -> impl Iterator<Item = (Option<u64>, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(bucket, &count)| (BUCKET_BOUNDS_MS.get(bucket).copied(), count))
+    }
+}
+
+impl fmt::Display for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millis = |q| self.quantile(q).unwrap_or_default().as_millis();
+        write!(
+            f,
+            "{} requests, {} failed, p50 <= {}ms, p95 <= {}ms, p99 <= {}ms, max {}ms",
+            self.requests,
+            self.failed,
+            millis(0.5),
+            millis(0.95),
+            millis(0.99),
+            self.max.as_millis()
+        )?;
+        let last = BUCKET_BOUNDS_MS[BUCKET_BOUNDS_MS.len() - 1];
+        for (bound, count) in self.buckets().filter(|(_, count)| *count > 0) {
+            match bound {
+                Some(bound) => write!(f, "\n  <= {:>5}ms: {}", bound, count)?,
+                None => write!(f, "\n   > {:>5}ms: {}", last, count)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Latencies below this many microseconds are counted exactly, larger ones in
+// buckets a 64th of their size wide, like an HdrHistogram of two significant
+// digits
+const EXACT_MICROS: u64 = 128;
+const SUB_BUCKETS: u64 = EXACT_MICROS / 2;
+
+// Index of the bucket `micros` falls into
+fn bucket_of(micros: u64) -> u64 {
+    if micros < EXACT_MICROS {
+        return micros;
+    }
+    // Shifted right by it, `micros` is in SUB_BUCKETS..EXACT_MICROS
+    let shift = 63 - micros.leading_zeros() as u64 - 6;
+    EXACT_MICROS + (shift - 1) * SUB_BUCKETS + (micros >> shift) - SUB_BUCKETS
+}
+
+// Largest latency in microseconds of `bucket`
+fn bucket_high(bucket: u64) -> u64 {
+    if bucket < EXACT_MICROS {
+        return bucket;
+    }
+    let shift = (bucket - EXACT_MICROS) / SUB_BUCKETS + 1;
+    let sub_bucket = (bucket - EXACT_MICROS) % SUB_BUCKETS + SUB_BUCKETS;
+    ((sub_bucket + 1) << shift) - 1
+}
+
+/// Latencies counted within 1.6% of their value, whatever their size, so
+/// percentiles keep their precision where `LatencyHistogram`'s buckets are
+/// wide
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyDistribution {
+    // Counts by bucket index, see `bucket_of`
+    counts: BTreeMap<u64, u64>,
+    count: u64,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+}
+
+impl LatencyDistribution {
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        *self.counts.entry(bucket_of(micros)).or_default() += 1;
+        self.min = if self.count == 0 {
+            latency
+        } else {
+            self.min.min(latency)
+        };
+        self.max = self.max.max(latency);
+        self.total += latency;
+        self.count += 1;
+    }
+
+    /// Add the latencies of `other`, e.g. of another worker
+    pub fn merge(&mut self, other: &LatencyDistribution) {
+        if other.count == 0 {
+            return;
+        }
+        for (bucket, count) in &other.counts {
+            *self.counts.entry(*bucket).or_default() += count;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+        self.count += other.count;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0)
+            .then(|| Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64))
+    }
+
+    /// Latency `quantile` of the latencies were within, the largest latency
+    /// of the bucket it falls into, bounded by the slowest recorded
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((self.count as f64 * quantile).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        let bucket = self
+            .counts
+            .iter()
+            .find(|(_, count)| {
+                seen += **count;
+                seen >= rank
+            })
+            .map_or(0, |(bucket, _)| *bucket);
+        Some(Duration::from_micros(bucket_high(bucket)).clamp(self.min, self.max))
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        let millis =
+            |latency: Option<Duration>| latency.unwrap_or_default().as_nanos() as f64 / 1e6;
+        LatencySummary {
+            count: self.count,
+            min_ms: millis(Some(self.min)),
+            mean_ms: millis(self.mean()),
+            p50_ms: millis(self.quantile(0.5)),
+            p90_ms: millis(self.quantile(0.9)),
+            p95_ms: millis(self.quantile(0.95)),
+            p99_ms: millis(self.quantile(0.99)),
+            p999_ms: millis(self.quantile(0.999)),
+            max_ms: millis(Some(self.max)),
+        }
+    }
+}
+
+/// Percentile table of a `LatencyDistribution`, as written to JSON reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+    pub max_ms: f64,
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} | min {:.2}ms | mean {:.2}ms | p50 {:.2}ms | p90 {:.2}ms | p95 {:.2}ms | \
+             p99 {:.2}ms | p99.9 {:.2}ms | max {:.2}ms",
+            self.count,
+            self.min_ms,
+            self.mean_ms,
+            self.p50_ms,
+            self.p90_ms,
+            self.p95_ms,
+            self.p99_ms,
+            self.p999_ms,
+            self.max_ms
+        )
+    }
+}
+
+/// `MetricsHook` keeping a `LatencyHistogram` of every request
+#[derive(Debug, Default)]
+pub struct LatencyRecorder {
+    histogram: Mutex<LatencyHistogram>,
+}
+
+impl LatencyRecorder {
+    /// The requests recorded so far
+    pub fn histogram(&self) -> LatencyHistogram {
+        self.histogram.lock().unwrap().clone()
+    }
+}
+
+impl MetricsHook for LatencyRecorder {
+    fn record(&self, metrics: &RequestMetrics) {
+        self.histogram.lock().unwrap().record(metrics);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(millis: u64, outcome: RequestOutcome) -> RequestMetrics {
+        RequestMetrics {
+            rows: 10,
+            bytes: 100,
+            latency: Duration::from_millis(millis),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_histogram_quantiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for millis in [3, 4, 4, 15, 15, 15, 80, 80, 150, 12000] {
+            histogram.record(&request(millis, RequestOutcome::Succeeded));
+        }
+        histogram.record(&request(30000, RequestOutcome::TimedOut));
+
+        assert_eq!(histogram.requests, 11);
+        assert_eq!(histogram.failed, 1);
+        assert_eq!(histogram.rows, 110);
+        assert_eq!(histogram.bytes, 1100);
+        assert_eq!(histogram.quantile(0.0), Some(Duration::from_millis(5)));
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_millis(20)));
+        assert_eq!(histogram.quantile(0.8), Some(Duration::from_millis(200)));
+        // Beyond the last bound only the slowest request is known
+        assert_eq!(histogram.quantile(0.99), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_histogram_display() {
+        let recorder = LatencyRecorder::default();
+        for millis in [1, 2, 7, 7] {
+            recorder.record(&request(millis, RequestOutcome::Succeeded));
+        }
+        recorder.record(&request(11000, RequestOutcome::Failed));
+
+        assert_eq!(
+            recorder.histogram().to_string(),
+            "5 requests, 1 failed, p50 <= 10ms, p95 <= 11000ms, p99 <= 11000ms, max 11000ms\n  \
+             <=     1ms: 1\n  \
+             <=     2ms: 1\n  \
+             <=    10ms: 2\n   \
+             > 10000ms: 1"
+        );
+    }
+
+    #[test]
+    fn test_buckets() {
+        let mut last = None;
+        for micros in (0..300).chain([1_000, 65_535, 65_536, 1 << 40]) {
+            let bucket = bucket_of(micros);
+            assert!(bucket_high(bucket) >= micros, "{}", micros);
+            assert!(
+                (bucket_high(bucket) - micros) as f64 <= micros as f64 / 64.0,
+                "{}",
+                micros
+            );
+            if let Some(last) = last {
+                assert!(bucket >= last, "{}", micros);
+            }
+            last = Some(bucket);
+        }
+        assert_eq!(bucket_of(127), 127);
+        assert_eq!(bucket_of(128), 128);
+        assert_eq!(bucket_high(128), 129);
+        assert_eq!(bucket_of(256), 192);
+        assert_eq!(bucket_high(192), 259);
+    }
+
+    #[test]
+    fn test_distribution_percentiles() {
+        let mut distribution = LatencyDistribution::default();
+        assert_eq!(distribution.quantile(0.5), None);
+        // 1ms to 1000ms, one request each
+        for millis in 1..=1000 {
+            distribution.record(Duration::from_millis(millis));
+        }
+        let summary = distribution.summary();
+        assert_eq!(summary.count, 1000);
+        assert_eq!(summary.min_ms, 1.0);
+        assert_eq!(summary.max_ms, 1000.0);
+        assert_eq!(summary.mean_ms, 500.5);
+        for (actual, expected) in [
+            (summary.p50_ms, 500.0),
+            (summary.p90_ms, 900.0),
+            (summary.p95_ms, 950.0),
+            (summary.p99_ms, 990.0),
+            (summary.p999_ms, 999.0),
+        ] {
+            assert!(
+                actual >= expected && actual <= expected * 1.016,
+                "{} {}",
+                actual,
+                expected
+            );
+        }
+
+        // Below 128us latencies are exact
+        let mut exact = LatencyDistribution::default();
+        for micros in [5, 7, 7, 90, 120] {
+            exact.record(Duration::from_micros(micros));
+        }
+        assert_eq!(exact.quantile(0.5), Some(Duration::from_micros(7)));
+        assert_eq!(exact.quantile(0.9), Some(Duration::from_micros(120)));
+
+        let mut merged = LatencyDistribution::default();
+        merged.merge(&exact);
+        merged.merge(&distribution);
+        assert_eq!(merged.count(), 1005);
+        assert_eq!(merged.summary().min_ms, 0.005);
+        assert_eq!(merged.summary().max_ms, 1000.0);
+        assert_eq!(merged.quantile(0.001), Some(Duration::from_micros(7)));
+
+        let json = serde_json::to_string(&summary).unwrap();
+        assert_eq!(
+            serde_json::from_str::<LatencySummary>(&json).unwrap(),
+            summary
+        );
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(RequestOutcome::of(&Ok(3)), RequestOutcome::Succeeded);
+        let timeout = crate::common::error::RequestTimeoutSnafu {
+            request_id: crate::client::request_id::RequestIds::new().next(),
+            elapsed: Duration::from_secs(1),
+            rows: 3usize,
+        }
+        .fail();
+        assert_eq!(RequestOutcome::of(&timeout), RequestOutcome::TimedOut);
+        let cancelled = crate::common::error::RequestCancelledSnafu {}.fail();
+        assert_eq!(RequestOutcome::of(&cancelled), RequestOutcome::Cancelled);
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use greptime_proto::v1::{
+    ddl_request::Expr as DdlExpr,
+    greptime_database_server::{GreptimeDatabase, GreptimeDatabaseServer},
+    greptime_request::Request,
+    greptime_response::Response,
+    AffectedRows, DdlRequest, GreptimeRequest, GreptimeResponse,
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{
+    codec::CompressionEncoding, metadata::MetadataValue, transport::Server, Status, Streaming,
+};
+
+use crate::common::error::GREPTIME_ERROR_CODE;
+
+/// In-process GreptimeDatabase service recording every request with the
+/// dbname of its header, accepting compressed requests. Inserts and deletes
+/// affect as many rows as they carry. Inserts into a table named `fail` are
+/// rejected, inserts into a table named `busy` are rejected with GreptimeDB's
+/// `RegionBusy` error code, unary inserts into a table named `hang` are never
+/// answered, unary inserts into a table named `slow` take 20ms, creating a
+/// table named `exists` fails as it already exists and creating a database
+/// named `denied` isn't permitted.
+#[derive(Clone, Default)]
+pub struct MockDatabase {
+    pub requests: Arc<Mutex<Vec<(String, Request)>>>,
+    // Unary requests rejected as `Unavailable` before the next one is served
+    pub unavailable: Arc<AtomicUsize>,
+    // Client address of every request, telling connections apart
+    pub peers: Arc<Mutex<Vec<SocketAddr>>>,
+    // `traceparent` in the header of every unary request, rejected ones included
+    pub traceparents: Arc<Mutex<Vec<String>>>,
+    // Unary requests being served and the most served at the same time
+    pub in_flight: Arc<AtomicUsize>,
+    pub max_in_flight: Arc<AtomicUsize>,
+}
+
+impl MockDatabase {
+    /// Serve on a random local port and return the endpoint to connect to
+    pub async fn start(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            Server::builder()
+                .add_service(
+                    GreptimeDatabaseServer::new(self.clone())
+                        .accept_compressed(CompressionEncoding::Gzip)
+                        .accept_compressed(CompressionEncoding::Zstd),
+                )
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        endpoint
+    }
+
+    /// Table names of the recorded row inserts, in arrival order
+    pub fn inserted_tables(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(_, request)| match request {
+                Request::RowInserts(requests) => Some(requests),
+                _ => None,
+            })
+            .flat_map(|requests| requests.inserts.iter().map(|r| r.table_name.clone()))
+            .collect()
+    }
+
+    fn record(&self, request: GreptimeRequest) -> Result<u32, Status> {
+        let dbname = request.header.unwrap_or_default().dbname;
+        let request = request
+            .request
+            .ok_or_else(|| Status::invalid_argument("empty request"))?;
+        let affected_rows = match &request {
+            Request::RowInserts(requests) => {
+                if requests.inserts.iter().any(|r| r.table_name == "fail") {
+                    return Err(Status::internal("mock insert failure"));
+                }
+                if requests.inserts.iter().any(|r| r.table_name == "busy") {
+                    let mut status = Status::internal("mock region busy");
+                    status
+                        .metadata_mut()
+                        .insert(GREPTIME_ERROR_CODE, MetadataValue::from(4009u32));
+                    return Err(status);
+                }
+                requests
+                    .inserts
+                    .iter()
+                    .filter_map(|r| r.rows.as_ref())
+                    .map(|rows| rows.rows.len() as u32)
+                    .sum()
+            }
+            Request::RowDeletes(requests) => requests
+                .deletes
+                .iter()
+                .filter_map(|r| r.rows.as_ref())
+                .map(|rows| rows.rows.len() as u32)
+                .sum(),
+            Request::Ddl(DdlRequest {
+                expr: Some(DdlExpr::CreateTable(expr)),
+            }) if expr.table_name == "exists" => {
+                return Err(Status::already_exists("mock table already exists"));
+            }
+            Request::Ddl(DdlRequest {
+                expr: Some(DdlExpr::CreateDatabase(expr)),
+            }) if expr.schema_name == "denied" => {
+                return Err(Status::permission_denied("mock access denied"));
+            }
+            _ => 0,
+        };
+        self.requests.lock().unwrap().push((dbname, request));
+        Ok(affected_rows)
+    }
+}
+
+// Counts a request as being served until dropped, a request the client
+// gave up on included
+struct Serving<'a>(&'a AtomicUsize);
+
+impl<'a> Serving<'a> {
+    fn start(in_flight: &'a AtomicUsize, max_in_flight: &AtomicUsize) -> Self {
+        let serving = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        max_in_flight.fetch_max(serving, Ordering::SeqCst);
+        Serving(in_flight)
+    }
+}
+
+impl Drop for Serving<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn affected_rows(value: u32) -> tonic::Response<GreptimeResponse> {
+    tonic::Response::new(GreptimeResponse {
+        header: None,
+        response: Some(Response::AffectedRows(AffectedRows { value })),
+    })
+}
+
+#[tonic::async_trait]
+impl GreptimeDatabase for MockDatabase {
+    async fn handle(
+        &self,
+        request: tonic::Request<GreptimeRequest>,
+    ) -> Result<tonic::Response<GreptimeResponse>, Status> {
+        let traceparent = request
+            .get_ref()
+            .header
+            .as_ref()
+            .and_then(|header| header.tracing_context.get("traceparent"));
+        if let Some(traceparent) = traceparent {
+            self.traceparents.lock().unwrap().push(traceparent.clone());
+        }
+        let unavailable = self
+            .unavailable
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        if unavailable.is_ok() {
+            return Err(Status::unavailable("mock server unavailable"));
+        }
+        if let Some(peer) = request.remote_addr() {
+            self.peers.lock().unwrap().push(peer);
+        }
+        let request = request.into_inner();
+        let _serving = Serving::start(&self.in_flight, &self.max_in_flight);
+        if let Some(Request::RowInserts(requests)) = &request.request {
+            if requests.inserts.iter().any(|r| r.table_name == "hang") {
+                std::future::pending::<()>().await;
+            }
+            if requests.inserts.iter().any(|r| r.table_name == "slow") {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+        self.record(request).map(affected_rows)
+    }
+
+    async fn handle_requests(
+        &self,
+        request: tonic::Request<Streaming<GreptimeRequest>>,
+    ) -> Result<tonic::Response<GreptimeResponse>, Status> {
+        let mut stream = request.into_inner();
+        let mut total = 0;
+        while let Some(request) = stream.message().await? {
+            total += self.record(request)?;
+        }
+        Ok(affected_rows(total))
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use greptime_proto::v1::RowInsertRequests;
+use tonic::{async_trait, Status};
+
+use super::{ddl::CreateTableSpec, greptime::HealthStatus, writer::GreptimeWriter};
+use crate::common::error::{Error, Result, TableAlreadyExistsSnafu};
+
+/// `GreptimeWriter` recording the requests it accepts in memory with the
+/// dbname they were written to. Statuses scripted with `fail_next` fail the
+/// next calls in order, nothing is recorded for a failed call. Creating a
+/// table that was created before fails as it already exists.
+#[derive(Clone, Default)]
+pub struct MockWriter {
+    dbname: String,
+    pub inserts: Arc<Mutex<Vec<(String, RowInsertRequests)>>>,
+    pub tables: Arc<Mutex<Vec<(String, CreateTableSpec)>>>,
+    failures: Arc<Mutex<VecDeque<Status>>>,
+}
+
+impl MockWriter {
+    pub fn new(dbname: &str) -> Self {
+        MockWriter {
+            dbname: dbname.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Fail the call after the ones already scripted to fail with `status`
+    pub fn fail_next(&self, status: Status) {
+        self.failures.lock().unwrap().push_back(status);
+    }
+
+    /// Rows recorded for every table, across databases
+    pub fn table_rows(&self) -> BTreeMap<String, usize> {
+        let mut table_rows = BTreeMap::new();
+        for (_, requests) in self.inserts.lock().unwrap().iter() {
+            for insert in &requests.inserts {
+                *table_rows.entry(insert.table_name.clone()).or_default() +=
+                    insert.rows.as_ref().map_or(0, |rows| rows.rows.len());
+            }
+        }
+        table_rows
+    }
+
+    fn next_failure(&self) -> Result<()> {
+        match self.failures.lock().unwrap().pop_front() {
+            Some(status) => Err(Error::from(status)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl GreptimeWriter for MockWriter {
+    fn dbname(&self) -> &str {
+        &self.dbname
+    }
+
+    fn with_dbname(&self, dbname: &str) -> Arc<dyn GreptimeWriter> {
+        Arc::new(MockWriter {
+            dbname: dbname.to_string(),
+            ..self.clone()
+        })
+    }
+
+    async fn row_insert(&self, requests: RowInsertRequests) -> Result<u32> {
+        self.next_failure()?;
+        let affected_rows = requests
+            .inserts
+            .iter()
+            .filter_map(|insert| insert.rows.as_ref())
+            .map(|rows| rows.rows.len() as u32)
+            .sum();
+        self.inserts
+            .lock()
+            .unwrap()
+            .push((self.dbname.clone(), requests));
+        Ok(affected_rows)
+    }
+
+    async fn create_table(&self, spec: CreateTableSpec) -> Result<u32> {
+        self.next_failure()?;
+        let mut tables = self.tables.lock().unwrap();
+        if tables
+            .iter()
+            .any(|(dbname, table)| *dbname == self.dbname && table.table_name == spec.table_name)
+        {
+            return TableAlreadyExistsSnafu {
+                table: spec.table_name,
+            }
+            .fail();
+        }
+        tables.push((self.dbname.clone(), spec));
+        Ok(0)
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus> {
+        self.next_failure()?;
+        Ok(HealthStatus {
+            latency: Duration::ZERO,
+            attempts: 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use greptime_proto::v1::{RowInsertRequest, Rows};
+
+    use super::*;
+    use crate::client::retry::RetryPolicy;
+
+    fn request(table_name: &str, num_rows: usize) -> RowInsertRequests {
+        RowInsertRequests {
+            inserts: vec![RowInsertRequest {
+                table_name: table_name.to_string(),
+                rows: Some(Rows {
+                    schema: vec![],
+                    rows: vec![Default::default(); num_rows],
+                }),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scripted_failures() {
+        let writer = MockWriter::new("public");
+        writer.fail_next(Status::unavailable("down"));
+        writer.fail_next(Status::internal("broken"));
+
+        let err = writer.row_insert(request("a", 1)).await.unwrap_err();
+        assert!(err.is_retryable(), "{}", err);
+        let err = writer.health_check().await.unwrap_err();
+        assert!(!err.is_retryable(), "{}", err);
+
+        // The default retry loop of the trait numbers no requests
+        writer.fail_next(Status::unavailable("down"));
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            jitter: 0.0,
+        };
+        let outcome = writer
+            .row_insert_with_retry(request("a", 2), &policy)
+            .await
+            .unwrap();
+        assert_eq!((outcome.affected_rows, outcome.attempts), (2, 2));
+        assert_eq!(outcome.request_id, None);
+
+        writer
+            .with_dbname("other")
+            .row_insert(request("b", 3))
+            .await
+            .unwrap();
+        assert_eq!(
+            writer.table_rows(),
+            BTreeMap::from([("a".to_string(), 2), ("b".to_string(), 3)])
+        );
+        let dbnames = writer
+            .inserts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(dbname, _)| dbname.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(dbnames, ["public", "other"]);
+    }
+}
@@ -1 +1,13 @@
+pub mod bulk;
+pub mod ddl;
 pub mod greptime;
+pub mod metrics;
+#[cfg(test)]
+pub(crate) mod mock;
+#[cfg(test)]
+pub(crate) mod mock_writer;
+pub mod query;
+pub mod request_id;
+pub mod retry;
+pub mod split;
+pub mod writer;
@@ -0,0 +1,106 @@
+use std::{sync::Arc, time::Duration};
+
+use arrow::{
+    array::{
+        ArrayRef, BinaryArray, Date32Array, Float64Array, Int64Array, RecordBatch, StringArray,
+        TimestampMicrosecondArray, UInt64Array,
+    },
+    datatypes::{Field, Schema},
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use snafu::ResultExt;
+use sqlx::{
+    mysql::{MySql, MySqlRow},
+    Column, Decode, Row, Type, TypeInfo,
+};
+
+use crate::common::error::{ArrowFileSnafu, ExecuteSqlSnafu, Result, UnsupportedColumnTypeSnafu};
+
+/// Outcome of a SQL statement run by `DatabaseClient::sql`
+#[derive(Debug, Clone)]
+pub struct QueryOutput {
+    // Rows returned, or affected by a statement returning none
+    pub rows: u64,
+    // From sending the statement to receiving its last row, measured on the
+    // client. The MySQL protocol doesn't report the server's share.
+    pub elapsed: Duration,
+    // The rows returned, only collected by `DatabaseClient::sql_with_rows`
+    pub batch: Option<RecordBatch>,
+}
+
+/// Rows of a MySQL result set as a record batch, typed by the columns of the
+/// first row. Integers widen to 64 bits, timestamps are read as microseconds.
+pub(crate) fn rows_to_record_batch(rows: &[MySqlRow]) -> Result<RecordBatch> {
+    let Some(first) = rows.first() else {
+        return Ok(RecordBatch::new_empty(Arc::new(Schema::empty())));
+    };
+    let mut fields = Vec::with_capacity(first.len());
+    let mut columns = Vec::with_capacity(first.len());
+    for (i, column) in first.columns().iter().enumerate() {
+        let type_name = column.type_info().name();
+        let array: ArrayRef = match type_name {
+            "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" => {
+                Arc::new(Int64Array::from(values::<i64>(rows, i)?))
+            }
+            name if name.ends_with(" UNSIGNED") => {
+                Arc::new(UInt64Array::from(values::<u64>(rows, i)?))
+            }
+            "FLOAT" => {
+                let values = values::<f32>(rows, i)?
+                    .into_iter()
+                    .map(|v| v.map(f64::from))
+                    .collect::<Vec<_>>();
+                Arc::new(Float64Array::from(values))
+            }
+            "DOUBLE" => Arc::new(Float64Array::from(values::<f64>(rows, i)?)),
+            "TIMESTAMP" | "DATETIME" => {
+                let micros = values::<NaiveDateTime>(rows, i)?
+                    .into_iter()
+                    .map(|t| t.map(|t| t.and_utc().timestamp_micros()))
+                    .collect::<Vec<_>>();
+                Arc::new(TimestampMicrosecondArray::from(micros))
+            }
+            "DATE" => {
+                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+                let days = values::<NaiveDate>(rows, i)?
+                    .into_iter()
+                    .map(|d| d.map(|d| (d - epoch).num_days() as i32))
+                    .collect::<Vec<_>>();
+                Arc::new(Date32Array::from(days))
+            }
+            "CHAR" | "VARCHAR" | "TINYTEXT" | "TEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM" => {
+                Arc::new(
+                    values::<String>(rows, i)?
+                        .into_iter()
+                        .collect::<StringArray>(),
+                )
+            }
+            "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB" => Arc::new(
+                values::<Vec<u8>>(rows, i)?
+                    .into_iter()
+                    .collect::<BinaryArray>(),
+            ),
+            _ => {
+                return UnsupportedColumnTypeSnafu {
+                    column: column.name(),
+                    datatype: type_name,
+                }
+                .fail()
+            }
+        };
+        fields.push(Field::new(column.name(), array.data_type().clone(), true));
+        columns.push(array);
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).context(ArrowFileSnafu {})
+}
+
+// The `index`th column of every row
+fn values<'r, T>(rows: &'r [MySqlRow], index: usize) -> Result<Vec<Option<T>>>
+where
+    T: Decode<'r, MySql> + Type<MySql>,
+{
+    rows.iter()
+        .map(|row| row.try_get::<Option<T>, _>(index))
+        .collect::<std::result::Result<_, _>>()
+        .context(ExecuteSqlSnafu {})
+}
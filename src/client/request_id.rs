@@ -0,0 +1,85 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Identifies a unary request in the logs of the client and of GreptimeDB.
+/// It's sent as the trace id of a W3C `traceparent` in the tracing context of
+/// the request header, GreptimeDB logs the trace id of a request it serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId {
+    // Random per client, tells runs apart
+    run: u64,
+    // Counts the requests of the client from 1
+    sequence: u64,
+}
+
+impl RequestId {
+    /// Tracing context of a request header carrying the id
+    pub fn tracing_context(&self) -> HashMap<String, String> {
+        // The sequence doubles as the parent span id, which mustn't be zero
+        let traceparent = format!("00-{}-{:016x}-01", self, self.sequence);
+        HashMap::from([("traceparent".to_string(), traceparent)])
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}{:016x}", self.run, self.sequence)
+    }
+}
+
+/// Hands out the `RequestId`s of a client and its clones
+#[derive(Debug)]
+pub struct RequestIds {
+    run: u64,
+    sequence: AtomicU64,
+}
+
+impl RequestIds {
+    pub fn new() -> Self {
+        RequestIds {
+            run: rand::random::<u64>().max(1),
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    pub fn next(&self) -> RequestId {
+        RequestId {
+            run: self.run,
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed) + 1,
+        }
+    }
+}
+
+impl Default for RequestIds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_ids() {
+        let ids = RequestIds::new();
+        let first = ids.next();
+        let second = ids.next();
+        assert_ne!(first, second);
+        assert_eq!(first.run, second.run);
+        assert_eq!(second.sequence, 2);
+
+        let id = RequestId {
+            run: 0xab,
+            sequence: 3,
+        };
+        assert_eq!(id.to_string(), "00000000000000ab0000000000000003");
+        assert_eq!(
+            id.tracing_context()["traceparent"],
+            "00-00000000000000ab0000000000000003-0000000000000003-01"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::{future::Future, time::Duration};
+
+use greptime_proto::v1::RowInsertRequests;
+use rand::Rng;
+use snafu::ResultExt;
+
+use crate::common::error::{Result, RetriesExhaustedSnafu};
+
+/// How often and how patiently a request failing with a retryable error is
+/// sent again, see `Error::is_retryable`. The delay before the `n`th retry is
+/// `base_delay * 2^(n-1)`, at most `max_delay`, shortened by a random share
+/// of up to `jitter` so clients failing together don't retry together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    // Attempts in total, the first one included
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // Between 0 (fixed delays) and 1
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A single attempt
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before retrying a request that failed `attempt` times
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        delay.mul_f64(1.0 - jitter * rand::thread_rng().gen::<f64>())
+    }
+}
+
+/// Send `requests` through `send` until it succeeds, fails with an error
+/// that isn't retryable or `policy` runs out of attempts, and get the rows
+/// written and the attempts it took. Only a request that may be retried is
+/// copied. A request failing after a retry fails with `RetriesExhausted`.
+pub async fn retry_insert<F, Fut>(
+    requests: RowInsertRequests,
+    policy: &RetryPolicy,
+    mut send: F,
+) -> Result<(u32, u32)>
+where
+    F: FnMut(RowInsertRequests) -> Fut,
+    Fut: Future<Output = Result<u32>>,
+{
+    let max_attempts = policy.max_attempts.max(1);
+    let mut requests = Some(requests);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let request = if attempts < max_attempts {
+            requests.clone()
+        } else {
+            requests.take()
+        };
+        let error = match send(request.unwrap_or_default()).await {
+            Ok(affected_rows) => return Ok((affected_rows, attempts)),
+            Err(e) => e,
+        };
+        if attempts == max_attempts || !error.is_retryable() {
+            return match attempts {
+                1 => Err(error),
+                _ => Err(error).context(RetriesExhaustedSnafu { attempts }),
+            };
+        }
+        tokio::time::sleep(policy.delay(attempts)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter: 0.0,
+        };
+        let delays = (1..=6)
+            .map(|attempt| policy.delay(attempt).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_jitter_shortens_delay() {
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(2);
+            assert!(delay > Duration::from_millis(100), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(200), "{:?}", delay);
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use greptime_proto::v1::{
+    Row, RowDeleteRequest, RowDeleteRequests, RowInsertRequest, RowInsertRequests, Rows,
+};
+use prost::Message;
+use snafu::ensure;
+
+use crate::common::error::{Result, RowTooLargeSnafu};
+
+/// Split `requests` into requests of at most `max_rows` rows and
+/// `max_bytes` encoded bytes each, keeping the rows in order. The rows of a
+/// table are cut into pieces that each carry the table's schema, pieces of
+/// several tables share a request while both limits allow. Tables without
+/// rows are dropped. A row that doesn't fit a request on its own fails with
+/// `RowTooLarge`.
+pub fn split_requests(
+    requests: RowInsertRequests,
+    max_rows: usize,
+    max_bytes: usize,
+) -> Result<Vec<RowInsertRequests>> {
+    let max_rows = max_rows.max(1);
+    let mut split = Vec::new();
+    let mut current = RowInsertRequests::default();
+    let mut current_rows = 0;
+    let mut current_bytes = 0;
+    for mut insert in requests.inserts {
+        let Some(Rows { schema, rows }) = insert.rows.take() else {
+            continue;
+        };
+        let template = RowInsertRequest {
+            rows: Some(Rows {
+                schema,
+                rows: Vec::new(),
+            }),
+            ..insert
+        };
+        // The field key and length prefix of the piece in the request, and
+        // the growth of the length prefix of its rows
+        let piece_base = template.encoded_len() + 1 + 5 + 4;
+        let mut piece = Vec::new();
+        for (index, row) in rows.into_iter().enumerate() {
+            let row_len = row.encoded_len();
+            let row_bytes = 1 + prost::length_delimiter_len(row_len) + row_len;
+            ensure!(
+                piece_base + row_bytes <= max_bytes,
+                RowTooLargeSnafu {
+                    table: &template.table_name,
+                    row: index,
+                    bytes: piece_base + row_bytes,
+                    max: max_bytes,
+                }
+            );
+            let added = if piece.is_empty() {
+                piece_base + row_bytes
+            } else {
+                row_bytes
+            };
+            if current_rows == max_rows || current_bytes + added > max_bytes {
+                if !piece.is_empty() {
+                    current.inserts.push(with_rows(&template, piece));
+                    piece = Vec::new();
+                }
+                split.push(std::mem::take(&mut current));
+                current_rows = 0;
+                current_bytes = piece_base + row_bytes;
+            } else {
+                current_bytes += added;
+            }
+            piece.push(row);
+            current_rows += 1;
+        }
+        if !piece.is_empty() {
+            current.inserts.push(with_rows(&template, piece));
+        }
+    }
+    if !current.inserts.is_empty() {
+        split.push(current);
+    }
+    Ok(split)
+}
+
+/// `split_requests` for delete requests, whose rows are limited the same way
+pub fn split_deletes(
+    requests: RowDeleteRequests,
+    max_rows: usize,
+    max_bytes: usize,
+) -> Result<Vec<RowDeleteRequests>> {
+    // Both requests encode as a table name and its rows
+    let inserts = requests
+        .deletes
+        .into_iter()
+        .map(|delete| RowInsertRequest {
+            table_name: delete.table_name,
+            rows: delete.rows,
+        })
+        .collect();
+    let split = split_requests(RowInsertRequests { inserts }, max_rows, max_bytes)?;
+    Ok(split
+        .into_iter()
+        .map(|requests| RowDeleteRequests {
+            deletes: requests
+                .inserts
+                .into_iter()
+                .map(|insert| RowDeleteRequest {
+                    table_name: insert.table_name,
+                    rows: insert.rows,
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+fn with_rows(template: &RowInsertRequest, rows: Vec<Row>) -> RowInsertRequest {
+    let mut piece = template.clone();
+    if let Some(piece_rows) = piece.rows.as_mut() {
+        piece_rows.rows = rows;
+    }
+    piece
+}
+
+#[cfg(test)]
+mod tests {
+    use greptime_proto::v1::ColumnDataType;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        common::error::Error,
+        loader::{field, i64_value, string_value, tag},
+    };
+
+    fn table(name: &str, rows: Vec<(i64, String)>) -> RowInsertRequest {
+        RowInsertRequest {
+            table_name: name.to_string(),
+            rows: Some(Rows {
+                schema: vec![
+                    tag("id", ColumnDataType::Int64),
+                    field("message", ColumnDataType::String),
+                ],
+                rows: rows
+                    .into_iter()
+                    .map(|(id, message)| Row {
+                        values: vec![i64_value(id), string_value(message)],
+                    })
+                    .collect(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    // Rows of every table in order, with the table they were written to
+    fn table_rows(requests: &[RowInsertRequest]) -> Vec<(String, Row)> {
+        requests
+            .iter()
+            .flat_map(|insert| {
+                let rows = insert
+                    .rows
+                    .as_ref()
+                    .map(|r| r.rows.clone())
+                    .unwrap_or_default();
+                rows.into_iter().map(|row| (insert.table_name.clone(), row))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_by_rows() {
+        let rows = |n: i64| -> Vec<_> { (0..n).map(|i| (i, "m".to_string())).collect() };
+        let requests = RowInsertRequests {
+            inserts: vec![table("a", rows(5)), table("b", rows(2))],
+        };
+        let split = split_requests(requests, 3, usize::MAX).unwrap();
+        let tables = split
+            .iter()
+            .map(|request| {
+                request
+                    .inserts
+                    .iter()
+                    .map(|insert| {
+                        (
+                            insert.table_name.as_str(),
+                            insert.rows.as_ref().unwrap().rows.len(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // The rest of `a` shares a request with `b`, every piece has the schema
+        assert_eq!(
+            tables,
+            [vec![("a", 3)], vec![("a", 2), ("b", 1)], vec![("b", 1)]]
+        );
+        assert!(split
+            .iter()
+            .flat_map(|request| &request.inserts)
+            .all(|insert| insert.rows.as_ref().unwrap().schema.len() == 2));
+    }
+
+    #[test]
+    fn test_split_row_over_limit() {
+        let requests = RowInsertRequests {
+            inserts: vec![table(
+                "a",
+                vec![(1, "m".to_string()), (2, "m".repeat(1000))],
+            )],
+        };
+        let err = split_requests(requests, 10, 512).unwrap_err();
+        assert!(
+            matches!(&err, Error::RowTooLarge { table, row: 1, max: 512, .. } if table == "a"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_split_deletes() {
+        let insert = table("a", (0..5).map(|i| (i, "m".to_string())).collect());
+        let requests = RowDeleteRequests {
+            deletes: vec![RowDeleteRequest {
+                table_name: insert.table_name,
+                rows: insert.rows,
+            }],
+        };
+        let split = split_deletes(requests, 2, usize::MAX).unwrap();
+        let rows = split
+            .iter()
+            .map(|request| request.deletes[0].rows.as_ref().unwrap().rows.len())
+            .collect::<Vec<_>>();
+        assert_eq!(rows, [2, 2, 1]);
+        assert!(split
+            .iter()
+            .all(|request| request.deletes[0].table_name == "a"));
+    }
+
+    #[test]
+    fn test_split_keeps_rows_within_limits() {
+        let mut rng = StdRng::seed_from_u64(1141);
+        for _ in 0..200 {
+            let inserts = (0..rng.gen_range(0..4))
+                .map(|t| {
+                    let rows = (0..rng.gen_range(0..40))
+                        .map(|id| (id, "x".repeat(rng.gen_range(0..200))))
+                        .collect();
+                    table(&format!("table_{}", t), rows)
+                })
+                .collect::<Vec<_>>();
+            let requests = RowInsertRequests { inserts };
+            let max_rows = rng.gen_range(1..20);
+            let max_bytes = rng.gen_range(400..4000);
+
+            let split = split_requests(requests.clone(), max_rows, max_bytes).unwrap();
+            for request in &split {
+                let rows = table_rows(&request.inserts).len();
+                assert!(rows > 0 && rows <= max_rows, "{} rows", rows);
+                assert!(request.encoded_len() <= max_bytes);
+            }
+            let split_rows = split
+                .iter()
+                .flat_map(|request| table_rows(&request.inserts))
+                .collect::<Vec<_>>();
+            assert_eq!(split_rows, table_rows(&requests.inserts));
+        }
+    }
+}
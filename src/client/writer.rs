@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use greptime_proto::v1::RowInsertRequests;
+use tonic::async_trait;
+
+use super::{
+    ddl::CreateTableSpec,
+    greptime::{DatabaseClient, HealthStatus, InsertOutcome},
+    retry::{retry_insert, RetryPolicy},
+};
+use crate::common::error::Result;
+
+/// Writes a loader makes to GreptimeDB, implemented by `DatabaseClient` and
+/// in tests by a writer keeping requests in memory
+#[async_trait]
+pub trait GreptimeWriter: Send + Sync {
+    /// Database requests are written to
+    fn dbname(&self) -> &str;
+
+    /// This writer writing to the database `dbname` instead
+    fn with_dbname(&self, dbname: &str) -> Arc<dyn GreptimeWriter>;
+
+    /// Send `requests` and get the rows written
+    async fn row_insert(&self, requests: RowInsertRequests) -> Result<u32>;
+
+    /// `row_insert` retried under `policy` while it fails with a retryable
+    /// error
+    async fn row_insert_with_retry(
+        &self,
+        requests: RowInsertRequests,
+        policy: &RetryPolicy,
+    ) -> Result<InsertOutcome> {
+        let (affected_rows, attempts) =
+            retry_insert(requests, policy, |request| self.row_insert(request)).await?;
+        Ok(InsertOutcome {
+            affected_rows,
+            attempts,
+            request_id: None,
+        })
+    }
+
+    /// Create a table, failing with `TableAlreadyExists` when it exists
+    async fn create_table(&self, spec: CreateTableSpec) -> Result<u32>;
+
+    async fn health_check(&self) -> Result<HealthStatus>;
+}
+
+#[async_trait]
+impl GreptimeWriter for DatabaseClient {
+    fn dbname(&self) -> &str {
+        DatabaseClient::dbname(self)
+    }
+
+    fn with_dbname(&self, dbname: &str) -> Arc<dyn GreptimeWriter> {
+        let mut client = self.clone();
+        client.set_dbname(dbname);
+        Arc::new(client)
+    }
+
+    async fn row_insert(&self, requests: RowInsertRequests) -> Result<u32> {
+        DatabaseClient::row_insert(self, requests).await
+    }
+
+    // Numbers the attempts with one request id and stops retrying on shutdown
+    async fn row_insert_with_retry(
+        &self,
+        requests: RowInsertRequests,
+        policy: &RetryPolicy,
+    ) -> Result<InsertOutcome> {
+        DatabaseClient::row_insert_with_retry(self, requests, policy).await
+    }
+
+    async fn create_table(&self, spec: CreateTableSpec) -> Result<u32> {
+        DatabaseClient::create_table(self, spec).await
+    }
+
+    async fn health_check(&self) -> Result<HealthStatus> {
+        DatabaseClient::health_check(self).await
+    }
+}
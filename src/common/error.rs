@@ -1,16 +1,126 @@
+use std::{error::Error as StdError, fmt, io, time::Duration};
+
 use arrow::error::ArrowError;
-use snafu::{Location, Snafu};
+use snafu::{GenerateImplicitData, Location, Snafu};
 use time::error::Parse;
-use tonic::Status;
+use tonic::{Code, Status};
+
+use crate::client::request_id::RequestId;
+use crate::usql::error::UsqlError;
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub))]
 pub enum Error {
-    // #[snafu(display("Invalid client tls config, {}", msg))]
-    // InvalidTlsConfig { msg: String },
+    #[snafu(display("Invalid client tls config, {}", msg))]
+    InvalidTlsConfig {
+        msg: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("Invalid file path"))]
     InvalidFilePath {
         source: std::io::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to read file {}, source: {}", path, source))]
+    ReadFile {
+        path: String,
+        source: std::io::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to write file {}, source: {}", path, source))]
+    WriteFile {
+        path: String,
+        source: std::io::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid load manifest {}, source: {}", path, source))]
+    InvalidManifest {
+        path: String,
+        source: serde_json::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("{} has more than {} malformed lines, last one: {}", path, max, source))]
+    TooManyMalformedLines {
+        path: String,
+        max: usize,
+        // The `ParseJsonLine` error of the last malformed line
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to parse line {} of {}: {}", line_no, path, reason))]
+    ParseJsonLine {
+        path: String,
+        line_no: usize,
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to decompress {}, source: {}", path, source))]
+    Compression {
+        path: String,
+        source: std::io::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to write CSV file {}, source: {}", path, source))]
+    WriteCsv {
+        path: String,
+        source: ArrowError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Row {} of table {} encodes to {} bytes, over the request limit of {}",
+        row,
+        table,
+        bytes,
+        max
+    ))]
+    RowTooLarge {
+        table: String,
+        row: usize,
+        bytes: usize,
+        max: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid results log {}, source: {}", path, source))]
+    InvalidResultsLog {
+        path: String,
+        source: serde_json::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Table {} depends on unknown table {}", table, depends_on))]
+    UnknownTableDependency {
+        table: String,
+        depends_on: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Dependency cycle between tables {}", tables))]
+    TableDependencyCycle {
+        tables: String,
+        #[snafu(implicit)]
         location: Location,
     },
 
@@ -21,6 +131,28 @@ pub enum Error {
         location: Location,
     },
 
+    #[snafu(display("Invalid gRPC endpoint {}, source: {}", endpoint, source))]
+    InvalidEndpoint {
+        endpoint: String,
+        source: tonic::transport::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid client config, {}", msg))]
+    InvalidClientConfig {
+        msg: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid connection URL, {}", msg))]
+    InvalidConnectionUrl {
+        msg: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("Failed to read Parquet file"))]
     ReadParquetFile {
         source: parquet::errors::ParquetError,
@@ -35,6 +167,14 @@ pub enum Error {
         location: Location,
     },
 
+    #[snafu(display("Column {} not found in {}", column, table))]
+    UnknownColumn {
+        table: String,
+        column: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("No more data in Parquet file"))]
     EndOfParquetFile {
         #[snafu(implicit)]
@@ -57,27 +197,722 @@ pub enum Error {
     // #[snafu(display("Missing required field in protobuf, field: {}", field))]
     // MissingField { field: String, location: Location },
 
-    // Server error carried in Tonic Status's metadata.
-    #[snafu(display("{}", msg))]
-    Server { status: Status, msg: String },
+    // Server error carried in Tonic Status's metadata, with GreptimeDB's error
+    // code when it sent one and the id of the unary request it answered
+    #[snafu(display("{}{}", msg, request_suffix(request_id)))]
+    Server {
+        status: Status,
+        msg: String,
+        code: Option<ServerErrorCode>,
+        request_id: Option<RequestId>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Request {} with {} rows timed out after {:.3}s",
+        request_id,
+        rows,
+        elapsed.as_secs_f64()
+    ))]
+    RequestTimeout {
+        request_id: RequestId,
+        elapsed: Duration,
+        rows: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Request failed after {} attempts, source: {}", attempts, source))]
+    RetriesExhausted {
+        attempts: u32,
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("{}, is GreptimeDB running on {}?", source, endpoint))]
+    DatabaseUnavailable {
+        endpoint: String,
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Table {} already exists", table))]
+    TableAlreadyExists {
+        table: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Not permitted to create database {}, source: {}", dbname, source))]
+    CreateDatabaseDenied {
+        dbname: String,
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Request cancelled by shutdown"))]
+    RequestCancelled {
+        #[snafu(implicit)]
+        location: Location,
+    },
 
     #[snafu(display("Illegal Database response: {err_msg}"))]
-    IllegalDatabaseResponse { err_msg: String },
-    // #[snafu(display("Failed to send request with streaming: {}", err_msg))]
-    // ClientStreaming { err_msg: String, location: Location },
-    #[snafu(display("Failed to parse date, source: {}", source))]
+    IllegalDatabaseResponse {
+        err_msg: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+    #[snafu(display("Failed to send request with streaming: {}", err_msg))]
+    ClientStreaming {
+        err_msg: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Unsupported data type {} for column {}", datatype, column))]
+    UnsupportedColumnType {
+        column: String,
+        datatype: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Failed to convert column {} of table {}{}: {}",
+        column,
+        table,
+        row.map(|row| format!(" at row {}", row)).unwrap_or_default(),
+        reason
+    ))]
+    ConvertColumn {
+        table: String,
+        column: String,
+        // Unset when no value of the column converts, e.g. for its type
+        row: Option<usize>,
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to load table {}, source: {}", table, source))]
+    LoadTable {
+        table: String,
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to send HTTP request, source: {}", source))]
+    HttpRequest {
+        source: reqwest::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("{} write failed with status {}: {}", target, status, body))]
+    HttpWrite {
+        // What was written to, e.g. InfluxDB
+        target: String,
+        status: u16,
+        body: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to connect to {}, source: {}", endpoint, source))]
+    ConnectSql {
+        // URL without the password
+        endpoint: String,
+        source: sqlx::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to execute SQL, source: {}", source))]
+    ExecuteSql {
+        source: sqlx::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("A {} task panicked or was cancelled, source: {}", task, source))]
+    JoinTask {
+        // What the task was doing, e.g. `insert`
+        task: String,
+        source: tokio::task::JoinError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "usql not found at `{}`, install it from https://github.com/xo/usql or set its path with --usql-path or $USQL_PATH",
+        program
+    ))]
+    UsqlNotFound {
+        program: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to start usql, source: {}", source))]
+    SpawnUsql {
+        source: std::io::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to execute `{}`, source: {}", statement, source))]
+    SqlStatement {
+        // The start of the statement, see `usql::error::statement_excerpt`
+        statement: String,
+        source: sqlx::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Statement batch {} of {} statements failed, source: {}",
+        batch,
+        statements,
+        source
+    ))]
+    SqlBatch {
+        // Position of the batch in the load, from 1
+        batch: usize,
+        statements: usize,
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Health check timed out after {:.3}s", timeout.as_secs_f64()))]
+    PingTimeout {
+        timeout: Duration,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to execute `{}` via usql, {}: {}", statement, kind, stderr))]
+    UsqlExecution {
+        // The start of the statement, see `usql::error::statement_excerpt`
+        statement: String,
+        stderr: String,
+        kind: UsqlError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Executing `{}` via usql timed out after {:.3}s, usql was killed",
+        statement,
+        timeout.as_secs_f64()
+    ))]
+    UsqlTimeout {
+        statement: String,
+        timeout: Duration,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Unexpected usql output: {}", msg))]
+    UsqlOutput {
+        msg: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Unexpected usql output {:?}, expected {}", value, expected))]
+    UsqlOutputValue {
+        value: String,
+        expected: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to parse usql JSON output, source: {}", source))]
+    UsqlJsonOutput {
+        source: serde_json::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("{} doesn't support {}", target, operation))]
+    UnsupportedOperation {
+        target: String,
+        operation: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid config, {}", violations.join("; ")))]
+    ConfigValidation {
+        // Every problem found, in the order they were checked
+        violations: Vec<String>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Invalid time {:?}, expected ISO 8601 like 2024-01-01T00:00:00Z or \
+         2024-01-01T08:00:00+08:00, source: {}",
+        input,
+        source
+    ))]
     ParseDate {
+        input: String,
         source: Parse,
         #[snafu(implicit)]
         location: Location,
     },
+
+    #[snafu(display("Invalid query file {} at line {}, {}", path, line_no, reason))]
+    InvalidQueryFile {
+        path: String,
+        line_no: usize,
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid query template {}, {}", template, msg))]
+    InvalidQueryTemplate {
+        template: String,
+        msg: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Query timed out after {:.3}s, SQL: {}",
+        timeout.as_secs_f64(),
+        statement
+    ))]
+    QueryTimeout {
+        timeout: Duration,
+        // Start of the query's SQL, see `statement_excerpt`
+        statement: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Stopped the run with {} of {} queries failed, over the error budget of {:.2}%",
+        failed,
+        executed,
+        max_error_rate * 100.0
+    ))]
+    ErrorBudgetExceeded {
+        failed: usize,
+        executed: usize,
+        max_error_rate: f64,
+        #[snafu(implicit)]
+        location: Location,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+// Exit status of the CLI by what made it fail, see `Error::exit_code`. Any
+// other failure, e.g. a dataset file that can't be read, exits with 1.
+pub const EXIT_FAILURE: u8 = 1;
+/// Invalid options or config, nothing was tried
+pub const EXIT_CONFIG: u8 = 2;
+/// The database couldn't be reached or didn't answer in time
+pub const EXIT_CONNECTIVITY: u8 = 3;
+/// The database rejected a request
+pub const EXIT_SERVER: u8 = 4;
+/// The load finished but its verification found mismatches
+pub const EXIT_VERIFICATION: u8 = 5;
+/// The dataset has values or types the loader can't write
+pub const EXIT_UNSUPPORTED_DATA: u8 = 6;
+
+impl Error {
+    /// Whether the failed operation may succeed when sent again as is: it
+    /// timed out or the server was unavailable or overloaded for the moment.
+    /// Every variant is listed so a new one has to be classified.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::RequestTimeout { .. }
+            | Error::PingTimeout { .. }
+            | Error::QueryTimeout { .. } => true,
+            // The error code tells more than the gRPC code it was sent with
+            Error::Server { status, code, .. } => code
+                .and_then(ServerErrorCode::is_retryable)
+                .unwrap_or_else(|| {
+                    matches!(
+                        status.code(),
+                        Code::Unavailable
+                            | Code::DeadlineExceeded
+                            | Code::ResourceExhausted
+                            | Code::Aborted
+                    )
+                }),
+            Error::CreateChannel { source, .. } => has_transient_io_source(source),
+            // Any I/O error while connecting counts, e.g. the host of a
+            // container that was just started may not resolve yet
+            Error::ConnectSql { source, .. } => {
+                matches!(source, sqlx::Error::Io(_)) || is_sqlx_retryable(source)
+            }
+            Error::ExecuteSql { source, .. } | Error::SqlStatement { source, .. } => {
+                is_sqlx_retryable(source)
+            }
+            Error::HttpRequest { source, .. } => source.is_timeout() || source.is_connect(),
+            Error::HttpWrite { status, .. } => matches!(status, 429 | 502 | 503 | 504),
+            Error::UsqlExecution { kind, .. } => *kind == UsqlError::ConnectionRefused,
+            Error::LoadTable { source, .. }
+            | Error::SqlBatch { source, .. }
+            | Error::DatabaseUnavailable { source, .. } => source.is_retryable(),
+            // Sent as often as the retry policy allows already
+            Error::RetriesExhausted { .. } => false,
+            Error::InvalidTlsConfig { .. }
+            | Error::InvalidFilePath { .. }
+            | Error::ReadFile { .. }
+            | Error::WriteFile { .. }
+            | Error::InvalidManifest { .. }
+            | Error::TooManyMalformedLines { .. }
+            | Error::ParseJsonLine { .. }
+            | Error::Compression { .. }
+            | Error::WriteCsv { .. }
+            | Error::RowTooLarge { .. }
+            | Error::InvalidResultsLog { .. }
+            | Error::UnknownTableDependency { .. }
+            | Error::TableDependencyCycle { .. }
+            | Error::InvalidEndpoint { .. }
+            | Error::InvalidClientConfig { .. }
+            | Error::InvalidConnectionUrl { .. }
+            | Error::ReadParquetFile { .. }
+            | Error::WriteParquetFile { .. }
+            | Error::UnknownColumn { .. }
+            | Error::EndOfParquetFile { .. }
+            | Error::ArrowFile { .. }
+            | Error::TableAlreadyExists { .. }
+            | Error::CreateDatabaseDenied { .. }
+            | Error::RequestCancelled { .. }
+            | Error::IllegalDatabaseResponse { .. }
+            | Error::ClientStreaming { .. }
+            | Error::UnsupportedColumnType { .. }
+            | Error::ConvertColumn { .. }
+            | Error::UsqlNotFound { .. }
+            | Error::SpawnUsql { .. }
+            | Error::UsqlTimeout { .. }
+            | Error::UsqlOutput { .. }
+            | Error::UsqlOutputValue { .. }
+            | Error::UsqlJsonOutput { .. }
+            | Error::UnsupportedOperation { .. }
+            | Error::ConfigValidation { .. }
+            | Error::ParseDate { .. }
+            | Error::InvalidQueryFile { .. }
+            | Error::InvalidQueryTemplate { .. }
+            | Error::ErrorBudgetExceeded { .. }
+            | Error::JoinTask { .. } => false,
+        }
+    }
+
+    /// Exit status of the CLI failing with this error, one of the `EXIT_`
+    /// codes. Errors wrapping another take the code of the one they wrap.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::LoadTable { source, .. }
+            | Error::SqlBatch { source, .. }
+            | Error::RetriesExhausted { source, .. } => source.exit_code(),
+            Error::ExecuteSql { source, .. } | Error::SqlStatement { source, .. } => {
+                if is_sqlx_retryable(source) {
+                    EXIT_CONNECTIVITY
+                } else {
+                    EXIT_SERVER
+                }
+            }
+            Error::UsqlExecution { kind, .. } => {
+                if *kind == UsqlError::ConnectionRefused {
+                    EXIT_CONNECTIVITY
+                } else {
+                    EXIT_SERVER
+                }
+            }
+            Error::InvalidTlsConfig { .. }
+            | Error::InvalidFilePath { .. }
+            | Error::UnknownTableDependency { .. }
+            | Error::TableDependencyCycle { .. }
+            | Error::InvalidEndpoint { .. }
+            | Error::InvalidClientConfig { .. }
+            | Error::InvalidConnectionUrl { .. }
+            | Error::UnknownColumn { .. }
+            | Error::UsqlNotFound { .. }
+            | Error::UnsupportedOperation { .. }
+            | Error::ConfigValidation { .. }
+            | Error::ParseDate { .. }
+            | Error::InvalidQueryFile { .. }
+            | Error::InvalidQueryTemplate { .. } => EXIT_CONFIG,
+            Error::CreateChannel { .. }
+            | Error::RequestTimeout { .. }
+            | Error::DatabaseUnavailable { .. }
+            | Error::ClientStreaming { .. }
+            | Error::HttpRequest { .. }
+            | Error::ConnectSql { .. }
+            | Error::PingTimeout { .. }
+            | Error::QueryTimeout { .. }
+            | Error::UsqlTimeout { .. } => EXIT_CONNECTIVITY,
+            Error::Server { .. }
+            | Error::TableAlreadyExists { .. }
+            | Error::CreateDatabaseDenied { .. }
+            | Error::IllegalDatabaseResponse { .. }
+            | Error::HttpWrite { .. }
+            | Error::ErrorBudgetExceeded { .. } => EXIT_SERVER,
+            Error::TooManyMalformedLines { .. }
+            | Error::ParseJsonLine { .. }
+            | Error::RowTooLarge { .. }
+            | Error::UnsupportedColumnType { .. }
+            | Error::ConvertColumn { .. } => EXIT_UNSUPPORTED_DATA,
+            Error::ReadFile { .. }
+            | Error::WriteFile { .. }
+            | Error::InvalidManifest { .. }
+            | Error::Compression { .. }
+            | Error::WriteCsv { .. }
+            | Error::InvalidResultsLog { .. }
+            | Error::ReadParquetFile { .. }
+            | Error::WriteParquetFile { .. }
+            | Error::EndOfParquetFile { .. }
+            | Error::ArrowFile { .. }
+            | Error::RequestCancelled { .. }
+            | Error::SpawnUsql { .. }
+            | Error::UsqlOutput { .. }
+            | Error::UsqlOutputValue { .. }
+            | Error::UsqlJsonOutput { .. }
+            | Error::JoinTask { .. } => EXIT_FAILURE,
+        }
+    }
+
+    /// GreptimeDB's error code of a server error, looked up through the
+    /// errors wrapping it
+    pub fn server_code(&self) -> Option<ServerErrorCode> {
+        match self {
+            Error::Server { code, .. } => *code,
+            Error::LoadTable { source, .. } | Error::RetriesExhausted { source, .. } => {
+                source.server_code()
+            }
+            _ => None,
+        }
+    }
+
+    /// What made usql fail a statement, looked up through the errors
+    /// wrapping it
+    pub fn usql_error(&self) -> Option<&UsqlError> {
+        match self {
+            Error::UsqlExecution { kind, .. } => Some(kind),
+            Error::LoadTable { source, .. } | Error::SqlBatch { source, .. } => source.usql_error(),
+            _ => None,
+        }
+    }
+
+    /// Whether a column has a type the loader can't convert, the tables that
+    /// `skip_unsupported` skips
+    pub fn is_unsupported_type(&self) -> bool {
+        matches!(
+            self,
+            Error::UnsupportedColumnType { .. } | Error::ConvertColumn { row: None, .. }
+        )
+    }
+
+    /// The `ConvertColumn` error, looked up through the errors wrapping it
+    pub fn conversion_failure(&self) -> Option<&Error> {
+        match self {
+            Error::ConvertColumn { .. } => Some(self),
+            Error::LoadTable { source, .. } => source.conversion_failure(),
+            _ => None,
+        }
+    }
+
+    /// Where in the code the error was raised
+    pub fn location(&self) -> &Location {
+        match self {
+            Error::InvalidTlsConfig { location, .. }
+            | Error::InvalidFilePath { location, .. }
+            | Error::ReadFile { location, .. }
+            | Error::WriteFile { location, .. }
+            | Error::InvalidManifest { location, .. }
+            | Error::TooManyMalformedLines { location, .. }
+            | Error::ParseJsonLine { location, .. }
+            | Error::Compression { location, .. }
+            | Error::WriteCsv { location, .. }
+            | Error::RowTooLarge { location, .. }
+            | Error::InvalidResultsLog { location, .. }
+            | Error::UnknownTableDependency { location, .. }
+            | Error::TableDependencyCycle { location, .. }
+            | Error::CreateChannel { location, .. }
+            | Error::InvalidEndpoint { location, .. }
+            | Error::InvalidClientConfig { location, .. }
+            | Error::InvalidConnectionUrl { location, .. }
+            | Error::ReadParquetFile { location, .. }
+            | Error::WriteParquetFile { location, .. }
+            | Error::UnknownColumn { location, .. }
+            | Error::EndOfParquetFile { location, .. }
+            | Error::ArrowFile { location, .. }
+            | Error::Server { location, .. }
+            | Error::RequestTimeout { location, .. }
+            | Error::RetriesExhausted { location, .. }
+            | Error::DatabaseUnavailable { location, .. }
+            | Error::TableAlreadyExists { location, .. }
+            | Error::CreateDatabaseDenied { location, .. }
+            | Error::RequestCancelled { location, .. }
+            | Error::IllegalDatabaseResponse { location, .. }
+            | Error::ClientStreaming { location, .. }
+            | Error::UnsupportedColumnType { location, .. }
+            | Error::ConvertColumn { location, .. }
+            | Error::LoadTable { location, .. }
+            | Error::HttpRequest { location, .. }
+            | Error::HttpWrite { location, .. }
+            | Error::ConnectSql { location, .. }
+            | Error::ExecuteSql { location, .. }
+            | Error::UsqlNotFound { location, .. }
+            | Error::SpawnUsql { location, .. }
+            | Error::SqlStatement { location, .. }
+            | Error::SqlBatch { location, .. }
+            | Error::PingTimeout { location, .. }
+            | Error::UsqlExecution { location, .. }
+            | Error::UsqlTimeout { location, .. }
+            | Error::UsqlOutput { location, .. }
+            | Error::UsqlOutputValue { location, .. }
+            | Error::UsqlJsonOutput { location, .. }
+            | Error::UnsupportedOperation { location, .. }
+            | Error::ConfigValidation { location, .. }
+            | Error::ParseDate { location, .. }
+            | Error::InvalidQueryFile { location, .. }
+            | Error::InvalidQueryTemplate { location, .. }
+            | Error::QueryTimeout { location, .. }
+            | Error::ErrorBudgetExceeded { location, .. }
+            | Error::JoinTask { location, .. } => location,
+        }
+    }
+
+    /// Tag a server error with the id of the request it answered
+    pub fn with_request_id(self, id: RequestId) -> Self {
+        match self {
+            Error::Server {
+                status,
+                msg,
+                code,
+                location,
+                ..
+            } => Error::Server {
+                status,
+                msg,
+                code,
+                request_id: Some(id),
+                location,
+            },
+            e => e,
+        }
+    }
+}
+
+/// Codes of GreptimeDB's `StatusCode` the benchmark tells apart, any other
+/// code is kept as it was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServerErrorCode {
+    TableAlreadyExists,
+    TableNotFound,
+    TableColumnNotFound,
+    DatabaseNotFound,
+    RegionNotFound,
+    RegionReadonly,
+    RegionNotReady,
+    RegionBusy,
+    TableUnavailable,
+    StorageUnavailable,
+    DatabaseAlreadyExists,
+    RuntimeResourcesExhausted,
+    RateLimited,
+    AccessDenied,
+    PermissionDenied,
+    Other(u32),
+}
+
+impl ServerErrorCode {
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            4000 => ServerErrorCode::TableAlreadyExists,
+            4001 => ServerErrorCode::TableNotFound,
+            4002 => ServerErrorCode::TableColumnNotFound,
+            4004 => ServerErrorCode::DatabaseNotFound,
+            4005 => ServerErrorCode::RegionNotFound,
+            4007 => ServerErrorCode::RegionReadonly,
+            4008 => ServerErrorCode::RegionNotReady,
+            4009 => ServerErrorCode::RegionBusy,
+            4010 => ServerErrorCode::TableUnavailable,
+            4011 => ServerErrorCode::DatabaseAlreadyExists,
+            5000 => ServerErrorCode::StorageUnavailable,
+            6000 => ServerErrorCode::RuntimeResourcesExhausted,
+            6001 => ServerErrorCode::RateLimited,
+            7005 => ServerErrorCode::AccessDenied,
+            7006 => ServerErrorCode::PermissionDenied,
+            code => ServerErrorCode::Other(code),
+        }
+    }
+
+    pub fn code(&self) -> u32 {
+        match self {
+            ServerErrorCode::TableAlreadyExists => 4000,
+            ServerErrorCode::TableNotFound => 4001,
+            ServerErrorCode::TableColumnNotFound => 4002,
+            ServerErrorCode::DatabaseNotFound => 4004,
+            ServerErrorCode::RegionNotFound => 4005,
+            ServerErrorCode::RegionReadonly => 4007,
+            ServerErrorCode::RegionNotReady => 4008,
+            ServerErrorCode::RegionBusy => 4009,
+            ServerErrorCode::TableUnavailable => 4010,
+            ServerErrorCode::DatabaseAlreadyExists => 4011,
+            ServerErrorCode::StorageUnavailable => 5000,
+            ServerErrorCode::RuntimeResourcesExhausted => 6000,
+            ServerErrorCode::RateLimited => 6001,
+            ServerErrorCode::AccessDenied => 7005,
+            ServerErrorCode::PermissionDenied => 7006,
+            ServerErrorCode::Other(code) => *code,
+        }
+    }
+
+    /// Whether a request failing with this code may succeed when sent again,
+    /// none for codes that don't tell
+    pub fn is_retryable(self) -> Option<bool> {
+        match self {
+            ServerErrorCode::RegionNotReady
+            | ServerErrorCode::RegionBusy
+            | ServerErrorCode::TableUnavailable
+            | ServerErrorCode::StorageUnavailable
+            | ServerErrorCode::RuntimeResourcesExhausted
+            | ServerErrorCode::RateLimited => Some(true),
+            ServerErrorCode::Other(_) => None,
+            _ => Some(false),
+        }
+    }
+}
+
+impl fmt::Display for ServerErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerErrorCode::Other(code) => write!(f, "{}", code),
+            known => write!(f, "{:?}({})", known, known.code()),
+        }
+    }
+}
+
 pub const INNER_ERROR_MSG: &str = "INNER_ERROR_MSG";
 
+// Metadata keys of the error code, sent by older and by newer GreptimeDB
+pub const INNER_ERROR_CODE: &str = "INNER_ERROR_CODE";
+pub const GREPTIME_ERROR_CODE: &str = "x-greptime-err-code";
+
 impl From<Status> for Error {
+    // Located at the caller, the `?` that converted the status
+    #[track_caller]
     fn from(e: Status) -> Self {
         fn get_metadata_value(e: &Status, key: &str) -> Option<String> {
             e.metadata()
@@ -86,7 +921,523 @@ impl From<Status> for Error {
         }
 
         let msg = get_metadata_value(&e, INNER_ERROR_MSG).unwrap_or(e.to_string());
+        let code = get_metadata_value(&e, GREPTIME_ERROR_CODE)
+            .or_else(|| get_metadata_value(&e, INNER_ERROR_CODE))
+            .and_then(|code| code.trim().parse().ok())
+            .map(ServerErrorCode::from_code);
+
+        Self::Server {
+            status: e,
+            msg,
+            code,
+            request_id: None,
+            location: Location::generate(),
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    // Located at the caller, like the conversion of a tonic Status
+    #[track_caller]
+    fn from(e: sqlx::Error) -> Self {
+        Self::ExecuteSql {
+            source: e,
+            location: Location::generate(),
+        }
+    }
+}
+
+/// `Ok` without `violations`, otherwise a `ConfigValidation` error listing
+/// all of them
+pub fn check_violations(violations: Vec<String>) -> Result<()> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+    ConfigValidationSnafu { violations }.fail()
+}
+
+/// One line per layer of `error` and the errors it wraps, outermost first,
+/// each with where it was raised. Layers outside the crate, e.g. an I/O error,
+/// have no location.
+pub fn format_error_chain(error: &Error) -> String {
+    let mut lines = Vec::new();
+    let mut layer: Option<&(dyn StdError + 'static)> = Some(error);
+    while let Some(e) = layer {
+        let mut line = format!("{}: {}", lines.len(), layer_message(e));
+        if let Some(e) = e.downcast_ref::<Error>() {
+            line.push_str(&format!(", at {}", e.location()));
+        }
+        lines.push(line);
+        layer = e.source();
+    }
+    lines.join("\n")
+}
 
-        Self::Server { status: e, msg }
+// The message of `error` without the message of its source, which the
+// displays of most variants end with
+fn layer_message(error: &dyn StdError) -> String {
+    let message = error.to_string();
+    let Some(source) = error.source() else {
+        return message;
+    };
+    match message.strip_suffix(&source.to_string()) {
+        Some(rest) if !rest.trim().is_empty() => {
+            let rest = rest.trim_end_matches([',', ':', ' ']);
+            [", source", ", last one"]
+                .iter()
+                .find_map(|joint| rest.strip_suffix(joint))
+                .unwrap_or(rest)
+                .to_string()
+        }
+        _ => message,
+    }
+}
+
+/// Whether a sqlx operation that failed with `error` may succeed when run
+/// again: the connection was refused or dropped, or no connection of the pool
+/// was free in time
+pub fn is_sqlx_retryable(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(e) => is_transient_io(e),
+        sqlx::Error::PoolTimedOut => true,
+        _ => false,
+    }
+}
+
+// A connection the server refused, reset or didn't answer in time
+fn is_transient_io(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::TimedOut
+    )
+}
+
+// Whether `error` was caused by a transient I/O error somewhere down its
+// chain of sources, as transport errors wrap them
+fn has_transient_io_source(error: &(dyn StdError + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if error
+            .downcast_ref::<io::Error>()
+            .is_some_and(is_transient_io)
+        {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+fn request_suffix(request_id: &Option<RequestId>) -> String {
+    request_id
+        .map(|id| format!(" (request {})", id))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use snafu::{IntoError, ResultExt};
+    use tonic::metadata::MetadataValue;
+
+    use super::*;
+    use crate::client::request_id::RequestIds;
+
+    fn status_with(code: Code, metadata: &[(&'static str, &str)]) -> Status {
+        let mut status = Status::new(code, "status message");
+        for (key, value) in metadata {
+            status
+                .metadata_mut()
+                .insert(*key, MetadataValue::try_from(*value).unwrap());
+        }
+        status
+    }
+
+    #[test]
+    fn test_server_error_code() {
+        let err = Error::from(status_with(
+            Code::Internal,
+            &[
+                (GREPTIME_ERROR_CODE, "4009"),
+                (INNER_ERROR_MSG, "region busy"),
+            ],
+        ));
+        assert_eq!(err.server_code(), Some(ServerErrorCode::RegionBusy));
+        assert_eq!(err.to_string(), "region busy");
+        // Retried for the code, whatever the gRPC code
+        assert!(err.is_retryable());
+
+        let err = Error::from(status_with(
+            Code::Unavailable,
+            &[(INNER_ERROR_CODE, "4001")],
+        ));
+        assert_eq!(err.server_code(), Some(ServerErrorCode::TableNotFound));
+        assert!(!err.is_retryable());
+
+        let err = Error::from(status_with(
+            Code::Unavailable,
+            &[(INNER_ERROR_CODE, "1003")],
+        ));
+        assert_eq!(err.server_code(), Some(ServerErrorCode::Other(1003)));
+        assert!(err.is_retryable());
+        assert_eq!(ServerErrorCode::Other(1003).to_string(), "1003");
+        assert_eq!(ServerErrorCode::RegionBusy.to_string(), "RegionBusy(4009)");
+
+        let err = Error::from(status_with(
+            Code::Internal,
+            &[(GREPTIME_ERROR_CODE, "busy")],
+        ));
+        assert_eq!(err.server_code(), None);
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let refused = || io::Error::from(io::ErrorKind::ConnectionRefused);
+        let not_found = || io::Error::from(io::ErrorKind::NotFound);
+        let json = || serde_json::from_str::<u8>("x").unwrap_err();
+        let transport = || {
+            tonic::transport::Endpoint::from_shared("not a uri")
+                .err()
+                .unwrap()
+        };
+        let load_table = |source: Error| LoadTableSnafu { table: "cpu" }.into_error(source);
+
+        let cases = [
+            // Transient
+            (
+                RequestTimeoutSnafu {
+                    request_id: RequestIds::new().next(),
+                    elapsed: Duration::from_secs(1),
+                    rows: 1usize,
+                }
+                .build(),
+                true,
+            ),
+            (
+                PingTimeoutSnafu {
+                    timeout: Duration::from_secs(5),
+                }
+                .build(),
+                true,
+            ),
+            (Error::from(Status::unavailable("down")), true),
+            (Error::from(Status::deadline_exceeded("slow")), true),
+            (Error::from(Status::resource_exhausted("full")), true),
+            (
+                ExecuteSqlSnafu {}.into_error(sqlx::Error::Io(refused())),
+                true,
+            ),
+            (
+                ExecuteSqlSnafu {}.into_error(sqlx::Error::PoolTimedOut),
+                true,
+            ),
+            (
+                SqlStatementSnafu {
+                    statement: "SELECT 1",
+                }
+                .into_error(sqlx::Error::PoolTimedOut),
+                true,
+            ),
+            (Error::from(sqlx::Error::PoolTimedOut), true),
+            (
+                ConnectSqlSnafu {
+                    endpoint: "mysql://127.0.0.1:4002/public",
+                }
+                .into_error(sqlx::Error::PoolTimedOut),
+                true,
+            ),
+            (
+                ConnectSqlSnafu {
+                    endpoint: "mysql://db:4002/public",
+                }
+                .into_error(sqlx::Error::Io(not_found())),
+                true,
+            ),
+            (
+                HttpWriteSnafu {
+                    target: "InfluxDB",
+                    status: 503u16,
+                    body: "",
+                }
+                .build(),
+                true,
+            ),
+            (
+                UsqlExecutionSnafu {
+                    statement: "SELECT 1",
+                    stderr: "connection refused",
+                    kind: UsqlError::ConnectionRefused,
+                }
+                .build(),
+                true,
+            ),
+            (
+                load_table(ExecuteSqlSnafu {}.into_error(sqlx::Error::PoolTimedOut)),
+                true,
+            ),
+            (
+                DatabaseUnavailableSnafu {
+                    endpoint: "mysql://127.0.0.1:4002/public",
+                }
+                .into_error(ExecuteSqlSnafu {}.into_error(sqlx::Error::PoolTimedOut)),
+                true,
+            ),
+            // Logic errors and failures retrying doesn't change
+            (Error::from(Status::invalid_argument("bad")), false),
+            (Error::from(Status::unauthenticated("who")), false),
+            (
+                ExecuteSqlSnafu {}.into_error(sqlx::Error::Io(not_found())),
+                false,
+            ),
+            (
+                ExecuteSqlSnafu {}.into_error(sqlx::Error::RowNotFound),
+                false,
+            ),
+            (
+                ExecuteSqlSnafu {}.into_error(sqlx::Error::Protocol("bad packet".to_string())),
+                false,
+            ),
+            (Error::from(sqlx::Error::RowNotFound), false),
+            (
+                ConnectSqlSnafu {
+                    endpoint: "mysql://127.0.0.1:4002/public",
+                }
+                .into_error(sqlx::Error::Configuration("bad url".into())),
+                false,
+            ),
+            (
+                HttpWriteSnafu {
+                    target: "InfluxDB",
+                    status: 400u16,
+                    body: "unable to parse",
+                }
+                .build(),
+                false,
+            ),
+            (
+                UsqlExecutionSnafu {
+                    statement: "SELEC 1",
+                    stderr: "syntax error",
+                    kind: UsqlError::Syntax { near: None },
+                }
+                .build(),
+                false,
+            ),
+            (CreateChannelSnafu {}.into_error(transport()), false),
+            (
+                InvalidEndpointSnafu { endpoint: "x" }.into_error(transport()),
+                false,
+            ),
+            (
+                UnsupportedColumnTypeSnafu {
+                    column: "ratio",
+                    datatype: "Float64",
+                }
+                .build(),
+                false,
+            ),
+            (
+                ConvertColumnSnafu {
+                    table: "cpu",
+                    column: "ts",
+                    row: 0usize,
+                    reason: "null in the time index",
+                }
+                .build(),
+                false,
+            ),
+            (InvalidClientConfigSnafu { msg: "bad" }.build(), false),
+            (InvalidConnectionUrlSnafu { msg: "bad" }.build(), false),
+            (ReadFileSnafu { path: "x" }.into_error(refused()), false),
+            (InvalidManifestSnafu { path: "x" }.into_error(json()), false),
+            (UsqlJsonOutputSnafu {}.into_error(json()), false),
+            (
+                ArrowFileSnafu {}.into_error(ArrowError::ComputeError("x".to_string())),
+                false,
+            ),
+            (
+                ReadParquetFileSnafu {}
+                    .into_error(parquet::errors::ParquetError::General("x".to_string())),
+                false,
+            ),
+            (RequestCancelledSnafu {}.build(), false),
+            (
+                QueryTimeoutSnafu {
+                    timeout: Duration::from_secs(5),
+                    statement: "SELECT 1",
+                }
+                .build(),
+                true,
+            ),
+            (
+                ErrorBudgetExceededSnafu {
+                    failed: 3usize,
+                    executed: 100usize,
+                    max_error_rate: 0.01,
+                }
+                .build(),
+                false,
+            ),
+            (
+                RetriesExhaustedSnafu { attempts: 3u32 }
+                    .into_error(Error::from(Status::unavailable("down"))),
+                false,
+            ),
+            (
+                load_table(Error::from(Status::invalid_argument("bad"))),
+                false,
+            ),
+        ];
+        for (err, retryable) in cases {
+            assert_eq!(err.is_retryable(), retryable, "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_server_code_through_wrappers() {
+        let err = Error::from(status_with(
+            Code::Internal,
+            &[(GREPTIME_ERROR_CODE, "6001")],
+        ));
+        let err = Err::<(), _>(err)
+            .context(RetriesExhaustedSnafu { attempts: 3u32 })
+            .context(LoadTableSnafu { table: "cpu" })
+            .unwrap_err();
+        assert_eq!(err.server_code(), Some(ServerErrorCode::RateLimited));
+        assert!(ServerErrorCode::from_code(6001).is_retryable().unwrap());
+        assert_eq!(ServerErrorCode::from_code(4000).code(), 4000);
+    }
+
+    #[test]
+    fn test_usql_error_chain() {
+        let err = Err::<(), _>(
+            UsqlExecutionSnafu {
+                statement: "INSERT INTO `web_logs`(`ts`) VALUES ('x')",
+                stderr: "error: syntax error",
+                kind: UsqlError::Syntax { near: None },
+            }
+            .build(),
+        )
+        .context(SqlBatchSnafu {
+            batch: 2usize,
+            statements: 10usize,
+        })
+        .context(LoadTableSnafu { table: "web_logs" })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to load table web_logs, source: Statement batch 2 of 10 statements \
+             failed, source: Failed to execute `INSERT INTO `web_logs`(`ts`) VALUES ('x')` \
+             via usql, syntax error: error: syntax error"
+        );
+        assert_eq!(err.usql_error(), Some(&UsqlError::Syntax { near: None }));
+    }
+
+    #[test]
+    fn test_format_error_chain() {
+        let at = |file, line| Location::new(file, line, 9);
+        let err = Error::LoadTable {
+            table: "http_logs".to_string(),
+            source: Box::new(Error::RetriesExhausted {
+                attempts: 3,
+                source: Box::new(Error::Server {
+                    status: Status::unavailable("region busy"),
+                    msg: "region busy".to_string(),
+                    code: Some(ServerErrorCode::RegionBusy),
+                    request_id: None,
+                    location: at("src/client/greptime.rs", 40),
+                }),
+                location: at("src/client/retry.rs", 80),
+            }),
+            location: at("src/loader/greptime_data_loader.rs", 120),
+        };
+        assert_eq!(
+            format_error_chain(&err),
+            "0: Failed to load table http_logs, at src/loader/greptime_data_loader.rs:120:9\n\
+             1: Request failed after 3 attempts, at src/client/retry.rs:80:9\n\
+             2: region busy, at src/client/greptime.rs:40:9"
+        );
+
+        // The I/O error is the last layer, without a location of its own
+        let err = Error::LoadTable {
+            table: "http_logs".to_string(),
+            source: Box::new(Error::ReadFile {
+                path: "http_logs.csv".to_string(),
+                source: io::Error::new(io::ErrorKind::NotFound, "no such file"),
+                location: at("src/loader/data_loader.rs", 500),
+            }),
+            location: at("src/loader/greptime_data_loader.rs", 120),
+        };
+        assert_eq!(
+            format_error_chain(&err),
+            "0: Failed to load table http_logs, at src/loader/greptime_data_loader.rs:120:9\n\
+             1: Failed to read file http_logs.csv, at src/loader/data_loader.rs:500:9\n\
+             2: no such file"
+        );
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let cases = [
+            (
+                ConfigValidationSnafu {
+                    violations: vec!["chunk size must be at least 1".to_string()],
+                }
+                .build(),
+                EXIT_CONFIG,
+            ),
+            (
+                ConnectSqlSnafu {
+                    endpoint: "mysql://127.0.0.1:4002/public",
+                }
+                .into_error(sqlx::Error::PoolTimedOut),
+                EXIT_CONNECTIVITY,
+            ),
+            (
+                Error::from(Status::invalid_argument("bad request")),
+                EXIT_SERVER,
+            ),
+            (
+                QueryTimeoutSnafu {
+                    timeout: Duration::from_secs(5),
+                    statement: "SELECT 1",
+                }
+                .build(),
+                EXIT_CONNECTIVITY,
+            ),
+            (
+                ErrorBudgetExceededSnafu {
+                    failed: 3usize,
+                    executed: 100usize,
+                    max_error_rate: 0.01,
+                }
+                .build(),
+                EXIT_SERVER,
+            ),
+            (
+                ConvertColumnSnafu {
+                    table: "web_logs",
+                    column: "ts",
+                    row: Some(3usize),
+                    reason: "null in the time index",
+                }
+                .build(),
+                EXIT_UNSUPPORTED_DATA,
+            ),
+            (
+                ReadFileSnafu { path: "data.csv" }
+                    .into_error(io::Error::from(io::ErrorKind::NotFound)),
+                EXIT_FAILURE,
+            ),
+        ];
+        for (err, code) in cases {
+            assert_eq!(err.exit_code(), code, "{}", err);
+            // Wrapping doesn't change the category
+            let err = LoadTableSnafu { table: "web_logs" }.into_error(err);
+            assert_eq!(err.exit_code(), code, "{}", err);
+        }
     }
 }
@@ -1,2 +1,5 @@
+pub mod connection;
 pub mod connector;
 pub mod error;
+pub mod rows;
+pub mod sql_executor;
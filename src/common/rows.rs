@@ -0,0 +1,206 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+/// Value of a column of `QueryRows`, decoded from the text the server sent
+/// after the type of the column
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryValue {
+    Null,
+    // Integers and booleans, as 1 and 0
+    Int(i64),
+    // Floats and decimals
+    Float(f64),
+    String(String),
+    // Without time zone, in UTC for types with one
+    Timestamp(NaiveDateTime),
+}
+
+impl QueryValue {
+    /// Decode `text` of a column of `type_name`, as named by sqlx, e.g.
+    /// `BIGINT` or `INT8`. Text that doesn't parse as the type, like an
+    /// unsigned integer beyond `i64`, is kept as a string.
+    pub fn from_text(type_name: &str, text: Option<String>) -> Self {
+        let Some(text) = text else {
+            return QueryValue::Null;
+        };
+        let type_name = type_name.to_uppercase();
+        let decoded = if type_name.starts_with("BOOL") {
+            match text.as_str() {
+                "1" | "t" | "true" => Some(QueryValue::Int(1)),
+                "0" | "f" | "false" => Some(QueryValue::Int(0)),
+                _ => None,
+            }
+        } else if type_name.contains("INT") {
+            text.parse().ok().map(QueryValue::Int)
+        } else if ["FLOAT", "DOUBLE", "DECIMAL", "NUMERIC", "REAL"]
+            .iter()
+            .any(|t| type_name.starts_with(t))
+        {
+            text.parse().ok().map(QueryValue::Float)
+        } else if type_name.starts_with("TIMESTAMP") || type_name.starts_with("DATETIME") {
+            parse_timestamp(&text).map(QueryValue::Timestamp)
+        } else {
+            None
+        };
+        decoded.unwrap_or(QueryValue::String(text))
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, QueryValue::Null)
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            QueryValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Floats, and integers as floats, e.g. for an `avg` over integers
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            QueryValue::Int(v) => Some(*v as f64),
+            QueryValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Timestamps, and strings holding one. GreptimeDB sends the results of
+    /// some time functions as strings over the MySQL protocol.
+    pub fn as_timestamp(&self) -> Option<NaiveDateTime> {
+        match self {
+            QueryValue::Timestamp(v) => Some(*v),
+            QueryValue::String(v) => parse_timestamp(v),
+            _ => None,
+        }
+    }
+}
+
+/// Result set of `Connector::query`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryRows {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<QueryValue>>,
+}
+
+impl QueryRows {
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Index of the column `name`
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column == name)
+    }
+
+    /// The only value of a result of one row and one column, e.g. a count
+    pub fn scalar(&self) -> Option<&QueryValue> {
+        match self.rows.as_slice() {
+            [row] if row.len() == 1 => row.first(),
+            _ => None,
+        }
+    }
+}
+
+// Timestamps as MySQL, GreptimeDB and Postgres print them, with a `T` or a
+// space between date and time and optionally an offset. Dates are midnight.
+pub(crate) fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(timestamp) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(timestamp);
+        }
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f%#z", "%Y-%m-%dT%H:%M:%S%.f%#z"] {
+        if let Ok(timestamp) = DateTime::parse_from_str(text, format) {
+            return Some(timestamp.naive_utc());
+        }
+    }
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Some(timestamp.naive_utc());
+    }
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f").unwrap()
+    }
+
+    #[test]
+    fn test_from_text() {
+        let text = |s: &str| Some(s.to_string());
+        let cases = [
+            ("BIGINT", text("42"), QueryValue::Int(42)),
+            ("INT4", text("-7"), QueryValue::Int(-7)),
+            ("BIGINT UNSIGNED", text("3"), QueryValue::Int(3)),
+            (
+                "BIGINT UNSIGNED",
+                text("18446744073709551615"),
+                QueryValue::String("18446744073709551615".to_string()),
+            ),
+            ("BOOLEAN", text("1"), QueryValue::Int(1)),
+            ("BOOL", text("f"), QueryValue::Int(0)),
+            ("DOUBLE", text("1.5"), QueryValue::Float(1.5)),
+            ("FLOAT8", text("NaN"), QueryValue::Float(f64::NAN)),
+            ("DECIMAL", text("10.25"), QueryValue::Float(10.25)),
+            (
+                "VARCHAR",
+                text("it's"),
+                QueryValue::String("it's".to_string()),
+            ),
+            ("TEXT", None, QueryValue::Null),
+            (
+                "TIMESTAMP",
+                text("2024-01-01 00:00:01.500"),
+                QueryValue::Timestamp(timestamp("2024-01-01 00:00:01.5")),
+            ),
+            (
+                "TIMESTAMPTZ",
+                text("2024-01-01 02:00:00+02"),
+                QueryValue::Timestamp(timestamp("2024-01-01 00:00:00")),
+            ),
+            (
+                "DATETIME",
+                text("2024-01-01T00:00:00"),
+                QueryValue::Timestamp(timestamp("2024-01-01 00:00:00")),
+            ),
+        ];
+        for (type_name, text, expected) in cases {
+            let value = QueryValue::from_text(type_name, text);
+            match (&value, &expected) {
+                (QueryValue::Float(v), QueryValue::Float(e)) if e.is_nan() => {
+                    assert!(v.is_nan(), "{}", type_name)
+                }
+                _ => assert_eq!(value, expected, "{}", type_name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_accessors() {
+        assert_eq!(QueryValue::Int(3).as_f64(), Some(3.0));
+        assert_eq!(QueryValue::Float(3.5).as_i64(), None);
+        assert!(QueryValue::Null.is_null());
+        // Timestamps sent as strings over the MySQL protocol
+        let value = QueryValue::from_text("VARCHAR", Some("2024-01-01 00:00:00+0000".to_string()));
+        assert_eq!(value.as_timestamp(), Some(timestamp("2024-01-01 00:00:00")));
+        assert_eq!(
+            QueryValue::String("2024-03-01".to_string()).as_timestamp(),
+            Some(timestamp("2024-03-01 00:00:00"))
+        );
+        assert_eq!(QueryValue::String("web".to_string()).as_timestamp(), None);
+
+        let rows = QueryRows {
+            columns: vec!["count(*)".to_string()],
+            rows: vec![vec![QueryValue::Int(2)]],
+        };
+        assert_eq!(rows.column("count(*)"), Some(0));
+        assert_eq!(rows.scalar(), Some(&QueryValue::Int(2)));
+        assert_eq!(QueryRows::default().scalar(), None);
+    }
+}
@@ -0,0 +1,141 @@
+use std::future::Future;
+
+use log::warn;
+
+use crate::{
+    common::{
+        connection::ConnectionSpec,
+        connector::Connector,
+        error::{Error, Result},
+    },
+    usql::{
+        output::{OutputFormat, QueryResult},
+        usql::{BatchOutcome, Usql, UsqlExecutor},
+    },
+};
+
+/// Runs the statements of the SQL load path, usql or the sqlx `Connector`
+pub trait SqlExecutor: Send {
+    /// Name recorded as the protocol of load reports
+    fn name(&self) -> &'static str;
+
+    /// Run `sql` and get its output, like `Usql::execute`
+    fn execute(&mut self, sql: &str) -> impl Future<Output = Result<String>> + Send;
+
+    /// Run `sql` and parse the result set of its last statement returning
+    /// rows, like `Usql::query`
+    fn query(&mut self, sql: &str) -> impl Future<Output = Result<QueryResult>> + Send;
+
+    /// Run `statements` in order until one fails, like `Usql::execute_batch`
+    fn execute_batch(
+        &mut self,
+        statements: &[String],
+        transactional: bool,
+    ) -> impl Future<Output = Result<BatchOutcome>> + Send;
+}
+
+/// The executor `open_sql_executor` picked
+pub enum SqlBackend {
+    Usql(UsqlExecutor),
+    Connector(Connector),
+}
+
+impl SqlExecutor for SqlBackend {
+    fn name(&self) -> &'static str {
+        match self {
+            SqlBackend::Usql(usql) => usql.name(),
+            SqlBackend::Connector(connector) => connector.name(),
+        }
+    }
+
+    async fn execute(&mut self, sql: &str) -> Result<String> {
+        match self {
+            SqlBackend::Usql(usql) => SqlExecutor::execute(usql, sql).await,
+            SqlBackend::Connector(connector) => connector.execute(sql).await,
+        }
+    }
+
+    async fn query(&mut self, sql: &str) -> Result<QueryResult> {
+        match self {
+            SqlBackend::Usql(usql) => SqlExecutor::query(usql, sql).await,
+            SqlBackend::Connector(connector) => SqlExecutor::query(connector, sql).await,
+        }
+    }
+
+    async fn execute_batch(
+        &mut self,
+        statements: &[String],
+        transactional: bool,
+    ) -> Result<BatchOutcome> {
+        match self {
+            SqlBackend::Usql(usql) => {
+                SqlExecutor::execute_batch(usql, statements, transactional).await
+            }
+            SqlBackend::Connector(connector) => {
+                connector.execute_batch(statements, transactional).await
+            }
+        }
+    }
+}
+
+/// Whether SQL that usql failed to start for with `error` can run on the
+/// `Connector` instead. Only a missing usql is worked around, a usql that is
+/// installed but broken is reported.
+pub fn falls_back_to_connector(error: &Error) -> bool {
+    matches!(error, Error::UsqlNotFound { .. })
+}
+
+/// usql installed at `program` connected to `spec`, or the `Connector` when
+/// usql isn't installed, see `falls_back_to_connector`
+pub async fn open_sql_executor(program: &str, spec: &ConnectionSpec) -> Result<SqlBackend> {
+    match Usql::connect(program, spec, OutputFormat::Csv) {
+        Ok(usql) => Ok(SqlBackend::Usql(UsqlExecutor::new(usql))),
+        Err(e) if falls_back_to_connector(&e) => {
+            warn!("{}, running SQL through sqlx instead", e);
+            let connector = Connector::connect(spec).await?;
+            Ok(SqlBackend::Connector(connector))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::error::{UsqlNotFoundSnafu, UsqlOutputSnafu};
+
+    // A path nothing is installed at, standing in for a machine without usql
+    const MISSING_USQL: &str = "/nonexistent/bin/usql";
+
+    #[test]
+    fn test_falls_back_to_connector() {
+        let not_found = UsqlNotFoundSnafu {
+            program: MISSING_USQL,
+        }
+        .build();
+        assert!(falls_back_to_connector(&not_found));
+
+        let broken = UsqlOutputSnafu { msg: "garbled" }.build();
+        assert!(!falls_back_to_connector(&broken));
+    }
+
+    #[tokio::test]
+    async fn test_open_sql_executor() {
+        // Installed, usql is used without connecting anything up front
+        let executor = open_sql_executor("true", &ConnectionSpec::default())
+            .await
+            .unwrap();
+        assert_eq!(executor.name(), "usql");
+
+        // Missing, the Connector of the scheme is tried, here against a port
+        // nothing listens on
+        for url in [
+            "mysql://127.0.0.1:1/public",
+            "postgres://127.0.0.1:1/public",
+        ] {
+            let spec = ConnectionSpec::parse(url).unwrap();
+            let err = open_sql_executor(MISSING_USQL, &spec).await.err().unwrap();
+            assert!(matches!(err, Error::ExecuteSql { .. }), "{}: {}", url, err);
+        }
+    }
+}
@@ -2,10 +2,14 @@ use arrow::array::RecordBatch;
 use derive_new::new;
 use greptime_proto::v1::ColumnSchema;
 use rand::rngs::StdRng;
+use time::{format_description::well_known::Iso8601, OffsetDateTime};
 
-use crate::common::error::Result;
+use snafu::ResultExt;
+
+use crate::common::error::{check_violations, ParseDateSnafu, Result};
 
 /// Data use case, currently we only implement Log data.
+#[derive(Clone)]
 pub enum UseCase {
     Log,
     Others,
@@ -23,6 +27,49 @@ pub struct DataGeneratorConfig {
     pub use_case: UseCase,
 }
 
+impl DataGeneratorConfig {
+    /// Everything wrong with the time range and interval
+    pub fn violations(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        let mut parse = |name: &str, value: &str| match parse_time(value) {
+            Ok(time) => Some(time),
+            Err(e) => {
+                violations.push(format!("{}: {}", name, e));
+                None
+            }
+        };
+        let start = parse("time_start", &self.time_start);
+        let end = parse("time_end", &self.time_end);
+        if let (Some(start), Some(end)) = (start, end) {
+            if start >= end {
+                violations.push(format!(
+                    "time_start {} isn't before time_end {}",
+                    self.time_start, self.time_end
+                ));
+            }
+        }
+        if self.interval <= 0 {
+            violations.push(format!("interval must be positive, got {}", self.interval));
+        }
+        violations
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        check_violations(self.violations())
+    }
+}
+
+/// Parse an ISO 8601 time with its offset, `Z` (in either case) for UTC or
+/// e.g. `+08:00`
+pub fn parse_time(input: &str) -> Result<OffsetDateTime> {
+    let time = input.trim();
+    let time = match time.strip_suffix('z') {
+        Some(time) => format!("{}Z", time),
+        None => time.to_string(),
+    };
+    OffsetDateTime::parse(&time, &Iso8601::DEFAULT).context(ParseDateSnafu { input })
+}
+
 pub trait DataGenerator {
     /// Generate Data
     fn generate(&self) -> Result<Vec<RecordBatch>>;
@@ -33,4 +80,26 @@ pub trait DataGenerator {
     // Table name
     fn table_name(table_name: &str) -> &'static str;
     fn table_names() -> Vec<&'static str>;
+    // (table, depends_on) pairs, a table is loaded after the tables it depends on
+    fn table_dependencies() -> Vec<(&'static str, &'static str)>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        let utc = parse_time("2024-01-01T00:00:00Z").unwrap();
+        assert_eq!(utc.unix_timestamp(), 1_704_067_200);
+        assert_eq!(parse_time("2024-01-01T00:00:00z").unwrap(), utc);
+        assert_eq!(parse_time(" 2024-01-01T08:00:00+08:00 ").unwrap(), utc);
+        assert_eq!(parse_time("2023-12-31T19:00:00-05:00").unwrap(), utc);
+
+        for input in ["2021-01-01 00:00:00", "2021-01-01T00:00:00", "yesterday"] {
+            let err = parse_time(input).unwrap_err().to_string();
+            assert!(err.contains(&format!("\"{}\"", input)), "{}", err);
+            assert!(err.contains("2024-01-01T00:00:00Z"), "{}", err);
+        }
+    }
 }
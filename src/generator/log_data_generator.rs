@@ -1,5 +1,5 @@
 use crate::{
-    common::error::{ArrowFileSnafu, ParseDateSnafu, Result, WriteParquetFileSnafu},
+    common::error::{check_violations, ArrowFileSnafu, Result, WriteParquetFileSnafu},
     loader::{field, tag, timestamp},
 };
 use arrow::{
@@ -20,9 +20,9 @@ use rand::{distributions::Distribution, rngs::StdRng, Rng};
 use rand::{distributions::WeightedIndex, seq::SliceRandom};
 use snafu::ResultExt;
 use std::{fs::File, iter::zip, sync::Arc};
-use time::{format_description::well_known::Iso8601, OffsetDateTime};
+use time::OffsetDateTime;
 
-use super::data_generator::{DataGenerator, DataGeneratorConfig};
+use super::data_generator::{parse_time, DataGenerator, DataGeneratorConfig};
 
 /// LogData with schema for different tables
 pub struct LogData {
@@ -117,6 +117,25 @@ pub struct LogConfig {
     num_of_pages: usize,
 }
 
+impl LogConfig {
+    pub fn new(num_of_users: usize, num_of_pages: usize) -> Self {
+        LogConfig {
+            num_of_users,
+            num_of_pages,
+        }
+    }
+
+    /// Users get the ids `0..num_of_users`
+    pub fn num_of_users(&self) -> usize {
+        self.num_of_users
+    }
+
+    /// Pages get the ids `0..num_of_pages`
+    pub fn num_of_pages(&self) -> usize {
+        self.num_of_pages
+    }
+}
+
 /// LogDataGenerator, the schema of Log Data can be referred to
 /// Web Server Access Logs https://www.kaggle.com/datasets/eliasdabbas/web-server-access-logs/
 pub struct LogDataGenerator {
@@ -247,9 +266,44 @@ impl DataGenerator for LogDataGenerator {
             "error_logs",
         ]
     }
+
+    fn table_dependencies() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("web_logs", "users"),
+            ("web_logs", "pages"),
+            ("web_logs", "devices"),
+            ("requests", "web_logs"),
+            ("responses", "web_logs"),
+            ("error_logs", "web_logs"),
+        ]
+    }
 }
 
 impl LogDataGenerator {
+    /// Generator of `generator_config` and `log_config`, when both are valid
+    pub fn new(generator_config: DataGeneratorConfig, log_config: LogConfig) -> Result<Self> {
+        let generator = LogDataGenerator {
+            generator_config,
+            log_config,
+            log_data: LogData::new(),
+        };
+        generator.validate()?;
+        Ok(generator)
+    }
+
+    /// Check the whole config, reporting every problem at once rather than
+    /// the first one generating runs into
+    pub fn validate(&self) -> Result<()> {
+        let mut violations = self.generator_config.violations();
+        if self.log_config.num_of_users == 0 {
+            violations.push("num_of_users must be at least 1".to_string());
+        }
+        if self.log_config.num_of_pages == 0 {
+            violations.push("num_of_pages must be at least 1".to_string());
+        }
+        check_violations(violations)
+    }
+
     // Generate data for `users` table
     // Return `RecordBatch`
     fn generate_users_data(&self) -> Result<RecordBatch> {
@@ -265,9 +319,7 @@ impl LogDataGenerator {
             usernames.push(Username().fake_with_rng(seed));
             emails.push(FreeEmail().fake_with_rng(seed));
 
-            let date_before =
-                OffsetDateTime::parse(&self.generator_config.time_start, &Iso8601::DEFAULT)
-                    .context(ParseDateSnafu {})?;
+            let date_before = parse_time(&self.generator_config.time_start)?;
             let date = DateTimeBefore(date_before).fake_with_rng::<OffsetDateTime, _>(seed);
             signup_dates.push(date.unix_timestamp() * 1_000_000 + date.microsecond() as i64);
         }
@@ -312,8 +364,7 @@ impl LogDataGenerator {
             let title: String = Sentence(3..6).fake_with_rng(seed); // 生成3到6个单词的句子
             page_titles.push(title);
 
-            let date = OffsetDateTime::parse(&self.generator_config.time_start, &Iso8601::DEFAULT)
-                .context(ParseDateSnafu {})?;
+            let date = parse_time(&self.generator_config.time_start)?;
             created_date.push(date.unix_timestamp() * 1_000_000 + date.microsecond() as i64);
         }
 
@@ -397,14 +448,8 @@ impl LogDataGenerator {
 
         // Get timestamp
         let start_time =
-            OffsetDateTime::parse(&self.generator_config.time_start, &Iso8601::DEFAULT)
-                .context(ParseDateSnafu {})?
-                .unix_timestamp()
-                * 1_000_000;
-        let end_time = OffsetDateTime::parse(&self.generator_config.time_end, &Iso8601::DEFAULT)
-            .context(ParseDateSnafu {})?
-            .unix_timestamp()
-            * 1_000_000;
+            parse_time(&self.generator_config.time_start)?.unix_timestamp() * 1_000_000;
+        let end_time = parse_time(&self.generator_config.time_end)?.unix_timestamp() * 1_000_000;
 
         let mut current_time = start_time;
 
@@ -668,6 +713,7 @@ mod tests {
     use crate::loader::data_loader::read_parquet_file;
 
     use super::*;
+    use crate::common::error::Error;
     use rand::SeedableRng;
     use std::fs::{self};
     use std::path::PathBuf;
@@ -695,6 +741,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_validate() {
+        assert!(create_log_data_generator().validate().is_ok());
+
+        let generator_config = DataGeneratorConfig {
+            seed: StdRng::seed_from_u64(42),
+            time_start: "yesterday".to_string(),
+            time_end: "2023-01-02T00:00:00Z".to_string(),
+            interval: 0,
+            use_case: UseCase::Log,
+        };
+        let err = LogDataGenerator::new(generator_config, LogConfig::new(0, 5))
+            .err()
+            .unwrap();
+        let Error::ConfigValidation { violations, .. } = &err else {
+            panic!("{}", err);
+        };
+        assert_eq!(violations.len(), 3, "{:?}", violations);
+        assert!(
+            violations[0].starts_with("time_start: Invalid time \"yesterday\", expected ISO 8601"),
+            "{}",
+            violations[0]
+        );
+        assert_eq!(violations[1], "interval must be positive, got 0");
+        assert_eq!(violations[2], "num_of_users must be at least 1");
+
+        let mut generator = create_log_data_generator();
+        generator.generator_config.time_end = generator.generator_config.time_start.clone();
+        assert_eq!(
+            generator.validate().unwrap_err().to_string(),
+            "Invalid config, time_start 2023-01-01T00:00:00Z isn't before time_end \
+             2023-01-01T00:00:00Z"
+        );
+    }
+
     #[test]
     fn test_generate() {
         let generator = create_log_data_generator();
@@ -2,4 +2,6 @@ pub mod client;
 pub mod common;
 pub mod generator;
 pub mod loader;
+pub mod query;
+pub mod sql;
 pub mod usql;
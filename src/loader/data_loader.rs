@@ -1,89 +1,1330 @@
-use std::{fs::File, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::File,
+    future::Future,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use arrow::array::RecordBatch;
-use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
-use snafu::{location, ResultExt};
+use arrow::{
+    array::RecordBatch,
+    csv::{reader::Format, ReaderBuilder},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+    error::ArrowError,
+    record_batch::RecordBatchReader,
+};
+use flate2::read::GzDecoder;
+use greptime_proto::v1::{ColumnDataType, ColumnSchema, SemanticType};
+use log::warn;
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ProjectionMask};
+use snafu::{IntoError, OptionExt, ResultExt};
 
+use crate::client::{
+    metrics::{LatencyHistogram, RequestMetrics, RequestOutcome},
+    retry::RetryPolicy,
+};
+use crate::common::connection::ConnectionSpec;
 use crate::common::error::{
-    ArrowFileSnafu, EndOfParquetFileSnafu, InvalidFilePathSnafu, ReadParquetFileSnafu, Result,
+    check_violations, ArrowFileSnafu, CompressionSnafu, EndOfParquetFileSnafu, Error,
+    InvalidFilePathSnafu, LoadTableSnafu, ReadFileSnafu, ReadParquetFileSnafu, Result,
+    SqlBatchSnafu, UnknownColumnSnafu, UnsupportedColumnTypeSnafu,
+};
+use crate::common::sql_executor::SqlExecutor;
+use crate::generator::{data_generator::UseCase, log_data_generator::LogDataGenerator};
+use crate::loader::dialect::SqlDialect;
+use crate::loader::load_order::{dimension_tables, table_load_levels};
+use crate::loader::report::{LoadReport, PreLoadStep, TableLoadReport};
+use crate::loader::time_range::TimeRange;
+use crate::loader::verify::verify_acknowledged_rows;
+use crate::loader::{field, tag, timestamp};
+use crate::sql::quote::quote_ident;
+use crate::usql::{
+    script::ScriptReport,
+    usql::{default_program, Usql},
 };
-use crate::generator::data_generator::UseCase;
-use crate::usql::usql::Usql;
 
+#[derive(Clone)]
 pub struct DataLoaderConfig {
     pub use_case: UseCase,
+    // Skip tables with unsupported column types instead of aborting the load
+    pub skip_unsupported: bool,
+    // Create target tables with explicit DDL before the first insert
+    pub create_tables: bool,
+    // Drop or truncate target tables before loading, destructive
+    pub pre_load_step: PreLoadStep,
+    // Rows sent per write request
+    pub chunk_size: usize,
+    // Upper bound on the text of a single generated INSERT statement
+    pub max_statement_bytes: usize,
+    // Flavor of the SQL statements generated for usql and the MySQL loader
+    pub sql_dialect: SqlDialect,
+    // Path of usql, see `usql::default_program`
+    pub usql_program: String,
+    // SQL endpoint usql connects to
+    pub sql_connection: ConnectionSpec,
+    // INSERT statements usql runs as one batch, and whether a batch runs in
+    // a transaction instead of autocommitting every statement
+    pub usql_batch_size: usize,
+    pub usql_transactions: bool,
+    // Write values of the mysql target into the text of its INSERT
+    // statements instead of binding them
+    pub inline_values: bool,
+    // INSERT statements the mysql and postgres targets commit as one
+    // transaction, 1 autocommits every statement
+    pub transaction_statements: usize,
+    // Upper bound on the encoded size of a single gRPC insert request (greptime)
+    pub max_request_bytes: usize,
+    // Write requests in flight at the same time
+    pub workers: usize,
+    // Send the chunks of a table through one client-streaming RPC (greptime)
+    pub streaming: bool,
+    // Adjust the insert requests in flight to their latency instead of
+    // sending one at a time per worker (greptime)
+    pub adaptive: bool,
+    // p95 request latency adaptive pacing keeps below
+    pub target_latency: Duration,
+    // Most insert requests adaptive pacing allows in flight across workers
+    pub max_concurrency: usize,
+    // Sending unary insert requests again after timeouts or while the server
+    // is unavailable (greptime)
+    pub retry: RetryPolicy,
+    // Compare row counts and time ranges of loaded tables against the dataset
+    pub verify: bool,
+    // Load tables again even if the load manifest lists them as complete
+    pub force: bool,
+    // Added around the base table names in the target database, parquet
+    // files are still looked up by the base name
+    pub table_prefix: String,
+    pub table_suffix: String,
+    // Database of each base table name, unmapped tables go to the client's dbname
+    pub table_databases: HashMap<String, String>,
+    // Columns loaded from the parquet file of each base table name, in this
+    // order and renamed, unmapped tables load every column
+    pub table_columns: HashMap<String, Vec<ColumnMapping>>,
+    // CSV file (gzipped when ending with `.gz`) loaded through usql
+    pub csv_path: PathBuf,
+    // Table the CSV file is loaded into
+    pub csv_table_name: String,
+    // Precision RFC3339 strings of inferred CSV timestamp columns are parsed into
+    pub csv_timestamp_unit: TimeUnit,
+    // NDJSON file (gzipped when ending with `.gz`) and the declared table it's loaded into
+    pub ndjson_path: PathBuf,
+    pub ndjson_table_name: String,
+    // Treat NDJSON keys that aren't columns of the table as malformed lines
+    pub strict: bool,
+    // Malformed NDJSON lines skipped before the load is aborted
+    pub max_malformed_lines: usize,
+    // Rows of fact tables loaded by their time index, dimension tables other
+    // tables depend on are loaded fully
+    pub time_range: TimeRange,
+    // Passes over the fact tables of a soak test, each shifted past the
+    // previous one (greptime)
+    pub loops: usize,
+    // Keep replaying the fact tables until this much time passed, at most
+    // `loops` passes when that's above 1
+    pub soak_duration: Option<Duration>,
+    // Share of the rows of every table loaded from parquet deleted again right
+    // after it's loaded, tables without a time index keep their rows (greptime)
+    pub delete_fraction: Option<f64>,
+}
+
+// 4 MiB, the smallest `max_allowed_packet` shipped by MySQL-compatible servers
+const DEFAULT_MAX_STATEMENT_BYTES: usize = 4 * 1024 * 1024;
+
+// 4 MiB, gRPC's default message limit, less room for the request header
+const DEFAULT_MAX_REQUEST_BYTES: usize = 4 * 1024 * 1024 - 64 * 1024;
+
+/// A column selected from a parquet file and the name it's loaded as
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMapping {
+    pub source: String,
+    pub target: String,
+}
+
+impl ColumnMapping {
+    /// Parse `source` or `source->target`
+    pub fn parse(spec: &str) -> Self {
+        let (source, target) = spec.split_once("->").unwrap_or((spec, spec));
+        ColumnMapping {
+            source: source.trim().to_string(),
+            target: target.trim().to_string(),
+        }
+    }
+}
+
+/// Where `gen_insert_stmt` starts a new statement, whichever is hit first
+#[derive(Debug, Clone, Copy)]
+pub struct StatementLimits {
+    pub max_rows: usize,
+    pub max_bytes: usize,
+}
+
+/// How `load_csv_by_usql` groups INSERT statements into batches
+#[derive(Debug, Clone, Copy)]
+pub struct UsqlBatching {
+    pub statements: usize,
+    pub transactional: bool,
+}
+
+impl Default for UsqlBatching {
+    fn default() -> Self {
+        UsqlBatching {
+            statements: 1,
+            transactional: false,
+        }
+    }
+}
+
+impl Default for StatementLimits {
+    fn default() -> Self {
+        StatementLimits {
+            max_rows: 1000,
+            max_bytes: DEFAULT_MAX_STATEMENT_BYTES,
+        }
+    }
+}
+
+/// What loading the parquet file of a table came to, see
+/// `DataLoaderConfig::load_tables`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableLoad {
+    pub affected_rows: u32,
+    // Rows the time range left out
+    pub skipped_rows: usize,
+    // Rows read and sent, unset for targets that don't count the rows they
+    // acknowledge
+    pub read_rows: Option<u64>,
+}
+
+impl DataLoaderConfig {
+    pub fn new(use_case: UseCase) -> Self {
+        DataLoaderConfig {
+            use_case,
+            skip_unsupported: false,
+            create_tables: false,
+            pre_load_step: PreLoadStep::None,
+            chunk_size: 1000,
+            max_statement_bytes: DEFAULT_MAX_STATEMENT_BYTES,
+            sql_dialect: SqlDialect::Greptime,
+            usql_program: default_program(),
+            sql_connection: ConnectionSpec::default(),
+            usql_batch_size: 100,
+            usql_transactions: false,
+            inline_values: false,
+            transaction_statements: 1,
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            workers: 1,
+            streaming: false,
+            adaptive: false,
+            target_latency: Duration::from_millis(200),
+            max_concurrency: 32,
+            retry: RetryPolicy::default(),
+            verify: false,
+            force: false,
+            table_prefix: String::new(),
+            table_suffix: String::new(),
+            table_databases: HashMap::new(),
+            table_columns: HashMap::new(),
+            csv_path: PathBuf::from("./data.gz"),
+            csv_table_name: "measurement".to_string(),
+            csv_timestamp_unit: TimeUnit::Millisecond,
+            ndjson_path: PathBuf::from("./data.ndjson"),
+            ndjson_table_name: "web_logs".to_string(),
+            strict: false,
+            max_malformed_lines: 0,
+            time_range: TimeRange::default(),
+            loops: 1,
+            soak_duration: None,
+            delete_fraction: None,
+        }
+    }
+
+    /// Name of `table` in the target database
+    pub fn target_table_name(&self, table: &str) -> String {
+        format!("{}{}{}", self.table_prefix, table, self.table_suffix)
+    }
+
+    pub fn statement_limits(&self) -> StatementLimits {
+        StatementLimits {
+            max_rows: self.chunk_size,
+            max_bytes: self.max_statement_bytes,
+        }
+    }
+
+    pub fn usql_batching(&self) -> UsqlBatching {
+        UsqlBatching {
+            statements: self.usql_batch_size,
+            transactional: self.usql_transactions,
+        }
+    }
+
+    /// Whether the fact tables are replayed after the first pass
+    pub fn is_soak_test(&self) -> bool {
+        self.loops > 1 || self.soak_duration.is_some()
+    }
+
+    /// Whether a soak test that ran `passes` passes in `elapsed` goes on
+    pub fn another_pass(&self, passes: usize, elapsed: Duration) -> bool {
+        match self.soak_duration {
+            Some(duration) => elapsed < duration && (self.loops <= 1 || passes < self.loops),
+            None => passes < self.loops,
+        }
+    }
+
+    /// Load the parquet files of the tables in load order, each through
+    /// `load_table`, timing them into `report`. With `skip_unsupported` a
+    /// table with a column of a type the target can't store is left out,
+    /// any other error stops the load. With `verify` the rows a target
+    /// acknowledged are checked against the ones read, for targets that
+    /// count them.
+    pub async fn load_tables<F, Fut>(
+        &self,
+        report: &mut LoadReport,
+        mut load_table: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&'static str) -> Fut,
+        Fut: Future<Output = Result<TableLoad>>,
+    {
+        for table_name in table_load_levels::<LogDataGenerator>()?.concat() {
+            let start = Instant::now();
+            let load = match load_table(table_name).await {
+                Ok(load) => load,
+                Err(e) if e.is_unsupported_type() && self.skip_unsupported => {
+                    warn!("Skipping table {}: {}", table_name, e);
+                    continue;
+                }
+                Err(e) => return Err(e).context(LoadTableSnafu { table: table_name }),
+            };
+            let target_table = self.target_table_name(table_name);
+            if let (true, Some(read_rows)) = (self.verify, load.read_rows) {
+                report.mismatches.extend(verify_acknowledged_rows(
+                    &target_table,
+                    read_rows,
+                    load.affected_rows as u64,
+                ));
+            }
+            report.tables.push(TableLoadReport {
+                table: target_table,
+                affected_rows: load.affected_rows,
+                elapsed: start.elapsed(),
+                skipped_rows: load.skipped_rows,
+            });
+        }
+        Ok(())
+    }
+
+    /// Read the parquet file of `table`, projected to its mapped columns and
+    /// filtered by the time range, along with the rows left out
+    pub fn read_table(&self, table: &str) -> Result<(RecordBatch, usize)> {
+        let time_range = if dimension_tables::<LogDataGenerator>().contains(&table) {
+            TimeRange::default()
+        } else {
+            self.time_range
+        };
+        read_parquet_range(
+            PathBuf::from(format!("{}.parquet", table)),
+            self.table_columns.get(table).map(Vec::as_slice),
+            &time_range,
+        )
+    }
+
+    /// Schema of the target table loaded from `table`, see `loader::table_schema`.
+    /// A declared schema is projected to the target names of the mapped columns.
+    pub fn table_schema(&self, table: &str, arrow_schema: &Schema) -> Result<Vec<ColumnSchema>> {
+        let schema = super::table_schema(&self.use_case, table, arrow_schema)?;
+        let Some(columns) = self.table_columns.get(table) else {
+            return Ok(schema);
+        };
+        columns
+            .iter()
+            .map(|column| {
+                schema
+                    .iter()
+                    .find(|c| c.column_name == column.target)
+                    .cloned()
+                    .context(UnknownColumnSnafu {
+                        table,
+                        column: &column.target,
+                    })
+            })
+            .collect()
+    }
+
+    /// Everything wrong with the config, see `validate`
+    pub fn violations(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        if self.chunk_size == 0 {
+            violations.push("chunk size must be at least 1".to_string());
+        }
+        let mut ratio = |name: &str, value: f64| {
+            if !(0.0..=1.0).contains(&value) {
+                violations.push(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        };
+        if let Some(fraction) = self.delete_fraction {
+            ratio("delete fraction", fraction);
+        }
+        ratio("retry jitter", self.retry.jitter);
+        violations
+    }
+
+    /// Check the config right after it's put together, reporting every
+    /// problem at once
+    pub fn validate(&self) -> Result<()> {
+        check_violations(self.violations())
+    }
+
+    /// Fail if a mapped column isn't in the parquet file of its table, so a
+    /// typo is reported before anything is inserted
+    pub fn check_table_columns(&self) -> Result<()> {
+        for (table, columns) in &self.table_columns {
+            let path = PathBuf::from(format!("{}.parquet", table));
+            let file = File::open(&path).context(InvalidFilePathSnafu {})?;
+            let builder =
+                ParquetRecordBatchReaderBuilder::try_new(file).context(ReadParquetFileSnafu {})?;
+            column_indices(&path, builder.schema(), columns)?;
+        }
+        Ok(())
+    }
 }
 
 pub trait DataLoader {
-    // Load data by Usql with auto-generated insert statement
-    fn load_data_by_usql(&self, usql: Option<Usql>);
-    fn load_data_from_raw_sql(&self, usql: Option<Usql>, raw_sql: &str);
-    fn load_data_from_parquet_file(
+    // Load data by Usql with auto-generated insert statement, or by the
+    // executor it falls back to when usql isn't installed
+    fn load_data_by_usql(
+        &self,
+        executor: Option<impl SqlExecutor>,
+    ) -> impl Future<Output = Result<LoadReport>> + Send;
+    // Execute user provided SQL statements by Usql one after another, until
+    // one fails
+    fn load_data_from_raw_sql(
         &self,
-    ) -> impl std::future::Future<Output = Result<Vec<u32>>> + Send;
+        usql: Option<Usql>,
+        raw_sql: &str,
+    ) -> impl Future<Output = Result<ScriptReport>> + Send;
+    // Load the parquet files of every table of the use case
+    fn load_data_from_parquet_file(&self) -> impl Future<Output = Result<LoadReport>> + Send;
+    // Load the configured CSV file into its table through the native write path
+    fn load_data_from_csv_file(&self) -> impl Future<Output = Result<LoadReport>> + Send;
+    // Load the configured NDJSON file into its table through the native write path
+    fn load_data_from_ndjson_file(&self) -> impl Future<Output = Result<LoadReport>> + Send;
+    // Backend named in reports, e.g. "greptime"
+    fn target(&self) -> &'static str;
+    // Write rows of `table_name` read by the caller to its target table, returning
+    // the acknowledged rows. Tables are expected to exist where the target needs it.
+    fn write_chunk(
+        &self,
+        table_name: &str,
+        chunk: &RecordBatch,
+    ) -> impl Future<Output = Result<u32>> + Send;
+}
+
+// Execute SQL statement via usql or its fallback, returning its output
+pub async fn execute_sql(executor: &mut impl SqlExecutor, sql: &str) -> Result<String> {
+    executor.execute(sql).await
+}
+
+// Load a CSV file by usql or its fallback. The header generates the create
+// table statement and data lines are streamed in batches of `limits.max_rows`
+// rows into insert statements of `dialect`, so the decompressed file is never
+// held in memory.
+// Insert statements are run in batches of `batching.statements` and their
+// latencies are returned along with the report.
+pub async fn load_csv_by_usql(
+    executor: &mut impl SqlExecutor,
+    dialect: SqlDialect,
+    path: &Path,
+    table_name: &str,
+    limits: &StatementLimits,
+    batching: &UsqlBatching,
+) -> Result<(TableLoadReport, LatencyHistogram)> {
+    let start = Instant::now();
+    let mut latency = LatencyHistogram::default();
+    let reader = open_input_file(path)?;
+
+    let mut lines = reader.lines();
+    let header = lines
+        .next()
+        .transpose()
+        .map_err(|e| read_input_error(path, e))?
+        .unwrap_or_default();
+    let cols: Vec<&str> = header.trim_end().split(',').map(str::trim).collect();
+    let (arrow_schema, columns) = csv_schema(&cols);
+    execute_sql(
+        executor,
+        &gen_create_table_stmt(dialect, table_name, &arrow_schema, &columns)?,
+    )
+    .await?;
+
+    let batch_size = limits.max_rows.max(1);
+    let mut affected_rows = 0;
+    // Statement batches run so far
+    let mut batches = 0;
+    let mut batch: Vec<Vec<SqlValue>> = Vec::with_capacity(batch_size);
+    // Statements not run yet and the rows they insert
+    let mut statements = Vec::new();
+    let mut statement_rows = 0;
+    for line in lines {
+        let line = line.map_err(|e| read_input_error(path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        batch.push(csv_row(&cols, &line));
+        if batch.len() == batch_size {
+            statements.extend(gen_insert_stmt(dialect, table_name, &cols, &batch, limits));
+            statement_rows += batch.len();
+            batch.clear();
+        }
+        if statements.len() >= batching.statements {
+            execute_batch(
+                executor,
+                &mut statements,
+                batching,
+                &mut latency,
+                &mut batches,
+            )
+            .await?;
+            affected_rows += std::mem::take(&mut statement_rows);
+        }
+    }
+    statements.extend(gen_insert_stmt(dialect, table_name, &cols, &batch, limits));
+    execute_batch(
+        executor,
+        &mut statements,
+        batching,
+        &mut latency,
+        &mut batches,
+    )
+    .await?;
+    affected_rows += statement_rows + batch.len();
+
+    let report = TableLoadReport {
+        table: table_name.to_string(),
+        affected_rows: affected_rows as u32,
+        elapsed: start.elapsed(),
+        skipped_rows: 0,
+    };
+    Ok((report, latency))
 }
 
-// Execute SQL statement via usql
-pub fn execute_sql(usql_conn: &mut Usql, sql: &str) {
-    match usql_conn.execute(sql) {
-        Ok(result) => println!("Success: {:?}", result),
-        Err(e) => eprintln!("Error executing query: {:?}", e),
+// Run `statements` as one batch and clear them, recording the latency of
+// every statement that ran and counting the batch in `batches`. Fails with
+// the statement that failed the batch, wrapped in the position of the batch.
+async fn execute_batch(
+    executor: &mut impl SqlExecutor,
+    statements: &mut Vec<String>,
+    batching: &UsqlBatching,
+    latency: &mut LatencyHistogram,
+    batches: &mut usize,
+) -> Result<()> {
+    if statements.is_empty() {
+        return Ok(());
+    }
+    *batches += 1;
+    let batch_context = SqlBatchSnafu {
+        batch: *batches,
+        statements: statements.len(),
+    };
+    let outcome = executor
+        .execute_batch(statements, batching.transactional)
+        .await
+        .context(batch_context)?;
+    for (timing, result) in outcome.timings.iter().zip(&outcome.results) {
+        latency.record(&RequestMetrics {
+            rows: timing.rows_hint.unwrap_or_default(),
+            bytes: statements[timing.statement_index].len(),
+            latency: timing.duration,
+            outcome: match result {
+                Ok(_) => RequestOutcome::Succeeded,
+                Err(_) => RequestOutcome::Failed,
+            },
+        });
+    }
+    statements.clear();
+    match outcome.into_error() {
+        Some(e) => Err(e).context(batch_context),
+        None => Ok(()),
     }
 }
 
-// Generate create table statement, currently we only support GreptimeDB dialect
-fn gen_create_table_stmt(hypertable: &str, cols: &[&str]) -> String {
-    let mut pk: Option<&str> = None;
-    let mut columns_def: Vec<String> = cols
+// Open a text input file for reading, decompressing it when it ends with `.gz`
+pub(crate) fn open_input_file(path: &Path) -> Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path).context(InvalidFilePathSnafu {})?;
+    if is_gzipped(path) {
+        Ok(Box::new(BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+fn is_gzipped(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+// Failure reading an input file opened by `open_input_file`, a corrupt gzip
+// stream is told apart from the file failing to read
+pub(crate) fn read_input_error(path: &Path, error: io::Error) -> Error {
+    let gzipped = is_gzipped(path);
+    let path = path.display().to_string();
+    if gzipped {
+        CompressionSnafu { path }.into_error(error)
+    } else {
+        ReadFileSnafu { path }.into_error(error)
+    }
+}
+
+// Failure of the CSV reader of an input file, see `read_input_error`
+pub(crate) fn read_csv_error(path: &Path, error: ArrowError) -> Error {
+    match error {
+        ArrowError::IoError(_, e) => read_input_error(path, e),
+        e => ArrowFileSnafu {}.into_error(e),
+    }
+}
+
+pub type CsvReader = arrow::csv::reader::BufReader<Box<dyn BufRead + Send>>;
+
+/// Read a CSV file with a header line into record batches of `batch_size`
+/// rows. Columns are typed by `schema` in file order when given, otherwise the
+/// schema is inferred from the file and timestamp columns are read in
+/// `timestamp_unit`. Timestamps may be given as RFC3339 strings either way.
+pub fn read_csv_file(
+    path: &Path,
+    schema: Option<Schema>,
+    timestamp_unit: TimeUnit,
+    batch_size: usize,
+) -> Result<CsvReader> {
+    let schema = match schema {
+        Some(schema) => schema,
+        None => {
+            let (inferred, _) = Format::default()
+                .with_header(true)
+                .infer_schema(open_input_file(path)?, None)
+                .map_err(|e| read_csv_error(path, e))?;
+            let fields = inferred
+                .fields()
+                .iter()
+                .map(|f| match f.data_type() {
+                    DataType::Timestamp(_, tz) => f
+                        .as_ref()
+                        .clone()
+                        .with_data_type(DataType::Timestamp(timestamp_unit, tz.clone())),
+                    _ => f.as_ref().clone(),
+                })
+                .collect::<Vec<_>>();
+            Schema::new(fields)
+        }
+    };
+    ReaderBuilder::new(Arc::new(schema))
+        .with_header(true)
+        .with_batch_size(batch_size.max(1))
+        .build_buffered(open_input_file(path)?)
+        .context(ArrowFileSnafu {})
+}
+
+// CSV files carry no types: every column is a string except `ts`, the time
+// index, and `tag`, the primary key
+fn csv_schema(cols: &[&str]) -> (Schema, Vec<ColumnSchema>) {
+    let (fields, columns) = cols
         .iter()
-        .map(|&col| {
-            if col == "tag" {
-                pk = Some(col);
-                format!("{} STRING", col)
-            } else if col == "ts" {
-                format!("{} TIMESTAMP DEFAULT CURRENT_TIMESTAMP() TIME INDEX", col)
-            } else {
-                format!("{} STRING", col)
-            }
+        .map(|&col| match col {
+            "ts" => (
+                Field::new(col, DataType::Timestamp(TimeUnit::Millisecond, None), false),
+                timestamp(col, ColumnDataType::TimestampMillisecond),
+            ),
+            "tag" => (
+                Field::new(col, DataType::Utf8, false),
+                tag(col, ColumnDataType::String),
+            ),
+            _ => (
+                Field::new(col, DataType::Utf8, true),
+                field(col, ColumnDataType::String),
+            ),
         })
-        .collect();
-    let pk_def = pk
-        .map(|pk| format!("PRIMARY KEY ({})", pk))
-        .unwrap_or_default();
-    columns_def.push(pk_def);
-    format!("CREATE TABLE {} ({});", hypertable, columns_def.join(", "))
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+    (Schema::new(fields), columns)
 }
 
-// Generate insert statement, currently we only support GreptimeDB dialect
-fn gen_insert_stmt(hypertable: &str, cols: &[&str], data: &[Vec<String>]) -> String {
-    let mut insert_stmt = format!("INSERT INTO {}({}) VALUES", hypertable, cols.join(","));
+// Type the values of a CSV line after `csv_schema`, empty values are NULL
+fn csv_row(cols: &[&str], line: &str) -> Vec<SqlValue> {
+    cols.iter()
+        .zip(line.split(','))
+        .map(|(&col, value)| match value.trim() {
+            "" => SqlValue::Null,
+            value if col == "ts" => SqlValue::Timestamp(value.to_string()),
+            value => SqlValue::String(value.to_string()),
+        })
+        .collect()
+}
+
+/// A value of a generated INSERT statement, rendered as a literal of its type
+/// by `SqlDialect::literal`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Boolean(bool),
+    Int(i64),
+    UInt(u64),
+    Float32(f32),
+    Float64(f64),
+    String(String),
+    Binary(Vec<u8>),
+    // ISO 8601 date
+    Date(String),
+    // ISO 8601 timestamp
+    Timestamp(String),
+}
+
+// Generate a create table statement typed after the Arrow schema. Tag and
+// timestamp designations come from the use case `ColumnSchema`s, columns
+// missing there are plain fields.
+pub(crate) fn gen_create_table_stmt(
+    dialect: SqlDialect,
+    table_name: &str,
+    arrow_schema: &Schema,
+    columns: &[ColumnSchema],
+) -> Result<String> {
+    let semantic_type = |name: &str| {
+        columns
+            .iter()
+            .find(|col| col.column_name == name)
+            .map(|col| col.semantic_type)
+            .unwrap_or(SemanticType::Field as i32)
+    };
+
+    let mut defs = Vec::with_capacity(arrow_schema.fields().len() + 2);
+    let mut time_index: Option<String> = None;
+    let mut primary_keys = Vec::new();
+    for f in arrow_schema.fields() {
+        let column_type =
+            dialect
+                .column_type(f.data_type())
+                .context(UnsupportedColumnTypeSnafu {
+                    column: f.name(),
+                    datatype: f.data_type().to_string(),
+                })?;
+        let name = quote_ident(dialect, f.name());
+        let semantic_type = semantic_type(f.name());
+        let is_time_index = semantic_type == SemanticType::Timestamp as i32;
+        defs.push(dialect.column_def(&name, column_type, f.is_nullable(), is_time_index));
+        if is_time_index {
+            time_index = Some(name);
+        } else if semantic_type == SemanticType::Tag as i32 {
+            primary_keys.push(name);
+        }
+    }
+    defs.extend(dialect.table_constraints(time_index.as_deref(), &primary_keys));
+
+    Ok(format!(
+        "CREATE TABLE IF NOT EXISTS {} ({}){};",
+        quote_ident(dialect, table_name),
+        defs.join(", "),
+        dialect.table_options(time_index.as_deref())
+    ))
+}
+
+// Generate insert statements in `dialect`. Rows are split over several
+// statements so none exceeds `limits`, except a single row that is larger
+// than `max_bytes` on its own.
+pub(crate) fn gen_insert_stmt(
+    dialect: SqlDialect,
+    hypertable: &str,
+    cols: &[&str],
+    data: &[Vec<SqlValue>],
+    limits: &StatementLimits,
+) -> Vec<String> {
+    let prefix = format!(
+        "INSERT INTO {}({}) VALUES",
+        quote_ident(dialect, hypertable),
+        cols.iter()
+            .map(|col| quote_ident(dialect, col))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    let max_rows = limits.max_rows.max(1);
 
-    for (i, row) in data.iter().enumerate() {
+    let mut insert_stmts = Vec::new();
+    let mut insert_stmt = String::new();
+    let mut rows = 0;
+    for row in data {
         let values = row
             .iter()
-            .map(|value| format!("'{}'", value.replace('\'', "''")))
+            .map(|value| dialect.literal(value))
             .collect::<Vec<_>>()
             .join(",");
+        let values = format!(" ({})", values);
 
-        insert_stmt.push_str(&format!("{} ({})", if i == 0 { "" } else { "," }, values));
+        // One byte for the separating comma and one for the closing semicolon
+        if rows > 0 && (rows == max_rows || insert_stmt.len() + values.len() + 2 > limits.max_bytes)
+        {
+            insert_stmt.push(';');
+            insert_stmts.push(std::mem::take(&mut insert_stmt));
+            rows = 0;
+        }
+        if rows == 0 {
+            insert_stmt.push_str(&prefix);
+        } else {
+            insert_stmt.push(',');
+        }
+        insert_stmt.push_str(&values);
+        rows += 1;
+    }
+    if rows > 0 {
+        insert_stmt.push(';');
+        insert_stmts.push(insert_stmt);
     }
-    insert_stmt.push(';');
 
-    insert_stmt
+    insert_stmts
 }
 
 // Read parquet file and return RecordBatch
 pub fn read_parquet_file(path: PathBuf) -> Result<RecordBatch> {
-    let file = File::open(path).context(InvalidFilePathSnafu {
-        location: location!(),
-    })?;
-    let builder =
+    read_parquet_columns(path, None)
+}
+
+/// Read `columns` of a parquet file, in their order and renamed. Columns left
+/// out aren't decoded at all.
+pub fn read_parquet_columns(
+    path: PathBuf,
+    columns: Option<&[ColumnMapping]>,
+) -> Result<RecordBatch> {
+    Ok(read_parquet_range(path, columns, &TimeRange::default())?.0)
+}
+
+/// Read `columns` of a parquet file like `read_parquet_columns`, keeping only
+/// rows whose time index, the first timestamp column read, is in `time_range`.
+/// Row groups whose statistics lie outside the range aren't decoded. Returns
+/// the rows left out along with the batch.
+pub fn read_parquet_range(
+    path: PathBuf,
+    columns: Option<&[ColumnMapping]>,
+    time_range: &TimeRange,
+) -> Result<(RecordBatch, usize)> {
+    let file = File::open(&path).context(InvalidFilePathSnafu {})?;
+    let mut builder =
         ParquetRecordBatchReaderBuilder::try_new(file).context(ReadParquetFileSnafu {})?;
+    let selected = match columns {
+        Some(columns) => {
+            let indices = column_indices(&path, builder.schema(), columns)?;
+            let mask = ProjectionMask::roots(builder.parquet_schema(), indices.clone());
+            builder = builder.with_projection(mask);
+            indices
+        }
+        None => (0..builder.schema().fields().len()).collect(),
+    };
+
+    let mut time_index = None;
+    let mut skipped_rows = 0;
+    let time_column = selected
+        .iter()
+        .find_map(|&i| match builder.schema().field(i).data_type() {
+            DataType::Timestamp(unit, _) => Some((i, *unit)),
+            _ => None,
+        });
+    if let (Some((root, unit)), false) = (time_column, time_range.is_unbounded()) {
+        let parquet_schema = builder.parquet_schema();
+        if let Some(leaf) = (0..parquet_schema.num_columns())
+            .find(|&leaf| parquet_schema.get_column_root_idx(leaf) == root)
+        {
+            let (row_groups, pruned_rows) =
+                time_range.prune_row_groups(builder.metadata(), leaf, unit);
+            skipped_rows += pruned_rows;
+            builder = builder.with_row_groups(row_groups);
+        }
+        time_index = Some(builder.schema().field(root).name().clone());
+    }
+
     let mut reader = builder.build().context(ReadParquetFileSnafu {})?;
-    let record_batch = reader
-        .next()
-        .transpose()
-        .context(ArrowFileSnafu {})?
-        .ok_or_else(|| EndOfParquetFileSnafu {}.build())?;
-    Ok(record_batch)
+    let record_batch = match reader.next().transpose().context(ArrowFileSnafu {})? {
+        Some(record_batch) => record_batch,
+        // Every row group is outside the time range
+        None if skipped_rows > 0 => RecordBatch::new_empty(reader.schema()),
+        None => return EndOfParquetFileSnafu {}.fail(),
+    };
+    let record_batch = match time_index {
+        Some(name) => {
+            let column = record_batch
+                .schema()
+                .index_of(&name)
+                .context(ArrowFileSnafu {})?;
+            let (filtered, filtered_rows) = time_range.filter(&record_batch, column)?;
+            skipped_rows += filtered_rows;
+            filtered
+        }
+        None => record_batch,
+    };
+    let record_batch = match columns {
+        Some(columns) => rename_columns(&record_batch, columns)?,
+        None => record_batch,
+    };
+    Ok((record_batch, skipped_rows))
+}
+
+// Indices of the source columns in the schema of the parquet file at `path`
+fn column_indices(path: &Path, schema: &Schema, columns: &[ColumnMapping]) -> Result<Vec<usize>> {
+    columns
+        .iter()
+        .map(|column| {
+            schema
+                .index_of(&column.source)
+                .ok()
+                .context(UnknownColumnSnafu {
+                    table: path.display().to_string(),
+                    column: &column.source,
+                })
+        })
+        .collect()
+}
+
+// A projection keeps the file's column order, reorder and rename to `columns`
+fn rename_columns(record_batch: &RecordBatch, columns: &[ColumnMapping]) -> Result<RecordBatch> {
+    let schema = record_batch.schema();
+    let mut fields = Vec::with_capacity(columns.len());
+    let mut arrays = Vec::with_capacity(columns.len());
+    for column in columns {
+        let index = schema.index_of(&column.source).context(ArrowFileSnafu {})?;
+        fields.push(schema.field(index).clone().with_name(&column.target));
+        arrays.push(record_batch.column(index).clone());
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays).context(ArrowFileSnafu {})
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use parquet::{arrow::ArrowWriter, file::properties::WriterProperties};
+
+    use super::*;
+    use crate::common::error::Error;
+    use crate::generator::{data_generator::DataGenerator, log_data_generator::LogDataGenerator};
+
+    #[tokio::test]
+    async fn test_load_tables() {
+        let tables = table_load_levels::<LogDataGenerator>().unwrap().concat();
+        let unsupported = tables[0];
+        let load = move |table: &'static str| async move {
+            if table == unsupported {
+                return UnsupportedColumnTypeSnafu {
+                    column: "payload",
+                    datatype: "Struct",
+                }
+                .fail();
+            }
+            Ok(TableLoad {
+                affected_rows: 2,
+                skipped_rows: 1,
+                read_rows: Some(3),
+            })
+        };
+
+        let mut config = DataLoaderConfig::new(UseCase::Log);
+        let mut report = LoadReport::new("test", "test", PreLoadStep::None);
+        let err = config.load_tables(&mut report, load).await.unwrap_err();
+        assert!(matches!(err, Error::LoadTable { .. }), "{:?}", err);
+        assert!(report.tables.is_empty());
+
+        // The table of the unsupported column is left out, the rows the
+        // others acknowledged fall short of the ones read
+        config.skip_unsupported = true;
+        config.verify = true;
+        config.table_prefix = "bench_".to_string();
+        config.load_tables(&mut report, load).await.unwrap();
+        assert_eq!(report.tables.len(), tables.len() - 1);
+        assert_eq!(report.tables[0].table, format!("bench_{}", tables[1]));
+        assert_eq!(report.tables[0].affected_rows, 2);
+        assert_eq!(report.tables[0].skipped_rows, 1);
+        assert_eq!(report.mismatches.len(), tables.len() - 1);
+    }
+
+    #[test]
+    fn test_read_csv_file_infers_timestamp_unit() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("data.csv");
+        std::fs::write(
+            &csv_path,
+            "ts,host,value\n2024-01-01T00:00:00Z,a,1\n2024-01-01T00:00:01.5+00:00,b,2\n",
+        )
+        .unwrap();
+
+        let mut reader = read_csv_file(&csv_path, None, TimeUnit::Millisecond, 10).unwrap();
+        let schema = reader.schema();
+        assert_eq!(
+            schema.field(0).data_type(),
+            &DataType::Timestamp(TimeUnit::Millisecond, None)
+        );
+        assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(2).data_type(), &DataType::Int64);
+
+        let batch = reader.next().unwrap().unwrap();
+        let ts = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<arrow::array::TimestampMillisecondArray>()
+            .unwrap();
+        assert_eq!(ts.values(), &[1_704_067_200_000, 1_704_067_201_500]);
+        assert!(reader.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_load_csv_by_usql() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("data.csv.gz");
+        let mut encoder = GzEncoder::new(File::create(&csv_path).unwrap(), Compression::default());
+        encoder
+            .write_all(b"ts,tag,msg\n1,a,hello\n2,b,it's\n\n3,c,\n")
+            .unwrap();
+        encoder.finish().unwrap();
+
+        // Every statement is appended to `out` on its own line instead of
+        // executed, INSERTs are reported to affect a row
+        let out = dir.path().join("statements.sql");
+        let mut usql = Usql::fake(&format!(
+            r#"printf '%s\n' "$line" >> {}; case "$line" in INSERT*) echo "INSERT 1" ;; esac"#,
+            out.display()
+        ));
+
+        let limits = StatementLimits {
+            max_rows: 2,
+            ..Default::default()
+        };
+        let batching = UsqlBatching {
+            statements: 2,
+            transactional: true,
+        };
+        let (report, latency) = load_csv_by_usql(
+            &mut usql,
+            SqlDialect::Greptime,
+            &csv_path,
+            "measurement",
+            &limits,
+            &batching,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.table, "measurement");
+        assert_eq!(report.affected_rows, 3);
+        // One sample per INSERT, BEGIN and COMMIT aren't timed
+        assert_eq!(latency.requests, 2);
+        assert_eq!(latency.failed, 0);
+        assert_eq!(latency.rows, 2);
+
+        let statements = std::fs::read_to_string(out).unwrap();
+        assert_eq!(
+            statements.lines().collect::<Vec<_>>(),
+            vec![
+                "CREATE TABLE IF NOT EXISTS `measurement` (`ts` TIMESTAMP(3) NOT NULL, `tag` STRING, `msg` STRING, TIME INDEX (`ts`), PRIMARY KEY (`tag`));",
+                "BEGIN;",
+                "INSERT INTO `measurement`(`ts`,`tag`,`msg`) VALUES ('1','a','hello'), ('2','b','it''s');",
+                "INSERT INTO `measurement`(`ts`,`tag`,`msg`) VALUES ('3','c',NULL);",
+                "COMMIT;",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_csv_by_usql_reports_failed_statement() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("data.csv");
+        std::fs::write(&csv_path, "ts,msg\n1,hello\n").unwrap();
+
+        let mut usql = Usql::fake("echo boom >&2");
+        let err = load_csv_by_usql(
+            &mut usql,
+            SqlDialect::Greptime,
+            &csv_path,
+            "measurement",
+            &Default::default(),
+            &Default::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("boom"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = DataLoaderConfig::new(UseCase::Log);
+        assert!(config.validate().is_ok());
+
+        config.chunk_size = 0;
+        config.delete_fraction = Some(1.5);
+        config.retry.jitter = -0.1;
+        assert_eq!(
+            config.violations(),
+            vec![
+                "chunk size must be at least 1",
+                "delete fraction must be between 0 and 1, got 1.5",
+                "retry jitter must be between 0 and 1, got -0.1",
+            ]
+        );
+        let err = config.validate().unwrap_err();
+        assert!(
+            matches!(&err, Error::ConfigValidation { violations, .. } if violations.len() == 3)
+        );
+    }
+
+    #[test]
+    fn test_target_table_name() {
+        let mut config = DataLoaderConfig::new(UseCase::Log);
+        assert_eq!(config.target_table_name("web_logs"), "web_logs");
+        config.table_prefix = "run1_".to_string();
+        config.table_suffix = "_zstd".to_string();
+        assert_eq!(config.target_table_name("web_logs"), "run1_web_logs_zstd");
+    }
+
+    #[test]
+    fn test_read_parquet_columns_unknown_column() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(arrow::array::Int32Array::from(vec![1, 2]))],
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ids.parquet");
+        let mut writer = ArrowWriter::try_new(File::create(&path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let renamed =
+            read_parquet_columns(path.clone(), Some(&[ColumnMapping::parse("id->key")])).unwrap();
+        assert_eq!(renamed.schema().field(0).name(), "key");
+        assert_eq!(renamed.num_rows(), 2);
+
+        let err = read_parquet_columns(path, Some(&[ColumnMapping::parse("name")])).unwrap_err();
+        assert!(err.to_string().contains("Column name not found"), "{}", err);
+    }
+
+    #[test]
+    fn test_read_parquet_range_prunes_row_groups() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+        ]));
+        let hour = 3_600_000_000;
+        let start = 1_704_067_200_000_000;
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(arrow::array::Int32Array::from(vec![0, 1, 2, 3])),
+                Arc::new(arrow::array::TimestampMicrosecondArray::from(vec![
+                    start,
+                    start + hour,
+                    start + 2 * hour,
+                    start + 3 * hour,
+                ])),
+            ],
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.parquet");
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(2)
+            .build();
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), schema, Some(properties)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        // The second hour only, the second row group starts at `to`
+        let time_range = TimeRange {
+            from: Some("2024-01-01T01:00:00Z".parse().unwrap()),
+            to: Some("2024-01-01T02:00:00Z".parse().unwrap()),
+        };
+        let (batch, skipped_rows) = read_parquet_range(path.clone(), None, &time_range).unwrap();
+        assert_eq!(skipped_rows, 3);
+        let ids = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<arrow::array::Int32Array>()
+            .unwrap();
+        assert_eq!(ids.values(), &[1]);
+
+        // Nothing in range
+        let time_range = TimeRange {
+            from: Some("2025-01-01T00:00:00Z".parse().unwrap()),
+            to: None,
+        };
+        let (batch, skipped_rows) = read_parquet_range(path, None, &time_range).unwrap();
+        assert_eq!((batch.num_rows(), skipped_rows), (0, 4));
+    }
+
+    #[test]
+    fn test_another_pass() {
+        let mut config = DataLoaderConfig::new(UseCase::Log);
+        assert!(!config.is_soak_test());
+        assert!(!config.another_pass(1, Duration::ZERO));
+
+        config.loops = 3;
+        assert!(config.another_pass(2, Duration::from_secs(3600)));
+        assert!(!config.another_pass(3, Duration::ZERO));
+
+        // A duration alone isn't capped by passes
+        config.loops = 1;
+        config.soak_duration = Some(Duration::from_secs(60));
+        assert!(config.is_soak_test());
+        assert!(config.another_pass(100, Duration::from_secs(59)));
+        assert!(!config.another_pass(2, Duration::from_secs(60)));
+
+        config.loops = 3;
+        assert!(!config.another_pass(3, Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_gen_create_table_stmt_web_logs() {
+        let arrow_schema = Schema::new(vec![
+            Field::new("log_id", DataType::Int32, false),
+            Field::new("user_id", DataType::Int32, false),
+            Field::new("page_id", DataType::Int32, false),
+            Field::new("device_id", DataType::Int32, false),
+            Field::new("runtime", DataType::Int32, false),
+            Field::new("ip_address", DataType::Utf8, false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+        ]);
+        let columns = LogDataGenerator::schema("web_logs");
+
+        assert_eq!(
+            gen_create_table_stmt(SqlDialect::Greptime, "web_logs", &arrow_schema, &columns)
+                .unwrap(),
+            "CREATE TABLE IF NOT EXISTS `web_logs` (\
+             `log_id` INT, `user_id` INT, `page_id` INT, `device_id` INT, `runtime` INT, \
+             `ip_address` STRING, `timestamp` TIMESTAMP(6) NOT NULL, \
+             TIME INDEX (`timestamp`), PRIMARY KEY (`log_id`));"
+        );
+    }
+
+    #[test]
+    fn test_gen_create_table_stmt_types() {
+        let arrow_schema = Schema::new(vec![
+            Field::new("host", DataType::Utf8, false),
+            Field::new("region", DataType::Utf8, false),
+            Field::new("bytes", DataType::Int64, true),
+            Field::new("usage", DataType::Float64, true),
+            Field::new("up", DataType::Boolean, true),
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+        ]);
+        let columns = vec![
+            tag("host", ColumnDataType::String),
+            tag("region", ColumnDataType::String),
+            timestamp("ts", ColumnDataType::TimestampMillisecond),
+        ];
+
+        assert_eq!(
+            gen_create_table_stmt(SqlDialect::Greptime, "cpu", &arrow_schema, &columns).unwrap(),
+            "CREATE TABLE IF NOT EXISTS `cpu` (\
+             `host` STRING, `region` STRING, `bytes` BIGINT, `usage` DOUBLE, `up` BOOLEAN, \
+             `ts` TIMESTAMP(3) NOT NULL, TIME INDEX (`ts`), PRIMARY KEY (`host`, `region`));"
+        );
+
+        let arrow_schema =
+            Schema::new(vec![Field::new("price", DataType::Decimal128(10, 2), true)]);
+        let err = gen_create_table_stmt(SqlDialect::Greptime, "t", &arrow_schema, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported data type Decimal128(10, 2) for column price"
+        );
+    }
+
+    #[test]
+    fn test_sql_literals() {
+        let row = vec![
+            SqlValue::Null,
+            SqlValue::Boolean(true),
+            SqlValue::Boolean(false),
+            SqlValue::Int(-3_000_000_000),
+            SqlValue::UInt(u64::MAX),
+            SqlValue::Float32(0.1),
+            SqlValue::Float64(f64::NAN),
+            SqlValue::String("it's".to_string()),
+            SqlValue::Binary(vec![0xde, 0xad]),
+            SqlValue::Date("2024-02-29".to_string()),
+            SqlValue::Timestamp("2024-02-29T12:00:00.000001".to_string()),
+        ];
+        assert_eq!(
+            gen_insert_stmt(
+                SqlDialect::Greptime,
+                "t",
+                &["c"; 11],
+                &[row],
+                &Default::default()
+            ),
+            vec![
+                "INSERT INTO `t`(`c`,`c`,`c`,`c`,`c`,`c`,`c`,`c`,`c`,`c`,`c`) VALUES \
+                  (NULL,TRUE,FALSE,-3000000000,18446744073709551615,0.1,NULL,'it''s',X'DEAD',\
+                  '2024-02-29','2024-02-29T12:00:00.000001');"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_insert_stmt_splits_by_rows() {
+        let data = (0..5)
+            .map(|i| vec![SqlValue::Int(i), SqlValue::String("x".to_string())])
+            .collect::<Vec<_>>();
+        let limits = StatementLimits {
+            max_rows: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            gen_insert_stmt(SqlDialect::Greptime, "t", &["a", "b"], &data, &limits),
+            vec![
+                "INSERT INTO `t`(`a`,`b`) VALUES (0,'x'), (1,'x');",
+                "INSERT INTO `t`(`a`,`b`) VALUES (2,'x'), (3,'x');",
+                "INSERT INTO `t`(`a`,`b`) VALUES (4,'x');",
+            ]
+        );
+        assert!(gen_insert_stmt(SqlDialect::Greptime, "t", &["a", "b"], &[], &limits).is_empty());
+    }
+
+    #[test]
+    fn test_gen_insert_stmt_splits_by_bytes() {
+        let data = (0..1000)
+            .map(|i| {
+                vec![
+                    SqlValue::String(i.to_string()),
+                    SqlValue::String("value".repeat(i % 7)),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let limits = StatementLimits {
+            max_rows: usize::MAX,
+            max_bytes: 512,
+        };
+        let stmts = gen_insert_stmt(SqlDialect::Greptime, "t", &["a", "b"], &data, &limits);
+        assert!(stmts.len() > 1);
+        assert!(stmts.iter().all(|stmt| stmt.len() <= limits.max_bytes));
+        let rows: usize = stmts.iter().map(|stmt| stmt.matches(" ('").count()).sum();
+        assert_eq!(rows, data.len());
+    }
+
+    #[test]
+    fn test_gen_insert_stmt_oversized_row() {
+        let data = vec![
+            vec![SqlValue::String("a".repeat(100))],
+            vec![SqlValue::String("b".to_string())],
+        ];
+        let limits = StatementLimits {
+            max_rows: 10,
+            max_bytes: 50,
+        };
+        let stmts = gen_insert_stmt(SqlDialect::Greptime, "t", &["v"], &data, &limits);
+        assert_eq!(stmts.len(), 2);
+        assert_eq!(stmts[1], "INSERT INTO `t`(`v`) VALUES ('b');");
+    }
 }
@@ -0,0 +1,381 @@
+use arrow::datatypes::{DataType, TimeUnit};
+
+use super::data_loader::SqlValue;
+use crate::sql::quote::{quote_ident, quote_literal};
+
+/// SQL flavor of the statements built by `gen_create_table_stmt` and
+/// `gen_insert_stmt`, which leave everything differing between databases to
+/// the dialect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SqlDialect {
+    #[default]
+    Greptime,
+    MySql,
+    Postgres,
+    ClickHouse,
+}
+
+impl SqlDialect {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "greptime" => Some(SqlDialect::Greptime),
+            "mysql" => Some(SqlDialect::MySql),
+            "postgres" => Some(SqlDialect::Postgres),
+            "clickhouse" => Some(SqlDialect::ClickHouse),
+            _ => None,
+        }
+    }
+
+    /// Column type of Arrow values, `None` for types the dialect can't store
+    pub fn column_type(&self, data_type: &DataType) -> Option<&'static str> {
+        if let DataType::Dictionary(_, value_type) = data_type {
+            return self.column_type(value_type);
+        }
+        match self {
+            SqlDialect::Greptime => match data_type {
+                DataType::Boolean => Some("BOOLEAN"),
+                DataType::Int8 | DataType::Int16 | DataType::Int32 => Some("INT"),
+                DataType::UInt8 | DataType::UInt16 | DataType::UInt32 => Some("INT UNSIGNED"),
+                DataType::Int64 => Some("BIGINT"),
+                DataType::UInt64 => Some("BIGINT UNSIGNED"),
+                DataType::Float32 => Some("FLOAT"),
+                DataType::Float64 => Some("DOUBLE"),
+                DataType::Utf8 | DataType::LargeUtf8 => Some("STRING"),
+                DataType::Date32 | DataType::Date64 => Some("DATE"),
+                DataType::Binary | DataType::LargeBinary => Some("VARBINARY"),
+                DataType::Timestamp(unit, _) => Some(match unit {
+                    TimeUnit::Second => "TIMESTAMP(0)",
+                    TimeUnit::Millisecond => "TIMESTAMP(3)",
+                    TimeUnit::Microsecond => "TIMESTAMP(6)",
+                    TimeUnit::Nanosecond => "TIMESTAMP(9)",
+                }),
+                _ => None,
+            },
+            SqlDialect::MySql => match data_type {
+                DataType::Boolean => Some("BOOLEAN"),
+                DataType::Int8 => Some("TINYINT"),
+                DataType::Int16 => Some("SMALLINT"),
+                DataType::Int32 => Some("INT"),
+                DataType::Int64 => Some("BIGINT"),
+                DataType::UInt8 => Some("TINYINT UNSIGNED"),
+                DataType::UInt16 => Some("SMALLINT UNSIGNED"),
+                DataType::UInt32 => Some("INT UNSIGNED"),
+                DataType::UInt64 => Some("BIGINT UNSIGNED"),
+                DataType::Float32 => Some("FLOAT"),
+                DataType::Float64 => Some("DOUBLE"),
+                DataType::Utf8 | DataType::LargeUtf8 => Some("TEXT"),
+                DataType::Date32 | DataType::Date64 => Some("DATE"),
+                DataType::Binary | DataType::LargeBinary => Some("BLOB"),
+                // MySQL keeps microseconds at most
+                DataType::Timestamp(unit, _) => Some(match unit {
+                    TimeUnit::Second => "DATETIME",
+                    TimeUnit::Millisecond => "DATETIME(3)",
+                    TimeUnit::Microsecond | TimeUnit::Nanosecond => "DATETIME(6)",
+                }),
+                _ => None,
+            },
+            SqlDialect::Postgres => match data_type {
+                DataType::Boolean => Some("BOOLEAN"),
+                DataType::Int8 | DataType::Int16 | DataType::UInt8 => Some("SMALLINT"),
+                DataType::Int32 | DataType::UInt16 => Some("INTEGER"),
+                // Postgres has no unsigned types, u32 fits into a BIGINT
+                DataType::Int64 | DataType::UInt32 => Some("BIGINT"),
+                DataType::Float32 => Some("REAL"),
+                DataType::Float64 => Some("DOUBLE PRECISION"),
+                DataType::Utf8 | DataType::LargeUtf8 => Some("TEXT"),
+                DataType::Date32 | DataType::Date64 => Some("DATE"),
+                DataType::Binary | DataType::LargeBinary => Some("BYTEA"),
+                DataType::Timestamp(_, _) => Some("TIMESTAMP"),
+                _ => None,
+            },
+            SqlDialect::ClickHouse => match data_type {
+                DataType::Boolean => Some("Bool"),
+                DataType::Int8 => Some("Int8"),
+                DataType::Int16 => Some("Int16"),
+                DataType::Int32 => Some("Int32"),
+                DataType::Int64 => Some("Int64"),
+                DataType::UInt8 => Some("UInt8"),
+                DataType::UInt16 => Some("UInt16"),
+                DataType::UInt32 => Some("UInt32"),
+                DataType::UInt64 => Some("UInt64"),
+                DataType::Float32 => Some("Float32"),
+                DataType::Float64 => Some("Float64"),
+                DataType::Utf8 | DataType::LargeUtf8 => Some("String"),
+                DataType::Binary | DataType::LargeBinary => Some("String"),
+                DataType::Date32 | DataType::Date64 => Some("Date32"),
+                DataType::Timestamp(unit, _) => Some(match unit {
+                    TimeUnit::Second => "DateTime64(0)",
+                    TimeUnit::Millisecond => "DateTime64(3)",
+                    TimeUnit::Microsecond => "DateTime64(6)",
+                    TimeUnit::Nanosecond => "DateTime64(9)",
+                }),
+                _ => None,
+            },
+        }
+    }
+
+    /// Definition of a column of `column_type`. The time index is never null,
+    /// GreptimeDB leaves the nullability of other columns to the data.
+    pub(crate) fn column_def(
+        &self,
+        name: &str,
+        column_type: &str,
+        nullable: bool,
+        time_index: bool,
+    ) -> String {
+        match self {
+            SqlDialect::Greptime if time_index => format!("{} {} NOT NULL", name, column_type),
+            SqlDialect::Greptime => format!("{} {}", name, column_type),
+            SqlDialect::MySql | SqlDialect::Postgres if time_index || !nullable => {
+                format!("{} {} NOT NULL", name, column_type)
+            }
+            SqlDialect::MySql | SqlDialect::Postgres => format!("{} {}", name, column_type),
+            SqlDialect::ClickHouse if nullable && !time_index => {
+                format!("{} Nullable({})", name, column_type)
+            }
+            SqlDialect::ClickHouse => format!("{} {}", name, column_type),
+        }
+    }
+
+    /// Constraints following the column definitions, of quoted names
+    pub(crate) fn table_constraints(
+        &self,
+        time_index: Option<&str>,
+        primary_keys: &[String],
+    ) -> Vec<String> {
+        let mut constraints = Vec::new();
+        match (self, time_index) {
+            (SqlDialect::Greptime, _) => {
+                if let Some(time_index) = time_index {
+                    constraints.push(format!("TIME INDEX ({})", time_index));
+                }
+                if !primary_keys.is_empty() {
+                    constraints.push(format!("PRIMARY KEY ({})", primary_keys.join(", ")));
+                }
+            }
+            (SqlDialect::MySql, Some(time_index)) => {
+                constraints.push(format!("INDEX ({})", time_index))
+            }
+            _ => {}
+        }
+        constraints
+    }
+
+    /// Clause following the parenthesized table definition, with a leading
+    /// space when there is one
+    pub(crate) fn table_options(&self, time_index: Option<&str>) -> String {
+        match (self, time_index) {
+            (SqlDialect::ClickHouse, Some(time_index)) => {
+                format!(" ENGINE = MergeTree ORDER BY ({})", time_index)
+            }
+            (SqlDialect::ClickHouse, None) => " ENGINE = MergeTree ORDER BY tuple()".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Statement turning `table_name` into a TimescaleDB hypertable
+    /// partitioned by `time_index`, where the dialect has hypertables
+    pub fn hypertable_stmt(&self, table_name: &str, time_index: &str) -> Option<String> {
+        match self {
+            // The table is parsed as an identifier, the column is taken as is
+            SqlDialect::Postgres => Some(format!(
+                "SELECT create_hypertable({}, {}, if_not_exists => TRUE, migrate_data => TRUE)",
+                quote_literal(*self, &quote_ident(*self, table_name)),
+                quote_literal(*self, time_index)
+            )),
+            _ => None,
+        }
+    }
+
+    /// `value` as a SQL literal of its type
+    pub fn literal(&self, value: &SqlValue) -> String {
+        match (self, value) {
+            (_, SqlValue::Null) => "NULL".to_string(),
+            (_, SqlValue::Boolean(v)) => if *v { "TRUE" } else { "FALSE" }.to_string(),
+            (_, SqlValue::Int(v)) => v.to_string(),
+            (_, SqlValue::UInt(v)) => v.to_string(),
+            // SQL has no literal for NaN and infinities
+            (_, SqlValue::Float32(v)) if !v.is_finite() => "NULL".to_string(),
+            (_, SqlValue::Float64(v)) if !v.is_finite() => "NULL".to_string(),
+            (_, SqlValue::Float32(v)) => v.to_string(),
+            (_, SqlValue::Float64(v)) => v.to_string(),
+            (SqlDialect::Postgres, SqlValue::Binary(v)) => format!("'\\x{}'::bytea", hex(v)),
+            (SqlDialect::ClickHouse, SqlValue::Binary(v)) => format!("unhex('{}')", hex(v)),
+            (_, SqlValue::Binary(v)) => format!("X'{}'", hex(v)),
+            (SqlDialect::Postgres, SqlValue::Date(v)) => {
+                format!("DATE {}", quote_literal(*self, v))
+            }
+            (SqlDialect::Postgres, SqlValue::Timestamp(v)) => {
+                format!("TIMESTAMP {}", quote_literal(*self, v))
+            }
+            // ClickHouse only parses ISO 8601 timestamps on a best effort basis
+            (SqlDialect::ClickHouse, SqlValue::Timestamp(v)) => {
+                format!("parseDateTime64BestEffort({}, 9)", quote_literal(*self, v))
+            }
+            (_, SqlValue::String(v) | SqlValue::Date(v) | SqlValue::Timestamp(v)) => {
+                quote_literal(*self, v)
+            }
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::datatypes::{Field, Schema};
+
+    use super::*;
+    use crate::{
+        generator::{data_generator::DataGenerator, log_data_generator::LogDataGenerator},
+        loader::data_loader::{gen_create_table_stmt, gen_insert_stmt},
+    };
+
+    fn web_logs_schema() -> Schema {
+        Schema::new(vec![
+            Field::new("log_id", DataType::Int32, false),
+            Field::new("user_id", DataType::Int32, false),
+            Field::new("page_id", DataType::Int32, false),
+            Field::new("device_id", DataType::Int32, false),
+            Field::new("runtime", DataType::Int32, false),
+            Field::new("ip_address", DataType::Utf8, true),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+        ])
+    }
+
+    fn web_logs_create_stmt(dialect: SqlDialect) -> String {
+        let columns = LogDataGenerator::schema("web_logs");
+        gen_create_table_stmt(dialect, "web_logs", &web_logs_schema(), &columns).unwrap()
+    }
+
+    fn web_logs_insert_stmt(dialect: SqlDialect) -> String {
+        let row = vec![
+            SqlValue::Int(1),
+            SqlValue::Int(2),
+            SqlValue::Int(3),
+            SqlValue::Int(4),
+            SqlValue::Int(120),
+            SqlValue::String("it's \\ 10.0.0.1".to_string()),
+            SqlValue::Timestamp("2024-01-01T00:00:00.000001".to_string()),
+        ];
+        let cols = web_logs_schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+        let cols = cols.iter().map(String::as_str).collect::<Vec<_>>();
+        gen_insert_stmt(dialect, "web_logs", &cols, &[row], &Default::default()).remove(0)
+    }
+
+    #[test]
+    fn test_greptime_web_logs() {
+        assert_eq!(
+            web_logs_create_stmt(SqlDialect::Greptime),
+            "CREATE TABLE IF NOT EXISTS `web_logs` (\
+             `log_id` INT, `user_id` INT, `page_id` INT, `device_id` INT, `runtime` INT, \
+             `ip_address` STRING, `timestamp` TIMESTAMP(6) NOT NULL, \
+             TIME INDEX (`timestamp`), PRIMARY KEY (`log_id`));"
+        );
+        assert_eq!(
+            web_logs_insert_stmt(SqlDialect::Greptime),
+            "INSERT INTO `web_logs`(`log_id`,`user_id`,`page_id`,`device_id`,`runtime`,\
+             `ip_address`,`timestamp`) VALUES \
+             (1,2,3,4,120,'it''s \\ 10.0.0.1','2024-01-01T00:00:00.000001');"
+        );
+    }
+
+    #[test]
+    fn test_mysql_web_logs() {
+        assert_eq!(
+            web_logs_create_stmt(SqlDialect::MySql),
+            "CREATE TABLE IF NOT EXISTS `web_logs` (\
+             `log_id` INT NOT NULL, `user_id` INT NOT NULL, `page_id` INT NOT NULL, \
+             `device_id` INT NOT NULL, `runtime` INT NOT NULL, `ip_address` TEXT, \
+             `timestamp` DATETIME(6) NOT NULL, INDEX (`timestamp`));"
+        );
+        assert_eq!(
+            web_logs_insert_stmt(SqlDialect::MySql),
+            "INSERT INTO `web_logs`(`log_id`,`user_id`,`page_id`,`device_id`,`runtime`,\
+             `ip_address`,`timestamp`) VALUES \
+             (1,2,3,4,120,'it''s \\\\ 10.0.0.1','2024-01-01T00:00:00.000001');"
+        );
+    }
+
+    #[test]
+    fn test_postgres_web_logs() {
+        assert_eq!(
+            web_logs_create_stmt(SqlDialect::Postgres),
+            "CREATE TABLE IF NOT EXISTS \"web_logs\" (\
+             \"log_id\" INTEGER NOT NULL, \"user_id\" INTEGER NOT NULL, \
+             \"page_id\" INTEGER NOT NULL, \"device_id\" INTEGER NOT NULL, \
+             \"runtime\" INTEGER NOT NULL, \"ip_address\" TEXT, \
+             \"timestamp\" TIMESTAMP NOT NULL);"
+        );
+        assert_eq!(
+            web_logs_insert_stmt(SqlDialect::Postgres),
+            "INSERT INTO \"web_logs\"(\"log_id\",\"user_id\",\"page_id\",\"device_id\",\
+             \"runtime\",\"ip_address\",\"timestamp\") VALUES \
+             (1,2,3,4,120,E'it''s \\\\ 10.0.0.1',TIMESTAMP '2024-01-01T00:00:00.000001');"
+        );
+        assert_eq!(
+            SqlDialect::Postgres.hypertable_stmt("web_logs", "timestamp"),
+            Some(
+                "SELECT create_hypertable('\"web_logs\"', 'timestamp', if_not_exists => TRUE, \
+                 migrate_data => TRUE)"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            SqlDialect::Greptime.hypertable_stmt("web_logs", "timestamp"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_clickhouse_web_logs() {
+        assert_eq!(
+            web_logs_create_stmt(SqlDialect::ClickHouse),
+            "CREATE TABLE IF NOT EXISTS `web_logs` (\
+             `log_id` Int32, `user_id` Int32, `page_id` Int32, `device_id` Int32, \
+             `runtime` Int32, `ip_address` Nullable(String), `timestamp` DateTime64(6)) \
+             ENGINE = MergeTree ORDER BY (`timestamp`);"
+        );
+        assert_eq!(
+            web_logs_insert_stmt(SqlDialect::ClickHouse),
+            "INSERT INTO `web_logs`(`log_id`,`user_id`,`page_id`,`device_id`,`runtime`,\
+             `ip_address`,`timestamp`) VALUES (1,2,3,4,120,'it''s \\\\ 10.0.0.1',\
+             parseDateTime64BestEffort('2024-01-01T00:00:00.000001', 9));"
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        let binary = SqlValue::Binary(vec![0xde, 0xad]);
+        let date = SqlValue::Date("2024-02-29".to_string());
+        let cases = [
+            (SqlDialect::Greptime, "X'DEAD'", "'2024-02-29'"),
+            (SqlDialect::MySql, "X'DEAD'", "'2024-02-29'"),
+            (
+                SqlDialect::Postgres,
+                "'\\xDEAD'::bytea",
+                "DATE '2024-02-29'",
+            ),
+            (SqlDialect::ClickHouse, "unhex('DEAD')", "'2024-02-29'"),
+        ];
+        for (dialect, binary_literal, date_literal) in cases {
+            assert_eq!(dialect.literal(&binary), binary_literal, "{:?}", dialect);
+            assert_eq!(dialect.literal(&date), date_literal, "{:?}", dialect);
+        }
+        assert_eq!(
+            SqlDialect::parse("clickhouse"),
+            Some(SqlDialect::ClickHouse)
+        );
+        assert_eq!(SqlDialect::parse("sqlite"), None);
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use arrow::array::RecordBatch;
+use log::warn;
+use snafu::ResultExt;
+
+use crate::{
+    common::error::{LoadTableSnafu, Result},
+    generator::log_data_generator::LogDataGenerator,
+    loader::data_loader::{read_parquet_columns, ColumnMapping},
+};
+
+use super::{
+    data_loader::DataLoader,
+    load_order::table_load_levels,
+    report::{DualLoadReport, LoadReport, PreLoadStep, TableLoadReport, TargetFailure},
+};
+
+/// Load the same data into two targets in one pass, e.g. two GreptimeDB
+/// versions or GreptimeDB and InfluxDB. Every parquet file is read once and
+/// each chunk is written to both targets concurrently, so reading the dataset
+/// isn't counted twice. Target tables aren't prepared, see
+/// `DataLoader::write_chunk`.
+pub struct DualDataLoader<A, B> {
+    pub first: A,
+    pub second: B,
+    // Rows read and written to both targets at a time
+    pub chunk_size: usize,
+    // Keep loading into the healthy target after the other one failed
+    pub best_effort: bool,
+    // Columns read from the parquet file of each table, see
+    // `DataLoaderConfig::table_columns`
+    pub table_columns: HashMap<String, Vec<ColumnMapping>>,
+}
+
+impl<A: DataLoader, B: DataLoader> DualDataLoader<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        DualDataLoader {
+            first,
+            second,
+            chunk_size: 1000,
+            best_effort: false,
+            table_columns: HashMap::new(),
+        }
+    }
+
+    /// Names of the two targets in reports, numbered when both are of the
+    /// same backend
+    pub fn labels(&self) -> [String; 2] {
+        let (first, second) = (self.first.target(), self.second.target());
+        if first == second {
+            [format!("{}#1", first), format!("{}#2", second)]
+        } else {
+            [first.to_string(), second.to_string()]
+        }
+    }
+
+    pub async fn load_data_from_parquet_file(&self) -> Result<DualLoadReport> {
+        let mut report = self.new_report();
+        for table_name in table_load_levels::<LogDataGenerator>()?.concat() {
+            let path = PathBuf::from(format!("{}.parquet", table_name));
+            let columns = self.table_columns.get(table_name).map(Vec::as_slice);
+            let record_batch = read_parquet_columns(path, columns)
+                .context(LoadTableSnafu { table: table_name })?;
+            self.load_record_batch(table_name, &record_batch, &mut report)
+                .await?;
+            if report.failures.len() == 2 {
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn new_report(&self) -> DualLoadReport {
+        DualLoadReport {
+            reports: self
+                .labels()
+                .map(|label| LoadReport::new(label, "dual-write", PreLoadStep::None)),
+            failures: Vec::new(),
+        }
+    }
+
+    /// Write `record_batch` to both targets in chunks of `chunk_size` rows. A
+    /// target that failed before receives nothing, under `best_effort` a
+    /// failing target is recorded in the report instead of failing the load.
+    pub async fn load_record_batch(
+        &self,
+        table_name: &str,
+        record_batch: &RecordBatch,
+        report: &mut DualLoadReport,
+    ) -> Result<()> {
+        let labels = self.labels();
+        let healthy = |report: &DualLoadReport, i: usize| {
+            !report.failures.iter().any(|f| f.target == labels[i])
+        };
+        let loading = [healthy(report, 0), healthy(report, 1)];
+        let mut tables = loading.map(|_| TableLoadReport {
+            table: table_name.to_string(),
+            affected_rows: 0,
+            elapsed: Duration::ZERO,
+            skipped_rows: 0,
+        });
+
+        let chunk_size = self.chunk_size.max(1);
+        for offset in (0..record_batch.num_rows()).step_by(chunk_size) {
+            let active = [healthy(report, 0), healthy(report, 1)];
+            if active == [false, false] {
+                break;
+            }
+            let len = chunk_size.min(record_batch.num_rows() - offset);
+            let chunk = record_batch.slice(offset, len);
+            let results = tokio::join!(
+                timed(active[0], self.first.write_chunk(table_name, &chunk)),
+                timed(active[1], self.second.write_chunk(table_name, &chunk)),
+            );
+
+            for (i, result) in [results.0, results.1].into_iter().enumerate() {
+                match result {
+                    None => {}
+                    Some((Ok(affected_rows), elapsed)) => {
+                        tables[i].affected_rows += affected_rows;
+                        tables[i].elapsed += elapsed;
+                    }
+                    Some((Err(e), _)) if self.best_effort => {
+                        warn!(
+                            "{} failed on table {}, continuing with the other target: {}",
+                            labels[i], table_name, e
+                        );
+                        report.failures.push(TargetFailure {
+                            target: labels[i].clone(),
+                            table: table_name.to_string(),
+                            error: e.to_string(),
+                            code: e.server_code(),
+                        });
+                    }
+                    Some((Err(e), _)) => {
+                        return Err(e).context(LoadTableSnafu {
+                            table: format!("{} ({})", table_name, labels[i]),
+                        })
+                    }
+                }
+            }
+        }
+
+        for ((target_report, table), loading) in report.reports.iter_mut().zip(tables).zip(loading)
+        {
+            if loading {
+                target_report.tables.push(table);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Run `write` unless its target is disabled, along with the time it took
+async fn timed(
+    enabled: bool,
+    write: impl Future<Output = Result<u32>>,
+) -> Option<(Result<u32>, Duration)> {
+    if !enabled {
+        return None;
+    }
+    let start = Instant::now();
+    let result = write.await;
+    Some((result, start.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+    };
+
+    use super::*;
+    use crate::common::error::{Error, UnsupportedOperationSnafu};
+    use crate::common::sql_executor::SqlExecutor;
+    use crate::usql::{script::ScriptReport, usql::Usql};
+
+    // Records the chunks written to it, failing from the `fail_at`th chunk on
+    struct MockLoader {
+        target: &'static str,
+        chunks: Mutex<Vec<(String, Vec<i32>)>>,
+        fail_at: Option<usize>,
+    }
+
+    impl MockLoader {
+        fn new(target: &'static str) -> Self {
+            MockLoader {
+                target,
+                chunks: Mutex::new(Vec::new()),
+                fail_at: None,
+            }
+        }
+
+        fn chunks(&self) -> Vec<(String, Vec<i32>)> {
+            self.chunks.lock().unwrap().clone()
+        }
+    }
+
+    impl DataLoader for MockLoader {
+        async fn load_data_by_usql(
+            &self,
+            _executor: Option<impl SqlExecutor>,
+        ) -> Result<LoadReport> {
+            UnsupportedOperationSnafu {
+                target: self.target,
+                operation: "loading through usql",
+            }
+            .fail()
+        }
+
+        async fn load_data_from_raw_sql(
+            &self,
+            _usql: Option<Usql>,
+            _raw_sql: &str,
+        ) -> Result<ScriptReport> {
+            UnsupportedOperationSnafu {
+                target: self.target,
+                operation: "raw SQL through usql",
+            }
+            .fail()
+        }
+
+        async fn load_data_from_parquet_file(&self) -> Result<LoadReport> {
+            UnsupportedOperationSnafu {
+                target: self.target,
+                operation: "loading parquet files",
+            }
+            .fail()
+        }
+
+        async fn load_data_from_csv_file(&self) -> Result<LoadReport> {
+            UnsupportedOperationSnafu {
+                target: self.target,
+                operation: "loading CSV files",
+            }
+            .fail()
+        }
+
+        async fn load_data_from_ndjson_file(&self) -> Result<LoadReport> {
+            UnsupportedOperationSnafu {
+                target: self.target,
+                operation: "loading NDJSON files",
+            }
+            .fail()
+        }
+
+        fn target(&self) -> &'static str {
+            self.target
+        }
+
+        async fn write_chunk(&self, table_name: &str, chunk: &RecordBatch) -> Result<u32> {
+            let mut chunks = self.chunks.lock().unwrap();
+            if self.fail_at.is_some_and(|n| chunks.len() + 1 >= n) {
+                return UnsupportedOperationSnafu {
+                    target: self.target,
+                    operation: "another chunk",
+                }
+                .fail();
+            }
+            let values = chunk
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap()
+                .values()
+                .to_vec();
+            chunks.push((table_name.to_string(), values));
+            Ok(chunk.num_rows() as u32)
+        }
+    }
+
+    fn batch(values: Vec<i32>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(values))]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_both_targets_receive_every_chunk() {
+        let mut loader =
+            DualDataLoader::new(MockLoader::new("greptime"), MockLoader::new("influxdb"));
+        loader.chunk_size = 2;
+        let mut report = loader.new_report();
+        loader
+            .load_record_batch("users", &batch(vec![1, 2, 3, 4, 5]), &mut report)
+            .await
+            .unwrap();
+        loader
+            .load_record_batch("pages", &batch(vec![6]), &mut report)
+            .await
+            .unwrap();
+
+        let expected = vec![
+            ("users".to_string(), vec![1, 2]),
+            ("users".to_string(), vec![3, 4]),
+            ("users".to_string(), vec![5]),
+            ("pages".to_string(), vec![6]),
+        ];
+        assert_eq!(loader.first.chunks(), expected);
+        assert_eq!(loader.second.chunks(), expected);
+
+        assert!(report.succeeded());
+        for (target_report, target) in report.reports.iter().zip(["greptime", "influxdb"]) {
+            assert_eq!(target_report.target, target);
+            assert_eq!(target_report.protocol, "dual-write");
+            let tables = target_report
+                .tables
+                .iter()
+                .map(|t| (t.table.as_str(), t.affected_rows))
+                .collect::<Vec<_>>();
+            assert_eq!(tables, [("users", 5), ("pages", 1)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_continues_with_healthy_target() {
+        let mut second = MockLoader::new("greptime");
+        second.fail_at = Some(2);
+        let mut loader = DualDataLoader::new(MockLoader::new("greptime"), second);
+        loader.chunk_size = 2;
+        loader.best_effort = true;
+        let mut report = loader.new_report();
+        loader
+            .load_record_batch("users", &batch(vec![1, 2, 3, 4, 5]), &mut report)
+            .await
+            .unwrap();
+        loader
+            .load_record_batch("pages", &batch(vec![6]), &mut report)
+            .await
+            .unwrap();
+
+        assert_eq!(loader.first.chunks().len(), 4);
+        assert_eq!(loader.second.chunks(), [("users".to_string(), vec![1, 2])]);
+
+        assert_eq!(report.reports[0].target, "greptime#1");
+        assert_eq!(report.reports[0].total_rows(), 6);
+        assert_eq!(report.reports[1].target, "greptime#2");
+        // The failing target keeps what it acknowledged before failing
+        assert_eq!(report.reports[1].tables.len(), 1);
+        assert_eq!(report.reports[1].tables[0].affected_rows, 2);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].target, "greptime#2");
+        assert_eq!(report.failures[0].table, "users");
+        assert!(!report.succeeded());
+    }
+
+    #[tokio::test]
+    async fn test_failure_aborts_without_best_effort() {
+        let mut first = MockLoader::new("greptime");
+        first.fail_at = Some(1);
+        let loader = DualDataLoader::new(first, MockLoader::new("influxdb"));
+        let mut report = loader.new_report();
+        let err = loader
+            .load_record_batch("users", &batch(vec![1]), &mut report)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, Error::LoadTable { .. }));
+        assert!(err.to_string().contains("users (greptime)"), "{}", err);
+    }
+}
@@ -1,169 +1,2531 @@
-use std::path::PathBuf;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use arrow::{
-    array::{RecordBatch, StringArray},
-    datatypes::{DataType, TimeUnit},
+    array::{ArrayRef, AsArray, RecordBatch, StringArray},
+    datatypes::{DataType, Schema, TimeUnit},
+};
+use futures::stream::{self, StreamExt};
+use greptime_proto::v1::{
+    ColumnSchema, Row, RowDeleteRequest, RowDeleteRequests, RowInsertRequest, RowInsertRequests,
+    Rows, SemanticType, Value,
 };
-use greptime_proto::v1::{ColumnSchema, Row, RowInsertRequest, RowInsertRequests, Rows};
+use log::{debug, info, warn};
+use prost::Message;
+use snafu::{ensure, OptionExt, ResultExt};
+use tokio::sync::{Semaphore, SemaphorePermit};
 
 use crate::{
-    client::greptime,
+    client::{
+        bulk::{bulk_insert_with, BulkErrorMode, BulkInsertOptions, InFlight},
+        ddl::CreateTableSpec,
+        greptime::{self, InsertOutcome},
+        metrics::LatencyRecorder,
+        writer::GreptimeWriter,
+    },
+    common::sql_executor::{open_sql_executor, SqlBackend, SqlExecutor},
     generator::{
         data_generator::{DataGenerator, UseCase},
         log_data_generator::LogDataGenerator,
     },
-    loader::data_loader::read_parquet_file,
-    usql::usql::Usql,
+    loader::data_loader::{read_csv_error, read_csv_file},
+    sql::quote::quote_ident,
+    usql::{output::OutputFormat, script::ScriptReport, usql::Usql},
 };
 
+use super::load_order::{dimension_tables, load_in_order, table_load_levels};
+use super::manifest::{load_unless_complete, LoadManifest, MANIFEST_FILE_NAME};
+use super::ndjson::NdjsonReader;
+use super::pacing::{AdaptivePacer, PacerPermit};
+use super::report::{IterationReport, LoadReport, PacingSample, PreLoadStep, TableLoadReport};
+use super::soak::SoakPlan;
+use super::verify::{verify_table, ExpectedTable};
 use super::{
-    data_loader::{execute_sql, DataLoader, DataLoaderConfig},
-    i32_value, string_value, timestamp_microsecond_value,
+    arrow_schema, binary_value, column_data_type,
+    data_loader::{execute_sql, load_csv_by_usql, DataLoader, DataLoaderConfig},
+    date_value, declared_schema, decode_dictionary, delete_keys, f32_value, i32_value, i64_value,
+    infer_schema, string_value, timestamp_microsecond_value, timestamp_millisecond_value,
+    timestamp_nanosecond_value, timestamp_second_value, u32_value, u64_value, MILLIS_PER_DAY,
 };
 
-use crate::common::error::Result;
+use crate::common::error::{
+    ConvertColumnSnafu, Error, LoadTableSnafu, Result, RowTooLargeSnafu, UnsupportedOperationSnafu,
+};
 
 pub struct GreptimeDataLoader {
     pub config: DataLoaderConfig,
     pub client: greptime::DatabaseClient,
+    // Sends unary inserts and creates tables, `client` unless replaced.
+    // Streams, deletes, drops and verification always go through `client`.
+    writer: Arc<dyn GreptimeWriter>,
+    // Latencies of the unary requests of `client`, for the load report
+    latency: Arc<LatencyRecorder>,
 }
 
 impl GreptimeDataLoader {
     pub fn new(use_case: UseCase, client: greptime::DatabaseClient) -> Self {
+        let latency = Arc::new(LatencyRecorder::default());
+        let client = client.with_metrics_hook(latency.clone());
         GreptimeDataLoader {
-            config: DataLoaderConfig { use_case },
+            config: DataLoaderConfig::new(use_case),
+            writer: Arc::new(client.clone()),
             client,
+            latency,
         }
     }
+
+    /// This loader sending unary inserts and creating tables through
+    /// `writer`, e.g. one that isn't backed by a server
+    pub fn with_writer(mut self, writer: Arc<dyn GreptimeWriter>) -> Self {
+        self.writer = writer;
+        self
+    }
+
     pub fn config(&self) -> &DataLoaderConfig {
         &self.config
     }
+
+    /// Client writing to the database `table_name` is routed to, the client's
+    /// own dbname for unmapped tables
+    pub fn client_for(&self, table_name: &str) -> Cow<'_, greptime::DatabaseClient> {
+        match self.config.table_databases.get(table_name) {
+            Some(dbname) => {
+                let mut client = self.client.clone();
+                client.set_dbname(dbname);
+                Cow::Owned(client)
+            }
+            None => Cow::Borrowed(&self.client),
+        }
+    }
+
+    // usql connected to the SQL endpoint of the config, or the sqlx connector
+    // when usql isn't installed
+    async fn sql_executor(&self) -> Result<SqlBackend> {
+        open_sql_executor(&self.config.usql_program, &self.config.sql_connection).await
+    }
+
+    // Load the configured CSV file through `executor`, named as the protocol
+    // of the report
+    async fn load_csv_through(&self, executor: &mut impl SqlExecutor) -> Result<LoadReport> {
+        let (table_report, latency) = load_csv_by_usql(
+            executor,
+            self.config.sql_dialect,
+            &self.config.csv_path,
+            &self.config.csv_table_name,
+            &self.config.statement_limits(),
+            &self.config.usql_batching(),
+        )
+        .await
+        .context(LoadTableSnafu {
+            table: &self.config.csv_table_name,
+        })?;
+
+        let mut report = LoadReport::new("greptime", executor.name(), PreLoadStep::None);
+        report.tables.push(table_report);
+        report.latency = latency;
+        Ok(report)
+    }
+
+    // usql connected to the SQL endpoint of the config
+    fn usql(&self) -> Result<Usql> {
+        Usql::connect(
+            &self.config.usql_program,
+            &self.config.sql_connection,
+            OutputFormat::Csv,
+        )
+    }
+
+    /// Writer writing to the database `table_name` is routed to, like
+    /// `client_for`
+    pub fn writer_for(&self, table_name: &str) -> Arc<dyn GreptimeWriter> {
+        match self.config.table_databases.get(table_name) {
+            Some(dbname) => self.writer.with_dbname(dbname),
+            None => self.writer.clone(),
+        }
+    }
 }
 
 impl DataLoader for GreptimeDataLoader {
-    fn load_data_by_usql(&self, usql: Option<Usql>) {
-        // TODO(Yue): Generate sql string from parquet file
-        // let mut usql_conn = usql.unwrap_or_else(|| Usql::new("mysql://127.0.0.1:4002"));
-
-        // let file = File::open("./data.gz").expect("Failed to open file");
-        // let gz = flate2::read::GzDecoder::new(file);
-        // let mut reader = BufReader::new(gz);
-
-        // // 读取 header
-        // let mut header = String::new();
-        // reader
-        //     .read_line(&mut header)
-        //     .expect("Failed to read header");
-        // let header = header.trim_end();
-        // let cols: Vec<&str> = header.split(',').collect();
-
-        // let create_table_stmt = gen_create_table_stmt("measurement", &cols);
-        // execute_sql(&mut usql_conn, &create_table_stmt);
-
-        // // 读取数据行
-        // let data: Vec<Vec<String>> = reader
-        //     .lines()
-        //     .map(|line| {
-        //         line.expect("Failed to read line")
-        //             .split(',')
-        //             .map(|v| v.trim().to_string())
-        //             .collect()
-        //     })
-        //     .collect();
-
-        // let insert_stmt = gen_insert_stmt("measurement", &cols, &data);
-        // execute_sql(&mut usql_conn, &insert_stmt);
-    }
-
-    fn load_data_from_raw_sql(&self, usql: Option<Usql>, raw_sql: &str) {
-        let mut usql_conn = usql.unwrap_or_else(|| Usql::new("mysql://127.0.0.1:4002"));
-        execute_sql(&mut usql_conn, raw_sql);
-    }
-
-    async fn load_data_from_parquet_file(&self) -> Result<Vec<u32>> {
-        // Read parquet file
-        let mut res = Vec::new();
-        for table_name in LogDataGenerator::table_names() {
+    async fn load_data_by_usql(&self, executor: Option<impl SqlExecutor>) -> Result<LoadReport> {
+        match executor {
+            Some(mut executor) => self.load_csv_through(&mut executor).await,
+            None => self.load_csv_through(&mut self.sql_executor().await?).await,
+        }
+    }
+
+    async fn load_data_from_raw_sql(
+        &self,
+        usql: Option<Usql>,
+        raw_sql: &str,
+    ) -> Result<ScriptReport> {
+        let mut usql_conn = match usql {
+            Some(usql) => usql,
+            None => self.usql()?,
+        };
+        Ok(usql_conn.execute_script(raw_sql))
+    }
+
+    async fn load_data_from_parquet_file(&self) -> Result<LoadReport> {
+        let started = Instant::now();
+        self.config.check_table_columns()?;
+        let manifest = Mutex::new(LoadManifest::open(MANIFEST_FILE_NAME)?);
+        match self.config.pre_load_step {
+            PreLoadStep::None => {}
+            PreLoadStep::Recreate => self.drop_tables().await?,
+            PreLoadStep::Truncate => self.truncate_tables().await?,
+        }
+        if self.config.pre_load_step != PreLoadStep::None {
+            // Tables the manifest lists as complete are empty now
+            manifest.lock().unwrap().clear("greptime")?;
+        }
+        if self.config.create_tables {
+            self.create_tables().await?;
+        }
+
+        // Dimension tables are loaded before the fact tables referencing them
+        let levels = table_load_levels::<LogDataGenerator>()?;
+        let manifest = &manifest;
+        let budget = &RequestBudget::new(&self.config);
+        let tables = load_in_order(&levels, self.config.workers, |table_name| async move {
+            let start = Instant::now();
             let path = PathBuf::from(format!("{}.parquet", table_name));
-            let record_batch = read_parquet_file(path)?;
-            let schema = match self.config.use_case {
-                UseCase::Log => LogDataGenerator::schema(table_name),
-                UseCase::Others => unimplemented!(),
+            let target_table = self.config.target_table_name(table_name);
+            let loaded = load_unless_complete(
+                manifest,
+                "greptime",
+                &target_table,
+                &path,
+                self.config.force,
+                || self.load_table_from_parquet_file(table_name, budget),
+            )
+            .await;
+            let (affected_rows, (expected, skipped_rows)) = match loaded {
+                Ok(Some(loaded)) => loaded,
+                Ok(None) => {
+                    info!("Skipping table {}, already loaded", table_name);
+                    return Ok(None);
+                }
+                Err(e) if e.is_unsupported_type() && self.config.skip_unsupported => {
+                    warn!("Skipping table {}: {}", table_name, e);
+                    return Ok(None);
+                }
+                Err(e) => return Err(e).context(LoadTableSnafu { table: table_name }),
+            };
+            let table_report = TableLoadReport {
+                table: target_table.clone(),
+                affected_rows,
+                elapsed: start.elapsed(),
+                skipped_rows,
+            };
+            let mismatches = if self.config.verify {
+                verify_table(&self.client_for(table_name), &target_table, &expected)
+                    .await
+                    .context(LoadTableSnafu { table: table_name })?
+            } else {
+                Vec::new()
             };
-            let insert_request = record_batch_to_insert_request(record_batch, table_name, schema)?;
-            let affected_rows = self.client.row_insert(insert_request).await?;
-            res.push(affected_rows);
+            // Deleted after verifying, which expects every row of the dataset
+            let delete_report = match self.config.delete_fraction {
+                Some(fraction) => self
+                    .delete_table_rows(table_name, fraction)
+                    .await
+                    .context(LoadTableSnafu { table: table_name })?,
+                None => None,
+            };
+            Ok(Some((table_report, mismatches, delete_report)))
+        })
+        .await?;
+
+        let mut report = LoadReport::new("greptime", self.protocol(), self.config.pre_load_step);
+        for (table_report, mismatches, delete_report) in tables.into_iter().flatten() {
+            report.tables.push(table_report);
+            report.mismatches.extend(mismatches);
+            report.deletes.extend(delete_report);
         }
-        Ok(res)
+        if self.config.is_soak_test() {
+            self.replay_fact_tables(&levels, budget, started, &mut report)
+                .await?;
+        }
+        self.fill_client_stats(&mut report, budget);
+        Ok(report)
+    }
+
+    async fn load_data_from_csv_file(&self) -> Result<LoadReport> {
+        let start = Instant::now();
+        let table_name = &self.config.csv_table_name;
+        let budget = RequestBudget::new(&self.config);
+        let affected_rows = self
+            .load_table_from_csv_file(table_name, &budget)
+            .await
+            .context(LoadTableSnafu { table: table_name })?;
+
+        let mut report = LoadReport::new("greptime", self.protocol(), PreLoadStep::None);
+        report.tables.push(TableLoadReport {
+            table: self.config.target_table_name(table_name),
+            affected_rows,
+            elapsed: start.elapsed(),
+            skipped_rows: 0,
+        });
+        self.fill_client_stats(&mut report, &budget);
+        Ok(report)
+    }
+
+    async fn load_data_from_ndjson_file(&self) -> Result<LoadReport> {
+        let start = Instant::now();
+        let table_name = &self.config.ndjson_table_name;
+        let budget = RequestBudget::new(&self.config);
+        let (affected_rows, malformed_lines) = self
+            .load_table_from_ndjson_file(table_name, &budget)
+            .await
+            .context(LoadTableSnafu { table: table_name })?;
+
+        let mut report = LoadReport::new("greptime", self.protocol(), PreLoadStep::None);
+        report.tables.push(TableLoadReport {
+            table: self.config.target_table_name(table_name),
+            affected_rows,
+            elapsed: start.elapsed(),
+            skipped_rows: 0,
+        });
+        report.malformed_lines = malformed_lines;
+        self.fill_client_stats(&mut report, &budget);
+        Ok(report)
+    }
+
+    fn target(&self) -> &'static str {
+        "greptime"
+    }
+
+    async fn write_chunk(&self, table_name: &str, chunk: &RecordBatch) -> Result<u32> {
+        let schema = self.config.table_schema(table_name, &chunk.schema())?;
+        let requests = chunk_insert_requests(
+            chunk.clone(),
+            &self.config.target_table_name(table_name),
+            schema,
+            chunk.num_rows(),
+            self.config.max_request_bytes,
+        );
+        let requests = stream::iter(requests.enumerate()).map(|(index, request)| {
+            request.map(|request| (request, ChunkInFlight::new(table_name, index)))
+        });
+        let writer = self.writer_for(table_name);
+        let outcome = bulk_insert_with(&*writer, requests, &self.bulk_options(1)).await?;
+        Ok(outcome.affected_rows())
     }
 }
 
-// Generate Insert grpc from GreptimeDB from RecordBatch
-fn record_batch_to_insert_request(
-    record_batch: RecordBatch,
-    table_name: &str,
-    schema: Vec<ColumnSchema>,
-) -> Result<RowInsertRequests> {
-    let mut rows = Vec::new();
+impl GreptimeDataLoader {
+    /// Drop every target table, tables that don't exist are ignored
+    pub async fn drop_tables(&self) -> Result<()> {
+        for table_name in LogDataGenerator::table_names() {
+            self.client_for(table_name)
+                .drop_table(&self.config.target_table_name(table_name))
+                .await
+                .context(LoadTableSnafu { table: table_name })?;
+        }
+        Ok(())
+    }
+
+    /// Truncate every target table through the SQL path, for servers that
+    /// support TRUNCATE
+    pub async fn truncate_tables(&self) -> Result<()> {
+        let mut executor = self.sql_executor().await?;
+        for table_name in LogDataGenerator::table_names() {
+            let dialect = self.config.sql_dialect;
+            let truncate_stmt = format!(
+                "TRUNCATE TABLE {}.{};",
+                quote_ident(dialect, self.client_for(table_name).dbname()),
+                quote_ident(dialect, &self.config.target_table_name(table_name))
+            );
+            execute_sql(&mut executor, &truncate_stmt)
+                .await
+                .context(LoadTableSnafu { table: table_name })?;
+        }
+        Ok(())
+    }
+
+    /// Create the database of the client and every database tables are
+    /// routed to, unless they exist
+    pub async fn ensure_databases(&self) -> Result<()> {
+        let mut dbnames = vec![self.client.dbname().as_str()];
+        dbnames.extend(self.config.table_databases.values().map(String::as_str));
+        dbnames.sort_unstable();
+        dbnames.dedup();
+        for dbname in dbnames {
+            let mut client = self.client.clone();
+            client.set_dbname(dbname);
+            client.ensure_database().await?;
+        }
+        Ok(())
+    }
+
+    /// Create every target table with `IF NOT EXISTS` semantics, so tag and
+    /// time index declarations don't depend on auto-create-on-insert
+    pub async fn create_tables(&self) -> Result<()> {
+        for table_name in LogDataGenerator::table_names() {
+            self.create_table(table_name)
+                .await
+                .context(LoadTableSnafu { table: table_name })?;
+        }
+        Ok(())
+    }
+
+    async fn create_table(&self, table_name: &str) -> Result<u32> {
+        let schema = match self.config.use_case {
+            // Declared, there is nothing to infer from
+            UseCase::Log => self.config.table_schema(table_name, &Schema::empty())?,
+            UseCase::Others => {
+                infer_schema(table_name, &self.config.read_table(table_name)?.0.schema())?
+            }
+        };
+        create_table_if_not_exists(
+            &*self.writer_for(table_name),
+            &self.config.target_table_name(table_name),
+            schema,
+        )
+        .await
+    }
+
+    // Returns the acknowledged rows along with what the table should hold and
+    // the rows outside the time range
+    async fn load_table_from_parquet_file(
+        &self,
+        table_name: &str,
+        budget: &RequestBudget,
+    ) -> Result<(u32, (ExpectedTable, usize))> {
+        // Read parquet file
+        let (record_batch, skipped_rows) = self.config.read_table(table_name)?;
+        let schema = self
+            .config
+            .table_schema(table_name, &record_batch.schema())?;
+        let expected = ExpectedTable::from_record_batch(&record_batch, &schema)?;
+
+        let target_table = self.config.target_table_name(table_name);
+        let chunks = chunk_insert_requests(
+            record_batch,
+            &target_table,
+            schema,
+            self.config.chunk_size,
+            self.config.max_request_bytes,
+        );
+        let affected_rows = self.insert_chunks(table_name, chunks, budget).await?;
+        Ok((affected_rows, (expected, skipped_rows)))
+    }
+
+    // Delete `fraction` of the rows of the parquet file of `table_name` from its
+    // target table, none for a table without a time index
+    async fn delete_table_rows(
+        &self,
+        table_name: &str,
+        fraction: f64,
+    ) -> Result<Option<TableLoadReport>> {
+        let (record_batch, _) = self.config.read_table(table_name)?;
+        let schema = self
+            .config
+            .table_schema(table_name, &record_batch.schema())?;
+        self.delete_batch_rows(table_name, &record_batch, &schema, fraction)
+            .await
+    }
 
-    for row_index in 0..record_batch.num_rows() {
-        let mut values = Vec::new();
+    // Delete `fraction` of the rows of `record_batch` by their keys, in requests
+    // of `chunk_size` rows. Only the requests are timed.
+    async fn delete_batch_rows(
+        &self,
+        table_name: &str,
+        record_batch: &RecordBatch,
+        schema: &[ColumnSchema],
+        fraction: f64,
+    ) -> Result<Option<TableLoadReport>> {
+        let Some((keys, key_schema)) = delete_keys(record_batch, schema, fraction)? else {
+            warn!(
+                "Not deleting from table {}, it has no time index",
+                table_name
+            );
+            return Ok(None);
+        };
 
-        for col in record_batch.columns() {
-            let value = match col.data_type() {
-                DataType::Utf8 => {
-                    let array = col.as_any().downcast_ref::<StringArray>().unwrap();
-                    string_value(array.value(row_index).to_string())
+        let client = self.client_for(table_name);
+        let target_table = self.config.target_table_name(table_name);
+        let chunks = chunk_insert_requests(
+            keys,
+            &target_table,
+            key_schema,
+            self.config.chunk_size,
+            self.config.max_request_bytes,
+        );
+        let start = Instant::now();
+        let mut affected_rows = 0;
+        for chunk in chunks {
+            affected_rows += client.row_delete(delete_request(chunk?)).await?;
+        }
+        Ok(Some(TableLoadReport {
+            table: target_table,
+            affected_rows,
+            elapsed: start.elapsed(),
+            skipped_rows: 0,
+        }))
+    }
+
+    // Returns the acknowledged rows. Columns are typed by the use case schema of
+    // the table when it declares one, otherwise after the CSV file itself.
+    async fn load_table_from_csv_file(
+        &self,
+        table_name: &str,
+        budget: &RequestBudget,
+    ) -> Result<u32> {
+        let declared = declared_schema(&self.config.use_case, table_name);
+        let reader = read_csv_file(
+            &self.config.csv_path,
+            declared.as_deref().map(arrow_schema).transpose()?,
+            self.config.csv_timestamp_unit,
+            self.config.chunk_size,
+        )?;
+        let schema = match declared {
+            Some(schema) => schema,
+            None => infer_schema(table_name, &reader.schema())?,
+        };
+
+        let target_table = self.config.target_table_name(table_name);
+        if self.config.create_tables {
+            create_table_if_not_exists(
+                &*self.writer_for(table_name),
+                &target_table,
+                schema.clone(),
+            )
+            .await?;
+        }
+        // The reader yields batches of `chunk_size` rows, cut further by the
+        // byte limit
+        let chunks = reader.flat_map(|batch| {
+            match batch.map_err(|e| read_csv_error(&self.config.csv_path, e)) {
+                Ok(batch) => self.chunk_batch(batch, &target_table, schema.clone()),
+                Err(e) => vec![Err(e)],
+            }
+        });
+        self.insert_chunks(table_name, chunks, budget).await
+    }
+
+    // Returns the acknowledged rows and the malformed lines skipped. Lines are
+    // read into the use case schema of the table, there is nothing to infer
+    // one from.
+    async fn load_table_from_ndjson_file(
+        &self,
+        table_name: &str,
+        budget: &RequestBudget,
+    ) -> Result<(u32, usize)> {
+        let schema = declared_schema(&self.config.use_case, table_name).context(
+            UnsupportedOperationSnafu {
+                target: "NDJSON loading",
+                operation: format!("table {} without a declared schema", table_name),
+            },
+        )?;
+        let mut reader = NdjsonReader::open(
+            &self.config.ndjson_path,
+            Arc::new(arrow_schema(&schema)?),
+            self.config.chunk_size,
+            self.config.strict,
+            self.config.max_malformed_lines,
+        )?;
+
+        let target_table = self.config.target_table_name(table_name);
+        if self.config.create_tables {
+            create_table_if_not_exists(
+                &*self.writer_for(table_name),
+                &target_table,
+                schema.clone(),
+            )
+            .await?;
+        }
+        let chunks = reader.by_ref().flat_map(|batch| match batch {
+            Ok(batch) => self.chunk_batch(batch, &target_table, schema.clone()),
+            Err(e) => vec![Err(e)],
+        });
+        let affected_rows = self.insert_chunks(table_name, chunks, budget).await?;
+        Ok((affected_rows, reader.malformed_lines()))
+    }
+
+    // Replay the fact tables loaded by the first pass of a soak test, shifted
+    // past the previous pass every time, until the configured passes or
+    // duration are reached. Dimension tables are only loaded once.
+    async fn replay_fact_tables(
+        &self,
+        levels: &[Vec<&'static str>],
+        budget: &RequestBudget,
+        started: Instant,
+        report: &mut LoadReport,
+    ) -> Result<()> {
+        let dimensions = dimension_tables::<LogDataGenerator>();
+        let loaded = |table: &str| {
+            let target_table = self.config.target_table_name(table);
+            report.tables.iter().any(|t| t.table == target_table)
+        };
+        let fact_levels = levels
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .copied()
+                    .filter(|table| !dimensions.contains(table) && loaded(table))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|level| !level.is_empty())
+            .collect::<Vec<_>>();
+
+        // Fact tables are read once and kept for every pass
+        let mut tables = HashMap::new();
+        for table_name in fact_levels.concat() {
+            let (batch, _) = self
+                .config
+                .read_table(table_name)
+                .context(LoadTableSnafu { table: table_name })?;
+            let schema = self.config.table_schema(table_name, &batch.schema())?;
+            tables.insert(table_name, (batch, schema));
+        }
+        let dimension_ids = dimensions
+            .iter()
+            .flat_map(|table| LogDataGenerator::schema(table))
+            .filter(|col| col.semantic_type == SemanticType::Tag as i32)
+            .map(|col| col.column_name)
+            .collect();
+        let batches = tables.values().map(|(batch, _)| batch).collect::<Vec<_>>();
+        let plan = SoakPlan::new(&batches, dimension_ids)?;
+
+        report.iterations.push(IterationReport {
+            iteration: 1,
+            rows: report.total_rows(),
+            elapsed: report.total_elapsed(),
+        });
+        let (tables, plan) = (&tables, &plan);
+        let mut passes = 1;
+        while self.config.another_pass(passes, started.elapsed()) {
+            let start = Instant::now();
+            let replayed =
+                load_in_order(&fact_levels, self.config.workers, |table_name| async move {
+                    let table_start = Instant::now();
+                    let (batch, schema) = &tables[table_name];
+                    let affected_rows = async {
+                        let chunks = chunk_insert_requests(
+                            plan.shift(batch, passes)?,
+                            &self.config.target_table_name(table_name),
+                            schema.clone(),
+                            self.config.chunk_size,
+                            self.config.max_request_bytes,
+                        );
+                        self.insert_chunks(table_name, chunks, budget).await
+                    }
+                    .await
+                    .context(LoadTableSnafu { table: table_name })?;
+                    Ok((table_name, affected_rows, table_start.elapsed()))
+                })
+                .await?;
+
+            let mut rows = 0;
+            for (table_name, affected_rows, elapsed) in replayed {
+                rows += affected_rows as u64;
+                let target_table = self.config.target_table_name(table_name);
+                if let Some(table) = report.tables.iter_mut().find(|t| t.table == target_table) {
+                    table.affected_rows += affected_rows;
+                    table.elapsed += elapsed;
                 }
-                DataType::Int32 => {
-                    let array = col
-                        .as_any()
-                        .downcast_ref::<arrow::array::Int32Array>()
-                        .unwrap();
-                    i32_value(array.value(row_index))
+            }
+            passes += 1;
+            report.iterations.push(IterationReport {
+                iteration: passes,
+                rows,
+                elapsed: start.elapsed(),
+            });
+        }
+        Ok(())
+    }
+
+    // Insert requests of a batch read in `chunk_size` rows, the batch is
+    // already as long as a chunk so only the byte limit cuts it further
+    fn chunk_batch(
+        &self,
+        batch: RecordBatch,
+        table_name: &str,
+        schema: Vec<ColumnSchema>,
+    ) -> Vec<Result<RowInsertRequests>> {
+        let num_rows = batch.num_rows();
+        chunk_insert_requests(
+            batch,
+            table_name,
+            schema,
+            num_rows,
+            self.config.max_request_bytes,
+        )
+        .collect()
+    }
+
+    // Send insert requests for `table_name` over one client-streaming RPC, or
+    // one unary RPC each through a bulk insert, returning the acknowledged rows.
+    // A unary request holds its bytes of `budget` until it's acknowledged, a
+    // streamed one until it's handed to the stream. Under adaptive pacing unary
+    // requests of the table are sent concurrently, as many as the pacer admits.
+    async fn insert_chunks(
+        &self,
+        table_name: &str,
+        chunks: impl Iterator<Item = Result<RowInsertRequests>>,
+        budget: &RequestBudget,
+    ) -> Result<u32> {
+        let mut affected_rows = 0;
+        if self.config.streaming {
+            let mut stream = self.client_for(table_name).row_insert_stream();
+            for chunk in chunks {
+                let chunk = chunk?;
+                let _permit = budget.acquire(chunk.encoded_len()).await;
+                stream.insert(chunk).await?;
+            }
+            affected_rows = stream.finish().await?;
+        } else {
+            let pacer = budget.pacer.as_ref();
+            let concurrency = pacer.map_or(1, AdaptivePacer::max_concurrency);
+            let requests = stream::iter(chunks.enumerate()).then(|(index, chunk)| async move {
+                let chunk = chunk?;
+                let permit = budget.acquire(chunk.encoded_len()).await;
+                let slot = match pacer {
+                    Some(pacer) => Some((pacer, pacer.acquire().await)),
+                    None => None,
+                };
+                let in_flight = ChunkInFlight {
+                    table_name,
+                    index,
+                    _permit: Some(permit),
+                    slot,
+                };
+                Ok((chunk, in_flight))
+            });
+            let writer = self.writer_for(table_name);
+            affected_rows = bulk_insert_with(&*writer, requests, &self.bulk_options(concurrency))
+                .await?
+                .affected_rows();
+        }
+        Ok(affected_rows)
+    }
+
+    // Bulk inserts of `concurrency` requests failing fast, each retried as configured
+    fn bulk_options(&self, concurrency: usize) -> BulkInsertOptions {
+        BulkInsertOptions {
+            concurrency,
+            mode: BulkErrorMode::FailFast,
+            retry: self.config.retry,
+        }
+    }
+
+    fn protocol(&self) -> &'static str {
+        if self.config.streaming {
+            "grpc-stream"
+        } else {
+            "grpc"
+        }
+    }
+
+    // What the client and `budget` saw of the requests of a load, whatever
+    // file it loaded
+    fn fill_client_stats(&self, report: &mut LoadReport, budget: &RequestBudget) {
+        report.pacing = budget.pacing_samples();
+        report.channels = self.client.pool_size();
+        report.latency = self.latency.histogram();
+        report.compression = self.client.compression().map(|c| c.as_str().to_string());
+    }
+}
+
+// Encoded bytes of insert requests in flight across the workers of a load,
+// `max_request_bytes` per request that may be in flight. Permits are KiB, a
+// semaphore holds fewer permits than a byte count may need. Under adaptive
+// pacing the number of requests in flight is paced by their latency as well.
+struct RequestBudget {
+    semaphore: Semaphore,
+    permits: u32,
+    pacer: Option<AdaptivePacer>,
+}
+
+impl RequestBudget {
+    fn new(config: &DataLoaderConfig) -> Self {
+        let pacer = config
+            .adaptive
+            .then(|| AdaptivePacer::new(config.target_latency, config.max_concurrency));
+        let requests = match &pacer {
+            Some(pacer) => pacer.max_concurrency().max(config.workers),
+            None => config.workers,
+        };
+        let permits = kib(requests.max(1).saturating_mul(config.max_request_bytes));
+        RequestBudget {
+            semaphore: Semaphore::new(permits as usize),
+            permits,
+            pacer,
+        }
+    }
+
+    // Steps adaptive pacing took so far, none without it
+    fn pacing_samples(&self) -> Vec<PacingSample> {
+        self.pacer
+            .as_ref()
+            .map(AdaptivePacer::samples)
+            .unwrap_or_default()
+    }
+
+    // Wait until `bytes` more fit the budget. A request is never larger than
+    // the share of one worker, capping keeps an oversized one from waiting forever.
+    async fn acquire(&self, bytes: usize) -> SemaphorePermit<'_> {
+        self.semaphore
+            .acquire_many(kib(bytes).min(self.permits))
+            .await
+            .expect("request budget semaphore is never closed")
+    }
+}
+
+// Chunk `index` of a table sent by a bulk insert, holding its bytes of the
+// request budget and its slot of the pacer until it's answered
+struct ChunkInFlight<'a> {
+    table_name: &'a str,
+    index: usize,
+    _permit: Option<SemaphorePermit<'a>>,
+    slot: Option<(&'a AdaptivePacer, PacerPermit<'a>)>,
+}
+
+impl<'a> ChunkInFlight<'a> {
+    fn new(table_name: &'a str, index: usize) -> Self {
+        ChunkInFlight {
+            table_name,
+            index,
+            _permit: None,
+            slot: None,
+        }
+    }
+}
+
+impl InFlight for ChunkInFlight<'_> {
+    fn finished(self, latency: Duration, result: &Result<InsertOutcome>) {
+        match result {
+            Ok(outcome) => {
+                match outcome.request_id {
+                    Some(request_id) => debug!(
+                        "Chunk {} of table {} written by request {} in {} attempts",
+                        self.index, self.table_name, request_id, outcome.attempts
+                    ),
+                    None => debug!(
+                        "Chunk {} of table {} written in {} attempts",
+                        self.index, self.table_name, outcome.attempts
+                    ),
                 }
-                DataType::Timestamp(TimeUnit::Microsecond, None) => {
-                    let array = col
-                        .as_any()
-                        .downcast_ref::<arrow::array::TimestampMicrosecondArray>()
-                        .unwrap();
-                    timestamp_microsecond_value(array.value(row_index))
+                if let Some((pacer, _)) = &self.slot {
+                    pacer.record(latency);
                 }
-                _ => {
-                    unimplemented!()
+            }
+            Err(e) => debug!(
+                "Chunk {} of table {} failed: {}",
+                self.index, self.table_name, e
+            ),
+        }
+    }
+}
+
+// Create a table unless it exists, a table that exists is left as it is
+async fn create_table_if_not_exists(
+    writer: &dyn GreptimeWriter,
+    table_name: &str,
+    schema: Vec<ColumnSchema>,
+) -> Result<u32> {
+    match writer
+        .create_table(CreateTableSpec::new(table_name, schema))
+        .await
+    {
+        Err(Error::TableAlreadyExists { .. }) => Ok(0),
+        created => created,
+    }
+}
+
+fn kib(bytes: usize) -> u32 {
+    bytes.div_ceil(1024).min(u32::MAX as usize) as u32
+}
+
+// Insert requests of at most `chunk_size` rows and `max_request_bytes` encoded
+// bytes each, converted lazily so only the chunk being sent is held as proto rows
+fn chunk_insert_requests(
+    record_batch: RecordBatch,
+    table_name: &str,
+    schema: Vec<ColumnSchema>,
+    chunk_size: usize,
+    max_request_bytes: usize,
+) -> InsertChunks {
+    let (columns, error) = match record_batch
+        .columns()
+        .iter()
+        .map(decode_dictionary)
+        .collect::<Result<Vec<_>>>()
+    {
+        Ok(columns) => (columns, None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    // Only the types `column_data_type` maps, the SQL conversion takes the same
+    let error = error.or_else(|| {
+        columns
+            .iter()
+            .zip(record_batch.schema().fields())
+            .find(|(col, _)| column_data_type(col.data_type()).is_none())
+            .map(|(col, col_field)| {
+                ConvertColumnSnafu {
+                    table: table_name,
+                    column: col_field.name(),
+                    row: None::<usize>,
+                    reason: format!("unsupported data type {}", col.data_type()),
                 }
+                .build()
+            })
+    });
+    let time_index = schema
+        .iter()
+        .find(|col| col.semantic_type == SemanticType::Timestamp as i32)
+        .and_then(|col| record_batch.schema().index_of(&col.column_name).ok());
+    let empty = insert_request(table_name, schema, Vec::new());
+    InsertChunks {
+        // The length prefixes of `Rows` and `RowInsertRequest` grow by up to 4
+        // bytes each as rows are added
+        base_len: empty.encoded_len() + 8,
+        empty,
+        num_rows: if error.is_some() {
+            0
+        } else {
+            record_batch.num_rows()
+        },
+        record_batch,
+        columns,
+        time_index,
+        error,
+        chunk_size: chunk_size.max(1),
+        max_request_bytes,
+        row_index: 0,
+        pending: None,
+    }
+}
+
+struct InsertChunks {
+    record_batch: RecordBatch,
+    // Columns of `record_batch` with dictionaries decoded
+    columns: Vec<ArrayRef>,
+    // Index of the time index column in `columns`, it can't hold nulls
+    time_index: Option<usize>,
+    // Failure decoding the columns, yielded as the first item
+    error: Option<Error>,
+    // Request without rows, the template of every chunk
+    empty: RowInsertRequests,
+    // Encoded length of `empty` plus the slack of its length prefixes
+    base_len: usize,
+    num_rows: usize,
+    chunk_size: usize,
+    max_request_bytes: usize,
+    row_index: usize,
+    // Row converted but cut from the previous chunk by the byte limit
+    pending: Option<Row>,
+}
+
+impl InsertChunks {
+    fn next_request(&mut self) -> Result<RowInsertRequests> {
+        let mut rows = Vec::new();
+        let mut request_bytes = self.base_len;
+        while rows.len() < self.chunk_size {
+            let row = match self.pending.take() {
+                Some(row) => row,
+                None if self.row_index < self.num_rows => {
+                    let row = batch_row(
+                        &self.empty.inserts[0].table_name,
+                        &self.record_batch,
+                        &self.columns,
+                        self.time_index,
+                        self.row_index,
+                    )?;
+                    self.row_index += 1;
+                    row
+                }
+                None => break,
             };
-            values.push(value);
+            // Field key, length prefix and the row itself
+            let row_len = row.encoded_len();
+            let row_bytes = 1 + prost::length_delimiter_len(row_len) + row_len;
+            if request_bytes + row_bytes > self.max_request_bytes {
+                if rows.is_empty() {
+                    return RowTooLargeSnafu {
+                        table: self.empty.inserts[0].table_name.clone(),
+                        row: self.row_index - 1,
+                        bytes: self.base_len + row_bytes,
+                        max: self.max_request_bytes,
+                    }
+                    .fail();
+                }
+                self.pending = Some(row);
+                break;
+            }
+            request_bytes += row_bytes;
+            rows.push(row);
         }
 
-        rows.push(Row { values });
+        let mut request = self.empty.clone();
+        if let Some(chunk) = request.inserts[0].rows.as_mut() {
+            chunk.rows = rows;
+        }
+        Ok(request)
     }
+}
+
+impl Iterator for InsertChunks {
+    type Item = Result<RowInsertRequests>;
 
-    Ok(RowInsertRequests {
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        if self.pending.is_none() && self.row_index >= self.num_rows {
+            return None;
+        }
+        let request = self.next_request();
+        if request.is_err() {
+            // Nothing follows a failed chunk
+            self.pending = None;
+            self.row_index = self.num_rows;
+        }
+        Some(request)
+    }
+}
+
+// Row `row_index` of the decoded `columns` of `record_batch`, loaded into
+// `table`. Null slots are sent as a `Value` without data instead of the
+// array's default value, except in the `time_index` column.
+fn batch_row(
+    table: &str,
+    record_batch: &RecordBatch,
+    columns: &[ArrayRef],
+    time_index: Option<usize>,
+    row_index: usize,
+) -> Result<Row> {
+    let mut values = Vec::new();
+    for (i, (col, col_field)) in columns
+        .iter()
+        .zip(record_batch.schema().fields())
+        .enumerate()
+    {
+        if col.is_null(row_index) {
+            ensure!(
+                time_index != Some(i),
+                ConvertColumnSnafu {
+                    table,
+                    column: col_field.name(),
+                    row: row_index,
+                    reason: "null in the time index",
+                }
+            );
+            values.push(Value { value_data: None });
+            continue;
+        }
+        let value = match col.data_type() {
+            DataType::Utf8 => {
+                let array = col.as_any().downcast_ref::<StringArray>().unwrap();
+                string_value(array.value(row_index).to_string())
+            }
+            DataType::LargeUtf8 => {
+                string_value(col.as_string::<i64>().value(row_index).to_string())
+            }
+            DataType::Int32 => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::Int32Array>()
+                    .unwrap();
+                i32_value(array.value(row_index))
+            }
+            DataType::Int64 => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::Int64Array>()
+                    .unwrap();
+                i64_value(array.value(row_index))
+            }
+            DataType::UInt32 => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::UInt32Array>()
+                    .unwrap();
+                u32_value(array.value(row_index))
+            }
+            DataType::UInt64 => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::UInt64Array>()
+                    .unwrap();
+                u64_value(array.value(row_index))
+            }
+            DataType::Float32 => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::Float32Array>()
+                    .unwrap();
+                f32_value(array.value(row_index))
+            }
+            // Arrow stores zoned timestamps as UTC epoch values, so the timezone is
+            // dropped and the value is sent as UTC
+            DataType::Timestamp(TimeUnit::Second, _) => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::TimestampSecondArray>()
+                    .unwrap();
+                timestamp_second_value(array.value(row_index))
+            }
+            DataType::Timestamp(TimeUnit::Millisecond, _) => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::TimestampMillisecondArray>()
+                    .unwrap();
+                timestamp_millisecond_value(array.value(row_index))
+            }
+            DataType::Timestamp(TimeUnit::Microsecond, _) => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::TimestampMicrosecondArray>()
+                    .unwrap();
+                timestamp_microsecond_value(array.value(row_index))
+            }
+            DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::TimestampNanosecondArray>()
+                    .unwrap();
+                timestamp_nanosecond_value(array.value(row_index))
+            }
+            DataType::Date32 => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::Date32Array>()
+                    .unwrap();
+                date_value(array.value(row_index))
+            }
+            // Date64 holds milliseconds since epoch but is still a calendar date,
+            // so it is truncated to days and sent as a Greptime date as well
+            DataType::Date64 => {
+                let array = col
+                    .as_any()
+                    .downcast_ref::<arrow::array::Date64Array>()
+                    .unwrap();
+                date_value(array.value(row_index).div_euclid(MILLIS_PER_DAY) as i32)
+            }
+            DataType::Binary => binary_value(col.as_binary::<i32>().value(row_index).to_vec()),
+            DataType::LargeBinary => binary_value(col.as_binary::<i64>().value(row_index).to_vec()),
+            datatype => {
+                return ConvertColumnSnafu {
+                    table,
+                    column: col_field.name(),
+                    row: None::<usize>,
+                    reason: format!("unsupported data type {}", datatype),
+                }
+                .fail();
+            }
+        };
+        values.push(value);
+    }
+    Ok(Row { values })
+}
+
+fn insert_request(
+    table_name: &str,
+    schema: Vec<ColumnSchema>,
+    rows: Vec<Row>,
+) -> RowInsertRequests {
+    RowInsertRequests {
         inserts: vec![RowInsertRequest {
             table_name: table_name.to_string(),
             rows: Some(Rows { schema, rows }),
         }],
-    })
+    }
+}
+
+// Delete request for the rows of an insert request holding only their keys
+fn delete_request(requests: RowInsertRequests) -> RowDeleteRequests {
+    RowDeleteRequests {
+        deletes: requests
+            .inserts
+            .into_iter()
+            .map(|insert| RowDeleteRequest {
+                table_name: insert.table_name,
+                rows: insert.rows,
+            })
+            .collect(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    // use std::path::PathBuf;
-
-    // use crate::client::greptime::DatabaseClient;
-    // use crate::common::error::Result;
-    // use crate::generator::data_generator::UseCase;
-    // use crate::loader::data_loader::DataLoader;
-    // use crate::loader::greptime_data_loader::GreptimeDataLoader;
-
-    // #[tokio::test]
-    // async fn test_row_insert() -> Result<()> {
-    //     let client = DatabaseClient::new("my_db_demo".to_string()).await?;
-    //     let loader = GreptimeDataLoader::new(UseCase::Log, client);
-    //     let path = PathBuf::from("./data.parquet");
-    //     let affected_rows = loader.load_data_from_parquet_file(path).await?;
-    //     assert!(affected_rows > 0, "Affected rows should greater than 0");
-    //     Ok(())
-    // }
+    use std::collections::BTreeMap;
+
+    use arrow::{
+        array::{
+            BinaryArray, Date32Array, Date64Array, DictionaryArray, Int32Array, Int64Array,
+            LargeBinaryArray, LargeStringArray, TimestampMicrosecondArray,
+            TimestampMillisecondArray, TimestampNanosecondArray, TimestampSecondArray, UInt32Array,
+            UInt64Array,
+        },
+        datatypes::{Field, Int32Type, Schema},
+    };
+    use greptime_proto::v1::{greptime_request::Request, ColumnDataType};
+    use parquet::arrow::ArrowWriter;
+    use tonic::{Code, Status};
+
+    use super::*;
+    use crate::client::{
+        greptime::DatabaseClient, mock::MockDatabase, mock_writer::MockWriter, retry::RetryPolicy,
+    };
+    use crate::loader::data_loader::{
+        read_parquet_columns, read_parquet_file, ColumnMapping, SqlValue,
+    };
+    use crate::loader::{field, infer_schema, record_batch_to_sql_rows, tag, timestamp};
+
+    // The whole batch as a single request
+    fn record_batch_to_insert_request(
+        record_batch: RecordBatch,
+        table_name: &str,
+        schema: Vec<ColumnSchema>,
+    ) -> Result<RowInsertRequests> {
+        let num_rows = record_batch.num_rows();
+        chunk_insert_requests(record_batch, table_name, schema, num_rows, usize::MAX)
+            .next()
+            .unwrap()
+    }
+
+    fn int_batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i32", DataType::Int32, false),
+            Field::new("i64", DataType::Int64, false),
+            Field::new("u32", DataType::UInt32, false),
+            Field::new("u64", DataType::UInt64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, i32::MAX])),
+                Arc::new(Int64Array::from(vec![i32::MAX as i64 + 1, i64::MAX])),
+                Arc::new(UInt32Array::from(vec![i32::MAX as u32 + 1, u32::MAX])),
+                Arc::new(UInt64Array::from(vec![u32::MAX as u64 + 1, u64::MAX])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_record_batch_to_insert_request_wide_integers() {
+        let batch = int_batch();
+        let schema = infer_schema("ints", &batch.schema()).unwrap();
+        let request = record_batch_to_insert_request(batch, "ints", schema).unwrap();
+
+        let rows = request.inserts[0].rows.as_ref().unwrap();
+        assert_eq!(rows.rows.len(), 2);
+        assert_eq!(
+            rows.rows[0].values,
+            vec![
+                i32_value(1),
+                i64_value(i32::MAX as i64 + 1),
+                u32_value(i32::MAX as u32 + 1),
+                u64_value(u32::MAX as u64 + 1),
+            ]
+        );
+        assert_eq!(
+            rows.rows[1].values,
+            vec![
+                i32_value(i32::MAX),
+                i64_value(i64::MAX),
+                u32_value(u32::MAX),
+                u64_value(u64::MAX),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_batch_to_insert_request_float32() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "temperature",
+            DataType::Float32,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(arrow::array::Float32Array::from(vec![
+                Some(-0.5),
+                None,
+            ]))],
+        )
+        .unwrap();
+        let schema = infer_schema("readings", &batch.schema()).unwrap();
+        assert_eq!(schema, vec![field("temperature", ColumnDataType::Float32)]);
+        let request = record_batch_to_insert_request(batch, "readings", schema).unwrap();
+
+        let rows = request.inserts[0].rows.as_ref().unwrap();
+        assert_eq!(rows.rows[0].values, vec![f32_value(-0.5)]);
+        assert_eq!(rows.rows[1].values, vec![Value { value_data: None }]);
+    }
+
+    #[tokio::test]
+    async fn test_table_database_routing() {
+        use greptime_proto::v1::ddl_request::Expr as DdlExpr;
+
+        let database = MockDatabase::default();
+        let client = DatabaseClient::connect(&database.start().await, "public")
+            .await
+            .unwrap();
+        let mut loader = GreptimeDataLoader::new(UseCase::Log, client);
+        for table in ["users", "pages", "devices"] {
+            loader
+                .config
+                .table_databases
+                .insert(table.to_string(), "meta_db".to_string());
+        }
+        loader
+            .config
+            .table_databases
+            .insert("web_logs".to_string(), "bench_db".to_string());
+        loader.create_tables().await.unwrap();
+        loader.drop_tables().await.unwrap();
+
+        let requests = database.requests.lock().unwrap();
+        let observed = requests
+            .iter()
+            .map(|(dbname, request)| match request {
+                Request::Ddl(ddl) => match ddl.expr.as_ref().unwrap() {
+                    DdlExpr::CreateTable(expr) => {
+                        assert_eq!(&expr.schema_name, dbname);
+                        (dbname.as_str(), expr.table_name.as_str())
+                    }
+                    DdlExpr::DropTable(expr) => {
+                        assert_eq!(&expr.schema_name, dbname);
+                        (dbname.as_str(), expr.table_name.as_str())
+                    }
+                    other => panic!("unexpected DDL {:?}", other),
+                },
+                other => panic!("unexpected request {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            ("meta_db", "users"),
+            ("meta_db", "pages"),
+            ("meta_db", "devices"),
+            ("bench_db", "web_logs"),
+            ("public", "requests"),
+            ("public", "responses"),
+            ("public", "error_logs"),
+        ];
+        assert_eq!(observed[..7], expected);
+        assert_eq!(observed[7..], expected);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_databases() {
+        use greptime_proto::v1::ddl_request::Expr as DdlExpr;
+
+        let (database, mut loader) = mock_loader(UseCase::Log).await;
+        for (table, dbname) in [
+            ("users", "meta_db"),
+            ("pages", "meta_db"),
+            ("web_logs", "bench_db"),
+        ] {
+            loader
+                .config
+                .table_databases
+                .insert(table.to_string(), dbname.to_string());
+        }
+        loader.ensure_databases().await.unwrap();
+
+        let created = database
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(dbname, request)| match request {
+                Request::Ddl(ddl) => match ddl.expr.as_ref().unwrap() {
+                    DdlExpr::CreateDatabase(expr) => {
+                        assert_eq!(&expr.schema_name, dbname);
+                        dbname.clone()
+                    }
+                    other => panic!("unexpected DDL {:?}", other),
+                },
+                other => panic!("unexpected request {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(created, ["bench_db", "meta_db", "public"]);
+    }
+
+    // Load `csv` through a mock database and return the recorded row inserts
+    async fn mock_loader(use_case: UseCase) -> (MockDatabase, GreptimeDataLoader) {
+        let database = MockDatabase::default();
+        let client = DatabaseClient::connect(&database.start().await, "public")
+            .await
+            .unwrap();
+        (database, GreptimeDataLoader::new(use_case, client))
+    }
+
+    fn recorded_inserts(database: &MockDatabase) -> Vec<RowInsertRequest> {
+        database
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|(_, request)| match request {
+                Request::RowInserts(requests) => requests.inserts.clone(),
+                other => panic!("unexpected request {:?}", other),
+            })
+            .collect()
+    }
+
+    // Rows of `host`, `ts` and `value` columns, `host` cycling through two hosts
+    fn keyed_batch(num_rows: i64) -> (RecordBatch, Vec<ColumnSchema>) {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("host", DataType::Utf8, false),
+                Field::new(
+                    "ts",
+                    DataType::Timestamp(TimeUnit::Millisecond, None),
+                    false,
+                ),
+                Field::new("value", DataType::Int64, true),
+            ])),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    (0..num_rows).map(|i| format!("host_{}", i % 2)),
+                )),
+                Arc::new(TimestampMillisecondArray::from_iter_values(0..num_rows)),
+                Arc::new(Int64Array::from_iter_values(0..num_rows)),
+            ],
+        )
+        .unwrap();
+        let schema = vec![
+            tag("host", ColumnDataType::String),
+            timestamp("ts", ColumnDataType::TimestampMillisecond),
+            field("value", ColumnDataType::Int64),
+        ];
+        (batch, schema)
+    }
+
+    #[test]
+    fn test_delete_keys() {
+        let (batch, schema) = keyed_batch(10);
+        let (keys, key_schema) = delete_keys(&batch, &schema, 0.3).unwrap().unwrap();
+        assert_eq!(key_schema, schema[..2]);
+        assert_eq!(keys.num_columns(), 2);
+        let ts = keys
+            .column(1)
+            .as_primitive::<arrow::datatypes::TimestampMillisecondType>();
+        assert_eq!(ts.values().to_vec(), [3, 6, 9]);
+
+        assert_eq!(
+            delete_keys(&batch, &schema, 0.0)
+                .unwrap()
+                .unwrap()
+                .0
+                .num_rows(),
+            0
+        );
+        assert_eq!(
+            delete_keys(&batch, &schema, 1.0)
+                .unwrap()
+                .unwrap()
+                .0
+                .num_rows(),
+            10
+        );
+        // Rows without a time index aren't keyed
+        assert!(delete_keys(&batch, &[schema[0].clone()], 0.5)
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_delete_batch_rows() {
+        let (database, mut loader) = mock_loader(UseCase::Log).await;
+        loader.config.chunk_size = 2;
+        let (batch, schema) = keyed_batch(8);
+        let report = loader
+            .delete_batch_rows("metrics", &batch, &schema, 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.table, "metrics");
+        assert_eq!(report.affected_rows, 4);
+
+        let requests = database.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let mut deleted = Vec::new();
+        for (_, request) in requests.iter() {
+            let Request::RowDeletes(requests) = request else {
+                panic!("expected a delete, got {:?}", request);
+            };
+            let rows = requests.deletes[0].rows.as_ref().unwrap();
+            assert_eq!(requests.deletes[0].table_name, "metrics");
+            assert_eq!(rows.schema, schema[..2]);
+            deleted.extend(rows.rows.iter().map(|row| row.values.clone()));
+        }
+        // Every other row, keyed by its host and timestamp
+        let expected = [1, 3, 5, 7]
+            .into_iter()
+            .map(|i| {
+                vec![
+                    string_value(format!("host_{}", i % 2)),
+                    timestamp_millisecond_value(i),
+                ]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(deleted, expected);
+    }
+
+    async fn load_csv(
+        use_case: UseCase,
+        table: &str,
+        csv: &str,
+        chunk_size: usize,
+    ) -> (LoadReport, Vec<RowInsertRequest>) {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join(format!("{}.csv", table));
+        std::fs::write(&csv_path, csv).unwrap();
+
+        let (database, mut loader) = mock_loader(use_case).await;
+        loader.config.csv_path = csv_path;
+        loader.config.csv_table_name = table.to_string();
+        loader.config.chunk_size = chunk_size;
+        let report = loader.load_data_from_csv_file().await.unwrap();
+        (report, recorded_inserts(&database))
+    }
+
+    #[tokio::test]
+    async fn test_load_csv_inferred_schema() {
+        let csv = "ts,host,cpu\n\
+                   2024-01-01T00:00:00Z,a,1\n\
+                   2024-01-01T00:00:01.5+00:00,b,\n\
+                   2024-01-01T08:00:02+08:00,c,3\n";
+        let (report, inserts) = load_csv(UseCase::Others, "cpu", csv, 2).await;
+
+        assert_eq!(report.tables[0].table, "cpu");
+        assert_eq!(report.tables[0].affected_rows, 3);
+        // Chunked like the parquet path
+        assert_eq!(inserts.len(), 2);
+        // The latency of every chunk is in the report
+        assert_eq!(report.latency.requests, 2);
+        assert_eq!(report.latency.rows, 3);
+        let rows = inserts
+            .iter()
+            .flat_map(|insert| insert.rows.as_ref().unwrap().rows.clone())
+            .map(|row| row.values)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    timestamp_millisecond_value(1_704_067_200_000),
+                    string_value("a".to_string()),
+                    i64_value(1),
+                ],
+                vec![
+                    timestamp_millisecond_value(1_704_067_201_500),
+                    string_value("b".to_string()),
+                    Value { value_data: None },
+                ],
+                vec![
+                    timestamp_millisecond_value(1_704_067_202_000),
+                    string_value("c".to_string()),
+                    i64_value(3),
+                ],
+            ]
+        );
+        assert_eq!(
+            inserts[0].rows.as_ref().unwrap().schema,
+            vec![
+                timestamp("ts", ColumnDataType::TimestampMillisecond),
+                field("host", ColumnDataType::String),
+                field("cpu", ColumnDataType::Int64),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_csv_declared_schema() {
+        let csv = "user_id,username,email,signup_date\n\
+                   7,ada,ada@example.com,2024-01-01T00:00:00.000001Z\n";
+        let (report, inserts) = load_csv(UseCase::Log, "users", csv, 1000).await;
+
+        assert_eq!(report.tables[0].affected_rows, 1);
+        let rows = inserts[0].rows.as_ref().unwrap();
+        assert_eq!(rows.schema, LogDataGenerator::schema("users"));
+        assert_eq!(
+            rows.rows[0].values,
+            vec![
+                i32_value(7),
+                string_value("ada".to_string()),
+                string_value("ada@example.com".to_string()),
+                timestamp_microsecond_value(1_704_067_200_000_001),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_csv_adaptive_pacing() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("cpu.csv");
+        let csv = (0..10).fold("ts,cpu\n".to_string(), |csv, i| {
+            csv + &format!("2024-01-01T00:00:{:02}Z,{}\n", i, i)
+        });
+        std::fs::write(&csv_path, csv).unwrap();
+
+        let (database, mut loader) = mock_loader(UseCase::Others).await;
+        loader.config.csv_path = csv_path;
+        loader.config.csv_table_name = "cpu".to_string();
+        loader.config.chunk_size = 1;
+        loader.config.adaptive = true;
+        loader.config.target_latency = Duration::from_secs(10);
+        let report = loader.load_data_from_csv_file().await.unwrap();
+
+        assert_eq!(report.tables[0].affected_rows, 10);
+        assert_eq!(recorded_inserts(&database).len(), 10);
+        // A window of 4 requests, then 4 more as 2 were in flight
+        let concurrency = report
+            .pacing
+            .iter()
+            .map(|sample| sample.concurrency)
+            .collect::<Vec<_>>();
+        assert_eq!(concurrency, [2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_load_ndjson() {
+        let dir = tempfile::tempdir().unwrap();
+        let ndjson_path = dir.path().join("devices.ndjson");
+        std::fs::write(
+            &ndjson_path,
+            "{\"device_id\": \"1\", \"browser\": \"firefox\"}\n\
+             {\"browser\": \"no id\"}\n\
+             {\"device_id\": 2}\n",
+        )
+        .unwrap();
+
+        let (database, mut loader) = mock_loader(UseCase::Log).await;
+        loader.config.ndjson_path = ndjson_path;
+        loader.config.ndjson_table_name = "devices".to_string();
+        loader.config.max_malformed_lines = 1;
+        let report = loader.load_data_from_ndjson_file().await.unwrap();
+
+        assert_eq!(report.total_rows(), 2);
+        assert_eq!(report.malformed_lines, 1);
+        let inserts = recorded_inserts(&database);
+        let rows = inserts[0].rows.as_ref().unwrap();
+        assert_eq!(rows.schema, LogDataGenerator::schema("devices"));
+        assert_eq!(
+            rows.rows
+                .iter()
+                .map(|row| row.values.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                vec![i32_value(1), string_value("firefox".to_string())],
+                vec![i32_value(2), Value { value_data: None }],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_ndjson_undeclared_table() {
+        let (_database, mut loader) = mock_loader(UseCase::Others).await;
+        loader.config.ndjson_table_name = "events".to_string();
+        let err = loader.load_data_from_ndjson_file().await.unwrap_err();
+        assert!(
+            err.to_string().contains("without a declared schema"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_chunk_insert_requests() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)])),
+            vec![Arc::new(Int32Array::from_iter_values(0..5))],
+        )
+        .unwrap();
+        let schema = infer_schema("ids", &batch.schema()).unwrap();
+
+        let chunks = chunk_insert_requests(batch, "ids", schema, 2, usize::MAX)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        let ids = chunks
+            .iter()
+            .map(|chunk| {
+                let rows = chunk.inserts[0].rows.as_ref().unwrap();
+                rows.rows.iter().map(|row| row.values[0].clone()).collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+        assert_eq!(
+            ids,
+            vec![
+                vec![i32_value(0), i32_value(1)],
+                vec![i32_value(2), i32_value(3)],
+                vec![i32_value(4)],
+            ]
+        );
+    }
+
+    // Rows of a 100 KiB message each, with their ids
+    fn oversized_batch(num_rows: i32) -> RecordBatch {
+        let message = "x".repeat(100 * 1024);
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("message", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..num_rows)),
+                Arc::new(StringArray::from(vec![message; num_rows as usize])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_chunk_insert_requests_byte_limit() {
+        let batch = oversized_batch(22);
+        let schema = infer_schema("logs", &batch.schema()).unwrap();
+        let max_request_bytes = 512 * 1024;
+
+        let chunks = chunk_insert_requests(batch, "logs", schema, 10, max_request_bytes)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        for chunk in &chunks {
+            assert!(
+                chunk.encoded_len() <= max_request_bytes,
+                "{}",
+                chunk.encoded_len()
+            );
+        }
+        // Five rows fit the limit, every row is sent once and in order
+        let lengths = chunks
+            .iter()
+            .map(|chunk| chunk.inserts[0].rows.as_ref().unwrap().rows.len())
+            .collect::<Vec<_>>();
+        assert_eq!(lengths, [5, 5, 5, 5, 2]);
+        let values = chunks
+            .iter()
+            .flat_map(|chunk| chunk.inserts[0].rows.as_ref().unwrap().rows.clone())
+            .map(|row| row.values[0].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..22).map(i32_value).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_chunk_insert_requests_row_over_limit() {
+        let batch = oversized_batch(3);
+        let schema = infer_schema("logs", &batch.schema()).unwrap();
+
+        let mut chunks = chunk_insert_requests(batch, "logs", schema, 10, 64 * 1024);
+        let err = chunks.next().unwrap().unwrap_err();
+        assert!(matches!(err, Error::RowTooLarge { row: 0, .. }), "{}", err);
+        assert!(chunks.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_chunk_byte_limit() {
+        let (database, mut loader) = mock_loader(UseCase::Others).await;
+        loader.config.max_request_bytes = 256 * 1024;
+        let affected_rows = loader
+            .write_chunk("logs", &oversized_batch(5))
+            .await
+            .unwrap();
+
+        assert_eq!(affected_rows, 5);
+        let requests = database.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        for (_, request) in requests.iter() {
+            assert!(request.encoded_len() <= 256 * 1024);
+        }
+    }
+
+    #[test]
+    fn test_record_batch_to_insert_request_nulls() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("size", DataType::Int64, true),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), None])),
+                Arc::new(StringArray::from(vec![None, Some("b")])),
+                Arc::new(Int64Array::from(vec![None, Some(2)])),
+                Arc::new(TimestampMicrosecondArray::from(vec![Some(10), None])),
+            ],
+        )
+        .unwrap();
+        // A null time index is rejected, see `test_null_time_index`
+        let mut schema = infer_schema("nulls", &batch.schema()).unwrap();
+        schema[3] = field("ts", ColumnDataType::TimestampMicrosecond);
+        let request = record_batch_to_insert_request(batch, "nulls", schema).unwrap();
+
+        let null = Value { value_data: None };
+        let rows = request.inserts[0].rows.as_ref().unwrap();
+        assert_eq!(
+            rows.rows[0].values,
+            vec![
+                i32_value(1),
+                null.clone(),
+                null.clone(),
+                timestamp_microsecond_value(10),
+            ]
+        );
+        assert_eq!(
+            rows.rows[1].values,
+            vec![
+                null.clone(),
+                string_value("b".to_string()),
+                i64_value(2),
+                null
+            ]
+        );
+    }
+
+    fn single_timestamp_column(array: ArrayRef) -> RowInsertRequests {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "ts",
+            array.data_type().clone(),
+            false,
+        )]));
+        let batch = RecordBatch::try_new(schema, vec![array]).unwrap();
+        let schema = infer_schema("ts", &batch.schema()).unwrap();
+        record_batch_to_insert_request(batch, "ts", schema).unwrap()
+    }
+
+    fn first_value(request: &RowInsertRequests) -> &Value {
+        &request.inserts[0].rows.as_ref().unwrap().rows[0].values[0]
+    }
+
+    fn first_column(request: &RowInsertRequests) -> &ColumnSchema {
+        &request.inserts[0].rows.as_ref().unwrap().schema[0]
+    }
+
+    #[test]
+    fn test_timestamp_second() {
+        let request = single_timestamp_column(Arc::new(TimestampSecondArray::from(vec![1])));
+        assert_eq!(first_value(&request), &timestamp_second_value(1));
+        assert_eq!(
+            first_column(&request),
+            &timestamp("ts", ColumnDataType::TimestampSecond)
+        );
+    }
+
+    #[test]
+    fn test_timestamp_millisecond() {
+        let request =
+            single_timestamp_column(Arc::new(TimestampMillisecondArray::from(vec![1_000])));
+        assert_eq!(first_value(&request), &timestamp_millisecond_value(1_000));
+        assert_eq!(
+            first_column(&request),
+            &timestamp("ts", ColumnDataType::TimestampMillisecond)
+        );
+    }
+
+    #[test]
+    fn test_timestamp_microsecond() {
+        let request =
+            single_timestamp_column(Arc::new(TimestampMicrosecondArray::from(vec![1_000_000])));
+        assert_eq!(
+            first_value(&request),
+            &timestamp_microsecond_value(1_000_000)
+        );
+        assert_eq!(
+            first_column(&request),
+            &timestamp("ts", ColumnDataType::TimestampMicrosecond)
+        );
+    }
+
+    #[test]
+    fn test_timestamp_nanosecond() {
+        let request = single_timestamp_column(Arc::new(TimestampNanosecondArray::from(vec![
+            1_000_000_000,
+        ])));
+        assert_eq!(
+            first_value(&request),
+            &timestamp_nanosecond_value(1_000_000_000)
+        );
+        assert_eq!(
+            first_column(&request),
+            &timestamp("ts", ColumnDataType::TimestampNanosecond)
+        );
+    }
+
+    #[test]
+    fn test_timestamp_with_timezone_is_utc() {
+        // 2024-01-01T08:00:00+08:00 is stored as 2024-01-01T00:00:00Z
+        let array = TimestampMillisecondArray::from(vec![1_704_067_200_000])
+            .with_timezone("+08:00".to_string());
+        let request = single_timestamp_column(Arc::new(array));
+        assert_eq!(
+            first_value(&request),
+            &timestamp_millisecond_value(1_704_067_200_000)
+        );
+        assert_eq!(
+            first_column(&request),
+            &timestamp("ts", ColumnDataType::TimestampMillisecond)
+        );
+    }
+
+    #[test]
+    fn test_dictionary_strings_from_parquet() {
+        let dictionary: DictionaryArray<Int32Type> =
+            vec!["GET", "POST", "GET"].into_iter().collect();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("method", dictionary.data_type().clone(), false),
+            Field::new("url", DataType::LargeUtf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(dictionary),
+                Arc::new(LargeStringArray::from(vec!["/a", "/b", "/c"])),
+            ],
+        )
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dictionary.parquet");
+        let mut writer =
+            ArrowWriter::try_new(std::fs::File::create(&path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let batch = read_parquet_file(path).unwrap();
+        assert!(matches!(
+            batch.schema().field(0).data_type(),
+            DataType::Dictionary(_, _)
+        ));
+        let schema = infer_schema("t", &batch.schema()).unwrap();
+        assert_eq!(
+            schema,
+            vec![
+                field("method", ColumnDataType::String),
+                field("url", ColumnDataType::String),
+            ]
+        );
+
+        let request = record_batch_to_insert_request(batch, "requests", schema).unwrap();
+        let rows = &request.inserts[0].rows.as_ref().unwrap().rows;
+        let expected = [("GET", "/a"), ("POST", "/b"), ("GET", "/c")];
+        for (row, (method, url)) in rows.iter().zip(expected) {
+            assert_eq!(
+                row.values,
+                vec![
+                    string_value(method.to_string()),
+                    string_value(url.to_string())
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_projected_columns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("email", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(StringArray::from(vec!["a@x", "b@x"])),
+            ],
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("accounts.parquet");
+        let mut writer =
+            ArrowWriter::try_new(std::fs::File::create(&path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let mut config = DataLoaderConfig::new(UseCase::Others);
+        let columns = vec![
+            ColumnMapping::parse("email"),
+            ColumnMapping::parse("id -> account_id"),
+        ];
+        config
+            .table_columns
+            .insert("accounts".to_string(), columns.clone());
+        let batch = read_parquet_columns(path, Some(&columns)).unwrap();
+        let schema = config.table_schema("accounts", &batch.schema()).unwrap();
+        assert_eq!(
+            schema,
+            vec![
+                field("email", ColumnDataType::String),
+                field("account_id", ColumnDataType::Int32),
+            ]
+        );
+
+        let request = record_batch_to_insert_request(batch, "accounts", schema).unwrap();
+        let rows = &request.inserts[0].rows.as_ref().unwrap().rows;
+        assert_eq!(
+            rows[1].values,
+            vec![string_value("b@x".to_string()), i32_value(2)]
+        );
+    }
+
+    #[test]
+    fn test_projected_declared_schema() {
+        let mut config = DataLoaderConfig::new(UseCase::Log);
+        config.table_columns.insert(
+            "users".to_string(),
+            vec![
+                ColumnMapping::parse("signup_date"),
+                ColumnMapping::parse("uid -> user_id"),
+            ],
+        );
+        let schema = config.table_schema("users", &Schema::empty()).unwrap();
+        assert_eq!(
+            schema,
+            vec![
+                timestamp("signup_date", ColumnDataType::TimestampMicrosecond),
+                tag("user_id", ColumnDataType::Int32),
+            ]
+        );
+
+        config
+            .table_columns
+            .insert("users".to_string(), vec![ColumnMapping::parse("nickname")]);
+        let err = config.table_schema("users", &Schema::empty()).unwrap_err();
+        assert!(matches!(err, Error::UnknownColumn { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_dates_and_binary_round_trip() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("signup_date", DataType::Date32, false),
+            Field::new("created_date", DataType::Date64, false),
+            Field::new("payload", DataType::Binary, false),
+            Field::new("large_payload", DataType::LargeBinary, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Date32Array::from(vec![19_723, -1])),
+                Arc::new(Date64Array::from(vec![19_723 * MILLIS_PER_DAY + 1, -1])),
+                Arc::new(BinaryArray::from_vec(vec![b"abc".as_ref(), b"".as_ref()])),
+                Arc::new(LargeBinaryArray::from_vec(vec![
+                    b"\x00\xff".as_ref(),
+                    b"z".as_ref(),
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let schema = infer_schema("t", &batch.schema()).unwrap();
+        assert_eq!(
+            schema,
+            vec![
+                field("signup_date", ColumnDataType::Date),
+                field("created_date", ColumnDataType::Date),
+                field("payload", ColumnDataType::Binary),
+                field("large_payload", ColumnDataType::Binary),
+            ]
+        );
+
+        let request = record_batch_to_insert_request(batch, "dates", schema).unwrap();
+        let rows = &request.inserts[0].rows.as_ref().unwrap().rows;
+        assert_eq!(
+            rows[0].values,
+            vec![
+                date_value(19_723),
+                date_value(19_723),
+                binary_value(b"abc".to_vec()),
+                binary_value(vec![0x00, 0xff]),
+            ]
+        );
+        assert_eq!(
+            rows[1].values,
+            vec![
+                date_value(-1),
+                date_value(-1),
+                binary_value(vec![]),
+                binary_value(b"z".to_vec()),
+            ]
+        );
+    }
+
+    fn float64_batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "ratio",
+            DataType::Float64,
+            false,
+        )]));
+        RecordBatch::try_new(
+            schema,
+            vec![Arc::new(arrow::array::Float64Array::from(vec![0.5]))],
+        )
+        .unwrap()
+    }
+
+    // Table, column, row and reason of a `ConvertColumn` error
+    fn conversion_failure(err: &Error) -> (&str, &str, Option<usize>, &str) {
+        match err.conversion_failure() {
+            Some(Error::ConvertColumn {
+                table,
+                column,
+                row,
+                reason,
+                ..
+            }) => (table, column, *row, reason),
+            _ => panic!("not a conversion failure: {}", err),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_type_names_column() {
+        let err = record_batch_to_insert_request(float64_batch(), "ratios", vec![]).unwrap_err();
+        assert!(err.is_unsupported_type());
+        assert_eq!(
+            conversion_failure(&err),
+            ("ratios", "ratio", None, "unsupported data type Float64")
+        );
+    }
+
+    #[test]
+    fn test_infer_schema_unsupported_type() {
+        let err = infer_schema("ratios", &float64_batch().schema()).unwrap_err();
+        assert!(err.is_unsupported_type());
+        assert_eq!(
+            err.to_string(),
+            "Failed to convert column ratio of table ratios: unsupported data type Float64"
+        );
+    }
+
+    #[test]
+    fn test_null_time_index() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("host", DataType::Utf8, false),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                Arc::new(TimestampMillisecondArray::from(vec![
+                    Some(1),
+                    Some(2),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+        let schema = vec![
+            tag("host", ColumnDataType::String),
+            timestamp("ts", ColumnDataType::TimestampMillisecond),
+        ];
+
+        let err = record_batch_to_insert_request(batch, "hosts", schema).unwrap_err();
+        assert!(!err.is_unsupported_type());
+        assert_eq!(
+            conversion_failure(&err),
+            ("hosts", "ts", Some(2), "null in the time index")
+        );
+        let err = Err::<(), _>(err)
+            .context(LoadTableSnafu { table: "hosts" })
+            .unwrap_err();
+        assert_eq!(conversion_failure(&err).2, Some(2));
+        assert!(
+            err.to_string()
+                .contains("Failed to convert column ts of table hosts at row 2"),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(feature = "integration")]
+    #[tokio::test]
+    async fn test_create_table_and_load() {
+        let client = DatabaseClient::new("public").await.unwrap();
+        let schema = LogDataGenerator::schema("devices");
+        client.drop_table("it_devices").await.unwrap();
+        let spec = CreateTableSpec::new("it_devices", schema.clone());
+        client.create_table(spec.clone()).await.unwrap();
+        let err = client.create_table(spec.clone()).await.unwrap_err();
+        assert!(matches!(err, Error::TableAlreadyExists { .. }), "{}", err);
+        // The loader leaves a table that exists as it is
+        assert_eq!(
+            create_table_if_not_exists(&client, "it_devices", spec.columns)
+                .await
+                .unwrap(),
+            0
+        );
+
+        let arrow_schema = Arc::new(Schema::new(vec![
+            Field::new("device_id", DataType::Int32, false),
+            Field::new("browser", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            arrow_schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            ],
+        )
+        .unwrap();
+        let request = record_batch_to_insert_request(batch, "it_devices", schema).unwrap();
+        assert_eq!(client.row_insert(request).await.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_infer_schema_wide_integers() {
+        let batch = int_batch();
+        assert_eq!(
+            infer_schema("ints", &batch.schema()).unwrap(),
+            vec![
+                field("i32", ColumnDataType::Int32),
+                field("i64", ColumnDataType::Int64),
+                field("u32", ColumnDataType::Uint32),
+                field("u64", ColumnDataType::Uint64),
+            ]
+        );
+    }
+
+    // A loader writing through a `MockWriter`, its client is never connected
+    fn writer_loader(use_case: UseCase) -> (MockWriter, GreptimeDataLoader) {
+        let writer = MockWriter::new("public");
+        let client = DatabaseClient::builder()
+            .dbname("public")
+            .build_lazy()
+            .unwrap();
+        let loader =
+            GreptimeDataLoader::new(use_case, client).with_writer(Arc::new(writer.clone()));
+        (writer, loader)
+    }
+
+    fn fast_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            jitter: 0.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_chunk_through_writer() {
+        let (writer, mut loader) = writer_loader(UseCase::Others);
+        loader.config.max_request_bytes = 256 * 1024;
+        loader
+            .config
+            .table_databases
+            .insert("ints".to_string(), "bench_db".to_string());
+
+        let affected_rows = loader
+            .write_chunk("logs", &oversized_batch(5))
+            .await
+            .unwrap();
+        assert_eq!(affected_rows, 5);
+        assert_eq!(loader.write_chunk("ints", &int_batch()).await.unwrap(), 2);
+
+        // Chunked by the byte limit, routed by table
+        let inserts = writer.inserts.lock().unwrap();
+        let dbnames = inserts
+            .iter()
+            .map(|(dbname, _)| dbname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(dbnames, ["public", "public", "public", "bench_db"]);
+        for (_, requests) in inserts.iter() {
+            assert!(requests.encoded_len() <= 256 * 1024);
+        }
+        drop(inserts);
+        assert_eq!(
+            writer.table_rows(),
+            BTreeMap::from([("ints".to_string(), 2), ("logs".to_string(), 5)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_chunk_retries() {
+        let (writer, mut loader) = writer_loader(UseCase::Others);
+        loader.config.retry = fast_retries(3);
+        writer.fail_next(Status::unavailable("restarting"));
+        writer.fail_next(Status::resource_exhausted("busy"));
+
+        assert_eq!(loader.write_chunk("ints", &int_batch()).await.unwrap(), 2);
+        // Recorded once, by the attempt that succeeded
+        assert_eq!(writer.inserts.lock().unwrap().len(), 1);
+
+        // Out of attempts
+        for _ in 0..3 {
+            writer.fail_next(Status::unavailable("down"));
+        }
+        let err = loader.write_chunk("ints", &int_batch()).await.unwrap_err();
+        assert!(
+            matches!(err, Error::RetriesExhausted { attempts: 3, .. }),
+            "{}",
+            err
+        );
+        assert_eq!(writer.table_rows()["ints"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_write_chunk_error_propagation() {
+        let (writer, mut loader) = writer_loader(UseCase::Others);
+        loader.config.max_request_bytes = 256 * 1024;
+        loader.config.retry = fast_retries(3);
+        writer.fail_next(Status::invalid_argument("bad column"));
+
+        // Not retried, and the chunks after the failed one aren't sent
+        let err = loader
+            .write_chunk("logs", &oversized_batch(5))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::Server { status, .. } if status.code() == Code::InvalidArgument),
+            "{}",
+            err
+        );
+        assert!(writer.inserts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_load_csv_through_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("cpu.csv");
+        let csv = (0..5).fold("ts,cpu\n".to_string(), |csv, i| {
+            csv + &format!("2024-01-01T00:00:{:02}Z,{}\n", i, i)
+        });
+        std::fs::write(&csv_path, csv).unwrap();
+
+        let (writer, mut loader) = writer_loader(UseCase::Others);
+        loader.config.csv_path = csv_path;
+        loader.config.csv_table_name = "cpu".to_string();
+        loader.config.chunk_size = 2;
+        loader.config.create_tables = true;
+        loader.config.table_prefix = "bench_".to_string();
+        let report = loader.load_data_from_csv_file().await.unwrap();
+
+        assert_eq!(report.tables[0].table, "bench_cpu");
+        assert_eq!(report.tables[0].affected_rows, 5);
+        assert_eq!(writer.inserts.lock().unwrap().len(), 3);
+        assert_eq!(
+            writer.table_rows(),
+            BTreeMap::from([("bench_cpu".to_string(), 5)])
+        );
+        let tables = writer.tables.lock().unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].1.table_name, "bench_cpu");
+        drop(tables);
+
+        // The table exists the second time and is left as it is, a failure to
+        // create it fails the load
+        loader.load_data_from_csv_file().await.unwrap();
+        assert_eq!(writer.tables.lock().unwrap().len(), 1);
+        writer.fail_next(Status::permission_denied("read only"));
+        let err = loader.load_data_from_csv_file().await.unwrap_err();
+        assert!(matches!(err, Error::LoadTable { .. }), "{}", err);
+        assert_eq!(writer.table_rows()["bench_cpu"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_create_tables_through_writer() {
+        let (writer, mut loader) = writer_loader(UseCase::Log);
+        loader
+            .config
+            .table_databases
+            .insert("users".to_string(), "meta_db".to_string());
+        loader.create_tables().await.unwrap();
+        loader.create_tables().await.unwrap();
+
+        let tables = writer.tables.lock().unwrap();
+        let created = tables
+            .iter()
+            .map(|(dbname, spec)| (dbname.as_str(), spec.table_name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(created.len(), LogDataGenerator::table_names().len());
+        assert_eq!(created[0], ("meta_db", "users"));
+        assert!(created[1..].iter().all(|(dbname, _)| *dbname == "public"));
+        assert_eq!(tables[0].1.columns, LogDataGenerator::schema("users"));
+    }
+
+    // A value of either conversion, to check both read every column alike
+    #[derive(Debug, PartialEq)]
+    enum Cell {
+        Null,
+        Int(i128),
+        Float(f32),
+        String(String),
+        Binary(Vec<u8>),
+        Date(chrono::NaiveDate),
+        Timestamp(chrono::NaiveDateTime),
+    }
+
+    fn proto_cell(value: &Value) -> Cell {
+        use greptime_proto::v1::value::ValueData;
+
+        let epoch = chrono::DateTime::UNIX_EPOCH;
+        let nanos = |nanos: i64| {
+            Cell::Timestamp((epoch + chrono::Duration::nanoseconds(nanos)).naive_utc())
+        };
+        match value.value_data.clone() {
+            None => Cell::Null,
+            Some(ValueData::I32Value(v)) => Cell::Int(v.into()),
+            Some(ValueData::I64Value(v)) => Cell::Int(v.into()),
+            Some(ValueData::U32Value(v)) => Cell::Int(v.into()),
+            Some(ValueData::U64Value(v)) => Cell::Int(v.into()),
+            Some(ValueData::F32Value(v)) => Cell::Float(v),
+            Some(ValueData::StringValue(v)) => Cell::String(v),
+            Some(ValueData::BinaryValue(v)) => Cell::Binary(v),
+            Some(ValueData::DateValue(v)) => {
+                Cell::Date(epoch.date_naive() + chrono::Duration::days(v.into()))
+            }
+            Some(ValueData::TimestampSecondValue(v)) => nanos(v * 1_000_000_000),
+            Some(ValueData::TimestampMillisecondValue(v)) => nanos(v * 1_000_000),
+            Some(ValueData::TimestampMicrosecondValue(v)) => nanos(v * 1_000),
+            Some(ValueData::TimestampNanosecondValue(v)) => nanos(v),
+            Some(other) => panic!("unexpected {:?}", other),
+        }
+    }
+
+    fn sql_cell(value: &SqlValue) -> Cell {
+        match value {
+            SqlValue::Null => Cell::Null,
+            SqlValue::Int(v) => Cell::Int((*v).into()),
+            SqlValue::UInt(v) => Cell::Int((*v).into()),
+            SqlValue::Float32(v) => Cell::Float(*v),
+            SqlValue::String(v) => Cell::String(v.clone()),
+            SqlValue::Binary(v) => Cell::Binary(v.clone()),
+            SqlValue::Date(v) => Cell::Date(v.parse().unwrap()),
+            SqlValue::Timestamp(v) => Cell::Timestamp(v.parse().unwrap()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_proto_and_sql_rows_agree() {
+        let cell_ts = |text: &str| {
+            Cell::Timestamp(
+                chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f").unwrap(),
+            )
+        };
+        let cell_date = |text: &str| Cell::Date(text.parse().unwrap());
+        let cases: Vec<(ArrayRef, Vec<Cell>)> = vec![
+            (
+                Arc::new(StringArray::from(vec![Some("it's"), None])),
+                vec![Cell::String("it's".to_string()), Cell::Null],
+            ),
+            (
+                Arc::new(LargeStringArray::from(vec![Some("/a"), None])),
+                vec![Cell::String("/a".to_string()), Cell::Null],
+            ),
+            (
+                Arc::new(Int32Array::from(vec![Some(i32::MIN), None])),
+                vec![Cell::Int(i32::MIN.into()), Cell::Null],
+            ),
+            (
+                Arc::new(Int64Array::from(vec![None, Some(i64::MAX)])),
+                vec![Cell::Null, Cell::Int(i64::MAX.into())],
+            ),
+            (
+                Arc::new(UInt32Array::from(vec![Some(u32::MAX), None])),
+                vec![Cell::Int(u32::MAX.into()), Cell::Null],
+            ),
+            (
+                Arc::new(UInt64Array::from(vec![Some(u64::MAX), None])),
+                vec![Cell::Int(u64::MAX.into()), Cell::Null],
+            ),
+            (
+                Arc::new(arrow::array::Float32Array::from(vec![Some(-0.5), None])),
+                vec![Cell::Float(-0.5), Cell::Null],
+            ),
+            (
+                Arc::new(BinaryArray::from(vec![Some(b"\x00'".as_ref()), None])),
+                vec![Cell::Binary(b"\x00'".to_vec()), Cell::Null],
+            ),
+            (
+                Arc::new(Date32Array::from(vec![Some(19_723), Some(-1)])),
+                vec![cell_date("2024-01-01"), cell_date("1969-12-31")],
+            ),
+            (
+                Arc::new(Date64Array::from(vec![
+                    Some(19_723 * MILLIS_PER_DAY + 1),
+                    None,
+                ])),
+                vec![cell_date("2024-01-01"), Cell::Null],
+            ),
+            (
+                Arc::new(TimestampSecondArray::from(vec![Some(1), None])),
+                vec![cell_ts("1970-01-01 00:00:01"), Cell::Null],
+            ),
+            (
+                Arc::new(TimestampMillisecondArray::from(vec![Some(-1), None])),
+                vec![cell_ts("1969-12-31 23:59:59.999"), Cell::Null],
+            ),
+            (
+                Arc::new(
+                    TimestampMicrosecondArray::from(vec![Some(1_000_001), None])
+                        .with_timezone("+08:00".to_string()),
+                ),
+                vec![cell_ts("1970-01-01 00:00:01.000001"), Cell::Null],
+            ),
+            (
+                Arc::new(TimestampNanosecondArray::from(vec![Some(1), None])),
+                vec![cell_ts("1970-01-01 00:00:00.000000001"), Cell::Null],
+            ),
+        ];
+        for (array, expected) in cases {
+            let data_type = array.data_type().clone();
+            let schema = Arc::new(Schema::new(vec![Field::new("c", data_type.clone(), true)]));
+            let batch = RecordBatch::try_new(schema, vec![array]).unwrap();
+
+            // Timestamps as fields, a null time index is rejected
+            let schema = infer_schema("t", &batch.schema())
+                .unwrap()
+                .into_iter()
+                .map(|col| field(&col.column_name, col.datatype()))
+                .collect();
+            let request = record_batch_to_insert_request(batch.clone(), "t", schema).unwrap();
+            let proto = request.inserts[0]
+                .rows
+                .as_ref()
+                .unwrap()
+                .rows
+                .iter()
+                .map(|row| proto_cell(&row.values[0]))
+                .collect::<Vec<_>>();
+            let (_, rows) = record_batch_to_sql_rows(&batch).unwrap();
+            let sql = rows.iter().map(|row| sql_cell(&row[0])).collect::<Vec<_>>();
+
+            assert_eq!(proto, expected, "{}", data_type);
+            assert_eq!(sql, expected, "{}", data_type);
+        }
+    }
+
+    #[test]
+    fn test_proto_and_sql_reject_alike() {
+        use arrow::array::{
+            BooleanArray, Float64Array, Int16Array, Int8Array, UInt16Array, UInt8Array,
+        };
+
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(BooleanArray::from(vec![Some(true), None])),
+            Arc::new(Int8Array::from(vec![Some(-1), None])),
+            Arc::new(Int16Array::from(vec![Some(-1), None])),
+            Arc::new(UInt8Array::from(vec![Some(1), None])),
+            Arc::new(UInt16Array::from(vec![Some(1), None])),
+            Arc::new(Float64Array::from(vec![Some(0.5), None])),
+        ];
+        for array in arrays {
+            let data_type = array.data_type().clone();
+            assert_eq!(column_data_type(&data_type), None, "{}", data_type);
+            let schema = Arc::new(Schema::new(vec![Field::new("c", data_type.clone(), true)]));
+            let batch = RecordBatch::try_new(schema, vec![array]).unwrap();
+
+            // An empty batch is rejected as well, before any row is converted
+            for batch in [batch.slice(0, 0), batch] {
+                let err = record_batch_to_insert_request(batch.clone(), "t", vec![]).unwrap_err();
+                assert!(err.is_unsupported_type(), "{}: {}", data_type, err);
+                let err = record_batch_to_sql_rows(&batch).unwrap_err();
+                assert!(err.is_unsupported_type(), "{}: {}", data_type, err);
+            }
+        }
+    }
 }
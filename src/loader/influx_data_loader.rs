@@ -0,0 +1,437 @@
+use arrow::{
+    array::{ArrayRef, AsArray, RecordBatch},
+    datatypes::{
+        DataType, Date32Type, Float32Type, Int32Type, Int64Type, TimeUnit,
+        TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+        TimestampSecondType, UInt32Type, UInt64Type,
+    },
+    util::display::array_value_to_string,
+};
+use greptime_proto::v1::{ColumnSchema, SemanticType};
+use log::warn;
+use snafu::{OptionExt, ResultExt};
+
+use crate::{
+    common::{
+        error::{
+            ArrowFileSnafu, ConvertColumnSnafu, HttpRequestSnafu, HttpWriteSnafu, Result,
+            UnsupportedColumnTypeSnafu, UnsupportedOperationSnafu,
+        },
+        sql_executor::SqlExecutor,
+    },
+    generator::data_generator::UseCase,
+    usql::{script::ScriptReport, usql::Usql},
+};
+
+use super::{
+    data_loader::{DataLoader, DataLoaderConfig, TableLoad},
+    decode_dictionary,
+    report::{LoadReport, PreLoadStep},
+};
+
+/// Connection settings of the InfluxDB v2 write API
+pub struct InfluxConfig {
+    // e.g. http://localhost:8086
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    pub token: String,
+}
+
+/// Load data into InfluxDB through the line protocol write API, tags and
+/// fields follow the semantic types of the use case schema
+pub struct InfluxDataLoader {
+    pub config: DataLoaderConfig,
+    pub influx: InfluxConfig,
+    client: reqwest::Client,
+}
+
+impl InfluxDataLoader {
+    pub fn new(use_case: UseCase, influx: InfluxConfig) -> Self {
+        InfluxDataLoader {
+            config: DataLoaderConfig::new(use_case),
+            influx,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn load_table_from_parquet_file(&self, table_name: &str) -> Result<TableLoad> {
+        let (record_batch, skipped_rows) = self.config.read_table(table_name)?;
+        let affected_rows = self.write_chunk(table_name, &record_batch).await?;
+        Ok(TableLoad {
+            affected_rows,
+            skipped_rows,
+            read_rows: None,
+        })
+    }
+
+    // POST of lines to `/api/v2/write`, timestamps are always sent in
+    // nanoseconds. The org and bucket are encoded as query parameters.
+    fn write_request(&self, lines: &[String]) -> reqwest::RequestBuilder {
+        let url = format!("{}/api/v2/write", self.influx.url.trim_end_matches('/'));
+        self.client
+            .post(url)
+            .query(&[
+                ("org", self.influx.org.as_str()),
+                ("bucket", self.influx.bucket.as_str()),
+                ("precision", "ns"),
+            ])
+            .header("Authorization", format!("Token {}", self.influx.token))
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(lines.join("\n"))
+    }
+
+    async fn write_lines(&self, lines: &[String]) -> Result<()> {
+        let response = self
+            .write_request(lines)
+            .send()
+            .await
+            .context(HttpRequestSnafu {})?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return HttpWriteSnafu {
+                target: "InfluxDB",
+                status: status.as_u16(),
+                body,
+            }
+            .fail();
+        }
+        Ok(())
+    }
+}
+
+impl DataLoader for InfluxDataLoader {
+    async fn load_data_by_usql(&self, _executor: Option<impl SqlExecutor>) -> Result<LoadReport> {
+        UnsupportedOperationSnafu {
+            target: "InfluxDB",
+            operation: "loading through usql",
+        }
+        .fail()
+    }
+
+    async fn load_data_from_raw_sql(
+        &self,
+        _usql: Option<Usql>,
+        _raw_sql: &str,
+    ) -> Result<ScriptReport> {
+        UnsupportedOperationSnafu {
+            target: "InfluxDB",
+            operation: "raw SQL through usql",
+        }
+        .fail()
+    }
+
+    async fn load_data_from_csv_file(&self) -> Result<LoadReport> {
+        UnsupportedOperationSnafu {
+            target: "InfluxDB",
+            operation: "loading CSV files",
+        }
+        .fail()
+    }
+
+    async fn load_data_from_ndjson_file(&self) -> Result<LoadReport> {
+        UnsupportedOperationSnafu {
+            target: "InfluxDB",
+            operation: "loading NDJSON files",
+        }
+        .fail()
+    }
+
+    async fn load_data_from_parquet_file(&self) -> Result<LoadReport> {
+        self.config.check_table_columns()?;
+        // Measurements are schemaless, there is nothing to create, drop or truncate
+        if self.config.create_tables || self.config.pre_load_step != PreLoadStep::None {
+            warn!("InfluxDB measurements are schemaless, skipping table preparation");
+        }
+
+        let mut report = LoadReport::new("influxdb", "http", PreLoadStep::None);
+        self.config
+            .load_tables(&mut report, |table_name| {
+                self.load_table_from_parquet_file(table_name)
+            })
+            .await?;
+        Ok(report)
+    }
+
+    fn target(&self) -> &'static str {
+        "influxdb"
+    }
+
+    async fn write_chunk(&self, table_name: &str, chunk: &RecordBatch) -> Result<u32> {
+        let schema = self.config.table_schema(table_name, &chunk.schema())?;
+        let measurement = self.config.target_table_name(table_name);
+        let lines = record_batch_to_line_protocol(chunk, &measurement, &schema)?;
+
+        for lines_chunk in lines.chunks(self.config.chunk_size.max(1)) {
+            self.write_lines(lines_chunk).await?;
+        }
+        Ok(lines.len() as u32)
+    }
+}
+
+// Generate InfluxDB line protocol from RecordBatch, one line per row.
+// Null values are left out of the line, rows without any field are skipped
+// because InfluxDB rejects them.
+fn record_batch_to_line_protocol(
+    record_batch: &RecordBatch,
+    table_name: &str,
+    schema: &[ColumnSchema],
+) -> Result<Vec<String>> {
+    let columns = record_batch
+        .columns()
+        .iter()
+        .map(decode_dictionary)
+        .collect::<Result<Vec<_>>>()?;
+    let measurement = escape(table_name, &[',', ' ']);
+
+    let mut lines = Vec::with_capacity(record_batch.num_rows());
+    for row_index in 0..record_batch.num_rows() {
+        let mut tags = String::new();
+        let mut fields = Vec::new();
+        let mut timestamp = None;
+
+        for (col, column_schema) in columns.iter().zip(schema) {
+            if col.is_null(row_index) {
+                continue;
+            }
+            let name = &column_schema.column_name;
+            if column_schema.semantic_type == SemanticType::Tag as i32 {
+                let value = array_value_to_string(col, row_index).context(ArrowFileSnafu {})?;
+                tags.push_str(&format!(
+                    ",{}={}",
+                    escape(name, &[',', '=', ' ']),
+                    escape(&value, &[',', '=', ' '])
+                ));
+            } else if column_schema.semantic_type == SemanticType::Timestamp as i32 {
+                timestamp = Some(timestamp_nanos(col, table_name, name, row_index)?);
+            } else {
+                fields.push(format!(
+                    "{}={}",
+                    escape(name, &[',', '=', ' ']),
+                    field_value(col, table_name, name, row_index)?
+                ));
+            }
+        }
+
+        if fields.is_empty() {
+            continue;
+        }
+        let mut line = format!("{}{} {}", measurement, tags, fields.join(","));
+        if let Some(timestamp) = timestamp {
+            line.push_str(&format!(" {}", timestamp));
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+fn field_value(col: &ArrayRef, table: &str, name: &str, row_index: usize) -> Result<String> {
+    let value = match col.data_type() {
+        DataType::Utf8 => quote(col.as_string::<i32>().value(row_index)),
+        DataType::LargeUtf8 => quote(col.as_string::<i64>().value(row_index)),
+        DataType::Int32 => format!("{}i", col.as_primitive::<Int32Type>().value(row_index)),
+        DataType::Int64 => format!("{}i", col.as_primitive::<Int64Type>().value(row_index)),
+        DataType::UInt32 => format!("{}u", col.as_primitive::<UInt32Type>().value(row_index)),
+        DataType::UInt64 => format!("{}u", col.as_primitive::<UInt64Type>().value(row_index)),
+        DataType::Float32 => col
+            .as_primitive::<Float32Type>()
+            .value(row_index)
+            .to_string(),
+        // Days since epoch
+        DataType::Date32 => format!("{}i", col.as_primitive::<Date32Type>().value(row_index)),
+        DataType::Timestamp(_, _) => {
+            format!("{}i", timestamp_nanos(col, table, name, row_index)?)
+        }
+        datatype => {
+            return UnsupportedColumnTypeSnafu {
+                column: name,
+                datatype: datatype.to_string(),
+            }
+            .fail()
+        }
+    };
+    Ok(value)
+}
+
+// A timestamp beyond the nanoseconds an i64 holds, about the years 1677 to
+// 2262, fails the conversion
+fn timestamp_nanos(col: &ArrayRef, table: &str, name: &str, row_index: usize) -> Result<i64> {
+    let (value, nanos_per_unit) = match col.data_type() {
+        DataType::Timestamp(TimeUnit::Second, _) => (
+            col.as_primitive::<TimestampSecondType>().value(row_index),
+            1_000_000_000,
+        ),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => (
+            col.as_primitive::<TimestampMillisecondType>()
+                .value(row_index),
+            1_000_000,
+        ),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => (
+            col.as_primitive::<TimestampMicrosecondType>()
+                .value(row_index),
+            1_000,
+        ),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => (
+            col.as_primitive::<TimestampNanosecondType>()
+                .value(row_index),
+            1,
+        ),
+        datatype => {
+            return UnsupportedColumnTypeSnafu {
+                column: name,
+                datatype: datatype.to_string(),
+            }
+            .fail()
+        }
+    };
+    value
+        .checked_mul(nanos_per_unit)
+        .context(ConvertColumnSnafu {
+            table,
+            column: name,
+            row: row_index,
+            reason: "timestamp out of the range of nanoseconds",
+        })
+}
+
+// Backslash-escape the characters that are special in this line protocol element
+fn escape(s: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// String field values are double quoted with quotes and backslashes escaped
+fn quote(s: &str) -> String {
+    format!("\"{}\"", escape(s, &['"', '\\']))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, StringArray, TimestampMicrosecondArray, TimestampSecondArray},
+        datatypes::{Field, Schema},
+    };
+    use greptime_proto::v1::ColumnDataType;
+
+    use super::*;
+    use crate::common::error::Error;
+    use crate::loader::{field, tag, timestamp};
+
+    #[test]
+    fn test_record_batch_to_line_protocol() {
+        let arrow_schema = Arc::new(Schema::new(vec![
+            Field::new("host", DataType::Utf8, false),
+            Field::new("runtime", DataType::Int32, true),
+            Field::new("message", DataType::Utf8, true),
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            arrow_schema,
+            vec![
+                Arc::new(StringArray::from(vec!["web 1", "web,2"])),
+                Arc::new(Int32Array::from(vec![Some(120), None])),
+                Arc::new(StringArray::from(vec![Some("say \"hi\""), None])),
+                Arc::new(TimestampMicrosecondArray::from(vec![1, 2])),
+            ],
+        )
+        .unwrap();
+        let schema = vec![
+            tag("host", ColumnDataType::String),
+            field("runtime", ColumnDataType::Int32),
+            field("message", ColumnDataType::String),
+            timestamp("ts", ColumnDataType::TimestampMicrosecond),
+        ];
+
+        let lines = record_batch_to_line_protocol(&batch, "web logs", &schema).unwrap();
+        // The second row has no non-null field and is skipped
+        assert_eq!(
+            lines,
+            vec![
+                "web\\ logs,host=web\\ 1 runtime=120i,message=\"say \\\"hi\\\"\" 1000".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_request_url() {
+        let loader = InfluxDataLoader::new(
+            UseCase::Others,
+            InfluxConfig {
+                url: "http://localhost:8086/".to_string(),
+                org: "acme & co".to_string(),
+                bucket: "logs#1".to_string(),
+                token: "secret".to_string(),
+            },
+        );
+        let request = loader.write_request(&[]).build().unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "http://localhost:8086/api/v2/write?org=acme+%26+co&bucket=logs%231&precision=ns"
+        );
+    }
+
+    #[test]
+    fn test_line_protocol_unsupported_field() {
+        let arrow_schema = Arc::new(Schema::new(vec![Field::new(
+            "payload",
+            DataType::Binary,
+            false,
+        )]));
+        let batch = RecordBatch::try_new(
+            arrow_schema,
+            vec![Arc::new(arrow::array::BinaryArray::from_vec(vec![
+                b"a".as_ref()
+            ]))],
+        )
+        .unwrap();
+        let schema = vec![field("payload", ColumnDataType::Binary)];
+
+        let err = record_batch_to_line_protocol(&batch, "payloads", &schema).unwrap_err();
+        assert!(err.to_string().contains("payload"));
+    }
+
+    #[test]
+    fn test_line_protocol_timestamp_out_of_range() {
+        let arrow_schema = Arc::new(Schema::new(vec![
+            Field::new("runtime", DataType::Int32, false),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Second, None), false),
+        ]));
+        let batch = RecordBatch::try_new(
+            arrow_schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(TimestampSecondArray::from(vec![0, i64::MAX / 1_000])),
+            ],
+        )
+        .unwrap();
+        let schema = vec![
+            field("runtime", ColumnDataType::Int32),
+            timestamp("ts", ColumnDataType::TimestampSecond),
+        ];
+
+        let err = record_batch_to_line_protocol(&batch, "runs", &schema).unwrap_err();
+        match err {
+            Error::ConvertColumn {
+                table, column, row, ..
+            } => assert_eq!(
+                (table.as_str(), column.as_str(), row),
+                ("runs", "ts", Some(1))
+            ),
+            other => panic!("unexpected {}", other),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::future::Future;
+
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+use crate::common::error::{Result, TableDependencyCycleSnafu, UnknownTableDependencySnafu};
+use crate::generator::data_generator::DataGenerator;
+
+/// Group `tables` into levels such that every table comes after the tables it
+/// depends on. Tables within a level don't depend on each other and keep the
+/// order of `tables`.
+pub fn load_levels(
+    tables: &[&'static str],
+    dependencies: &[(&'static str, &'static str)],
+) -> Result<Vec<Vec<&'static str>>> {
+    for &(table, depends_on) in dependencies {
+        if !tables.contains(&table) || !tables.contains(&depends_on) {
+            return UnknownTableDependencySnafu { table, depends_on }.fail();
+        }
+    }
+
+    let mut remaining = tables.to_vec();
+    let mut levels = Vec::new();
+    while !remaining.is_empty() {
+        let (level, rest): (Vec<_>, Vec<_>) = remaining.iter().copied().partition(|&table| {
+            dependencies
+                .iter()
+                .all(|&(t, depends_on)| t != table || !remaining.contains(&depends_on))
+        });
+        if level.is_empty() {
+            return TableDependencyCycleSnafu {
+                tables: rest.join(", "),
+            }
+            .fail();
+        }
+        levels.push(level);
+        remaining = rest;
+    }
+    Ok(levels)
+}
+
+/// Load levels of the tables of a use case
+pub fn table_load_levels<G: DataGenerator>() -> Result<Vec<Vec<&'static str>>> {
+    load_levels(&G::table_names(), &G::table_dependencies())
+}
+
+/// Dimension tables of a use case, the tables other tables depend on while
+/// depending on none themselves
+pub fn dimension_tables<G: DataGenerator>() -> Vec<&'static str> {
+    let dependencies = G::table_dependencies();
+    G::table_names()
+        .into_iter()
+        .filter(|&table| {
+            dependencies
+                .iter()
+                .any(|&(_, depends_on)| depends_on == table)
+                && dependencies.iter().all(|&(t, _)| t != table)
+        })
+        .collect()
+}
+
+/// Run `load` for every table, level after level. Up to `workers` tables of a
+/// level are loaded concurrently, the next level only starts once the current
+/// one completed. Results keep the table order of the levels.
+pub async fn load_in_order<T, F, Fut>(
+    levels: &[Vec<&'static str>],
+    workers: usize,
+    load: F,
+) -> Result<Vec<T>>
+where
+    F: Fn(&'static str) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut loaded = Vec::new();
+    for level in levels {
+        let level_loaded: Vec<T> = stream::iter(level.iter().map(|&table| load(table)))
+            .buffered(workers.max(1))
+            .try_collect()
+            .await?;
+        loaded.extend(level_loaded);
+    }
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::common::error::UnsupportedOperationSnafu;
+    use crate::generator::log_data_generator::LogDataGenerator;
+
+    #[test]
+    fn test_log_load_levels() {
+        assert_eq!(
+            table_load_levels::<LogDataGenerator>().unwrap(),
+            vec![
+                vec!["users", "pages", "devices"],
+                vec!["web_logs"],
+                vec!["requests", "responses", "error_logs"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dimension_tables() {
+        assert_eq!(
+            dimension_tables::<LogDataGenerator>(),
+            ["users", "pages", "devices"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_dependencies() {
+        let err = load_levels(&["a", "b"], &[("a", "c")]).unwrap_err();
+        assert_eq!(err.to_string(), "Table a depends on unknown table c");
+
+        let err = load_levels(&["a", "b", "c"], &[("a", "b"), ("b", "a")]).unwrap_err();
+        assert_eq!(err.to_string(), "Dependency cycle between tables a, b");
+    }
+
+    #[tokio::test]
+    async fn test_load_in_order() {
+        let levels = table_load_levels::<LogDataGenerator>().unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        // Mock table loads, earlier tables of a level take longer so loads overlap
+        let loaded = load_in_order(&levels, 4, |table| {
+            let events = events.clone();
+            async move {
+                events.lock().unwrap().push(format!("start {}", table));
+                let delay = match table {
+                    "users" => 30,
+                    "pages" => 20,
+                    _ => 10,
+                };
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                events.lock().unwrap().push(format!("end {}", table));
+                Ok(table)
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(loaded, LogDataGenerator::table_names());
+
+        let events = events.lock().unwrap();
+        let position = |event: &str| events.iter().position(|e| e == event).unwrap();
+        let web_logs_start = position("start web_logs");
+        for table in ["users", "pages", "devices"] {
+            assert!(position(&format!("end {}", table)) < web_logs_start);
+        }
+        // Tables within a level run concurrently
+        assert!(position("start devices") < position("end users"));
+    }
+
+    #[tokio::test]
+    async fn test_load_in_order_stops_after_failed_level() {
+        let levels = vec![vec!["a", "b"], vec!["c"]];
+        let started = Arc::new(Mutex::new(Vec::new()));
+
+        let err = load_in_order(&levels, 2, |table| {
+            let started = started.clone();
+            async move {
+                started.lock().unwrap().push(table);
+                if table == "a" {
+                    return UnsupportedOperationSnafu {
+                        target: "mock",
+                        operation: "table a",
+                    }
+                    .fail();
+                }
+                Ok(())
+            }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "mock doesn't support table a");
+        assert!(!started.lock().unwrap().contains(&"c"));
+    }
+}
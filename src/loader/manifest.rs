@@ -0,0 +1,263 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    future::Future,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+
+use crate::common::error::{
+    InvalidManifestSnafu, ReadFileSnafu, ReadParquetFileSnafu, Result, WriteFileSnafu,
+};
+
+/// File recording the tables a previous run completed, next to the dataset
+pub const MANIFEST_FILE_NAME: &str = "load_manifest.json";
+
+/// A table that was fully loaded from a parquet file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    // SHA-256 of the parquet file
+    pub hash: String,
+    // Rows acknowledged by the target
+    pub rows: u64,
+}
+
+/// Tables completed per target, so re-running a load after a partial failure
+/// only sends the tables that didn't make it
+#[derive(Debug, Default)]
+pub struct LoadManifest {
+    path: PathBuf,
+    targets: BTreeMap<String, BTreeMap<String, ManifestEntry>>,
+}
+
+impl LoadManifest {
+    /// Read the manifest at `path`, a missing file is an empty manifest
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let targets = match fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content).context(InvalidManifestSnafu {
+                path: path.display().to_string(),
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(e).context(ReadFileSnafu {
+                    path: path.display().to_string(),
+                })
+            }
+        };
+        Ok(LoadManifest { path, targets })
+    }
+
+    pub fn entry(&self, target: &str, table: &str) -> Option<&ManifestEntry> {
+        self.targets.get(target)?.get(table)
+    }
+
+    /// Record a completed table and persist the manifest
+    pub fn record(&mut self, target: &str, table: &str, entry: ManifestEntry) -> Result<()> {
+        self.targets
+            .entry(target.to_string())
+            .or_default()
+            .insert(table.to_string(), entry);
+        self.save()
+    }
+
+    /// Forget every table of `target`, e.g. after they were dropped or truncated
+    pub fn clear(&mut self, target: &str) -> Result<()> {
+        if self.targets.remove(target).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    // Write to a temporary file first so a crash never leaves a torn manifest
+    fn save(&self) -> Result<()> {
+        let content = serde_json::to_vec_pretty(&self.targets).context(InvalidManifestSnafu {
+            path: self.path.display().to_string(),
+        })?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .context(WriteFileSnafu {
+                path: self.path.display().to_string(),
+            })
+    }
+}
+
+/// SHA-256 of a file, hex encoded
+pub fn file_hash(path: &Path) -> Result<String> {
+    let read_file = || ReadFileSnafu {
+        path: path.display().to_string(),
+    };
+    let mut file = File::open(path).context(read_file())?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).context(read_file())?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+// Row count from the parquet footer, without decoding any data
+fn parquet_rows(path: &Path) -> Result<u64> {
+    let file = File::open(path).context(ReadFileSnafu {
+        path: path.display().to_string(),
+    })?;
+    let reader = SerializedFileReader::new(file).context(ReadParquetFileSnafu {})?;
+    Ok(reader.metadata().file_metadata().num_rows() as u64)
+}
+
+/// Run `load` for a table unless the manifest shows the same parquet file was
+/// already fully loaded into `target`, in which case `None` is returned. `load`
+/// returns the acknowledged rows along with any value of its own, the manifest
+/// is only updated once it succeeded.
+pub async fn load_unless_complete<T, F, Fut>(
+    manifest: &Mutex<LoadManifest>,
+    target: &str,
+    table: &str,
+    parquet_path: &Path,
+    force: bool,
+    load: F,
+) -> Result<Option<(u32, T)>>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<(u32, T)>>,
+{
+    let hash = file_hash(parquet_path)?;
+    let recorded = manifest.lock().unwrap().entry(target, table).cloned();
+    if let Some(entry) = recorded.filter(|_| !force) {
+        if entry.hash == hash && entry.rows == parquet_rows(parquet_path)? {
+            return Ok(None);
+        }
+    }
+
+    let (affected_rows, loaded) = load().await?;
+    manifest.lock().unwrap().record(
+        target,
+        table,
+        ManifestEntry {
+            hash,
+            rows: affected_rows as u64,
+        },
+    )?;
+    Ok(Some((affected_rows, loaded)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, RecordBatch},
+        datatypes::{DataType, Field, Schema},
+    };
+    use parquet::arrow::ArrowWriter;
+
+    use super::*;
+    use crate::common::error::UnsupportedOperationSnafu;
+
+    fn write_parquet(path: &Path, rows: i32) {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..rows))],
+        )
+        .unwrap();
+        let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    // Load both tables, failing `pages` when `fail_pages` is set, and return
+    // the tables that were sent
+    async fn run_load(dir: &Path, fail_pages: bool, force: bool) -> (Vec<&'static str>, bool) {
+        let manifest = Mutex::new(LoadManifest::open(dir.join(MANIFEST_FILE_NAME)).unwrap());
+        let sent = Mutex::new(Vec::new());
+        let mut failed = false;
+        for table in ["users", "pages"] {
+            let path = dir.join(format!("{}.parquet", table));
+            let load = || async {
+                sent.lock().unwrap().push(table);
+                if fail_pages && table == "pages" {
+                    return UnsupportedOperationSnafu {
+                        target: "mock",
+                        operation: "pages",
+                    }
+                    .fail();
+                }
+                Ok((parquet_rows(&path)? as u32, ()))
+            };
+            let result =
+                load_unless_complete(&manifest, "greptime", table, &path, force, load).await;
+            if result.is_err() {
+                failed = true;
+                break;
+            }
+        }
+        (sent.into_inner().unwrap(), failed)
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_partial_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        write_parquet(&dir.path().join("users.parquet"), 3);
+        write_parquet(&dir.path().join("pages.parquet"), 5);
+
+        assert_eq!(
+            run_load(dir.path(), true, false).await,
+            (vec!["users", "pages"], true)
+        );
+        let manifest = LoadManifest::open(dir.path().join(MANIFEST_FILE_NAME)).unwrap();
+        assert_eq!(manifest.entry("greptime", "users").unwrap().rows, 3);
+        assert!(manifest.entry("greptime", "pages").is_none());
+
+        // Only the failed table is sent again, then nothing is left to send
+        assert_eq!(
+            run_load(dir.path(), false, false).await,
+            (vec!["pages"], false)
+        );
+        assert_eq!(run_load(dir.path(), false, false).await, (vec![], false));
+
+        // A changed file or --force sends the table again
+        write_parquet(&dir.path().join("users.parquet"), 4);
+        assert_eq!(
+            run_load(dir.path(), false, false).await,
+            (vec!["users"], false)
+        );
+        assert_eq!(
+            run_load(dir.path(), false, true).await,
+            (vec!["users", "pages"], false)
+        );
+    }
+
+    #[test]
+    fn test_clear_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(MANIFEST_FILE_NAME);
+        let entry = ManifestEntry {
+            hash: "abc".to_string(),
+            rows: 1,
+        };
+        let mut manifest = LoadManifest::open(&path).unwrap();
+        manifest.record("greptime", "users", entry.clone()).unwrap();
+        manifest.record("influxdb", "users", entry.clone()).unwrap();
+        manifest.clear("greptime").unwrap();
+
+        let manifest = LoadManifest::open(&path).unwrap();
+        assert!(manifest.entry("greptime", "users").is_none());
+        assert_eq!(manifest.entry("influxdb", "users"), Some(&entry));
+    }
+}
@@ -1,7 +1,55 @@
+use arrow::{
+    array::{Array, ArrayRef, AsArray, RecordBatch, UInt64Array},
+    compute::{cast, take_record_batch},
+    datatypes::{
+        DataType, Date32Type, Date64Type, Field, Float32Type, Int32Type, Int64Type, Schema,
+        TimeUnit, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+        TimestampSecondType, UInt32Type, UInt64Type,
+    },
+};
+use chrono::DateTime;
 use greptime_proto::v1::{value::ValueData, ColumnDataType, ColumnSchema, SemanticType, Value};
+use snafu::{ensure, OptionExt, ResultExt};
+
+use crate::common::error::{
+    ArrowFileSnafu, ConvertColumnSnafu, Result, UnsupportedColumnTypeSnafu,
+};
+use crate::generator::{
+    data_generator::{DataGenerator, UseCase},
+    log_data_generator::LogDataGenerator,
+};
+use data_loader::SqlValue;
 
 pub mod data_loader;
+pub mod dialect;
+pub mod dual_data_loader;
 pub mod greptime_data_loader;
+pub mod influx_data_loader;
+pub mod load_order;
+pub mod manifest;
+pub mod mysql_data_loader;
+pub mod ndjson;
+pub mod pacing;
+pub mod postgres_data_loader;
+pub mod report;
+pub mod results_log;
+pub mod soak;
+pub mod time_range;
+pub mod verify;
+
+#[inline]
+pub fn timestamp_second_value(v: i64) -> Value {
+    Value {
+        value_data: Some(ValueData::TimestampSecondValue(v)),
+    }
+}
+
+#[inline]
+pub fn timestamp_millisecond_value(v: i64) -> Value {
+    Value {
+        value_data: Some(ValueData::TimestampMillisecondValue(v)),
+    }
+}
 
 #[inline]
 pub fn timestamp_microsecond_value(v: i64) -> Value {
@@ -10,6 +58,13 @@ pub fn timestamp_microsecond_value(v: i64) -> Value {
     }
 }
 
+#[inline]
+pub fn timestamp_nanosecond_value(v: i64) -> Value {
+    Value {
+        value_data: Some(ValueData::TimestampNanosecondValue(v)),
+    }
+}
+
 #[inline]
 pub fn string_value(v: String) -> Value {
     Value {
@@ -17,6 +72,41 @@ pub fn string_value(v: String) -> Value {
     }
 }
 
+#[inline]
+pub fn i64_value(v: i64) -> Value {
+    Value {
+        value_data: Some(ValueData::I64Value(v)),
+    }
+}
+
+#[inline]
+pub fn u32_value(v: u32) -> Value {
+    Value {
+        value_data: Some(ValueData::U32Value(v)),
+    }
+}
+
+#[inline]
+pub fn u64_value(v: u64) -> Value {
+    Value {
+        value_data: Some(ValueData::U64Value(v)),
+    }
+}
+
+#[inline]
+pub fn date_value(v: i32) -> Value {
+    Value {
+        value_data: Some(ValueData::DateValue(v)),
+    }
+}
+
+#[inline]
+pub fn binary_value(v: Vec<u8>) -> Value {
+    Value {
+        value_data: Some(ValueData::BinaryValue(v)),
+    }
+}
+
 #[inline]
 pub fn f32_value(v: f32) -> Value {
     Value {
@@ -57,3 +147,288 @@ pub fn field(name: &str, datatype: ColumnDataType) -> ColumnSchema {
         ..Default::default()
     }
 }
+
+/// Map an Arrow data type to the GreptimeDB column data type used for inserts.
+/// Timestamps with a timezone map to the same type as naive ones, Arrow already
+/// stores them as UTC epoch values. The types mapped are the ones both gRPC
+/// insert requests and `record_batch_to_sql_rows` take.
+pub fn column_data_type(data_type: &DataType) -> Option<ColumnDataType> {
+    match data_type {
+        DataType::Utf8 | DataType::LargeUtf8 => Some(ColumnDataType::String),
+        DataType::Dictionary(_, value_type)
+            if matches!(value_type.as_ref(), DataType::Utf8 | DataType::LargeUtf8) =>
+        {
+            Some(ColumnDataType::String)
+        }
+        DataType::Int32 => Some(ColumnDataType::Int32),
+        DataType::Int64 => Some(ColumnDataType::Int64),
+        DataType::UInt32 => Some(ColumnDataType::Uint32),
+        DataType::UInt64 => Some(ColumnDataType::Uint64),
+        DataType::Float32 => Some(ColumnDataType::Float32),
+        DataType::Date32 | DataType::Date64 => Some(ColumnDataType::Date),
+        DataType::Binary | DataType::LargeBinary => Some(ColumnDataType::Binary),
+        DataType::Timestamp(unit, _) => Some(match unit {
+            TimeUnit::Second => ColumnDataType::TimestampSecond,
+            TimeUnit::Millisecond => ColumnDataType::TimestampMillisecond,
+            TimeUnit::Microsecond => ColumnDataType::TimestampMicrosecond,
+            TimeUnit::Nanosecond => ColumnDataType::TimestampNanosecond,
+        }),
+        _ => None,
+    }
+}
+
+/// Arrow data type of a GreptimeDB column data type, the inverse of
+/// `column_data_type` for the types it maps
+pub fn arrow_data_type(datatype: ColumnDataType) -> Option<DataType> {
+    match datatype {
+        ColumnDataType::String => Some(DataType::Utf8),
+        ColumnDataType::Int32 => Some(DataType::Int32),
+        ColumnDataType::Int64 => Some(DataType::Int64),
+        ColumnDataType::Uint32 => Some(DataType::UInt32),
+        ColumnDataType::Uint64 => Some(DataType::UInt64),
+        ColumnDataType::Float32 => Some(DataType::Float32),
+        ColumnDataType::Date => Some(DataType::Date32),
+        ColumnDataType::Binary => Some(DataType::Binary),
+        ColumnDataType::TimestampSecond => Some(DataType::Timestamp(TimeUnit::Second, None)),
+        ColumnDataType::TimestampMillisecond => {
+            Some(DataType::Timestamp(TimeUnit::Millisecond, None))
+        }
+        ColumnDataType::TimestampMicrosecond => {
+            Some(DataType::Timestamp(TimeUnit::Microsecond, None))
+        }
+        ColumnDataType::TimestampNanosecond => {
+            Some(DataType::Timestamp(TimeUnit::Nanosecond, None))
+        }
+        _ => None,
+    }
+}
+
+/// Arrow schema of declared `ColumnSchema`s, fields are nullable like the
+/// columns of a `CreateTableSpec`
+pub fn arrow_schema(columns: &[ColumnSchema]) -> Result<Schema> {
+    let fields = columns
+        .iter()
+        .map(|col| {
+            let datatype = col.datatype();
+            let data_type = arrow_data_type(datatype).context(UnsupportedColumnTypeSnafu {
+                column: &col.column_name,
+                datatype: datatype.as_str_name(),
+            })?;
+            Ok(Field::new(
+                &col.column_name,
+                data_type,
+                col.semantic_type == SemanticType::Field as i32,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Schema::new(fields))
+}
+
+/// Infer `ColumnSchema`s from an Arrow schema, used when the use case doesn't
+/// declare one. The first timestamp column becomes the time index, every other
+/// column is a field.
+pub fn infer_schema(table: &str, schema: &Schema) -> Result<Vec<ColumnSchema>> {
+    let mut has_time_index = false;
+    let mut columns = Vec::with_capacity(schema.fields().len());
+    for f in schema.fields() {
+        let datatype = column_data_type(f.data_type()).context(ConvertColumnSnafu {
+            table,
+            column: f.name(),
+            row: None::<usize>,
+            reason: format!("unsupported data type {}", f.data_type()),
+        })?;
+        if matches!(f.data_type(), DataType::Timestamp(_, _)) && !has_time_index {
+            has_time_index = true;
+            columns.push(timestamp(f.name(), datatype));
+        } else {
+            columns.push(field(f.name(), datatype));
+        }
+    }
+    Ok(columns)
+}
+
+/// Schema the use case declares for a table, `None` for tables it doesn't know
+pub fn declared_schema(use_case: &UseCase, table_name: &str) -> Option<Vec<ColumnSchema>> {
+    match use_case {
+        UseCase::Log if LogDataGenerator::table_names().contains(&table_name) => {
+            Some(LogDataGenerator::schema(table_name))
+        }
+        _ => None,
+    }
+}
+
+/// Schema of a target table, declared by the use case or inferred from the
+/// Arrow schema of the data to load
+pub fn table_schema(
+    use_case: &UseCase,
+    table_name: &str,
+    arrow_schema: &Schema,
+) -> Result<Vec<ColumnSchema>> {
+    match declared_schema(use_case, table_name) {
+        Some(schema) => Ok(schema),
+        None => infer_schema(table_name, arrow_schema),
+    }
+}
+
+/// Indices of the columns rows of a table are keyed by, its tags and its
+/// timestamp column in schema order. `None` for a table without a time index,
+/// whose rows can't be deleted by key.
+pub fn key_columns(schema: &[ColumnSchema]) -> Option<Vec<usize>> {
+    schema
+        .iter()
+        .any(|col| col.semantic_type == SemanticType::Timestamp as i32)
+        .then(|| {
+            schema
+                .iter()
+                .enumerate()
+                .filter(|(_, col)| col.semantic_type != SemanticType::Field as i32)
+                .map(|(index, _)| index)
+                .collect()
+        })
+}
+
+/// Keys to delete `fraction` of the rows of `record_batch` by, rows spread
+/// evenly over the batch: the rows projected to the `key_columns` of
+/// `schema`, with the schema of those columns. `None` for a table without a
+/// time index.
+pub fn delete_keys(
+    record_batch: &RecordBatch,
+    schema: &[ColumnSchema],
+    fraction: f64,
+) -> Result<Option<(RecordBatch, Vec<ColumnSchema>)>> {
+    let Some(keys) = key_columns(schema) else {
+        return Ok(None);
+    };
+    let fraction = fraction.clamp(0.0, 1.0);
+    // Every row that brings the running share of deleted rows to the next whole row
+    let rows = (0..record_batch.num_rows() as u64)
+        .filter(|&row| ((row + 1) as f64 * fraction).floor() > (row as f64 * fraction).floor());
+    let sampled = take_record_batch(record_batch, &UInt64Array::from_iter_values(rows))
+        .context(ArrowFileSnafu {})?;
+    let projected = sampled.project(&keys).context(ArrowFileSnafu {})?;
+    let key_schema = keys.iter().map(|&index| schema[index].clone()).collect();
+    Ok(Some((projected, key_schema)))
+}
+
+// Resolve dictionary-encoded columns (as handed back by parquet readers for
+// low-cardinality strings) to their plain value arrays
+pub(crate) fn decode_dictionary(col: &ArrayRef) -> Result<ArrayRef> {
+    match col.data_type() {
+        DataType::Dictionary(_, value_type) => {
+            cast(col, value_type.as_ref()).context(ArrowFileSnafu {})
+        }
+        _ => Ok(col.clone()),
+    }
+}
+
+/// Column names and rows of typed values of a RecordBatch, as taken by
+/// `gen_insert_stmt` and `Connector::insert_rows`. Values map like the rows
+/// of gRPC insert requests: nulls stay nulls, zoned timestamps are UTC and
+/// Date64 is truncated to its day. Columns of types `column_data_type`
+/// doesn't map are rejected, like gRPC rejects them.
+pub fn record_batch_to_sql_rows(
+    record_batch: &RecordBatch,
+) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+    let schema = record_batch.schema();
+    let cols = schema
+        .fields()
+        .iter()
+        .map(|f| f.name().to_string())
+        .collect();
+    let columns = record_batch
+        .columns()
+        .iter()
+        .map(decode_dictionary)
+        .collect::<Result<Vec<_>>>()?;
+    for (col, col_field) in columns.iter().zip(schema.fields()) {
+        ensure!(
+            column_data_type(col.data_type()).is_some(),
+            UnsupportedColumnTypeSnafu {
+                column: col_field.name(),
+                datatype: col.data_type().to_string(),
+            }
+        );
+    }
+
+    let mut rows = Vec::with_capacity(record_batch.num_rows());
+    for row_index in 0..record_batch.num_rows() {
+        let row = columns
+            .iter()
+            .zip(schema.fields())
+            .map(|(col, col_field)| sql_value(col, col_field.name(), row_index))
+            .collect::<Result<Vec<_>>>()?;
+        rows.push(row);
+    }
+    Ok((cols, rows))
+}
+
+fn sql_value(col: &ArrayRef, name: &str, row_index: usize) -> Result<SqlValue> {
+    if col.is_null(row_index) {
+        return Ok(SqlValue::Null);
+    }
+    let value = match col.data_type() {
+        DataType::Int32 => SqlValue::Int(col.as_primitive::<Int32Type>().value(row_index) as i64),
+        DataType::Int64 => SqlValue::Int(col.as_primitive::<Int64Type>().value(row_index)),
+        DataType::UInt32 => {
+            SqlValue::UInt(col.as_primitive::<UInt32Type>().value(row_index) as u64)
+        }
+        DataType::UInt64 => SqlValue::UInt(col.as_primitive::<UInt64Type>().value(row_index)),
+        DataType::Float32 => SqlValue::Float32(col.as_primitive::<Float32Type>().value(row_index)),
+        DataType::Utf8 => SqlValue::String(col.as_string::<i32>().value(row_index).to_string()),
+        DataType::LargeUtf8 => {
+            SqlValue::String(col.as_string::<i64>().value(row_index).to_string())
+        }
+        DataType::Binary => SqlValue::Binary(col.as_binary::<i32>().value(row_index).to_vec()),
+        DataType::LargeBinary => SqlValue::Binary(col.as_binary::<i64>().value(row_index).to_vec()),
+        DataType::Date32 => sql_date(col.as_primitive::<Date32Type>().value(row_index) as i64),
+        DataType::Date64 => sql_date(
+            col.as_primitive::<Date64Type>()
+                .value(row_index)
+                .div_euclid(MILLIS_PER_DAY),
+        ),
+        DataType::Timestamp(TimeUnit::Second, _) => sql_timestamp(
+            col.as_primitive::<TimestampSecondType>().value(row_index) as i128 * 1_000_000_000,
+        ),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => sql_timestamp(
+            col.as_primitive::<TimestampMillisecondType>()
+                .value(row_index) as i128
+                * 1_000_000,
+        ),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => sql_timestamp(
+            col.as_primitive::<TimestampMicrosecondType>()
+                .value(row_index) as i128
+                * 1_000,
+        ),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => sql_timestamp(
+            col.as_primitive::<TimestampNanosecondType>()
+                .value(row_index) as i128,
+        ),
+        other => {
+            return UnsupportedColumnTypeSnafu {
+                column: name,
+                datatype: other.to_string(),
+            }
+            .fail()
+        }
+    };
+    Ok(value)
+}
+
+pub(crate) const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+// ISO 8601 date `days` after the epoch
+fn sql_date(days: i64) -> SqlValue {
+    let date = DateTime::UNIX_EPOCH.date_naive() + chrono::Duration::days(days);
+    SqlValue::Date(date.format("%Y-%m-%d").to_string())
+}
+
+// ISO 8601 timestamp `nanos` after the epoch, in UTC, with as many
+// fractional digits as it needs
+fn sql_timestamp(nanos: i128) -> SqlValue {
+    let timestamp = DateTime::from_timestamp(
+        nanos.div_euclid(1_000_000_000) as i64,
+        nanos.rem_euclid(1_000_000_000) as u32,
+    )
+    .unwrap_or_default();
+    SqlValue::Timestamp(timestamp.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+}
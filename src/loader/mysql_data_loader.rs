@@ -0,0 +1,305 @@
+use arrow::array::RecordBatch;
+use log::warn;
+
+use crate::{
+    common::{
+        connector::Connector,
+        error::{Result, UnsupportedOperationSnafu},
+        sql_executor::SqlExecutor,
+    },
+    generator::data_generator::UseCase,
+    usql::{script::ScriptReport, usql::Usql},
+};
+
+use super::{
+    data_loader::{gen_insert_stmt, DataLoader, DataLoaderConfig, SqlValue, TableLoad},
+    record_batch_to_sql_rows,
+    report::{LoadReport, PreLoadStep},
+};
+
+/// Load data through the MySQL protocol with batched INSERT statements. As
+/// GreptimeDB speaks MySQL on :4002 this is the SQL counterpart of the gRPC
+/// ingestion path.
+pub struct MySqlDataLoader {
+    pub config: DataLoaderConfig,
+    pub connector: Connector,
+}
+
+impl MySqlDataLoader {
+    pub fn new(use_case: UseCase, connector: Connector) -> Self {
+        MySqlDataLoader {
+            config: DataLoaderConfig::new(use_case),
+            connector,
+        }
+    }
+
+    // Run INSERT statements of literals, `transaction_statements` at a time,
+    // returns the rows the server acknowledged
+    async fn insert_stmts(&self, insert_stmts: Vec<String>) -> Result<u64> {
+        let mut affected_rows = 0;
+        if self.config.transaction_statements <= 1 {
+            for insert_stmt in insert_stmts {
+                affected_rows += self.connector.insert(insert_stmt).await?;
+            }
+            return Ok(affected_rows);
+        }
+        for batch in insert_stmts.chunks(self.config.transaction_statements) {
+            let batch = batch.to_vec();
+            affected_rows += self
+                .connector
+                .transaction(|tx| {
+                    Box::pin(async move {
+                        let mut affected_rows = 0;
+                        for insert_stmt in &batch {
+                            affected_rows += tx.insert(insert_stmt).await?;
+                        }
+                        Ok(affected_rows)
+                    })
+                })
+                .await?;
+        }
+        Ok(affected_rows)
+    }
+
+    // Insert `rows` with bound values, up to `chunk_size` rows per statement
+    // and `transaction_statements` statements at a time, returns the rows the
+    // server acknowledged
+    async fn insert_rows(&self, table: &str, cols: &[&str], rows: &[Vec<SqlValue>]) -> Result<u64> {
+        let max_rows = self.config.statement_limits().max_rows.max(1);
+        let mut affected_rows = 0;
+        if self.config.transaction_statements <= 1 {
+            for rows in rows.chunks(max_rows) {
+                affected_rows += self.connector.insert_rows(table, cols, rows).await?;
+            }
+            return Ok(affected_rows);
+        }
+        for batch in rows.chunks(max_rows * self.config.transaction_statements) {
+            let (table, batch) = (table.to_string(), batch.to_vec());
+            let cols = cols.iter().map(|col| col.to_string()).collect::<Vec<_>>();
+            affected_rows += self
+                .connector
+                .transaction(|tx| {
+                    Box::pin(async move {
+                        let cols = cols.iter().map(String::as_str).collect::<Vec<_>>();
+                        let mut affected_rows = 0;
+                        for rows in batch.chunks(max_rows) {
+                            affected_rows += tx.insert_rows(&table, &cols, rows).await?;
+                        }
+                        Ok(affected_rows)
+                    })
+                })
+                .await?;
+        }
+        Ok(affected_rows)
+    }
+
+    async fn load_table_from_parquet_file(&self, table_name: &str) -> Result<TableLoad> {
+        let (record_batch, skipped_rows) = self.config.read_table(table_name)?;
+        let affected_rows = self.write_chunk(table_name, &record_batch).await?;
+        Ok(TableLoad {
+            affected_rows,
+            skipped_rows,
+            read_rows: Some(record_batch.num_rows() as u64),
+        })
+    }
+}
+
+impl DataLoader for MySqlDataLoader {
+    async fn load_data_by_usql(&self, _executor: Option<impl SqlExecutor>) -> Result<LoadReport> {
+        UnsupportedOperationSnafu {
+            target: "MySQL loader",
+            operation: "loading through usql",
+        }
+        .fail()
+    }
+
+    async fn load_data_from_raw_sql(
+        &self,
+        _usql: Option<Usql>,
+        _raw_sql: &str,
+    ) -> Result<ScriptReport> {
+        UnsupportedOperationSnafu {
+            target: "MySQL loader",
+            operation: "raw SQL through usql",
+        }
+        .fail()
+    }
+
+    async fn load_data_from_csv_file(&self) -> Result<LoadReport> {
+        UnsupportedOperationSnafu {
+            target: "MySQL loader",
+            operation: "loading CSV files",
+        }
+        .fail()
+    }
+
+    async fn load_data_from_ndjson_file(&self) -> Result<LoadReport> {
+        UnsupportedOperationSnafu {
+            target: "MySQL loader",
+            operation: "loading NDJSON files",
+        }
+        .fail()
+    }
+
+    async fn load_data_from_parquet_file(&self) -> Result<LoadReport> {
+        self.config.check_table_columns()?;
+        if self.config.create_tables || self.config.pre_load_step != PreLoadStep::None {
+            warn!("MySQL loader doesn't prepare tables, they must already exist");
+        }
+
+        let mut report = LoadReport::new("mysql", "mysql", PreLoadStep::None);
+        self.config
+            .load_tables(&mut report, |table_name| {
+                self.load_table_from_parquet_file(table_name)
+            })
+            .await?;
+        Ok(report)
+    }
+
+    fn target(&self) -> &'static str {
+        "mysql"
+    }
+
+    async fn write_chunk(&self, table_name: &str, chunk: &RecordBatch) -> Result<u32> {
+        let (cols, rows) = record_batch_to_sql_rows(chunk)?;
+        let cols = cols.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let target_table = self.config.target_table_name(table_name);
+        let affected_rows = if self.config.inline_values {
+            let insert_stmts = gen_insert_stmt(
+                self.config.sql_dialect,
+                &target_table,
+                &cols,
+                &rows,
+                &self.config.statement_limits(),
+            );
+            self.insert_stmts(insert_stmts).await?
+        } else {
+            self.insert_rows(&target_table, &cols, &rows).await?
+        };
+        Ok(affected_rows as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Float32Array, Int32Array, Int64Array, StringArray, TimestampMicrosecondArray},
+        datatypes::{DataType, Field, Schema, TimeUnit},
+    };
+
+    use super::*;
+    use crate::loader::dialect::SqlDialect;
+
+    #[test]
+    fn test_record_batch_to_sql_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("device_id", DataType::Int32, false),
+            Field::new("browser", DataType::Utf8, true),
+            Field::new("bytes", DataType::Int64, true),
+            Field::new("ratio", DataType::Float32, false),
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![7, 8])),
+                Arc::new(StringArray::from(vec![Some("Mozilla's"), None])),
+                Arc::new(Int64Array::from(vec![Some(5_000_000_000), None])),
+                Arc::new(Float32Array::from(vec![0.5, 1.25])),
+                Arc::new(TimestampMicrosecondArray::from(vec![1_000_001, 2_000_000])),
+            ],
+        )
+        .unwrap();
+
+        let (cols, rows) = record_batch_to_sql_rows(&batch).unwrap();
+        assert_eq!(cols, vec!["device_id", "browser", "bytes", "ratio", "ts"]);
+        assert_eq!(
+            rows[1],
+            vec![
+                SqlValue::Int(8),
+                SqlValue::Null,
+                SqlValue::Null,
+                SqlValue::Float32(1.25),
+                SqlValue::Timestamp("1970-01-01T00:00:02".to_string()),
+            ]
+        );
+
+        let cols = cols.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(
+            gen_insert_stmt(
+                SqlDialect::Greptime,
+                "devices",
+                &cols,
+                &rows,
+                &Default::default()
+            ),
+            vec![
+                "INSERT INTO `devices`(`device_id`,`browser`,`bytes`,`ratio`,`ts`) VALUES \
+                  (7,'Mozilla''s',5000000000,0.5,'1970-01-01T00:00:01.000001'), \
+                  (8,NULL,NULL,1.25,'1970-01-01T00:00:02');"
+            ]
+        );
+    }
+
+    #[cfg(feature = "integration")]
+    #[tokio::test]
+    async fn test_numeric_literals_round_trip() {
+        use sqlx::{mysql::MySqlPool, Row};
+
+        use crate::loader::{data_loader::gen_create_table_stmt, field, timestamp};
+        use greptime_proto::v1::ColumnDataType;
+
+        let url = "mysql://127.0.0.1:4002/public";
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("bytes", DataType::Int64, false),
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+        ]));
+        let columns = vec![
+            field("bytes", ColumnDataType::Int64),
+            timestamp("ts", ColumnDataType::TimestampMicrosecond),
+        ];
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![5_000_000_000])),
+                Arc::new(TimestampMicrosecondArray::from(vec![1_000_001])),
+            ],
+        )
+        .unwrap();
+
+        let connector = Connector::new(url).await.unwrap();
+        let create_stmt =
+            gen_create_table_stmt(SqlDialect::Greptime, "it_literals", &schema, &columns).unwrap();
+        connector.insert(create_stmt).await.unwrap();
+        let (cols, rows) = record_batch_to_sql_rows(&batch).unwrap();
+        let cols = cols.iter().map(String::as_str).collect::<Vec<_>>();
+        for insert_stmt in gen_insert_stmt(
+            SqlDialect::Greptime,
+            "it_literals",
+            &cols,
+            &rows,
+            &Default::default(),
+        ) {
+            connector.insert(insert_stmt).await.unwrap();
+        }
+
+        // The literal compares as a number, no implicit string cast involved
+        let pool = MySqlPool::connect(url).await.unwrap();
+        let row = sqlx::query("SELECT bytes + 1 AS next FROM it_literals WHERE bytes = 5000000000")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<i64, _>("next"), 5_000_000_001);
+    }
+}
@@ -0,0 +1,398 @@
+use std::{
+    io::{BufRead, Lines},
+    path::Path,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        ArrayRef, BinaryArray, Date32Array, Float32Array, Int32Array, Int64Array, RecordBatch,
+        StringArray, TimestampMicrosecondArray, TimestampMillisecondArray,
+        TimestampNanosecondArray, TimestampSecondArray, UInt32Array, UInt64Array,
+    },
+    datatypes::{DataType, SchemaRef, TimeUnit},
+};
+use chrono::{DateTime, NaiveDate};
+use log::warn;
+use serde_json::{Map, Value};
+use snafu::ResultExt;
+
+use crate::common::error::{
+    ArrowFileSnafu, ParseJsonLineSnafu, Result, TooManyMalformedLinesSnafu,
+};
+use crate::loader::data_loader::{open_input_file, read_input_error};
+
+/// Reads an NDJSON file into record batches of a declared schema, one object
+/// per line. Keys missing from a line are NULL, values of the wrong JSON type
+/// are coerced where lossless (e.g. `"42"` into an integer column). Lines that
+/// can't be read into the schema are skipped and counted, the reader fails
+/// once more than `max_malformed_lines` were skipped.
+pub struct NdjsonReader {
+    lines: Lines<Box<dyn BufRead + Send>>,
+    path: String,
+    schema: SchemaRef,
+    batch_size: usize,
+    // Reject keys that aren't columns of the schema
+    strict: bool,
+    max_malformed_lines: usize,
+    line_number: usize,
+    malformed_lines: usize,
+}
+
+// A parsed value, typed after its column
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    Str(String),
+}
+
+impl NdjsonReader {
+    pub fn open(
+        path: &Path,
+        schema: SchemaRef,
+        batch_size: usize,
+        strict: bool,
+        max_malformed_lines: usize,
+    ) -> Result<Self> {
+        Ok(NdjsonReader {
+            lines: open_input_file(path)?.lines(),
+            path: path.display().to_string(),
+            schema,
+            batch_size: batch_size.max(1),
+            strict,
+            max_malformed_lines,
+            line_number: 0,
+            malformed_lines: 0,
+        })
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Lines skipped so far because they couldn't be parsed
+    pub fn malformed_lines(&self) -> usize {
+        self.malformed_lines
+    }
+
+    fn parse_line(&self, line: &str) -> std::result::Result<Vec<Cell>, String> {
+        let object = serde_json::from_str::<Map<String, Value>>(line).map_err(|e| e.to_string())?;
+        if self.strict {
+            if let Some(key) = object
+                .keys()
+                .find(|key| self.schema.field_with_name(key).is_err())
+            {
+                return Err(format!("unknown key {}", key));
+            }
+        }
+        self.schema
+            .fields()
+            .iter()
+            .map(|f| {
+                let cell = match object.get(f.name()) {
+                    None | Some(Value::Null) => Cell::Null,
+                    Some(value) => parse_value(f.data_type(), value)
+                        .map_err(|e| format!("column {}: {}", f.name(), e))?,
+                };
+                if cell == Cell::Null && !f.is_nullable() {
+                    return Err(format!("column {} is missing", f.name()));
+                }
+                Ok(cell)
+            })
+            .collect()
+    }
+
+    fn build_batch(&self, columns: Vec<Vec<Cell>>) -> Result<RecordBatch> {
+        let arrays = self
+            .schema
+            .fields()
+            .iter()
+            .zip(columns)
+            .map(|(f, cells)| build_array(f.data_type(), cells))
+            .collect();
+        RecordBatch::try_new(self.schema.clone(), arrays).context(ArrowFileSnafu {})
+    }
+}
+
+impl Iterator for NdjsonReader {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut columns = vec![Vec::with_capacity(self.batch_size); self.schema.fields().len()];
+        let mut rows = 0;
+        while rows < self.batch_size {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(read_input_error(Path::new(&self.path), e))),
+                None if rows == 0 => return None,
+                None => break,
+            };
+            self.line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match self.parse_line(&line) {
+                Ok(cells) => {
+                    for (column, cell) in columns.iter_mut().zip(cells) {
+                        column.push(cell);
+                    }
+                    rows += 1;
+                }
+                Err(reason) => {
+                    let parse_err = ParseJsonLineSnafu {
+                        path: self.path.clone(),
+                        line_no: self.line_number,
+                        reason,
+                    }
+                    .build();
+                    self.malformed_lines += 1;
+                    if self.malformed_lines > self.max_malformed_lines {
+                        return Some(Err(parse_err).context(TooManyMalformedLinesSnafu {
+                            path: self.path.clone(),
+                            max: self.max_malformed_lines,
+                        }));
+                    }
+                    warn!("Skipping malformed line: {}", parse_err);
+                }
+            }
+        }
+        Some(self.build_batch(columns))
+    }
+}
+
+// Read a JSON value into a cell of `data_type`, numbers given as strings are
+// parsed and scalars given to string columns are kept as their JSON text
+fn parse_value(data_type: &DataType, value: &Value) -> std::result::Result<Cell, String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(_) | Value::Bool(_) => value.to_string(),
+        _ => return Err(format!("unexpected {}", value)),
+    };
+    let invalid = || format!("invalid {} {}", data_type, value);
+    match data_type {
+        DataType::Utf8 | DataType::Binary => match value {
+            Value::String(s) => Ok(Cell::Str(s.clone())),
+            _ => Ok(Cell::Str(text)),
+        },
+        DataType::Int32 => text
+            .parse::<i32>()
+            .map(|v| Cell::Int(v as i64))
+            .map_err(|_| invalid()),
+        DataType::Int64 => text.parse::<i64>().map(Cell::Int).map_err(|_| invalid()),
+        DataType::UInt32 => text
+            .parse::<u32>()
+            .map(|v| Cell::UInt(v as u64))
+            .map_err(|_| invalid()),
+        DataType::UInt64 => text.parse::<u64>().map(Cell::UInt).map_err(|_| invalid()),
+        DataType::Float32 => text.parse::<f32>().map(Cell::Float).map_err(|_| invalid()),
+        DataType::Date32 => match text.parse::<i32>() {
+            Ok(days) => Ok(Cell::Int(days as i64)),
+            Err(_) => NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+                .map(|date| Cell::Int((date - NaiveDate::default()).num_days()))
+                .map_err(|_| invalid()),
+        },
+        // Epoch values are taken in the column's unit, RFC3339 strings are converted to it
+        DataType::Timestamp(unit, _) => match text.parse::<i64>() {
+            Ok(v) => Ok(Cell::Int(v)),
+            Err(_) => {
+                let ts = DateTime::parse_from_rfc3339(&text).map_err(|_| invalid())?;
+                match unit {
+                    TimeUnit::Second => Some(ts.timestamp()),
+                    TimeUnit::Millisecond => Some(ts.timestamp_millis()),
+                    TimeUnit::Microsecond => Some(ts.timestamp_micros()),
+                    TimeUnit::Nanosecond => ts.timestamp_nanos_opt(),
+                }
+                .map(Cell::Int)
+                .ok_or_else(invalid)
+            }
+        },
+        _ => Err(format!("unsupported column type {}", data_type)),
+    }
+}
+
+fn build_array(data_type: &DataType, cells: Vec<Cell>) -> ArrayRef {
+    let ints = || {
+        cells.iter().map(|c| match c {
+            Cell::Int(v) => Some(*v),
+            _ => None,
+        })
+    };
+    let uints = || {
+        cells.iter().map(|c| match c {
+            Cell::UInt(v) => Some(*v),
+            _ => None,
+        })
+    };
+    let strs = || {
+        cells.iter().map(|c| match c {
+            Cell::Str(v) => Some(v.as_str()),
+            _ => None,
+        })
+    };
+    match data_type {
+        DataType::Utf8 => Arc::new(StringArray::from_iter(strs())),
+        DataType::Binary => Arc::new(BinaryArray::from_iter(strs().map(|s| s.map(str::as_bytes)))),
+        DataType::Int32 => Arc::new(Int32Array::from_iter(ints().map(|v| v.map(|v| v as i32)))),
+        DataType::Int64 => Arc::new(Int64Array::from_iter(ints())),
+        DataType::UInt32 => Arc::new(UInt32Array::from_iter(uints().map(|v| v.map(|v| v as u32)))),
+        DataType::UInt64 => Arc::new(UInt64Array::from_iter(uints())),
+        DataType::Float32 => Arc::new(Float32Array::from_iter(cells.iter().map(|c| match c {
+            Cell::Float(v) => Some(*v),
+            _ => None,
+        }))),
+        DataType::Date32 => Arc::new(Date32Array::from_iter(ints().map(|v| v.map(|v| v as i32)))),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            Arc::new(TimestampSecondArray::from_iter(ints()))
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            Arc::new(TimestampMillisecondArray::from_iter(ints()))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            Arc::new(TimestampMicrosecondArray::from_iter(ints()))
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            Arc::new(TimestampNanosecondArray::from_iter(ints()))
+        }
+        // `parse_value` rejects every other type, so no line of it was kept
+        _ => arrow::array::new_null_array(data_type, cells.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::AsArray,
+        datatypes::{Field, Int32Type, Schema, TimestampMillisecondType},
+    };
+
+    use super::*;
+    use crate::common::error::Error;
+
+    fn events_schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new(
+                "ts",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("host", DataType::Utf8, true),
+            Field::new("status", DataType::Int32, true),
+        ]))
+    }
+
+    fn reader(
+        ndjson: &str,
+        strict: bool,
+        max_malformed_lines: usize,
+    ) -> (tempfile::TempDir, NdjsonReader) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.ndjson");
+        std::fs::write(&path, ndjson).unwrap();
+        let reader =
+            NdjsonReader::open(&path, events_schema(), 2, strict, max_malformed_lines).unwrap();
+        (dir, reader)
+    }
+
+    #[test]
+    fn test_coercion() {
+        let ndjson = r#"{"ts": "2024-01-01T00:00:00.5Z", "host": 42, "status": "200"}
+{"ts": 1704067201000, "status": 404, "extra": true}
+
+{"ts": "1704067202000", "host": "c", "status": null}
+"#;
+        let (_dir, reader) = reader(ndjson, false, 0);
+        let batches = reader.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            [2, 1]
+        );
+
+        let batch = arrow::compute::concat_batches(&events_schema(), &batches).unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<TimestampMillisecondType>()
+                .values(),
+            &[1_704_067_200_500, 1_704_067_201_000, 1_704_067_202_000]
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_string::<i32>()
+                .iter()
+                .collect::<Vec<_>>(),
+            [Some("42"), None, Some("c")]
+        );
+        assert_eq!(
+            batch
+                .column(2)
+                .as_primitive::<Int32Type>()
+                .iter()
+                .collect::<Vec<_>>(),
+            [Some(200), Some(404), None]
+        );
+    }
+
+    #[test]
+    fn test_malformed_lines_within_threshold() {
+        let ndjson = r#"{"ts": 1, "status": "7"}
+not json
+{"host": "no ts"}
+{"ts": 2, "extra": 1}
+{"ts": 3, "status": "ok"}
+{"ts": 4}
+"#;
+        // Under strict mode the unknown `extra` key makes its line malformed too
+        let (_dir, mut reader) = reader(ndjson, true, 4);
+        let batches = reader.by_ref().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+        assert_eq!(reader.malformed_lines(), 4);
+    }
+
+    #[test]
+    fn test_malformed_lines_threshold_aborts() {
+        let ndjson = "{\"ts\": 1}\nnot json\n{\"ts\": \"yesterday\"}\n{\"ts\": 3}\n";
+        let (_dir, reader) = reader(ndjson, false, 1);
+        let err = reader.collect::<Result<Vec<_>>>().unwrap_err();
+        match &err {
+            Error::TooManyMalformedLines { source, .. } => assert!(
+                matches!(**source, Error::ParseJsonLine { line_no: 3, .. }),
+                "{:?}",
+                source
+            ),
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(err.to_string().contains("column ts"), "{}", err);
+    }
+
+    #[test]
+    fn test_corrupt_line() {
+        let ndjson = "{\"ts\": 1}\n{\"ts\": 2, \"host\": \"a\"\n{\"ts\": 3}\n";
+        let (_dir, reader) = reader(ndjson, false, 0);
+        let err = reader.collect::<Result<Vec<_>>>().unwrap_err();
+        match err {
+            Error::TooManyMalformedLines { source, .. } => match *source {
+                Error::ParseJsonLine { line_no, .. } => assert_eq!(line_no, 2),
+                e => panic!("unexpected source {:?}", e),
+            },
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_corrupt_gzip_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.ndjson.gz");
+        // A valid gzip header followed by a deflate stream that isn't one
+        let mut bytes = vec![0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff];
+        bytes.extend([0xff; 32]);
+        std::fs::write(&path, bytes).unwrap();
+
+        let reader = NdjsonReader::open(&path, events_schema(), 2, false, 0).unwrap();
+        let err = reader.collect::<Result<Vec<_>>>().unwrap_err();
+        assert!(matches!(err, Error::Compression { .. }), "{:?}", err);
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+
+use crate::loader::report::PacingSample;
+
+// Fewest latencies the p95 of a window is taken over
+const MIN_WINDOW: usize = 4;
+
+/// Additive-increase/multiplicative-decrease control of the requests in
+/// flight, driven by their latency. Once a window of latencies is recorded,
+/// one more request is allowed when their p95 is within the target, half as
+/// many when it's over. A window holds as many latencies as requests were
+/// allowed, so every request in flight is heard from before the next step.
+#[derive(Debug, Clone)]
+pub struct AimdController {
+    target_latency: Duration,
+    max_concurrency: usize,
+    concurrency: usize,
+    window: Vec<Duration>,
+}
+
+impl AimdController {
+    /// Starts from a single request in flight
+    pub fn new(target_latency: Duration, max_concurrency: usize) -> Self {
+        AimdController {
+            target_latency,
+            max_concurrency: max_concurrency.max(1),
+            concurrency: 1,
+            window: Vec::new(),
+        }
+    }
+
+    /// Requests allowed in flight
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Record the latency of an acknowledged request. Completing a window
+    /// adjusts the concurrency and returns the p95 latency of the window.
+    pub fn record(&mut self, latency: Duration) -> Option<Duration> {
+        self.window.push(latency);
+        if self.window.len() < self.concurrency.max(MIN_WINDOW) {
+            return None;
+        }
+        let p95 = p95(&mut self.window);
+        self.window.clear();
+        self.concurrency = if p95 <= self.target_latency {
+            (self.concurrency + 1).min(self.max_concurrency)
+        } else {
+            (self.concurrency / 2).max(1)
+        };
+        Some(p95)
+    }
+}
+
+// 95th percentile by the nearest-rank method
+fn p95(latencies: &mut [Duration]) -> Duration {
+    latencies.sort_unstable();
+    let rank = (latencies.len() * 95).div_ceil(100);
+    latencies[rank.max(1) - 1]
+}
+
+/// Admits requests shared across the workers of a load as far as an
+/// `AimdController` allows, recording the controller's steps as samples
+pub struct AdaptivePacer {
+    state: Mutex<PacerState>,
+    released: Notify,
+    started: Instant,
+    max_concurrency: usize,
+}
+
+struct PacerState {
+    controller: AimdController,
+    in_flight: usize,
+    samples: Vec<PacingSample>,
+}
+
+impl AdaptivePacer {
+    pub fn new(target_latency: Duration, max_concurrency: usize) -> Self {
+        let controller = AimdController::new(target_latency, max_concurrency);
+        AdaptivePacer {
+            max_concurrency: max_concurrency.max(1),
+            state: Mutex::new(PacerState {
+                controller,
+                in_flight: 0,
+                samples: Vec::new(),
+            }),
+            released: Notify::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Most requests the controller may ever allow in flight
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Wait until the controller allows another request in flight. The
+    /// request is in flight until the returned permit is dropped.
+    pub async fn acquire(&self) -> PacerPermit<'_> {
+        loop {
+            // Created before checking, so a release in between isn't missed
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.controller.concurrency() {
+                    state.in_flight += 1;
+                    return PacerPermit { pacer: self };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Record the latency of an acknowledged request
+    pub fn record(&self, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(p95_latency) = state.controller.record(latency) {
+            let sample = PacingSample {
+                elapsed: self.started.elapsed(),
+                concurrency: state.controller.concurrency(),
+                p95_latency,
+            };
+            state.samples.push(sample);
+            // A raised concurrency admits waiting requests right away
+            self.released.notify_waiters();
+        }
+    }
+
+    /// Every step of the controller so far
+    pub fn samples(&self) -> Vec<PacingSample> {
+        self.state.lock().unwrap().samples.clone()
+    }
+}
+
+/// A request admitted by an `AdaptivePacer`
+pub struct PacerPermit<'a> {
+    pacer: &'a AdaptivePacer,
+}
+
+impl Drop for PacerPermit<'_> {
+    fn drop(&mut self) {
+        self.pacer.state.lock().unwrap().in_flight -= 1;
+        self.pacer.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    // Feed windows of `latency` until the controller steps, returning the
+    // concurrency after every step
+    fn steps(controller: &mut AimdController, latencies: &[u64]) -> Vec<usize> {
+        latencies
+            .iter()
+            .map(|&latency| {
+                while controller.record(millis(latency)).is_none() {}
+                controller.concurrency()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_increases_additively_up_to_max() {
+        let mut controller = AimdController::new(millis(100), 6);
+        assert_eq!(controller.concurrency(), 1);
+        assert_eq!(
+            steps(&mut controller, &[50, 50, 50, 100, 50, 50, 50]),
+            [2, 3, 4, 5, 6, 6, 6]
+        );
+    }
+
+    #[test]
+    fn test_backs_off_multiplicatively() {
+        let mut controller = AimdController::new(millis(100), 64);
+        steps(&mut controller, &[10; 20]);
+        assert_eq!(controller.concurrency(), 21);
+        assert_eq!(
+            steps(&mut controller, &[500, 500, 500, 500, 500, 50, 50]),
+            [10, 5, 2, 1, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_window_grows_with_concurrency() {
+        let mut controller = AimdController::new(millis(100), 64);
+        steps(&mut controller, &[10; 7]);
+        assert_eq!(controller.concurrency(), 8);
+        // 8 requests in flight are all heard from before the next step
+        for _ in 0..7 {
+            assert_eq!(controller.record(millis(10)), None);
+        }
+        assert_eq!(controller.record(millis(10)), Some(millis(10)));
+        assert_eq!(controller.concurrency(), 9);
+    }
+
+    #[test]
+    fn test_judges_by_p95() {
+        let mut controller = AimdController::new(millis(100), 64);
+        steps(&mut controller, &[10; 19]);
+        assert_eq!(controller.concurrency(), 20);
+        // One slow request in 20 is within the 95th percentile
+        for _ in 0..19 {
+            controller.record(millis(10));
+        }
+        assert_eq!(controller.record(millis(900)), Some(millis(10)));
+        assert_eq!(controller.concurrency(), 21);
+        // Two in 21 aren't
+        for _ in 0..19 {
+            controller.record(millis(10));
+        }
+        controller.record(millis(900));
+        assert_eq!(controller.record(millis(900)), Some(millis(900)));
+        assert_eq!(controller.concurrency(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_pacer_admits_up_to_concurrency() {
+        let pacer = AdaptivePacer::new(millis(100), 8);
+        let first = pacer.acquire().await;
+        // A single request is allowed in flight until a window is in
+        let second = pacer.acquire();
+        tokio::pin!(second);
+        assert!(futures::poll!(second.as_mut()).is_pending());
+        drop(first);
+        let second = second.await;
+
+        for _ in 0..MIN_WINDOW {
+            pacer.record(millis(10));
+        }
+        let third = tokio::time::timeout(millis(1000), pacer.acquire()).await;
+        assert!(third.is_ok());
+        drop(second);
+
+        let samples = pacer.samples();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].concurrency, 2);
+        assert_eq!(samples[0].p95_latency, millis(10));
+    }
+}
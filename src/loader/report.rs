@@ -0,0 +1,536 @@
+use std::{fmt, time::Duration};
+
+use crate::{client::metrics::LatencyHistogram, common::error::ServerErrorCode};
+
+/// Step run against the target tables before loading
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PreLoadStep {
+    #[default]
+    None,
+    // Drop every target table, they are recreated by the create/load steps
+    Recreate,
+    // Truncate every target table through the SQL path
+    Truncate,
+}
+
+impl fmt::Display for PreLoadStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreLoadStep::None => write!(f, "none"),
+            PreLoadStep::Recreate => write!(f, "recreate"),
+            PreLoadStep::Truncate => write!(f, "truncate"),
+        }
+    }
+}
+
+/// Rows loaded into a single table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableLoadReport {
+    pub table: String,
+    pub affected_rows: u32,
+    pub elapsed: Duration,
+    // Rows of the dataset outside the loaded time range
+    pub skipped_rows: usize,
+}
+
+impl TableLoadReport {
+    pub fn rows_per_sec(&self) -> f64 {
+        rows_per_sec(self.affected_rows as u64, self.elapsed)
+    }
+}
+
+/// One pass over the dataset of a soak test, the first one includes the
+/// dimension tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IterationReport {
+    pub iteration: usize,
+    pub rows: u64,
+    pub elapsed: Duration,
+}
+
+impl IterationReport {
+    pub fn rows_per_sec(&self) -> f64 {
+        rows_per_sec(self.rows, self.elapsed)
+    }
+}
+
+/// A step of adaptive pacing, the concurrency it settled on and the p95
+/// latency of the requests it was judged by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacingSample {
+    // Since the load started
+    pub elapsed: Duration,
+    pub concurrency: usize,
+    pub p95_latency: Duration,
+}
+
+/// A loaded table that doesn't hold what was read from the dataset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationMismatch {
+    pub table: String,
+    // What was compared, e.g. "row count" or "min(timestamp)"
+    pub check: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for VerificationMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} expected {}, got {}",
+            self.table, self.check, self.expected, self.actual
+        )
+    }
+}
+
+/// Summary of a load run, so results of different runs are comparable
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    // Backend the data was loaded into, e.g. "greptime" or "influxdb"
+    pub target: String,
+    // Ingestion protocol, e.g. "grpc" or "mysql"
+    pub protocol: String,
+    // Connections requests were spread across
+    pub channels: usize,
+    // Compression of the requests, e.g. "gzip", none when uncompressed
+    pub compression: Option<String>,
+    pub pre_load_step: PreLoadStep,
+    pub tables: Vec<TableLoadReport>,
+    // Filled by post-load verification, empty when it passed or didn't run
+    pub mismatches: Vec<VerificationMismatch>,
+    // Input lines skipped because they couldn't be parsed
+    pub malformed_lines: usize,
+    // Throughput of every pass of a soak test, empty for a single pass. Table
+    // reports add up all passes.
+    pub iterations: Vec<IterationReport>,
+    // Steps of adaptive pacing in the order they were taken, empty without it
+    pub pacing: Vec<PacingSample>,
+    // Latencies of the unary gRPC requests or of the INSERT statements run
+    // through usql, empty for other protocols
+    pub latency: LatencyHistogram,
+    // Rows deleted from each table after loading it, timed apart from the
+    // load. Empty unless a share of the rows is deleted.
+    pub deletes: Vec<TableLoadReport>,
+}
+
+impl LoadReport {
+    pub fn new(
+        target: impl Into<String>,
+        protocol: impl Into<String>,
+        pre_load_step: PreLoadStep,
+    ) -> Self {
+        LoadReport {
+            target: target.into(),
+            protocol: protocol.into(),
+            channels: 1,
+            compression: None,
+            pre_load_step,
+            tables: Vec::new(),
+            mismatches: Vec::new(),
+            malformed_lines: 0,
+            iterations: Vec::new(),
+            pacing: Vec::new(),
+            latency: LatencyHistogram::default(),
+            deletes: Vec::new(),
+        }
+    }
+
+    /// Whether post-load verification found no mismatch
+    pub fn verified(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Total rows acknowledged by the server across all tables
+    pub fn total_rows(&self) -> u64 {
+        self.tables.iter().map(|t| t.affected_rows as u64).sum()
+    }
+
+    /// Time spent loading across all tables
+    pub fn total_elapsed(&self) -> Duration {
+        self.tables.iter().map(|t| t.elapsed).sum()
+    }
+
+    pub fn rows_per_sec(&self) -> f64 {
+        rows_per_sec(self.total_rows(), self.total_elapsed())
+    }
+
+    /// Total rows deleted after loading across all tables
+    pub fn deleted_rows(&self) -> u64 {
+        self.deletes.iter().map(|t| t.affected_rows as u64).sum()
+    }
+
+    /// Rows deleted per second of time spent deleting
+    pub fn delete_rows_per_sec(&self) -> f64 {
+        let elapsed = self.deletes.iter().map(|t| t.elapsed).sum();
+        rows_per_sec(self.deleted_rows(), elapsed)
+    }
+}
+
+/// A target that stopped receiving writes during a dual-write load
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetFailure {
+    pub target: String,
+    pub table: String,
+    pub error: String,
+    // GreptimeDB's error code of the failure, none for other targets and
+    // errors without one
+    pub code: Option<ServerErrorCode>,
+}
+
+impl fmt::Display for TargetFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed on {}", self.target, self.table)?;
+        if let Some(code) = self.code {
+            write!(f, " with {}", code)?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+/// Summary of a dual-write load, one report per target. Time spent reading
+/// the dataset is shared and counted in neither.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DualLoadReport {
+    pub reports: [LoadReport; 2],
+    pub failures: Vec<TargetFailure>,
+}
+
+impl DualLoadReport {
+    /// Whether both targets received every chunk
+    pub fn succeeded(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for DualLoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{}", self.reports[0], self.reports[1])?;
+        if !self.succeeded() {
+            write!(f, "\n\nFailures:")?;
+            for failure in &self.failures {
+                write!(f, "\n  {}", failure)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rows_per_sec(rows: u64, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        return 0.0;
+    }
+    rows as f64 / elapsed.as_secs_f64()
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Target: {} ({}", self.target, self.protocol)?;
+        if let Some(compression) = &self.compression {
+            write!(f, " with {}", compression)?;
+        }
+        if self.channels > 1 {
+            write!(f, " over {} channels", self.channels)?;
+        }
+        writeln!(f, ")")?;
+        writeln!(f, "Pre-load step: {}", self.pre_load_step)?;
+        for table in &self.tables {
+            write!(
+                f,
+                "  {}: {} rows in {:.3}s ({:.0} rows/s)",
+                table.table,
+                table.affected_rows,
+                table.elapsed.as_secs_f64(),
+                table.rows_per_sec()
+            )?;
+            if table.skipped_rows > 0 {
+                write!(
+                    f,
+                    ", {} rows outside the time range skipped",
+                    table.skipped_rows
+                )?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "Total: {} rows in {:.3}s ({:.0} rows/s)",
+            self.total_rows(),
+            self.total_elapsed().as_secs_f64(),
+            self.rows_per_sec()
+        )?;
+        if self.malformed_lines > 0 {
+            write!(f, "\nMalformed lines skipped: {}", self.malformed_lines)?;
+        }
+        if !self.iterations.is_empty() {
+            write!(f, "\nIterations:")?;
+            for iteration in &self.iterations {
+                write!(
+                    f,
+                    "\n  {}: {} rows in {:.3}s ({:.0} rows/s)",
+                    iteration.iteration,
+                    iteration.rows,
+                    iteration.elapsed.as_secs_f64(),
+                    iteration.rows_per_sec()
+                )?;
+            }
+        }
+        if !self.deletes.is_empty() {
+            write!(f, "\nDeletes:")?;
+            for table in &self.deletes {
+                write!(
+                    f,
+                    "\n  {}: {} rows in {:.3}s ({:.0} rows/s)",
+                    table.table,
+                    table.affected_rows,
+                    table.elapsed.as_secs_f64(),
+                    table.rows_per_sec()
+                )?;
+            }
+            write!(
+                f,
+                "\nTotal deleted: {} rows ({:.0} rows/s)",
+                self.deleted_rows(),
+                self.delete_rows_per_sec()
+            )?;
+        }
+        if !self.pacing.is_empty() {
+            write!(f, "\nPacing:")?;
+            for sample in &self.pacing {
+                write!(
+                    f,
+                    "\n  {:>8.1}s: p95 {:>6}ms, concurrency {:>3} {}",
+                    sample.elapsed.as_secs_f64(),
+                    sample.p95_latency.as_millis(),
+                    sample.concurrency,
+                    "#".repeat(sample.concurrency)
+                )?;
+            }
+        }
+        if self.latency.requests > 0 {
+            write!(f, "\nRequest latency: {}", self.latency)?;
+        }
+        if !self.verified() {
+            write!(f, "\nVerification failed:")?;
+            for mismatch in &self.mismatches {
+                write!(f, "\n  {}", mismatch)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::metrics::{RequestMetrics, RequestOutcome};
+
+    #[test]
+    fn test_display() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::Recreate);
+        report.tables.push(TableLoadReport {
+            table: "users".to_string(),
+            affected_rows: 10,
+            elapsed: Duration::from_millis(500),
+            skipped_rows: 0,
+        });
+        report.tables.push(TableLoadReport {
+            table: "web_logs".to_string(),
+            affected_rows: 30,
+            elapsed: Duration::from_millis(1500),
+            skipped_rows: 0,
+        });
+
+        assert_eq!(
+            report.to_string(),
+            "Target: greptime (grpc)\n\
+             Pre-load step: recreate\n  \
+             users: 10 rows in 0.500s (20 rows/s)\n  \
+             web_logs: 30 rows in 1.500s (20 rows/s)\n\
+             Total: 40 rows in 2.000s (20 rows/s)"
+        );
+    }
+
+    #[test]
+    fn test_display_skipped_rows() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        report.tables.push(TableLoadReport {
+            table: "web_logs".to_string(),
+            affected_rows: 30,
+            elapsed: Duration::from_millis(1500),
+            skipped_rows: 70,
+        });
+
+        assert_eq!(
+            report.to_string(),
+            "Target: greptime (grpc)\n\
+             Pre-load step: none\n  \
+             web_logs: 30 rows in 1.500s (20 rows/s), 70 rows outside the time range skipped\n\
+             Total: 30 rows in 1.500s (20 rows/s)"
+        );
+    }
+
+    #[test]
+    fn test_display_iterations() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        for (iteration, millis) in [(1, 1000), (2, 1250)] {
+            report.iterations.push(IterationReport {
+                iteration,
+                rows: 100,
+                elapsed: Duration::from_millis(millis),
+            });
+        }
+
+        assert_eq!(
+            report.to_string(),
+            "Target: greptime (grpc)\n\
+             Pre-load step: none\n\
+             Total: 0 rows in 0.000s (0 rows/s)\n\
+             Iterations:\n  \
+             1: 100 rows in 1.000s (100 rows/s)\n  \
+             2: 100 rows in 1.250s (80 rows/s)"
+        );
+    }
+
+    #[test]
+    fn test_display_latency() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        for millis in [40, 60] {
+            report.latency.record(&RequestMetrics {
+                rows: 10,
+                bytes: 100,
+                latency: Duration::from_millis(millis),
+                outcome: RequestOutcome::Succeeded,
+            });
+        }
+
+        assert!(report.to_string().ends_with(
+            "\nRequest latency: 2 requests, 0 failed, p50 <= 50ms, p95 <= 60ms, p99 <= 60ms, max 60ms\n  \
+             <=    50ms: 1\n  \
+             <=   100ms: 1"
+        ));
+    }
+
+    #[test]
+    fn test_display_channels() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        report.channels = 4;
+        assert!(report
+            .to_string()
+            .starts_with("Target: greptime (grpc over 4 channels)\n"));
+        report.compression = Some("zstd".to_string());
+        assert!(report
+            .to_string()
+            .starts_with("Target: greptime (grpc with zstd over 4 channels)\n"));
+    }
+
+    #[test]
+    fn test_display_deletes() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        report.tables.push(TableLoadReport {
+            table: "web_logs".to_string(),
+            affected_rows: 100,
+            elapsed: Duration::from_secs(1),
+            skipped_rows: 0,
+        });
+        report.deletes.push(TableLoadReport {
+            table: "web_logs".to_string(),
+            affected_rows: 10,
+            elapsed: Duration::from_millis(250),
+            skipped_rows: 0,
+        });
+
+        assert_eq!(
+            report.to_string(),
+            "Target: greptime (grpc)\n\
+             Pre-load step: none\n  \
+             web_logs: 100 rows in 1.000s (100 rows/s)\n\
+             Total: 100 rows in 1.000s (100 rows/s)\n\
+             Deletes:\n  \
+             web_logs: 10 rows in 0.250s (40 rows/s)\n\
+             Total deleted: 10 rows (40 rows/s)"
+        );
+    }
+
+    #[test]
+    fn test_display_pacing() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        for (millis, concurrency, p95) in [(400, 2, 80), (1300, 3, 95), (2000, 1, 310)] {
+            report.pacing.push(PacingSample {
+                elapsed: Duration::from_millis(millis),
+                concurrency,
+                p95_latency: Duration::from_millis(p95),
+            });
+        }
+
+        assert_eq!(
+            report.to_string(),
+            "Target: greptime (grpc)\n\
+             Pre-load step: none\n\
+             Total: 0 rows in 0.000s (0 rows/s)\n\
+             Pacing:\n  \
+             \x20    0.4s: p95     80ms, concurrency   2 ##\n  \
+             \x20    1.3s: p95     95ms, concurrency   3 ###\n  \
+             \x20    2.0s: p95    310ms, concurrency   1 #"
+        );
+    }
+
+    #[test]
+    fn test_display_mismatches() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        report.mismatches.push(VerificationMismatch {
+            table: "users".to_string(),
+            check: "row count".to_string(),
+            expected: "10".to_string(),
+            actual: "9".to_string(),
+        });
+
+        assert!(!report.verified());
+        assert_eq!(
+            report.to_string(),
+            "Target: greptime (grpc)\n\
+             Pre-load step: none\n\
+             Total: 0 rows in 0.000s (0 rows/s)\n\
+             Verification failed:\n  \
+             users: row count expected 10, got 9"
+        );
+    }
+
+    #[test]
+    fn test_display_failure_codes() {
+        let failure = |code| TargetFailure {
+            target: "greptime#2".to_string(),
+            table: "users".to_string(),
+            error: "region busy".to_string(),
+            code,
+        };
+        assert_eq!(
+            failure(Some(ServerErrorCode::RegionBusy)).to_string(),
+            "greptime#2 failed on users with RegionBusy(4009): region busy"
+        );
+        assert_eq!(
+            failure(None).to_string(),
+            "greptime#2 failed on users: region busy"
+        );
+    }
+
+    #[test]
+    fn test_display_malformed_lines() {
+        let mut report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        report.malformed_lines = 3;
+
+        assert_eq!(
+            report.to_string(),
+            "Target: greptime (grpc)\n\
+             Pre-load step: none\n\
+             Total: 0 rows in 0.000s (0 rows/s)\n\
+             Malformed lines skipped: 3"
+        );
+    }
+
+    #[test]
+    fn test_rows_per_sec_without_elapsed_time() {
+        let report = LoadReport::new("greptime", "grpc", PreLoadStep::None);
+        assert_eq!(report.rows_per_sec(), 0.0);
+    }
+}
@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, AsArray, RecordBatch},
+    compute::{cast, max, min},
+    datatypes::{DataType, Int32Type, Int64Type, TimeUnit},
+};
+use snafu::ResultExt;
+
+use crate::common::error::{ArrowFileSnafu, Result};
+
+/// How the fact tables are shifted on every replay of a soak test, so each
+/// pass continues the timeline of the previous one with ids of its own.
+/// Timestamps move past the span the fact tables cover, integer `_id` columns
+/// by one more than the largest id, except ids of dimension tables, which are
+/// only loaded once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoakPlan {
+    // From the earliest to the latest time index of the fact tables, plus a
+    // tick of the coarsest timestamp unit so that passes don't share an
+    // instant
+    time_span_nanos: i64,
+    // One more than the largest id of the fact tables
+    id_span: i64,
+    // Id columns referencing dimension tables
+    dimension_ids: Vec<String>,
+}
+
+impl SoakPlan {
+    pub fn new(fact_tables: &[&RecordBatch], dimension_ids: Vec<String>) -> Result<Self> {
+        let mut time_range: Option<(i64, i64)> = None;
+        let mut tick = 1;
+        let mut max_id = -1;
+        for batch in fact_tables {
+            for (field, col) in batch.schema().fields().iter().zip(batch.columns()) {
+                if let DataType::Timestamp(unit, _) = field.data_type() {
+                    tick = tick.max(nanos_per_tick(*unit));
+                    let values = cast(col, &DataType::Int64).context(ArrowFileSnafu {})?;
+                    let values = values.as_primitive::<Int64Type>();
+                    if let Some((lo, hi)) = min(values).zip(max(values)) {
+                        let (lo, hi) = (to_nanos(lo, *unit), to_nanos(hi, *unit));
+                        time_range = Some(match time_range {
+                            Some((start, end)) => (start.min(lo), end.max(hi)),
+                            None => (lo, hi),
+                        });
+                    }
+                } else if is_id(field.name(), field.data_type(), &dimension_ids) {
+                    let values = cast(col, &DataType::Int64).context(ArrowFileSnafu {})?;
+                    if let Some(id) = max(values.as_primitive::<Int64Type>()) {
+                        max_id = max_id.max(id);
+                    }
+                }
+            }
+        }
+
+        Ok(SoakPlan {
+            // Rounded up to whole ticks, so every unit shifts by whole values
+            time_span_nanos: time_range.map_or(0, |(start, end)| {
+                ((end - start) / tick)
+                    .saturating_add(1)
+                    .saturating_mul(tick)
+            }),
+            id_span: max_id + 1,
+            dimension_ids,
+        })
+    }
+
+    /// `batch` as loaded on the `iteration`th replay, the first load being
+    /// iteration 0
+    pub fn shift(&self, batch: &RecordBatch, iteration: usize) -> Result<RecordBatch> {
+        let iteration = iteration as i64;
+        let time_offset = self.time_span_nanos.saturating_mul(iteration);
+        let id_offset = self.id_span.saturating_mul(iteration);
+        let columns = batch
+            .schema()
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(field, col)| match field.data_type() {
+                DataType::Timestamp(unit, _) => {
+                    shift_timestamps(col, time_offset / nanos_per_tick(*unit))
+                }
+                DataType::Int32 if is_id(field.name(), field.data_type(), &self.dimension_ids) => {
+                    let offset = id_offset as i32;
+                    let ids = col.as_primitive::<Int32Type>();
+                    let shifted = ids.unary::<_, Int32Type>(|v| v.wrapping_add(offset));
+                    Ok(Arc::new(shifted) as ArrayRef)
+                }
+                DataType::Int64 if is_id(field.name(), field.data_type(), &self.dimension_ids) => {
+                    let ids = col.as_primitive::<Int64Type>();
+                    let shifted = ids.unary::<_, Int64Type>(|v| v.wrapping_add(id_offset));
+                    Ok(Arc::new(shifted) as ArrayRef)
+                }
+                _ => Ok(col.clone()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        RecordBatch::try_new(batch.schema(), columns).context(ArrowFileSnafu {})
+    }
+}
+
+// Integer `_id` columns that don't reference a dimension table
+fn is_id(name: &str, datatype: &DataType, dimension_ids: &[String]) -> bool {
+    matches!(datatype, DataType::Int32 | DataType::Int64)
+        && name.ends_with("_id")
+        && !dimension_ids.iter().any(|id| id == name)
+}
+
+fn shift_timestamps(col: &ArrayRef, offset: i64) -> Result<ArrayRef> {
+    let values = cast(col, &DataType::Int64).context(ArrowFileSnafu {})?;
+    let shifted = values
+        .as_primitive::<Int64Type>()
+        .unary::<_, Int64Type>(|v| v.saturating_add(offset));
+    cast(&shifted, col.data_type()).context(ArrowFileSnafu {})
+}
+
+fn nanos_per_tick(unit: TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Second => 1_000_000_000,
+        TimeUnit::Millisecond => 1_000_000,
+        TimeUnit::Microsecond => 1_000,
+        TimeUnit::Nanosecond => 1,
+    }
+}
+
+fn to_nanos(value: i64, unit: TimeUnit) -> i64 {
+    value.saturating_mul(nanos_per_tick(unit))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{Int32Array, Int64Array, TimestampMicrosecondArray, TimestampSecondArray},
+        datatypes::{Field, Schema, TimestampMicrosecondType, TimestampSecondType},
+    };
+
+    use super::*;
+
+    fn web_logs() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("log_id", DataType::Int32, false),
+            Field::new("user_id", DataType::Int32, false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(Int32Array::from(vec![7, 8, 7])),
+                Arc::new(TimestampMicrosecondArray::from(vec![
+                    1_000_000, 2_500_000, 4_000_000,
+                ])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn error_logs() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("error_log_id", DataType::Int64, false),
+            Field::new("log_id", DataType::Int32, false),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Second, None),
+                false,
+            ),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![10, 11])),
+                Arc::new(Int32Array::from(vec![1, 3])),
+                Arc::new(TimestampSecondArray::from(vec![2, 5])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn plan() -> SoakPlan {
+        SoakPlan::new(&[&web_logs(), &error_logs()], vec!["user_id".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_plan_spans() {
+        assert_eq!(
+            plan(),
+            SoakPlan {
+                // 1s to 5s over both tables, plus a second of error_logs
+                time_span_nanos: 5_000_000_000,
+                // error_log_id 11 is the largest id
+                id_span: 12,
+                dimension_ids: vec!["user_id".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_shift() {
+        let plan = plan();
+        assert_eq!(plan.shift(&web_logs(), 0).unwrap(), web_logs());
+
+        let shifted = plan.shift(&web_logs(), 2).unwrap();
+        let log_ids = shifted.column(0).as_primitive::<Int32Type>();
+        assert_eq!(log_ids.values(), &[25, 26, 27]);
+        // Dimension ids are left alone
+        assert_eq!(shifted.column(1), web_logs().column(1));
+        let timestamps = shifted.column(2).as_primitive::<TimestampMicrosecondType>();
+        assert_eq!(timestamps.values(), &[11_000_000, 12_500_000, 14_000_000]);
+
+        let shifted = plan.shift(&error_logs(), 1).unwrap();
+        let error_log_ids = shifted.column(0).as_primitive::<Int64Type>();
+        assert_eq!(error_log_ids.values(), &[22, 23]);
+        // References keep pointing at the shifted rows of their table
+        let log_ids = shifted.column(1).as_primitive::<Int32Type>();
+        assert_eq!(log_ids.values(), &[13, 15]);
+        let timestamps = shifted.column(2).as_primitive::<TimestampSecondType>();
+        assert_eq!(timestamps.values(), &[7, 10]);
+        assert_eq!(shifted.schema(), error_logs().schema());
+    }
+
+    #[test]
+    fn test_passes_dont_share_timestamps() {
+        let plan = plan();
+        // Time indexes of both tables on a pass, in nanoseconds
+        let pass = |iteration| {
+            let web = plan.shift(&web_logs(), iteration).unwrap();
+            let error = plan.shift(&error_logs(), iteration).unwrap();
+            let web = web.column(2).as_primitive::<TimestampMicrosecondType>();
+            let error = error.column(2).as_primitive::<TimestampSecondType>();
+            web.values()
+                .iter()
+                .map(|&v| to_nanos(v, TimeUnit::Microsecond))
+                .chain(
+                    error
+                        .values()
+                        .iter()
+                        .map(|&v| to_nanos(v, TimeUnit::Second)),
+                )
+                .collect::<Vec<_>>()
+        };
+        for iteration in 0..3 {
+            let (this, next) = (pass(iteration), pass(iteration + 1));
+            assert!(
+                this.iter().max() < next.iter().min(),
+                "pass {} ends at or after pass {} starts",
+                iteration,
+                iteration + 1
+            );
+        }
+    }
+}
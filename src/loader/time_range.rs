@@ -0,0 +1,186 @@
+use arrow::{
+    array::{AsArray, BooleanArray, RecordBatch},
+    compute::{cast, filter_record_batch},
+    datatypes::{DataType, Int64Type, TimeUnit},
+};
+use chrono::{DateTime, Utc};
+use parquet::file::{metadata::ParquetMetaData, statistics::Statistics};
+use snafu::ResultExt;
+
+use crate::common::error::{ArrowFileSnafu, Result};
+
+/// Slice of a dataset to load by the time index of its rows. `from` is
+/// inclusive and `to` exclusive, so consecutive ranges load every row once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    /// Whether every row is in the range
+    pub fn is_unbounded(&self) -> bool {
+        self.from.is_none() && self.to.is_none()
+    }
+
+    /// Whether a time index value of `unit` precision is in the range
+    pub fn contains(&self, value: i64, unit: TimeUnit) -> bool {
+        let (from, to) = self.bounds(unit);
+        from.map_or(true, |from| value >= from) && to.map_or(true, |to| value < to)
+    }
+
+    /// Whether any value between `min` and `max`, both inclusive, is in the range
+    pub fn overlaps(&self, min: i64, max: i64, unit: TimeUnit) -> bool {
+        let (from, to) = self.bounds(unit);
+        from.map_or(true, |from| max >= from) && to.map_or(true, |to| min < to)
+    }
+
+    // Both bounds in ticks of `unit`, rounded up so a value `v` is in the range
+    // exactly when `from <= v < to`
+    fn bounds(&self, unit: TimeUnit) -> (Option<i64>, Option<i64>) {
+        (
+            self.from.map(|t| ticks(t, unit)),
+            self.to.map(|t| ticks(t, unit)),
+        )
+    }
+
+    /// Row groups of a parquet file that may hold rows in the range, judged by
+    /// the statistics of the time index column at `column`, and the rows of
+    /// the groups left out. Groups without statistics are kept.
+    pub fn prune_row_groups(
+        &self,
+        metadata: &ParquetMetaData,
+        column: usize,
+        unit: TimeUnit,
+    ) -> (Vec<usize>, usize) {
+        let mut kept = Vec::new();
+        let mut skipped_rows = 0;
+        for (i, row_group) in metadata.row_groups().iter().enumerate() {
+            let overlaps = match row_group.column(column).statistics() {
+                Some(Statistics::Int64(stats)) if stats.has_min_max_set() => {
+                    self.overlaps(*stats.min(), *stats.max(), unit)
+                }
+                _ => true,
+            };
+            if overlaps {
+                kept.push(i);
+            } else {
+                skipped_rows += row_group.num_rows() as usize;
+            }
+        }
+        (kept, skipped_rows)
+    }
+
+    /// Rows of `record_batch` whose time index at `column` is in the range,
+    /// along with the number of rows left out. Null time indexes are left out.
+    pub fn filter(
+        &self,
+        record_batch: &RecordBatch,
+        column: usize,
+    ) -> Result<(RecordBatch, usize)> {
+        let col = record_batch.column(column);
+        let DataType::Timestamp(unit, _) = col.data_type() else {
+            return Ok((record_batch.clone(), 0));
+        };
+        let values = cast(col, &DataType::Int64).context(ArrowFileSnafu {})?;
+        let mask = values
+            .as_primitive::<Int64Type>()
+            .iter()
+            .map(|value| Some(value.is_some_and(|v| self.contains(v, *unit))))
+            .collect::<BooleanArray>();
+        let filtered = filter_record_batch(record_batch, &mask).context(ArrowFileSnafu {})?;
+        let skipped_rows = record_batch.num_rows() - filtered.num_rows();
+        Ok((filtered, skipped_rows))
+    }
+}
+
+// `time` in ticks of `unit` since the epoch, rounded up
+fn ticks(time: DateTime<Utc>, unit: TimeUnit) -> i64 {
+    let nanos = time.timestamp_subsec_nanos() as i64;
+    let (whole, rest) = match unit {
+        TimeUnit::Second => (time.timestamp(), nanos),
+        TimeUnit::Millisecond => (time.timestamp_millis(), nanos % 1_000_000),
+        TimeUnit::Microsecond => (time.timestamp_micros(), nanos % 1_000),
+        TimeUnit::Nanosecond => (time.timestamp_nanos_opt().unwrap_or(i64::MAX), 0),
+    };
+    whole.saturating_add((rest > 0) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, TimestampMillisecondArray},
+        datatypes::{Field, Schema},
+    };
+
+    use super::*;
+
+    fn range(from: &str, to: &str) -> TimeRange {
+        TimeRange {
+            from: Some(from.parse().unwrap()),
+            to: Some(to.parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let range = range("2024-01-01T00:00:00Z", "2024-01-01T01:00:00Z");
+        let from = 1_704_067_200_000;
+        let to = from + 3_600_000;
+        // `from` is included, `to` excluded
+        assert!(!range.contains(from - 1, TimeUnit::Millisecond));
+        assert!(range.contains(from, TimeUnit::Millisecond));
+        assert!(range.contains(to - 1, TimeUnit::Millisecond));
+        assert!(!range.contains(to, TimeUnit::Millisecond));
+        assert!(range.contains(from / 1000, TimeUnit::Second));
+        assert!(!range.contains(to * 1_000_000, TimeUnit::Nanosecond));
+
+        assert!(range.overlaps(from - 10, from, TimeUnit::Millisecond));
+        assert!(!range.overlaps(from - 10, from - 1, TimeUnit::Millisecond));
+        assert!(!range.overlaps(to, to + 10, TimeUnit::Millisecond));
+        assert!(TimeRange::default().contains(i64::MIN, TimeUnit::Second));
+    }
+
+    #[test]
+    fn test_sub_unit_bounds_round_up() {
+        // Half a second past `from` excludes the whole second it falls into
+        let range = range("2024-01-01T00:00:00.5Z", "2024-01-01T00:00:01.5Z");
+        let second = 1_704_067_200;
+        assert!(!range.contains(second, TimeUnit::Second));
+        assert!(range.contains(second + 1, TimeUnit::Second));
+        assert!(!range.contains(second + 2, TimeUnit::Second));
+    }
+
+    #[test]
+    fn test_filter() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+        ]));
+        let from = 1_704_067_200_000;
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5])),
+                Arc::new(TimestampMillisecondArray::from(vec![
+                    Some(from - 1),
+                    Some(from),
+                    None,
+                    Some(from + 3_599_999),
+                    Some(from + 3_600_000),
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let range = range("2024-01-01T00:00:00Z", "2024-01-01T01:00:00Z");
+        let (filtered, skipped_rows) = range.filter(&batch, 1).unwrap();
+        assert_eq!(skipped_rows, 3);
+        let ids = filtered
+            .column(0)
+            .as_primitive::<arrow::datatypes::Int32Type>();
+        assert_eq!(ids.values(), &[2, 4]);
+    }
+}
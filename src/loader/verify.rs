@@ -0,0 +1,265 @@
+use arrow::{
+    array::{Array, AsArray, RecordBatch},
+    compute::{cast, max, min},
+    datatypes::{DataType, Int64Type},
+};
+use greptime_proto::v1::{ColumnSchema, SemanticType};
+use snafu::ResultExt;
+
+use crate::{
+    client::greptime::DatabaseClient,
+    common::error::{ArrowFileSnafu, Result},
+    sql::quote::quote_ident,
+};
+
+use super::{dialect::SqlDialect, report::VerificationMismatch};
+
+/// What a table should hold after loading, taken from the data read from the
+/// dataset. Only meaningful for tables that were empty before the load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedTable {
+    pub rows: u64,
+    // Time index column with the min and max of its raw values
+    pub time_range: Option<(String, i64, i64)>,
+}
+
+impl ExpectedTable {
+    pub fn from_record_batch(record_batch: &RecordBatch, schema: &[ColumnSchema]) -> Result<Self> {
+        let time_index = schema
+            .iter()
+            .find(|col| col.semantic_type == SemanticType::Timestamp as i32)
+            .map(|col| col.column_name.as_str());
+        let time_column =
+            time_index.and_then(|name| record_batch.column_by_name(name).map(|col| (name, col)));
+        let time_range = match time_column {
+            Some((name, col)) => {
+                let values = cast(col, &DataType::Int64).context(ArrowFileSnafu {})?;
+                let values = values.as_primitive::<Int64Type>();
+                min(values)
+                    .zip(max(values))
+                    .map(|(min, max)| (name.to_string(), min, max))
+            }
+            None => None,
+        };
+
+        Ok(ExpectedTable {
+            rows: record_batch.num_rows() as u64,
+            time_range,
+        })
+    }
+}
+
+/// Query a loaded table in the database of `client` and list how it differs
+/// from `expected`
+pub async fn verify_table(
+    client: &DatabaseClient,
+    table: &str,
+    expected: &ExpectedTable,
+) -> Result<Vec<VerificationMismatch>> {
+    let qualified = format!(
+        "{}.{}",
+        quote_ident(SqlDialect::Greptime, client.dbname()),
+        quote_ident(SqlDialect::Greptime, table)
+    );
+    let mut mismatches = Vec::new();
+    let mut check = |check: String, expected: Option<i64>, actual: Option<i64>| {
+        if expected != actual {
+            mismatches.push(VerificationMismatch {
+                table: table.to_string(),
+                check,
+                expected: display(expected),
+                actual: display(actual),
+            });
+        }
+    };
+
+    let count = fetch_i64s(client, &format!("SELECT count(*) FROM {}", qualified)).await?;
+    check(
+        "row count".to_string(),
+        Some(expected.rows as i64),
+        count.first().copied().flatten(),
+    );
+
+    if let Some((column, expected_min, expected_max)) = &expected.time_range {
+        // Cast to the raw epoch values, in the unit of the column
+        let range = fetch_i64s(
+            client,
+            &format!(
+                "SELECT CAST(min({0}) AS BIGINT), CAST(max({0}) AS BIGINT) FROM {1}",
+                quote_ident(SqlDialect::Greptime, column),
+                qualified
+            ),
+        )
+        .await?;
+        check(
+            format!("min({})", column),
+            Some(*expected_min),
+            range.first().copied().flatten(),
+        );
+        check(
+            format!("max({})", column),
+            Some(*expected_max),
+            range.get(1).copied().flatten(),
+        );
+    }
+
+    Ok(mismatches)
+}
+
+/// Compare the rows the server acknowledged inserting into `table` with the
+/// rows read from the dataset, for SQL loads that don't query the table back
+pub fn verify_acknowledged_rows(
+    table: &str,
+    read_rows: u64,
+    acknowledged_rows: u64,
+) -> Option<VerificationMismatch> {
+    (read_rows != acknowledged_rows).then(|| VerificationMismatch {
+        table: table.to_string(),
+        check: "acknowledged rows".to_string(),
+        expected: read_rows.to_string(),
+        actual: acknowledged_rows.to_string(),
+    })
+}
+
+// Columns of the first row returned by `query` as 64-bit integers
+async fn fetch_i64s(client: &DatabaseClient, query: &str) -> Result<Vec<Option<i64>>> {
+    let output = client.sql_with_rows(query).await?;
+    let Some(batch) = output.batch.filter(|batch| batch.num_rows() > 0) else {
+        return Ok(Vec::new());
+    };
+    batch
+        .columns()
+        .iter()
+        .map(|col| {
+            let values = cast(col, &DataType::Int64).context(ArrowFileSnafu {})?;
+            let values = values.as_primitive::<Int64Type>();
+            Ok(values.is_valid(0).then(|| values.value(0)))
+        })
+        .collect()
+}
+
+fn display(value: Option<i64>) -> String {
+    value.map_or_else(|| "NULL".to_string(), |v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, TimestampMicrosecondArray},
+        datatypes::{Field, Schema, TimeUnit},
+    };
+    use greptime_proto::v1::ColumnDataType;
+
+    use super::*;
+    use crate::loader::{field, tag, timestamp};
+
+    #[test]
+    fn test_expected_table() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("user_id", DataType::Int32, false),
+            Field::new(
+                "signup_date",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(TimestampMicrosecondArray::from(vec![
+                    Some(20),
+                    None,
+                    Some(10),
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let columns = vec![
+            tag("user_id", ColumnDataType::Int32),
+            timestamp("signup_date", ColumnDataType::TimestampMicrosecond),
+        ];
+        assert_eq!(
+            ExpectedTable::from_record_batch(&batch, &columns).unwrap(),
+            ExpectedTable {
+                rows: 3,
+                time_range: Some(("signup_date".to_string(), 10, 20)),
+            }
+        );
+
+        // Without a time index only the row count is checked
+        let columns = vec![field("user_id", ColumnDataType::Int32)];
+        assert_eq!(
+            ExpectedTable::from_record_batch(&batch, &columns)
+                .unwrap()
+                .time_range,
+            None
+        );
+    }
+
+    #[test]
+    fn test_verify_acknowledged_rows() {
+        assert_eq!(verify_acknowledged_rows("web_logs", 3, 3), None);
+        assert_eq!(
+            verify_acknowledged_rows("web_logs", 3, 2)
+                .unwrap()
+                .to_string(),
+            "web_logs: acknowledged rows expected 3, got 2"
+        );
+    }
+
+    #[cfg(feature = "integration")]
+    #[tokio::test]
+    async fn test_verify_reports_mismatch() {
+        use crate::{
+            client::ddl::CreateTableSpec,
+            generator::{data_generator::DataGenerator, log_data_generator::LogDataGenerator},
+        };
+
+        let client = DatabaseClient::new("public").await.unwrap();
+        let schema = LogDataGenerator::schema("users");
+        client.drop_table("it_verify_users").await.unwrap();
+        client
+            .create_table(CreateTableSpec::new("it_verify_users", schema))
+            .await
+            .unwrap();
+        client
+            .sql(
+                "INSERT INTO it_verify_users(user_id, username, email, signup_date) \
+                 VALUES (1, 'a', 'a@example.com', 1000), (2, 'b', 'b@example.com', 2000)",
+            )
+            .await
+            .unwrap();
+
+        let expected = ExpectedTable {
+            rows: 2,
+            time_range: Some(("signup_date".to_string(), 1000, 2000)),
+        };
+        assert!(verify_table(&client, "it_verify_users", &expected)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Deliberately wrong expectations
+        let expected = ExpectedTable {
+            rows: 3,
+            time_range: Some(("signup_date".to_string(), 1000, 3000)),
+        };
+        let mismatches = verify_table(&client, "it_verify_users", &expected)
+            .await
+            .unwrap();
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "it_verify_users: row count expected 3, got 2",
+                "it_verify_users: max(signup_date) expected 3000, got 2000",
+            ]
+        );
+    }
+}
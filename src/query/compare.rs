@@ -0,0 +1,484 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::common::rows::{QueryRows, QueryValue};
+use crate::query::generator::orders_rows;
+use crate::query::report::QueryRunReport;
+
+/// Differing cells a diff lists, the count of the others follows them
+pub const DIFF_CELLS_KEPT: usize = 20;
+
+/// Rows of either result a mismatch shows
+pub const DIFF_ROWS_SHOWN: usize = 10;
+
+/// Mismatches a comparison keeps for its diff report
+pub const MISMATCHES_KEPT: usize = 100;
+
+// Mismatches the text of a comparison report lists
+const MISMATCHES_SHOWN: usize = 10;
+
+/// File `run_queries --compare` writes the diff report to by default
+pub const DIFF_REPORT_FILE_NAME: &str = "query_diff.txt";
+
+/// How far results of two targets may be apart and still match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompareOptions {
+    // Relative difference of floats that still match, of the larger of the
+    // two and at least 1. Aggregates over floats differ in the last digits
+    // with the order they are summed in.
+    pub float_tolerance: f64,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        CompareOptions {
+            float_tolerance: 1e-9,
+        }
+    }
+}
+
+impl CompareOptions {
+    /// Whether `left` and `right` are the same value. NULL only matches
+    /// NULL, integers and floats match by value, and timestamps match
+    /// strings holding the same time.
+    pub fn values_match(&self, left: &QueryValue, right: &QueryValue) -> bool {
+        match (left, right) {
+            (QueryValue::Null, right) => right.is_null(),
+            (_, QueryValue::Null) => false,
+            (QueryValue::Int(left), QueryValue::Int(right)) => left == right,
+            (QueryValue::String(left), QueryValue::String(right)) => left == right,
+            _ => match (left.as_f64(), right.as_f64()) {
+                (Some(left), Some(right)) => self.floats_match(left, right),
+                (None, None) => left
+                    .as_timestamp()
+                    .zip(right.as_timestamp())
+                    .is_some_and(|(left, right)| left == right),
+                _ => false,
+            },
+        }
+    }
+
+    fn floats_match(&self, left: f64, right: f64) -> bool {
+        if left.is_nan() || right.is_nan() {
+            return left.is_nan() && right.is_nan();
+        }
+        if left == right {
+            return true;
+        }
+        let scale = left.abs().max(right.abs()).max(1.0);
+        (left - right).abs() <= self.float_tolerance * scale
+    }
+
+    /// Differences between the results of `sql` on two targets, empty when
+    /// they match. Both are normalized first, see `normalize`.
+    pub fn diff(&self, sql: &str, left: &QueryRows, right: &QueryRows) -> Vec<String> {
+        let ordered = orders_rows(sql);
+        let right = match align_columns(left, right) {
+            Some(right) => right,
+            None => {
+                return vec![format!(
+                    "columns {} on the left, {} on the right",
+                    left.columns.join(", "),
+                    right.columns.join(", ")
+                )]
+            }
+        };
+        let (left, right) = (normalize(left, ordered), normalize(&right, ordered));
+        if left.rows.len() != right.rows.len() {
+            return vec![format!(
+                "{} rows on the left, {} on the right",
+                left.rows.len(),
+                right.rows.len()
+            )];
+        }
+        let mut diffs = Vec::new();
+        let mut differing = 0;
+        for (i, (left_row, right_row)) in left.rows.iter().zip(&right.rows).enumerate() {
+            for (column, (left_value, right_value)) in left_row.iter().zip(right_row).enumerate() {
+                if self.values_match(left_value, right_value) {
+                    continue;
+                }
+                differing += 1;
+                if diffs.len() < DIFF_CELLS_KEPT {
+                    diffs.push(format!(
+                        "row {} column {}: {} on the left, {} on the right",
+                        i,
+                        left.columns[column],
+                        ValueText(left_value),
+                        ValueText(right_value)
+                    ));
+                }
+            }
+        }
+        if differing > diffs.len() {
+            diffs.push(format!("{} more cells differ", differing - diffs.len()));
+        }
+        diffs
+    }
+}
+
+// `right` with its columns in the order of those of `left`, going by their
+// names regardless of case. Servers name expressions differently, results
+// with other names are compared column by column as they are. `None` for
+// results of a different number of columns.
+fn align_columns(left: &QueryRows, right: &QueryRows) -> Option<QueryRows> {
+    if left.columns.len() != right.columns.len() {
+        return None;
+    }
+    let positions = left
+        .columns
+        .iter()
+        .map(|name| {
+            right
+                .columns
+                .iter()
+                .position(|other| other.eq_ignore_ascii_case(name))
+        })
+        .collect::<Option<Vec<_>>>();
+    let Some(positions) = positions else {
+        return Some(right.clone());
+    };
+    Some(QueryRows {
+        columns: positions
+            .iter()
+            .map(|&i| right.columns[i].clone())
+            .collect(),
+        rows: right
+            .rows
+            .iter()
+            .map(|row| positions.iter().map(|&i| row[i].clone()).collect())
+            .collect(),
+    })
+}
+
+/// `rows` as compared with the result of another target: the rows sorted
+/// unless the query orders them, NULLs first
+pub fn normalize(rows: &QueryRows, ordered: bool) -> QueryRows {
+    let mut rows = rows.clone();
+    if !ordered {
+        rows.rows.sort_by(|left, right| {
+            left.iter()
+                .zip(right)
+                .map(|(left, right)| compare_values(left, right))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+    rows
+}
+
+// Total order of values to sort rows by: NULL, numbers, timestamps, then
+// strings
+fn compare_values(left: &QueryValue, right: &QueryValue) -> Ordering {
+    fn rank(value: &QueryValue) -> u8 {
+        match value {
+            QueryValue::Null => 0,
+            QueryValue::Int(_) | QueryValue::Float(_) => 1,
+            QueryValue::Timestamp(_) => 2,
+            QueryValue::String(_) => 3,
+        }
+    }
+    match (left, right) {
+        (QueryValue::Int(left), QueryValue::Int(right)) => left.cmp(right),
+        (QueryValue::Timestamp(left), QueryValue::Timestamp(right)) => left.cmp(right),
+        (QueryValue::String(left), QueryValue::String(right)) => left.cmp(right),
+        _ => match (left.as_f64(), right.as_f64()) {
+            (Some(left), Some(right)) => left.total_cmp(&right),
+            _ => rank(left).cmp(&rank(right)),
+        },
+    }
+}
+
+// A value as a diff shows it
+struct ValueText<'a>(&'a QueryValue);
+
+impl fmt::Display for ValueText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            QueryValue::Null => write!(f, "NULL"),
+            QueryValue::Int(v) => write!(f, "{}", v),
+            QueryValue::Float(v) => write!(f, "{}", v),
+            QueryValue::String(v) => write!(f, "{:?}", v),
+            QueryValue::Timestamp(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// A query whose results on the two targets differ, or that failed on one
+/// of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultMismatch {
+    // Position of the query in the run
+    pub index: usize,
+    // Empty for a query of a .sql file
+    pub name: String,
+    pub sql: String,
+    // Result or error of either target
+    pub left: Result<QueryRows, String>,
+    pub right: Result<QueryRows, String>,
+    pub diffs: Vec<String>,
+}
+
+impl fmt::Display for ResultMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Query {} {}", self.index, self.name)?;
+        writeln!(f, "  {}", self.sql)?;
+        for (side, result) in [("Left", &self.left), ("Right", &self.right)] {
+            match result {
+                Ok(rows) => {
+                    writeln!(f, "{}: {} rows", side, rows.rows.len())?;
+                    writeln!(f, "  {}", rows.columns.join(" | "))?;
+                    for row in rows.rows.iter().take(DIFF_ROWS_SHOWN) {
+                        let values = row
+                            .iter()
+                            .map(|value| ValueText(value).to_string())
+                            .collect::<Vec<_>>();
+                        writeln!(f, "  {}", values.join(" | "))?;
+                    }
+                    if rows.rows.len() > DIFF_ROWS_SHOWN {
+                        writeln!(f, "  ...")?;
+                    }
+                }
+                Err(e) => writeln!(f, "{}: failed, {}", side, e)?,
+            }
+        }
+        writeln!(f, "Differences:")?;
+        for diff in &self.diffs {
+            writeln!(f, "  {}", diff)?;
+        }
+        Ok(())
+    }
+}
+
+/// Summary of a run of the queries on two targets: the report of either,
+/// and the queries whose results differ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComparisonReport {
+    pub left: QueryRunReport,
+    pub right: QueryRunReport,
+    // Queries that ran on both targets, the ones that failed on both left
+    // out
+    pub compared: usize,
+    // The first `MISMATCHES_KEPT` of them
+    pub mismatches: Vec<ResultMismatch>,
+    pub mismatch_count: usize,
+}
+
+impl ComparisonReport {
+    pub fn record(&mut self, mismatch: Option<ResultMismatch>) {
+        self.compared += 1;
+        if let Some(mismatch) = mismatch {
+            self.mismatch_count += 1;
+            if self.mismatches.len() < MISMATCHES_KEPT {
+                self.mismatches.push(mismatch);
+            }
+        }
+    }
+
+    /// The mismatches kept, as written by `run_queries --diff-report`
+    pub fn diff_report(&self) -> String {
+        let mut report = self
+            .mismatches
+            .iter()
+            .map(ResultMismatch::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.mismatch_count > self.mismatches.len() {
+            report.push_str(&format!(
+                "\n{} more mismatches left out\n",
+                self.mismatch_count - self.mismatches.len()
+            ));
+        }
+        report
+    }
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Left:\n{}", self.left)?;
+        writeln!(f, "Right:\n{}", self.right)?;
+        write!(
+            f,
+            "Compared {} results, {} differ",
+            self.compared, self.mismatch_count
+        )?;
+        // The diff report has the differences of every one kept
+        for mismatch in self.mismatches.iter().take(MISMATCHES_SHOWN) {
+            write!(
+                f,
+                "\n  Query {} {}: {}",
+                mismatch.index,
+                mismatch.name,
+                mismatch.diffs.first().map_or("", String::as_str)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    fn rows(columns: &[&str], rows: Vec<Vec<QueryValue>>) -> QueryRows {
+        QueryRows {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows,
+        }
+    }
+
+    #[test]
+    fn test_values_match() {
+        use QueryValue::{Float, Int, Null, String as Str};
+
+        let options = CompareOptions::default();
+        let time =
+            NaiveDateTime::parse_from_str("2023-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let cases = [
+            (Null, Null, true),
+            (Null, Int(0), false),
+            (Float(0.0), Null, false),
+            (Null, Str("NULL".to_string()), false),
+            (Int(3), Int(3), true),
+            (Int(3), Float(3.0), true),
+            (Float(0.1 + 0.2), Float(0.3), true),
+            (Float(1e12), Float(1e12 + 1e-2), true),
+            (Float(1e12), Float(1e12 + 1e4), false),
+            (Float(1e-12), Float(2e-12), true),
+            (Float(1.0), Float(1.001), false),
+            (Float(f64::NAN), Float(f64::NAN), true),
+            (Float(f64::NAN), Float(0.0), false),
+            (Str("a".to_string()), Str("a".to_string()), true),
+            (Str("a".to_string()), Str("A".to_string()), false),
+            (Str("3".to_string()), Int(3), false),
+            (
+                QueryValue::Timestamp(time),
+                Str("2023-01-01T00:00:00Z".to_string()),
+                true,
+            ),
+        ];
+        for (left, right, matching) in cases {
+            assert_eq!(
+                options.values_match(&left, &right),
+                matching,
+                "{:?} {:?}",
+                left,
+                right
+            );
+            assert_eq!(options.values_match(&right, &left), matching);
+        }
+        let loose = CompareOptions {
+            float_tolerance: 0.01,
+        };
+        assert!(loose.values_match(&Float(1.0), &Float(1.001)));
+    }
+
+    #[test]
+    fn test_normalize() {
+        use QueryValue::{Float, Int, Null};
+
+        let left = rows(
+            &["page_id", "hits"],
+            vec![
+                vec![Int(2), Float(5.0)],
+                vec![Null, Float(1.0)],
+                vec![Int(1), Null],
+            ],
+        );
+        // Other column order and case, rows in another order
+        let right = rows(
+            &["HITS", "page_id"],
+            vec![
+                vec![Null, Int(1)],
+                vec![Int(5), Int(2)],
+                vec![Float(1.0), Null],
+            ],
+        );
+        let options = CompareOptions::default();
+        let unordered = "SELECT page_id, hits FROM t";
+        assert_eq!(options.diff(unordered, &left, &right), Vec::<String>::new());
+        assert_eq!(
+            normalize(&left, false).rows[0],
+            [Null, Float(1.0)],
+            "NULLs sort first"
+        );
+        // The order of an ordered query counts
+        let ordered = "SELECT page_id, hits FROM t ORDER BY hits DESC";
+        assert_eq!(
+            options.diff(ordered, &left, &right),
+            [
+                "row 0 column page_id: 2 on the left, 1 on the right",
+                "row 0 column hits: 5 on the left, NULL on the right",
+                "row 1 column page_id: NULL on the left, 2 on the right",
+                "row 1 column hits: 1 on the left, 5 on the right",
+                "row 2 column page_id: 1 on the left, NULL on the right",
+                "row 2 column hits: NULL on the left, 1 on the right"
+            ]
+        );
+        // Not by an `ORDER BY` in a literal
+        assert!(!orders_rows("SELECT 'order by' FROM t"));
+        assert!(orders_rows("SELECT a FROM t order\nby a"));
+
+        let fewer = rows(&["page_id", "hits"], vec![vec![Int(2), Float(5.0)]]);
+        assert_eq!(
+            options.diff(unordered, &left, &fewer),
+            ["3 rows on the left, 1 on the right"]
+        );
+        let other = rows(&["page_id"], vec![]);
+        assert_eq!(
+            options.diff(unordered, &left, &other),
+            ["columns page_id, hits on the left, page_id on the right"]
+        );
+        // Columns named otherwise compare as they are
+        let renamed = rows(
+            &["page_id", "count(*)"],
+            vec![
+                vec![Int(1), Null],
+                vec![Int(2), Int(5)],
+                vec![Null, Float(1.0)],
+            ],
+        );
+        assert!(options.diff(unordered, &left, &renamed).is_empty());
+    }
+
+    #[test]
+    fn test_diff_cells_kept() {
+        let options = CompareOptions::default();
+        let left = rows(&["n"], (0..30).map(|i| vec![QueryValue::Int(i)]).collect());
+        let right = rows(
+            &["n"],
+            (0..30).map(|i| vec![QueryValue::Int(i + 100)]).collect(),
+        );
+        let diffs = options.diff("SELECT n FROM t ORDER BY n", &left, &right);
+        assert_eq!(diffs.len(), DIFF_CELLS_KEPT + 1);
+        assert_eq!(diffs[0], "row 0 column n: 0 on the left, 100 on the right");
+        assert_eq!(diffs[DIFF_CELLS_KEPT], "10 more cells differ");
+
+        let mut report = ComparisonReport::default();
+        report.record(None);
+        report.record(Some(ResultMismatch {
+            index: 1,
+            name: "top_pages".to_string(),
+            sql: "SELECT n FROM t ORDER BY n".to_string(),
+            left: Ok(left),
+            right: Err("table not found".to_string()),
+            diffs: vec!["failed on the right".to_string()],
+        }));
+        assert_eq!((report.compared, report.mismatch_count), (2, 1));
+        let diff = report.diff_report();
+        assert!(
+            diff.starts_with(
+                "Query 1 top_pages\n  SELECT n FROM t ORDER BY n\nLeft: 30 rows\n  n\n  0\n"
+            ),
+            "{}",
+            diff
+        );
+        assert!(diff.contains("\n  9\n  ...\nRight: failed, table not found\n"));
+        assert!(diff.ends_with("Differences:\n  failed on the right\n"));
+        assert!(report
+            .to_string()
+            .ends_with("Compared 2 results, 1 differ\n  Query 1 top_pages: failed on the right"));
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::iter::zip;
+
+use arrow::array::{Array, Int32Array, RecordBatch, StringArray, TimestampMicrosecondArray};
+use serde::{Deserialize, Serialize};
+
+use crate::common::rows::{QueryRows, QueryValue};
+use crate::query::generator::{QueryKind, QuerySpec};
+
+/// Bounds a value of a result is within, the same for a value known exactly
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ValueBounds {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ValueBounds {
+    pub fn exact(value: f64) -> Self {
+        ValueBounds {
+            min: value,
+            max: value,
+        }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        // Leeway for the server dividing or summing in another order
+        let tolerance = 1e-9 * self.min.abs().max(self.max.abs()).max(1.0);
+        value >= self.min - tolerance && value <= self.max + tolerance
+    }
+}
+
+impl fmt::Display for ValueBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{} to {}", self.min, self.max)
+        }
+    }
+}
+
+/// What a query returns on the generated data, known when generating it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpectedResult {
+    pub rows: usize,
+    // Position of the column `values` are of
+    pub column: usize,
+    // Values of the column, row by row in the order of the query, empty when
+    // only the number of rows is known
+    pub values: Vec<ValueBounds>,
+}
+
+impl ExpectedResult {
+    /// `Err` with the first difference of `rows` from the expected result
+    pub fn check(&self, rows: &QueryRows) -> std::result::Result<(), String> {
+        if rows.rows.len() != self.rows {
+            return Err(format!("{} rows, expected {}", rows.rows.len(), self.rows));
+        }
+        for (i, (row, bounds)) in zip(&rows.rows, &self.values).enumerate() {
+            let value = row.get(self.column);
+            if !value
+                .and_then(QueryValue::as_f64)
+                .is_some_and(|value| bounds.contains(value))
+            {
+                return Err(format!(
+                    "row {} column {} is {:?}, expected {}",
+                    i, self.column, value, bounds
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The web logs of generated data with what the built-in queries read of
+/// them, to work out the results of the queries
+pub struct GeneratedLogs {
+    // Microseconds since the epoch
+    timestamps: Vec<i64>,
+    user_ids: Vec<i32>,
+    page_ids: Vec<i32>,
+    runtimes: Vec<i32>,
+    // Status of the response to the log, by log id
+    statuses: Vec<String>,
+    // Error logs of the log, by log id
+    errors: Vec<usize>,
+}
+
+impl GeneratedLogs {
+    /// The logs of `batches`, in the order `LogDataGenerator::generate`
+    /// returns them
+    pub fn new(batches: &[RecordBatch]) -> Self {
+        fn column<'a, T: 'static>(batch: &'a RecordBatch, index: usize) -> &'a T {
+            batch
+                .column(index)
+                .as_any()
+                .downcast_ref::<T>()
+                .expect("column of the generated log schema")
+        }
+        let (web_logs, responses, error_logs) = (&batches[3], &batches[5], &batches[6]);
+        let timestamps = column::<TimestampMicrosecondArray>(web_logs, 6)
+            .values()
+            .to_vec();
+        let mut statuses = vec![String::new(); timestamps.len()];
+        let log_ids = column::<Int32Array>(responses, 1);
+        let status_codes = column::<StringArray>(responses, 2);
+        for (log_id, status_code) in zip(log_ids.iter(), status_codes.iter()) {
+            if let (Some(log_id), Some(status_code)) = (log_id, status_code) {
+                if let Some(status) = statuses.get_mut(log_id as usize) {
+                    *status = status_code.to_string();
+                }
+            }
+        }
+        let mut errors = vec![0; timestamps.len()];
+        for log_id in column::<Int32Array>(error_logs, 1).iter().flatten() {
+            if let Some(count) = errors.get_mut(log_id as usize) {
+                *count += 1;
+            }
+        }
+        GeneratedLogs {
+            timestamps,
+            user_ids: column::<Int32Array>(web_logs, 1).values().to_vec(),
+            page_ids: column::<Int32Array>(web_logs, 2).values().to_vec(),
+            runtimes: column::<Int32Array>(web_logs, 4).values().to_vec(),
+            statuses,
+            errors,
+        }
+    }
+
+    /// The result of the built-in query `spec`, `None` for a custom one
+    pub fn expected(&self, spec: &QuerySpec) -> Option<ExpectedResult> {
+        let log_id = spec.params.log_id;
+        // A row of the log for a lookup, a row per error log of it for a
+        // lookup of its error logs, no rows for a log that wasn't generated
+        let lookup = |rows: fn(&Self, usize) -> usize, column| {
+            let rows = usize::try_from(log_id)
+                .ok()
+                .filter(|&i| i < self.timestamps.len())
+                .map_or(0, |i| rows(self, i));
+            Some(ExpectedResult {
+                rows,
+                column,
+                values: vec![ValueBounds::exact(log_id as f64); rows],
+            })
+        };
+        match spec.kind {
+            QueryKind::LogLookup => return lookup(|_, _| 1, 0),
+            QueryKind::ErrorLogLookup => return lookup(|logs, i| logs.errors[i], 1),
+            _ => {}
+        }
+        let start = spec.window().start.timestamp_micros();
+        let end = spec.window().end.timestamp_micros();
+        let logs = (0..self.timestamps.len())
+            .filter(|&i| (start..end).contains(&self.timestamps[i]))
+            .collect::<Vec<_>>();
+        // Logs per value of `key`, in the order of the values
+        let group = |key: &dyn Fn(usize) -> i64| {
+            let mut groups = BTreeMap::<i64, Vec<usize>>::new();
+            for &i in &logs {
+                groups.entry(key(i)).or_default().push(i);
+            }
+            groups
+        };
+        // Values in descending order, the first `limit` of them
+        let top = |mut values: Vec<f64>, limit: usize| {
+            values.sort_by(|a, b| b.total_cmp(a));
+            values.truncate(limit);
+            values
+        };
+        let (column, values) = match spec.kind {
+            QueryKind::TopPages => {
+                let hits = group(&|i| self.page_ids[i] as i64)
+                    .into_values()
+                    .map(|logs| logs.len() as f64)
+                    .collect();
+                (1, top(hits, 10))
+            }
+            QueryKind::ErrorRate => {
+                let rates = group(&|i| self.timestamps[i].div_euclid(300_000_000))
+                    .into_values()
+                    .map(|logs| {
+                        let errors = logs
+                            .iter()
+                            .filter(|&&i| self.statuses[i].starts_with('5'))
+                            .count();
+                        errors as f64 / logs.len() as f64
+                    })
+                    .collect();
+                (1, rates)
+            }
+            QueryKind::DistinctUsers => {
+                let users = group(&|i| self.timestamps[i].div_euclid(3_600_000_000))
+                    .into_values()
+                    .map(|logs| {
+                        let users = logs
+                            .iter()
+                            .map(|&i| self.user_ids[i])
+                            .collect::<BTreeSet<_>>();
+                        users.len() as f64
+                    })
+                    .collect();
+                (1, users)
+            }
+            // Users without error logs are joined away
+            QueryKind::TopErrorUsers => {
+                let errors = group(&|i| self.user_ids[i] as i64)
+                    .into_values()
+                    .map(|logs| logs.iter().map(|&i| self.errors[i]).sum::<usize>())
+                    .filter(|&errors| errors > 0)
+                    .map(|errors| errors as f64)
+                    .collect();
+                (2, top(errors, spec.params.limit))
+            }
+            QueryKind::SlowestPages => {
+                let runtimes = group(&|i| self.page_ids[i] as i64)
+                    .into_values()
+                    .map(|logs| {
+                        let total = logs.iter().map(|&i| self.runtimes[i] as f64).sum::<f64>();
+                        total / logs.len() as f64
+                    })
+                    .collect();
+                (2, top(runtimes, spec.params.limit))
+            }
+            QueryKind::PageResponses => {
+                // Pages are told apart by id, titles are generated sentences
+                // that are unlikely to repeat
+                let mut responses = BTreeMap::<(i32, &str), usize>::new();
+                for &i in &logs {
+                    *responses
+                        .entry((self.page_ids[i], self.statuses[i].as_str()))
+                        .or_default() += 1;
+                }
+                let responses = responses.into_values().map(|count| count as f64).collect();
+                (2, top(responses, spec.params.limit))
+            }
+            // Buckets start at multiples of their width since the epoch, with
+            // range queries as with `date_bin`
+            QueryKind::RangeBuckets | QueryKind::BinnedBuckets => {
+                let width = spec.params.bucket.as_micros() as i64;
+                if width == 0 {
+                    return None;
+                }
+                let logs = group(&|i| self.timestamps[i].div_euclid(width))
+                    .into_values()
+                    .map(|logs| logs.len() as f64)
+                    .collect();
+                (2, logs)
+            }
+            // Approximate percentiles, only the pages are known
+            QueryKind::P95ResponseTime => {
+                return Some(ExpectedResult {
+                    rows: group(&|i| self.page_ids[i] as i64).len(),
+                    column: 1,
+                    values: Vec::new(),
+                })
+            }
+            QueryKind::LogLookup | QueryKind::ErrorLogLookup | QueryKind::Custom => return None,
+        };
+        Some(ExpectedResult {
+            rows: values.len(),
+            column,
+            values: values
+                .into_iter()
+                .map(ValueBounds::exact)
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{DateTime, Utc};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::generator::data_generator::{DataGenerator, DataGeneratorConfig, UseCase};
+    use crate::generator::log_data_generator::{LogConfig, LogDataGenerator};
+    use crate::query::generator::{QueryWindow, WindowSize};
+    use crate::query::template::QueryParams;
+
+    fn spec(kind: QueryKind, start: &str, end: &str) -> QuerySpec {
+        let time = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
+        QuerySpec {
+            name: kind.name().to_string(),
+            kind,
+            sql: String::new(),
+            params: QueryParams {
+                window: QueryWindow {
+                    start: time(start),
+                    end: time(end),
+                },
+                page_id: 0,
+                user_id: 0,
+                limit: 10,
+                log_id: 0,
+                bucket: Duration::ZERO,
+            },
+            window_size: WindowSize::Kind,
+            expected: None,
+            requires_join: false,
+            lookup_hit: None,
+            bucket_width: None,
+        }
+    }
+
+    #[test]
+    fn test_expected_results() {
+        // A log a minute, of user (i + 1) % 3 on page (i + 1) % 2, give or
+        // take half a second
+        let generator = LogDataGenerator::new(
+            DataGeneratorConfig::new(
+                60 * 1_000_000,
+                "2023-01-01T00:00:00Z".to_string(),
+                "2023-01-01T02:00:00Z".to_string(),
+                StdRng::seed_from_u64(11),
+                UseCase::Log,
+            ),
+            LogConfig::new(3, 2),
+        )
+        .unwrap();
+        let batches = generator.generate().unwrap();
+        let logs = GeneratedLogs::new(&batches);
+        assert_eq!(logs.timestamps.len(), 121);
+
+        // Logs 1 to 4, of pages 0, 1, 0, 1 and users 2, 0, 1, 2
+        let top_pages = logs
+            .expected(&spec(
+                QueryKind::TopPages,
+                "2023-01-01T00:00:30Z",
+                "2023-01-01T00:04:30Z",
+            ))
+            .unwrap();
+        assert_eq!(top_pages.rows, 2);
+        assert_eq!(top_pages.values, [ValueBounds::exact(2.0); 2]);
+        let distinct_users = logs
+            .expected(&spec(
+                QueryKind::DistinctUsers,
+                "2023-01-01T00:00:30Z",
+                "2023-01-01T00:04:30Z",
+            ))
+            .unwrap();
+        assert_eq!(distinct_users.values, [ValueBounds::exact(3.0)]);
+        let slowest_pages = logs
+            .expected(&spec(
+                QueryKind::SlowestPages,
+                "2023-01-01T00:00:30Z",
+                "2023-01-01T00:04:30Z",
+            ))
+            .unwrap();
+        let mut means =
+            [(1, 3), (2, 4)].map(|(a, b)| (logs.runtimes[a] + logs.runtimes[b]) as f64 / 2.0);
+        means.sort_by(|a, b| b.total_cmp(a));
+        assert_eq!(slowest_pages.column, 2);
+        assert_eq!(slowest_pages.values, means.map(ValueBounds::exact));
+        // Users 2, 0 and 1 have all the error logs of logs 1 to 4 between them
+        let top_error_users = logs
+            .expected(&spec(
+                QueryKind::TopErrorUsers,
+                "2023-01-01T00:00:30Z",
+                "2023-01-01T00:04:30Z",
+            ))
+            .unwrap();
+        assert_eq!(
+            top_error_users
+                .values
+                .iter()
+                .map(|bounds| bounds.min as usize)
+                .sum::<usize>(),
+            logs.errors[1..=4].iter().sum::<usize>()
+        );
+
+        // Logs 1 to 9 fall into two 5 minute buckets, log 5 into either
+        // depending on its jitter
+        let error_rate = logs
+            .expected(&spec(
+                QueryKind::ErrorRate,
+                "2023-01-01T00:00:30Z",
+                "2023-01-01T00:09:30Z",
+            ))
+            .unwrap();
+        assert_eq!(error_rate.rows, 2);
+        let first_bucket = (1..=9)
+            .filter(|&i| logs.timestamps[i] < 1_672_531_500_000_000)
+            .collect::<Vec<_>>();
+        let errors = first_bucket
+            .iter()
+            .filter(|&&i| logs.statuses[i].starts_with('5'))
+            .count();
+        assert_eq!(
+            error_rate.values[0],
+            ValueBounds::exact(errors as f64 / first_bucket.len() as f64)
+        );
+
+        // Logs 10 to 69, 30 of every page, in 2 hours of 3 users each
+        let all = spec(
+            QueryKind::TopPages,
+            "2023-01-01T00:09:30Z",
+            "2023-01-01T01:09:30Z",
+        );
+        assert_eq!(
+            logs.expected(&all).unwrap().values,
+            [ValueBounds::exact(30.0); 2]
+        );
+        let p95 = spec(
+            QueryKind::P95ResponseTime,
+            "2023-01-01T00:09:30Z",
+            "2023-01-01T01:09:30Z",
+        );
+        assert_eq!(logs.expected(&p95).unwrap().rows, 2);
+        let distinct_users = spec(
+            QueryKind::DistinctUsers,
+            "2023-01-01T00:09:30Z",
+            "2023-01-01T01:09:30Z",
+        );
+        assert_eq!(
+            logs.expected(&distinct_users).unwrap().values,
+            [ValueBounds::exact(3.0); 2]
+        );
+        let mut lookup = spec(
+            QueryKind::ErrorLogLookup,
+            "2023-01-01T00:00:00Z",
+            "2023-01-01T02:00:00Z",
+        );
+        for log_id in [0, 60, 120, 121, 5000] {
+            lookup.params.log_id = log_id;
+            let errors = logs.errors.get(log_id as usize).copied().unwrap_or(0);
+            let expected = logs.expected(&lookup).unwrap();
+            assert_eq!(expected.rows, errors);
+            assert_eq!(expected.column, 1);
+            assert_eq!(
+                expected.values,
+                vec![ValueBounds::exact(log_id as f64); errors]
+            );
+        }
+        // Logs 10 to 69 again, in buckets from 00:10:00 on, the first log
+        // in the one before when its jitter is negative
+        let mut buckets = spec(
+            QueryKind::BinnedBuckets,
+            "2023-01-01T00:09:30Z",
+            "2023-01-01T01:09:30Z",
+        );
+        for width in [60, 600, 97] {
+            buckets.params.bucket = Duration::from_secs(width);
+            let expected = logs.expected(&buckets).unwrap();
+            let starts = (10..70)
+                .map(|i| logs.timestamps[i].div_euclid(width as i64 * 1_000_000))
+                .collect::<BTreeSet<_>>();
+            assert_eq!(expected.rows, starts.len(), "{}", width);
+            let counts = expected.values.iter().map(|bounds| bounds.min as usize);
+            assert_eq!(counts.sum::<usize>(), 60);
+        }
+        buckets.params.bucket = Duration::from_secs(600);
+        let rows = logs.expected(&buckets).unwrap().rows;
+        assert!(rows == 6 || rows == 7, "{}", rows);
+        lookup.kind = QueryKind::LogLookup;
+        lookup.params.log_id = 120;
+        assert_eq!(logs.expected(&lookup).unwrap().rows, 1);
+        lookup.params.log_id = 121;
+        assert_eq!(logs.expected(&lookup).unwrap().rows, 0);
+        assert_eq!(
+            logs.expected(&spec(
+                QueryKind::Custom,
+                "2023-01-01T00:00:00Z",
+                "2023-01-01T01:00:00Z"
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let expected = ExpectedResult {
+            rows: 2,
+            column: 1,
+            values: vec![ValueBounds::exact(0.1), ValueBounds { min: 1.0, max: 2.0 }],
+        };
+        let rows = |values: [QueryValue; 2]| QueryRows {
+            columns: vec!["bucket".to_string(), "value".to_string()],
+            rows: values
+                .into_iter()
+                .map(|value| vec![QueryValue::Null, value])
+                .collect(),
+        };
+        assert_eq!(
+            expected.check(&rows([QueryValue::Float(0.1 + 1e-12), QueryValue::Int(2)])),
+            Ok(())
+        );
+        assert_eq!(
+            expected.check(&rows([QueryValue::Float(0.1), QueryValue::Int(3)])),
+            Err("row 1 column 1 is Some(Int(3)), expected 1 to 2".to_string())
+        );
+        assert_eq!(
+            expected.check(&rows([QueryValue::Null, QueryValue::Int(1)])),
+            Err("row 0 column 1 is Some(Null), expected 0.1".to_string())
+        );
+        let mut one_row = rows([QueryValue::Float(0.1), QueryValue::Int(1)]);
+        one_row.rows.pop();
+        assert_eq!(
+            expected.check(&one_row),
+            Err("1 rows, expected 2".to_string())
+        );
+    }
+}
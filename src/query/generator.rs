@@ -0,0 +1,1058 @@
+use std::fmt;
+use std::iter::zip;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+
+use crate::common::error::{check_violations, Result};
+use crate::generator::data_generator::{parse_time, DataGeneratorConfig};
+use crate::generator::log_data_generator::LogConfig;
+use crate::query::expected::{ExpectedResult, GeneratedLogs};
+use crate::query::template::{Placeholder, QueryParams, QueryTemplate};
+
+/// Queries of the log analytics suite over the generated tables. Requests
+/// per country wait for the dataset to have a country column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueryKind {
+    // Top 10 pages by hits
+    TopPages,
+    // Share of 5xx responses per 5 minute bucket
+    ErrorRate,
+    // p95 response time per page
+    P95ResponseTime,
+    // Distinct users per hour
+    DistinctUsers,
+    // Users with the most error logs, with their usernames
+    TopErrorUsers,
+    // Pages with the longest mean runtime, with their titles
+    SlowestPages,
+    // Responses per page title and status, joining pages and responses
+    PageResponses,
+    // A web log by its id
+    LogLookup,
+    // The error logs of a web log
+    ErrorLogLookup,
+    // Mean runtime and logs per bucket of time, with GreptimeDB's range
+    // query syntax
+    RangeBuckets,
+    // The same with plain `date_bin` grouping
+    BinnedBuckets,
+    // A template of the user's, not part of the built-in suite
+    Custom,
+}
+
+impl QueryKind {
+    pub const ALL: [QueryKind; 11] = [
+        QueryKind::TopPages,
+        QueryKind::ErrorRate,
+        QueryKind::P95ResponseTime,
+        QueryKind::DistinctUsers,
+        QueryKind::TopErrorUsers,
+        QueryKind::SlowestPages,
+        QueryKind::PageResponses,
+        QueryKind::LogLookup,
+        QueryKind::ErrorLogLookup,
+        QueryKind::RangeBuckets,
+        QueryKind::BinnedBuckets,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        QueryKind::ALL
+            .into_iter()
+            .chain([QueryKind::Custom])
+            .find(|kind| kind.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            QueryKind::TopPages => "top_pages",
+            QueryKind::ErrorRate => "error_rate",
+            QueryKind::P95ResponseTime => "p95_response_time",
+            QueryKind::DistinctUsers => "distinct_users",
+            QueryKind::TopErrorUsers => "top_error_users",
+            QueryKind::SlowestPages => "slowest_pages",
+            QueryKind::PageResponses => "page_responses",
+            QueryKind::LogLookup => "log_lookup",
+            QueryKind::ErrorLogLookup => "error_log_lookup",
+            QueryKind::RangeBuckets => "range_buckets",
+            QueryKind::BinnedBuckets => "binned_buckets",
+            QueryKind::Custom => "custom",
+        }
+    }
+
+    // Time the query covers, shortened to the dataset's range when that's
+    // shorter. Lookups cover all of it, their windows go unused.
+    fn window_length(&self) -> Duration {
+        match self {
+            QueryKind::LogLookup | QueryKind::ErrorLogLookup => Duration::MAX,
+            QueryKind::TopPages
+            | QueryKind::ErrorRate
+            | QueryKind::P95ResponseTime
+            | QueryKind::TopErrorUsers
+            | QueryKind::SlowestPages
+            | QueryKind::PageResponses
+            | QueryKind::RangeBuckets
+            | QueryKind::BinnedBuckets
+            | QueryKind::Custom => Duration::from_secs(3600),
+            QueryKind::DistinctUsers => Duration::from_secs(24 * 3600),
+        }
+    }
+
+    // GreptimeDB SQL of the built-in query, see `QueryTemplate`
+    fn template(&self) -> Option<&'static str> {
+        match self {
+            QueryKind::TopPages => Some(
+                "SELECT page_id, COUNT(*) AS hits FROM web_logs \
+                 WHERE `timestamp` >= {{time_start}} AND `timestamp` < {{time_end}} \
+                 GROUP BY page_id ORDER BY hits DESC LIMIT 10",
+            ),
+            QueryKind::ErrorRate => Some(
+                "SELECT date_bin(INTERVAL '5 minutes', w.`timestamp`) AS bucket, \
+                 SUM(CASE WHEN r.status_code LIKE '5%' THEN 1 ELSE 0 END) * 1.0 / COUNT(*) AS error_rate \
+                 FROM web_logs w JOIN responses r ON r.log_id = w.log_id \
+                 WHERE w.`timestamp` >= {{time_start}} AND w.`timestamp` < {{time_end}} \
+                 GROUP BY bucket ORDER BY bucket",
+            ),
+            QueryKind::P95ResponseTime => Some(
+                "SELECT w.page_id, approx_percentile_cont(r.response_time, 0.95) AS p95_response_time \
+                 FROM web_logs w JOIN responses r ON r.log_id = w.log_id \
+                 WHERE w.`timestamp` >= {{time_start}} AND w.`timestamp` < {{time_end}} \
+                 GROUP BY w.page_id ORDER BY p95_response_time DESC",
+            ),
+            QueryKind::DistinctUsers => Some(
+                "SELECT date_trunc('hour', `timestamp`) AS hour, COUNT(DISTINCT user_id) AS users \
+                 FROM web_logs WHERE `timestamp` >= {{time_start}} AND `timestamp` < {{time_end}} \
+                 GROUP BY hour ORDER BY hour",
+            ),
+            QueryKind::TopErrorUsers => Some(
+                "SELECT u.user_id, u.username, COUNT(*) AS errors \
+                 FROM web_logs w JOIN error_logs e ON e.log_id = w.log_id \
+                 JOIN users u ON u.user_id = w.user_id \
+                 WHERE w.`timestamp` >= {{time_start}} AND w.`timestamp` < {{time_end}} \
+                 GROUP BY u.user_id, u.username ORDER BY errors DESC LIMIT {{limit}}",
+            ),
+            QueryKind::SlowestPages => Some(
+                "SELECT p.page_id, p.page_title, AVG(w.runtime) AS mean_runtime \
+                 FROM web_logs w JOIN pages p ON p.page_id = w.page_id \
+                 WHERE w.`timestamp` >= {{time_start}} AND w.`timestamp` < {{time_end}} \
+                 GROUP BY p.page_id, p.page_title ORDER BY mean_runtime DESC LIMIT {{limit}}",
+            ),
+            QueryKind::PageResponses => Some(
+                "SELECT p.page_title, r.status_code, COUNT(*) AS responses, \
+                 AVG(r.response_time) AS mean_response_time \
+                 FROM web_logs w JOIN pages p ON p.page_id = w.page_id \
+                 JOIN responses r ON r.log_id = w.log_id \
+                 WHERE w.`timestamp` >= {{time_start}} AND w.`timestamp` < {{time_end}} \
+                 GROUP BY p.page_title, r.status_code ORDER BY responses DESC LIMIT {{limit}}",
+            ),
+            QueryKind::LogLookup => Some(
+                "SELECT log_id, user_id, page_id, runtime, `timestamp` FROM web_logs \
+                 WHERE log_id = {{log_id}}",
+            ),
+            QueryKind::ErrorLogLookup => Some(
+                "SELECT error_log_id, log_id, error_code, error_message, `timestamp` \
+                 FROM error_logs WHERE log_id = {{log_id}} ORDER BY error_log_id",
+            ),
+            // `BY ()` aggregates all logs of a bucket together instead of
+            // per log_id, the primary key
+            QueryKind::RangeBuckets => Some(
+                "SELECT `timestamp`, AVG(runtime) RANGE {{bucket}} AS mean_runtime, \
+                 COUNT(*) RANGE {{bucket}} AS logs FROM web_logs \
+                 WHERE `timestamp` >= {{time_start}} AND `timestamp` < {{time_end}} \
+                 ALIGN {{bucket}} BY () ORDER BY `timestamp`",
+            ),
+            QueryKind::BinnedBuckets => Some(
+                "SELECT date_bin(INTERVAL {{bucket}}, `timestamp`) AS bucket, \
+                 AVG(runtime) AS mean_runtime, COUNT(*) AS logs FROM web_logs \
+                 WHERE `timestamp` >= {{time_start}} AND `timestamp` < {{time_end}} \
+                 GROUP BY bucket ORDER BY bucket",
+            ),
+            QueryKind::Custom => None,
+        }
+    }
+}
+
+/// Templates of the built-in suite, one per kind of `QueryKind::ALL`
+pub fn builtin_templates() -> Vec<QueryTemplate> {
+    QueryKind::ALL
+        .into_iter()
+        .filter_map(|kind| {
+            let text = kind.template()?;
+            Some(QueryTemplate::parse(kind.name(), kind, text).expect("valid built-in template"))
+        })
+        .collect()
+}
+
+impl fmt::Display for QueryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Time a query covers, `start` inclusive and `end` exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl QueryWindow {
+    pub fn length(&self) -> Duration {
+        (self.end - self.start).to_std().unwrap_or_default()
+    }
+}
+
+/// Length of a query's window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindowSize {
+    // The length of the query's kind, see `QueryKind::window_length`
+    Kind,
+    Fixed(Duration),
+    // The whole time range of the data
+    Full,
+}
+
+impl WindowSize {
+    /// `kind`, `full` or a whole number of seconds, minutes, hours or days,
+    /// e.g. `90s` or `1h`, as `WindowSize` is displayed
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "kind" => return Some(WindowSize::Kind),
+            "full" => return Some(WindowSize::Full),
+            _ => {}
+        }
+        let unit = match s.chars().last()? {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 24 * 3600,
+            _ => return None,
+        };
+        let count: u64 = s[..s.len() - 1].parse().ok()?;
+        Some(WindowSize::Fixed(Duration::from_secs(
+            count.checked_mul(unit)?,
+        )))
+    }
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowSize::Kind => write!(f, "kind"),
+            WindowSize::Full => write!(f, "full"),
+            WindowSize::Fixed(length) => {
+                let secs = length.as_secs();
+                match secs {
+                    _ if secs > 0 && secs % (24 * 3600) == 0 => {
+                        write!(f, "{}d", secs / (24 * 3600))
+                    }
+                    _ if secs > 0 && secs % 3600 == 0 => write!(f, "{}h", secs / 3600),
+                    _ if secs > 0 && secs % 60 == 0 => write!(f, "{}m", secs / 60),
+                    _ => write!(f, "{}s", secs),
+                }
+            }
+        }
+    }
+}
+
+/// Width of the buckets of a query grouping by time, after the interval of
+/// the generated data. Buckets of a multiple of the interval hold as many
+/// logs each, others don't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BucketWidth {
+    Interval,
+    TenIntervals,
+    // The smallest prime number of seconds past one and a half intervals,
+    // of milliseconds for intervals under 2s
+    Prime,
+}
+
+impl BucketWidth {
+    pub const ALL: [BucketWidth; 3] = [
+        BucketWidth::Interval,
+        BucketWidth::TenIntervals,
+        BucketWidth::Prime,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        BucketWidth::ALL
+            .into_iter()
+            .find(|width| width.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BucketWidth::Interval => "1x",
+            BucketWidth::TenIntervals => "10x",
+            BucketWidth::Prime => "prime",
+        }
+    }
+
+    /// The width for data of a log every `interval`
+    pub fn width(&self, interval: Duration) -> Duration {
+        match self {
+            BucketWidth::Interval => interval,
+            BucketWidth::TenIntervals => interval * 10,
+            BucketWidth::Prime => {
+                let (unit, count) = if interval.subsec_millis() == 0 && interval.as_secs() >= 2 {
+                    (Duration::from_secs(1), interval.as_secs())
+                } else {
+                    (Duration::from_millis(1), interval.as_millis() as u64)
+                };
+                // A prime above the interval's count of units is no multiple
+                // of it
+                let prime = (count * 3 / 2 + 1..)
+                    .find(|&n| n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| n % d != 0))
+                    .expect("primes are endless");
+                unit * prime as u32
+            }
+        }
+    }
+}
+
+impl fmt::Display for BucketWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Weights of the kinds of a workload, a query is of a kind with the chance
+/// of its weight over the sum of the weights. Written as e.g.
+/// `top_pages=5,error_rate=1`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct QueryMix(pub Vec<(QueryKind, u32)>);
+
+impl QueryMix {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Share of the queries of `kind`, 0 for a kind without a weight
+    pub fn share(&self, kind: QueryKind) -> f64 {
+        let total: u32 = self.0.iter().map(|(_, weight)| weight).sum();
+        let weight: u32 = self
+            .0
+            .iter()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, weight)| weight)
+            .sum();
+        if total == 0 {
+            0.0
+        } else {
+            weight as f64 / total as f64
+        }
+    }
+}
+
+impl fmt::Display for QueryMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs = self
+            .0
+            .iter()
+            .map(|(kind, weight)| format!("{}={}", kind, weight))
+            .collect::<Vec<_>>();
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
+impl FromStr for QueryMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        s.split(',')
+            .map(|pair| {
+                let invalid = || format!("Invalid query mix {:?}, expected e.g. top_pages=5", pair);
+                let (kind, weight) = pair.trim().split_once('=').ok_or_else(invalid)?;
+                let kind = QueryKind::parse(kind.trim()).ok_or_else(invalid)?;
+                let weight = weight.trim().parse().map_err(|_| invalid())?;
+                Ok((kind, weight))
+            })
+            .collect::<std::result::Result<_, _>>()
+            .map(QueryMix)
+    }
+}
+
+/// How the kinds and windows of the generated queries are picked
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryConfig {
+    /// Kinds of the queries of a workload, all templates alike without any
+    pub mix: QueryMix,
+    /// Sizes with their weights, a query gets one of them with the chance of
+    /// its weight over the sum of the weights. Without any, every query gets
+    /// the window of its kind.
+    pub windows: Vec<(WindowSize, u32)>,
+    /// 0 starts windows anywhere in the range alike, larger values favour
+    /// recent data
+    pub recency: f64,
+    /// Share of the lookups of an id no generated log has
+    pub miss_fraction: f64,
+    /// Seed of everything drawn for the queries, apart from the seed of the
+    /// data so that the same data gets other queries
+    pub query_seed: u64,
+}
+
+impl QueryConfig {
+    pub fn violations(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        if !self.windows.is_empty() && self.windows.iter().all(|(_, weight)| *weight == 0) {
+            violations.push("windows need a weight above 0".to_string());
+        }
+        if self
+            .windows
+            .iter()
+            .any(|(size, _)| *size == WindowSize::Fixed(Duration::ZERO))
+        {
+            violations.push("windows must be longer than 0s".to_string());
+        }
+        if !self.mix.is_empty() && self.mix.0.iter().all(|(_, weight)| *weight == 0) {
+            violations.push("mix needs a weight above 0".to_string());
+        }
+        if !(self.recency.is_finite() && self.recency >= 0.0) {
+            violations.push(format!("recency must be at least 0, got {}", self.recency));
+        }
+        if !(0.0..=1.0).contains(&self.miss_fraction) {
+            violations.push(format!(
+                "miss_fraction must be between 0 and 1, got {}",
+                self.miss_fraction
+            ));
+        }
+        violations
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        check_violations(self.violations())
+    }
+}
+
+// RFC3339 string literal GreptimeDB compares timestamps with
+pub(crate) fn time_literal(time: &DateTime<Utc>) -> String {
+    format!("'{}'", time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Whether `sql` joins tables, going by a `JOIN` outside its literals and
+/// quoted names. Servers with limited join support can't run such queries.
+pub fn joins_tables(sql: &str) -> bool {
+    unquoted_words(sql)
+        .iter()
+        .any(|word| word.eq_ignore_ascii_case("join"))
+}
+
+/// Whether `sql` returns its rows in an order, going by an `ORDER BY`
+/// outside its literals and quoted names. Servers may return the rows of
+/// other queries in any order.
+pub fn orders_rows(sql: &str) -> bool {
+    unquoted_words(sql)
+        .windows(2)
+        .any(|words| words[0].eq_ignore_ascii_case("order") && words[1].eq_ignore_ascii_case("by"))
+}
+
+// Words of `sql` outside its literals and quoted names
+fn unquoted_words(sql: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut quote = None;
+    let mut word = String::new();
+    for c in sql.chars().chain([' ']) {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+                continue;
+            }
+            None if matches!(c, '\'' | '`' | '"') => quote = Some(c),
+            None if c.is_alphanumeric() || c == '_' => {
+                word.push(c);
+                continue;
+            }
+            None => {}
+        }
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+    }
+    words
+}
+
+/// A generated query, the template it comes from and the values it was
+/// rendered with
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuerySpec {
+    pub name: String,
+    pub kind: QueryKind,
+    pub sql: String,
+    pub params: QueryParams,
+    // Size the window was drawn with, the same for all windows of a size
+    // even when the range cuts them short
+    pub window_size: WindowSize,
+    // Result on the generated data, for a generator with its logs
+    pub expected: Option<ExpectedResult>,
+    // The query joins tables, see `joins_tables`
+    pub requires_join: bool,
+    // Whether the log a lookup is of was generated, unset for a query that
+    // looks up no log
+    pub lookup_hit: Option<bool>,
+    // Width `params.bucket` was derived with, unset for a query without
+    // buckets
+    pub bucket_width: Option<BucketWidth>,
+}
+
+impl QuerySpec {
+    pub fn window(&self) -> &QueryWindow {
+        &self.params.window
+    }
+}
+
+/// Values `{{limit}}` takes
+const LIMITS: [usize; 4] = [10, 20, 50, 100];
+
+/// Instantiates query templates with windows inside the time range of the
+/// generated data and ids of its users, pages and logs, picked by the seed of
+/// the data's config
+pub struct QueryGenerator {
+    range: QueryWindow,
+    num_of_users: usize,
+    num_of_pages: usize,
+    num_of_logs: usize,
+    // Between the logs of the generated data
+    interval: Duration,
+    templates: Vec<QueryTemplate>,
+    query_config: QueryConfig,
+    // Index of `query_config.windows`, `None` without windows
+    windows: Option<WeightedIndex<u32>>,
+    // Logs the results of the queries are worked out on
+    logs: Option<GeneratedLogs>,
+    rng: StdRng,
+}
+
+impl QueryGenerator {
+    pub fn new(
+        config: &DataGeneratorConfig,
+        log_config: &LogConfig,
+        query_config: QueryConfig,
+    ) -> Result<Self> {
+        config.validate()?;
+        query_config.validate()?;
+        let windows = (!query_config.windows.is_empty()).then(|| {
+            WeightedIndex::new(query_config.windows.iter().map(|(_, weight)| *weight))
+                .expect("validated window weights")
+        });
+        let (start, end) = (
+            parse_time(&config.time_start)?,
+            parse_time(&config.time_end)?,
+        );
+        // A log every interval from the start up to and including the end,
+        // as `LogDataGenerator` generates them
+        let num_of_logs =
+            (end.unix_timestamp() - start.unix_timestamp()) * 1_000_000 / config.interval + 1;
+        Ok(QueryGenerator {
+            range: QueryWindow {
+                start: utc(start),
+                end: utc(end),
+            },
+            num_of_users: log_config.num_of_users(),
+            num_of_pages: log_config.num_of_pages(),
+            num_of_logs: num_of_logs as usize,
+            interval: Duration::from_micros(config.interval as u64),
+            templates: builtin_templates(),
+            query_config,
+            windows,
+            logs: None,
+            rng: StdRng::seed_from_u64(query_config.query_seed),
+        })
+    }
+
+    /// Uses `templates` in place of the built-in suite
+    pub fn with_templates(mut self, templates: Vec<QueryTemplate>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Works out the results of the built-in queries on `logs`, which have
+    /// to be the ones of the data the queries run on
+    pub fn with_expected(mut self, logs: GeneratedLogs) -> Self {
+        self.logs = Some(logs);
+        self
+    }
+
+    pub fn templates(&self) -> &[QueryTemplate] {
+        &self.templates
+    }
+
+    /// A query of `template` over a window of a size drawn from the config,
+    /// starting at a random second of the range
+    pub fn generate(&mut self, template: &QueryTemplate) -> QuerySpec {
+        let window_size = match &self.windows {
+            Some(windows) => self.query_config.windows[windows.sample(&mut self.rng)].0,
+            None => WindowSize::Kind,
+        };
+        let length = match window_size {
+            WindowSize::Kind => template.kind.window_length(),
+            WindowSize::Fixed(length) => length,
+            WindowSize::Full => self.range.length(),
+        }
+        .min(self.range.length());
+        let slack = (self.range.length() - length).as_secs();
+        // u^(1 / (1 + recency)) of a uniform u leans towards 1, i.e. towards
+        // the end of the range, the more the larger recency is
+        let lean = self
+            .rng
+            .gen::<f64>()
+            .powf(1.0 / (1.0 + self.query_config.recency));
+        let offset = ((lean * (slack + 1) as f64) as u64).min(slack);
+        let start = self.range.start + chrono::Duration::seconds(offset as i64);
+        let mut params = QueryParams {
+            window: QueryWindow {
+                start,
+                end: start + chrono::Duration::from_std(length).unwrap_or_default(),
+            },
+            // Ids of an empty table never match, 0 is as good as any
+            user_id: self.rng.gen_range(0..self.num_of_users.max(1)) as i32,
+            page_id: self.rng.gen_range(0..self.num_of_pages.max(1)) as i32,
+            limit: *LIMITS.choose(&mut self.rng).unwrap(),
+            log_id: 0,
+            bucket: Duration::ZERO,
+        };
+        let mut lookup_hit = None;
+        if template.placeholders().any(|p| p == Placeholder::LogId) {
+            let hit = !self.rng.gen_bool(self.query_config.miss_fraction);
+            let logs = self.num_of_logs.clamp(1, i32::MAX as usize / 2);
+            // Misses are past the last log, by up to as many logs again
+            let id = self.rng.gen_range(0..logs);
+            params.log_id = if hit { id as i32 } else { (logs + id) as i32 };
+            lookup_hit = Some(hit);
+        }
+        let mut bucket_width = None;
+        if template.placeholders().any(|p| p == Placeholder::Bucket) {
+            let width = *BucketWidth::ALL.choose(&mut self.rng).unwrap();
+            params.bucket = width.width(self.interval);
+            bucket_width = Some(width);
+        }
+        let sql = template.render(&params);
+        let mut spec = QuerySpec {
+            name: template.name.clone(),
+            kind: template.kind,
+            requires_join: joins_tables(&sql),
+            sql,
+            params,
+            window_size,
+            expected: None,
+            lookup_hit,
+            bucket_width,
+        };
+        spec.expected = self.logs.as_ref().and_then(|logs| logs.expected(&spec));
+        spec
+    }
+
+    /// `count` queries of every template, template after template
+    pub fn suite(&mut self, count: usize) -> Vec<QuerySpec> {
+        let templates = self.templates.clone();
+        templates
+            .iter()
+            .flat_map(|template| std::iter::repeat(template).take(count))
+            .map(|template| self.generate(template))
+            .collect()
+    }
+
+    /// `count` queries of kinds drawn from the mix one query at a time, each
+    /// of a template of its kind picked alike. Fails when the mix weighs a
+    /// kind no template is of.
+    pub fn workload(&mut self, count: usize) -> Result<Vec<QuerySpec>> {
+        let templates = self.templates.clone();
+        if self.query_config.mix.is_empty() {
+            return Ok((0..count)
+                .filter_map(|_| templates.choose(&mut self.rng).cloned())
+                .map(|template| self.generate(&template))
+                .collect());
+        }
+        let mix = self.query_config.mix.0.clone();
+        let by_kind = mix
+            .iter()
+            .map(|(kind, _)| {
+                templates
+                    .iter()
+                    .filter(|template| template.kind == *kind)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        check_violations(
+            zip(&mix, &by_kind)
+                .filter(|((_, weight), templates)| *weight > 0 && templates.is_empty())
+                .map(|((kind, _), _)| format!("mix weighs {} but no template is of it", kind))
+                .collect(),
+        )?;
+        let kinds = WeightedIndex::new(mix.iter().map(|(_, weight)| *weight))
+            .expect("validated mix weights");
+        Ok((0..count)
+            .map(|_| {
+                let template = *by_kind[kinds.sample(&mut self.rng)]
+                    .choose(&mut self.rng)
+                    .unwrap();
+                self.generate(template)
+            })
+            .collect())
+    }
+}
+
+fn utc(time: time::OffsetDateTime) -> DateTime<Utc> {
+    DateTime::from_timestamp(time.unix_timestamp(), time.nanosecond()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::generator::data_generator::UseCase;
+
+    fn config(seed: u64) -> DataGeneratorConfig {
+        DataGeneratorConfig::new(
+            60 * 1_000_000,
+            "2023-01-01T00:00:00Z".to_string(),
+            "2023-01-03T00:00:00Z".to_string(),
+            StdRng::seed_from_u64(seed),
+            UseCase::Log,
+        )
+    }
+
+    // Catches broken templates without a SQL parser: one statement whose
+    // quotes and parentheses are balanced
+    fn check_sql(sql: &str) {
+        assert!(sql.starts_with("SELECT "), "{}", sql);
+        assert!(!sql.contains(['{', '}', ';', '\n']), "{}", sql);
+        let mut depth = 0;
+        let mut quote = None;
+        for c in sql.chars() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '`' | '"') => quote = Some(c),
+                (None, '(') => depth += 1,
+                (None, ')') => {
+                    depth -= 1;
+                    assert!(depth >= 0, "{}", sql);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(quote, None, "{}", sql);
+        assert_eq!(depth, 0, "{}", sql);
+    }
+
+    fn generator(config: &DataGeneratorConfig, query_seed: u64) -> QueryGenerator {
+        let query_config = QueryConfig {
+            query_seed,
+            ..Default::default()
+        };
+        QueryGenerator::new(config, &LogConfig::new(100, 20), query_config).unwrap()
+    }
+
+    fn template(kind: QueryKind) -> QueryTemplate {
+        builtin_templates()
+            .into_iter()
+            .find(|template| template.kind == kind)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_render_suite() {
+        let mut generator = generator(&config(7), 7);
+        let suite = generator.suite(3);
+        assert_eq!(suite.len(), 3 * QueryKind::ALL.len());
+        for spec in &suite {
+            check_sql(&spec.sql);
+            assert_eq!(spec.name, spec.kind.name());
+            assert_eq!(
+                spec.requires_join,
+                spec.sql.contains(" JOIN "),
+                "{}",
+                spec.sql
+            );
+            if spec.lookup_hit.is_some() {
+                assert!(spec.sql.ends_with(&spec.params.log_id.to_string()));
+                continue;
+            }
+            assert!(spec.sql.contains(&time_literal(&spec.window().start)));
+            assert!(spec.sql.contains(&time_literal(&spec.window().end)));
+            assert_eq!(spec.window().length(), spec.kind.window_length());
+            assert!(spec.window().start >= generator.range.start);
+            assert!(spec.window().end <= generator.range.end);
+        }
+        assert_eq!(
+            template(QueryKind::TopPages).render(&QueryParams {
+                window: generator.range,
+                page_id: 0,
+                user_id: 0,
+                limit: 10,
+                log_id: 0,
+                bucket: Duration::ZERO,
+            }),
+            "SELECT page_id, COUNT(*) AS hits FROM web_logs WHERE `timestamp` >= \
+             '2023-01-01T00:00:00Z' AND `timestamp` < '2023-01-03T00:00:00Z' \
+             GROUP BY page_id ORDER BY hits DESC LIMIT 10"
+        );
+
+        // The same query seed gives the same queries, whatever the data seed
+        assert_eq!(generator(&config(7), 7).suite(3), suite);
+        assert_eq!(generator(&config(8), 7).suite(3), suite);
+        assert_ne!(generator(&config(7), 8).suite(3), suite);
+    }
+
+    #[test]
+    fn test_custom_templates() {
+        let custom = QueryTemplate::parse(
+            "user_pages",
+            QueryKind::Custom,
+            "SELECT * FROM web_logs WHERE user_id = {{user_id}} AND page_id = {{page_id}} \
+             LIMIT {{limit}}",
+        )
+        .unwrap();
+        let mut generator = generator(&config(3), 3).with_templates(vec![custom]);
+        let suite = generator.suite(50);
+        assert_eq!(suite.len(), 50);
+        for spec in &suite {
+            check_sql(&spec.sql);
+            assert_eq!(spec.name, "user_pages");
+            assert!((0..100).contains(&spec.params.user_id));
+            assert!((0..20).contains(&spec.params.page_id));
+            assert!(LIMITS.contains(&spec.params.limit));
+            assert!(spec.sql.ends_with(&format!("LIMIT {}", spec.params.limit)));
+        }
+    }
+
+    #[test]
+    fn test_window_clamped_to_range() {
+        let mut config = config(1);
+        config.time_end = "2023-01-01T02:00:00Z".to_string();
+        let mut generator = generator(&config, 1);
+        let spec = generator.generate(&template(QueryKind::DistinctUsers));
+        assert_eq!(spec.window(), &generator.range);
+        let spec = generator.generate(&template(QueryKind::TopPages));
+        assert_eq!(spec.window().length(), Duration::from_secs(3600));
+
+        for kind in QueryKind::ALL {
+            assert_eq!(QueryKind::parse(kind.name()), Some(kind));
+        }
+        assert_eq!(QueryKind::parse("country"), None);
+    }
+
+    #[test]
+    fn test_window_distribution() {
+        let five_minutes = WindowSize::Fixed(Duration::from_secs(300));
+        let hour = WindowSize::Fixed(Duration::from_secs(3600));
+        let query_config = QueryConfig {
+            windows: vec![(five_minutes, 70), (hour, 25), (WindowSize::Full, 5)],
+            query_seed: 5,
+            ..Default::default()
+        };
+        let mut generator =
+            QueryGenerator::new(&config(5), &LogConfig::new(100, 20), query_config).unwrap();
+        let template = template(QueryKind::TopPages);
+        let specs = (0..10_000)
+            .map(|_| generator.generate(&template))
+            .collect::<Vec<_>>();
+        for (size, expected) in [(five_minutes, 7000), (hour, 2500), (WindowSize::Full, 500)] {
+            let windows = specs
+                .iter()
+                .filter(|spec| spec.window_size == size)
+                .map(QuerySpec::window)
+                .collect::<Vec<_>>();
+            assert!(
+                windows.len().abs_diff(expected) < 200,
+                "{} {}",
+                size,
+                windows.len()
+            );
+            for window in windows {
+                let length = match size {
+                    WindowSize::Fixed(length) => length,
+                    _ => generator.range.length(),
+                };
+                assert_eq!(window.length(), length);
+                assert!(window.start >= generator.range.start);
+                assert!(window.end <= generator.range.end);
+            }
+        }
+        assert_eq!(
+            [five_minutes, hour, WindowSize::Full].map(|size| size.to_string()),
+            ["5m", "1h", "full"]
+        );
+        for size in [five_minutes, hour, WindowSize::Full, WindowSize::Kind] {
+            assert_eq!(WindowSize::parse(&size.to_string()), Some(size));
+        }
+        assert_eq!(WindowSize::parse("5x"), None);
+    }
+
+    #[test]
+    fn test_window_recency() {
+        // Mean start of 5 minute windows, as a share of the room they have
+        let mean_start = |recency: f64| {
+            let query_config = QueryConfig {
+                windows: vec![(WindowSize::Fixed(Duration::from_secs(300)), 1)],
+                recency,
+                query_seed: 9,
+                ..Default::default()
+            };
+            let mut generator =
+                QueryGenerator::new(&config(9), &LogConfig::new(100, 20), query_config).unwrap();
+            let slack = (generator.range.length() - Duration::from_secs(300)).as_secs_f64();
+            let template = template(QueryKind::TopPages);
+            (0..5_000)
+                .map(|_| {
+                    let spec = generator.generate(&template);
+                    (spec.window().start - generator.range.start).num_seconds() as f64 / slack
+                })
+                .sum::<f64>()
+                / 5_000.0
+        };
+        // The mean of u^(1 / (1 + r)) is (1 + r) / (2 + r)
+        assert!((mean_start(0.0) - 0.5).abs() < 0.02);
+        assert!((mean_start(3.0) - 0.8).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_lookup_misses() {
+        let query_config = QueryConfig {
+            miss_fraction: 0.2,
+            query_seed: 6,
+            ..Default::default()
+        };
+        let mut generator =
+            QueryGenerator::new(&config(6), &LogConfig::new(100, 20), query_config).unwrap();
+        // A log a minute over 2 days and the one at the end
+        assert_eq!(generator.num_of_logs, 2 * 24 * 60 + 1);
+        let lookup = template(QueryKind::LogLookup);
+        let specs = (0..10_000)
+            .map(|_| generator.generate(&lookup))
+            .collect::<Vec<_>>();
+        let misses = specs
+            .iter()
+            .filter(|spec| spec.lookup_hit == Some(false))
+            .count();
+        assert!(misses.abs_diff(2_000) < 200, "{}", misses);
+        for spec in &specs {
+            let generated = (0..generator.num_of_logs as i32).contains(&spec.params.log_id);
+            assert_eq!(spec.lookup_hit, Some(generated), "{}", spec.params.log_id);
+            assert!(spec.params.log_id < 2 * generator.num_of_logs as i32);
+            assert_eq!(
+                spec.sql,
+                format!(
+                    "SELECT log_id, user_id, page_id, runtime, `timestamp` FROM web_logs \
+                     WHERE log_id = {}",
+                    spec.params.log_id
+                )
+            );
+        }
+        // Queries without lookups don't get a hit or miss
+        let spec = generator.generate(&template(QueryKind::TopPages));
+        assert_eq!(spec.lookup_hit, None);
+    }
+
+    #[test]
+    fn test_bucket_widths() {
+        let minute = Duration::from_secs(60);
+        let widths = BucketWidth::ALL.map(|width| width.width(minute));
+        assert_eq!(widths, [60, 600, 97].map(Duration::from_secs));
+        let half_second = Duration::from_millis(500);
+        let widths = BucketWidth::ALL.map(|width| width.width(half_second));
+        assert_eq!(widths, [500, 5000, 751].map(Duration::from_millis));
+        assert_eq!(
+            BucketWidth::Prime.width(Duration::from_secs(1)).as_millis(),
+            1511
+        );
+
+        // Buckets follow the interval of the data
+        let mut config = config(2);
+        config.interval = 10 * 1_000_000;
+        let mut generator = generator(&config, 2);
+        let binned = template(QueryKind::BinnedBuckets);
+        let mut seen = BTreeSet::new();
+        for _ in 0..100 {
+            let spec = generator.generate(&binned);
+            let width = spec.bucket_width.unwrap();
+            assert_eq!(spec.params.bucket, width.width(Duration::from_secs(10)));
+            assert!(spec.sql.starts_with(&format!(
+                "SELECT date_bin(INTERVAL '{}s', `timestamp`)",
+                spec.params.bucket.as_secs()
+            )));
+            seen.insert(width.name());
+        }
+        assert_eq!(seen.len(), BucketWidth::ALL.len());
+        let spec = generator.generate(&template(QueryKind::TopPages));
+        assert_eq!(spec.bucket_width, None);
+        assert_eq!(BucketWidth::parse("prime"), Some(BucketWidth::Prime));
+        assert_eq!(BucketWidth::parse("3x"), None);
+    }
+
+    #[test]
+    fn test_query_config_violations() {
+        let query_config = QueryConfig {
+            mix: QueryMix(vec![(QueryKind::TopPages, 0)]),
+            windows: vec![(WindowSize::Fixed(Duration::ZERO), 0)],
+            recency: -1.0,
+            miss_fraction: 1.5,
+            query_seed: 0,
+        };
+        assert_eq!(
+            query_config.violations(),
+            [
+                "windows need a weight above 0",
+                "windows must be longer than 0s",
+                "mix needs a weight above 0",
+                "recency must be at least 0, got -1",
+                "miss_fraction must be between 0 and 1, got 1.5"
+            ]
+        );
+        assert!(QueryGenerator::new(&config(1), &LogConfig::new(1, 1), query_config).is_err());
+        assert!(QueryConfig::default().violations().is_empty());
+    }
+
+    #[test]
+    fn test_workload_mix() {
+        let mix: QueryMix = "top_pages=6, error_rate=3,distinct_users=1,p95_response_time=0"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            mix.to_string(),
+            "top_pages=6,error_rate=3,distinct_users=1,p95_response_time=0"
+        );
+        assert_eq!(mix.share(QueryKind::ErrorRate), 0.3);
+        let query_config = QueryConfig {
+            mix: mix.clone(),
+            query_seed: 4,
+            ..Default::default()
+        };
+        let mut generator =
+            QueryGenerator::new(&config(4), &LogConfig::new(100, 20), query_config).unwrap();
+        let workload = generator.workload(10_000).unwrap();
+        assert_eq!(workload.len(), 10_000);
+        for kind in QueryKind::ALL {
+            let count = workload.iter().filter(|spec| spec.kind == kind).count();
+            let expected = (mix.share(kind) * 10_000.0) as usize;
+            assert!(count.abs_diff(expected) < 200, "{} {}", kind, count);
+        }
+        // Kinds are drawn one query at a time, not in blocks
+        let switches = workload
+            .windows(2)
+            .filter(|pair| pair[0].kind != pair[1].kind)
+            .count();
+        assert!(switches > 4_000, "{}", switches);
+
+        let mut generator = generator.with_templates(vec![template(QueryKind::TopPages)]);
+        assert_eq!(
+            generator.workload(1).unwrap_err().to_string(),
+            "Invalid config, mix weighs error_rate but no template is of it; \
+             mix weighs distinct_users but no template is of it"
+        );
+
+        for mix in ["top_pages", "top_pages=x", "country=1"] {
+            assert!(mix.parse::<QueryMix>().is_err(), "{}", mix);
+        }
+    }
+}
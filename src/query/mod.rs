@@ -0,0 +1,8 @@
+pub mod compare;
+pub mod expected;
+pub mod generator;
+pub mod query_file;
+pub mod report;
+pub mod results_csv;
+pub mod runner;
+pub mod template;
@@ -0,0 +1,469 @@
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use crate::common::error::{InvalidQueryFileSnafu, ReadFileSnafu, Result};
+use crate::query::expected::ExpectedResult;
+use crate::query::generator::{
+    joins_tables, BucketWidth, QueryKind, QueryMix, QuerySpec, QueryWindow, WindowSize,
+};
+use crate::query::template::QueryParams;
+
+/// Layout of a query file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryFormat {
+    // One statement per line, the mix and query seed in `-- mix: ` and
+    // `-- query_seed: ` comments
+    Sql,
+    // One `QueryRecord` per line, the mix and query seed in a
+    // `{"mix": ..., "query_seed": ...}` first line
+    Jsonl,
+}
+
+impl QueryFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sql" => Some(QueryFormat::Sql),
+            "jsonl" => Some(QueryFormat::Jsonl),
+            _ => None,
+        }
+    }
+
+    /// `Jsonl` for a `.jsonl` file, `Sql` for anything else
+    pub fn of_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl") => QueryFormat::Jsonl,
+            _ => QueryFormat::Sql,
+        }
+    }
+}
+
+/// Placeholder values of a `QueryRecord`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordParams {
+    pub page_id: i32,
+    pub user_id: i32,
+    pub limit: usize,
+    #[serde(default)]
+    pub log_id: i32,
+    #[serde(default)]
+    pub bucket_ms: u64,
+}
+
+/// A generated query as written to a `.jsonl` query file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRecord {
+    pub name: String,
+    pub kind: String,
+    pub sql: String,
+    // RFC3339 UTC times
+    pub window_start: String,
+    pub window_end: String,
+    pub window_size: String,
+    pub params: RecordParams,
+    // Position of the query in the generated sequence
+    pub seed_index: usize,
+    // Result on the generated data, when it was worked out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<ExpectedResult>,
+    // The query joins tables, servers without joins can't run it
+    #[serde(default)]
+    pub requires_join: bool,
+    // Whether the log a lookup is of was generated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookup_hit: Option<bool>,
+    // Name of the width the buckets of the query were derived with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket_width: Option<String>,
+}
+
+impl QueryRecord {
+    pub fn new(seed_index: usize, spec: &QuerySpec) -> Self {
+        let time = |time: &DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        QueryRecord {
+            name: spec.name.clone(),
+            kind: spec.kind.name().to_string(),
+            sql: spec.sql.clone(),
+            window_start: time(&spec.window().start),
+            window_end: time(&spec.window().end),
+            window_size: spec.window_size.to_string(),
+            params: RecordParams {
+                page_id: spec.params.page_id,
+                user_id: spec.params.user_id,
+                limit: spec.params.limit,
+                log_id: spec.params.log_id,
+                bucket_ms: spec.params.bucket.as_millis() as u64,
+            },
+            seed_index,
+            expected: spec.expected.clone(),
+            requires_join: spec.requires_join,
+            lookup_hit: spec.lookup_hit,
+            bucket_width: spec.bucket_width.map(|width| width.name().to_string()),
+        }
+    }
+
+    /// The spec the record was written from, `Err` with the reason the
+    /// record isn't one
+    pub fn to_spec(&self) -> std::result::Result<QuerySpec, String> {
+        let time = |name: &str, time: &str| {
+            DateTime::parse_from_rfc3339(time)
+                .map(|time| time.with_timezone(&Utc))
+                .map_err(|e| format!("invalid {} {:?}: {}", name, time, e))
+        };
+        Ok(QuerySpec {
+            name: self.name.clone(),
+            kind: QueryKind::parse(&self.kind)
+                .ok_or_else(|| format!("unknown query kind {:?}", self.kind))?,
+            sql: self.sql.clone(),
+            params: QueryParams {
+                window: QueryWindow {
+                    start: time("window_start", &self.window_start)?,
+                    end: time("window_end", &self.window_end)?,
+                },
+                page_id: self.params.page_id,
+                user_id: self.params.user_id,
+                limit: self.params.limit,
+                log_id: self.params.log_id,
+                bucket: Duration::from_millis(self.params.bucket_ms),
+            },
+            window_size: WindowSize::parse(&self.window_size)
+                .ok_or_else(|| format!("invalid window_size {:?}", self.window_size))?,
+            expected: self.expected.clone(),
+            requires_join: self.requires_join,
+            lookup_hit: self.lookup_hit,
+            bucket_width: self
+                .bucket_width
+                .as_deref()
+                .map(|name| {
+                    BucketWidth::parse(name)
+                        .ok_or_else(|| format!("invalid bucket_width {:?}", name))
+                })
+                .transpose()?,
+        })
+    }
+}
+
+// First line of a `.jsonl` query file with a mix or query seed
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonHeader {
+    #[serde(default)]
+    mix: Option<String>,
+    #[serde(default)]
+    query_seed: Option<u64>,
+}
+
+// A line of a `.jsonl` query file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonLine {
+    Header(JsonHeader),
+    Query(QueryRecord),
+}
+
+/// A query of a query file, with what it was generated from when the file
+/// records it
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileQuery {
+    Sql(String),
+    Generated { seed_index: usize, spec: QuerySpec },
+}
+
+impl FileQuery {
+    pub fn sql(&self) -> &str {
+        match self {
+            FileQuery::Sql(sql) => sql,
+            FileQuery::Generated { spec, .. } => &spec.sql,
+        }
+    }
+
+    pub fn spec(&self) -> Option<&QuerySpec> {
+        match self {
+            FileQuery::Sql(_) => None,
+            FileQuery::Generated { spec, .. } => Some(spec),
+        }
+    }
+
+    /// Whether the query joins tables, as recorded or going by its SQL
+    pub fn requires_join(&self) -> bool {
+        match self {
+            FileQuery::Sql(sql) => joins_tables(sql),
+            FileQuery::Generated { spec, .. } => spec.requires_join,
+        }
+    }
+}
+
+/// The queries of a query file, with the mix and seed they were drawn with
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryFile {
+    pub mix: Option<QueryMix>,
+    pub query_seed: Option<u64>,
+    pub queries: Vec<FileQuery>,
+}
+
+/// Write `specs` in `format`, with `mix` and `query_seed` as the header when
+/// there are any
+pub fn write_queries(
+    writer: &mut impl Write,
+    format: QueryFormat,
+    mix: Option<&QueryMix>,
+    query_seed: Option<u64>,
+    specs: &[QuerySpec],
+) -> std::io::Result<()> {
+    match format {
+        QueryFormat::Sql => {
+            if let Some(mix) = mix {
+                writeln!(writer, "-- mix: {}", mix)?;
+            }
+            if let Some(query_seed) = query_seed {
+                writeln!(writer, "-- query_seed: {}", query_seed)?;
+            }
+            for spec in specs {
+                // Templates may span lines, the file has a line per query
+                writeln!(writer, "{};", spec.sql.replace(['\r', '\n'], " "))?;
+            }
+        }
+        QueryFormat::Jsonl => {
+            let mut header = serde_json::Map::new();
+            if let Some(mix) = mix {
+                header.insert("mix".to_string(), mix.to_string().into());
+            }
+            if let Some(query_seed) = query_seed {
+                header.insert("query_seed".to_string(), query_seed.into());
+            }
+            if !header.is_empty() {
+                writeln!(writer, "{}", serde_json::Value::Object(header))?;
+            }
+            for (seed_index, spec) in specs.iter().enumerate() {
+                let record = QueryRecord::new(seed_index, spec);
+                writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read a query file in the format of its extension, see `QueryFormat::of_path`
+pub fn read_queries(path: &Path) -> Result<QueryFile> {
+    let text = std::fs::read_to_string(path).context(ReadFileSnafu {
+        path: path.display().to_string(),
+    })?;
+    parse_queries(&text, QueryFormat::of_path(path)).map_err(|(line_no, reason)| {
+        InvalidQueryFileSnafu {
+            path: path.display().to_string(),
+            line_no,
+            reason,
+        }
+        .build()
+    })
+}
+
+// Queries of a query file, `Err` with the line number and reason of the first
+// line that doesn't parse
+fn parse_queries(
+    text: &str,
+    format: QueryFormat,
+) -> std::result::Result<QueryFile, (usize, String)> {
+    let mut file = QueryFile::default();
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (mix, query_seed) = match format {
+            QueryFormat::Sql => {
+                if let Some(mix) = line.strip_prefix("-- mix:") {
+                    (Some(mix.trim().to_string()), None)
+                } else if let Some(seed) = line.strip_prefix("-- query_seed:") {
+                    let seed = seed
+                        .trim()
+                        .parse()
+                        .map_err(|_| (line_no, format!("invalid query_seed {:?}", seed.trim())))?;
+                    (None, Some(seed))
+                } else if line.starts_with("--") {
+                    continue;
+                } else {
+                    let sql = line.trim_end_matches(';').trim_end();
+                    file.queries.push(FileQuery::Sql(sql.to_string()));
+                    continue;
+                }
+            }
+            QueryFormat::Jsonl => match serde_json::from_str(line) {
+                Ok(JsonLine::Header(header)) => (header.mix, header.query_seed),
+                Ok(JsonLine::Query(record)) => {
+                    let spec = record.to_spec().map_err(|reason| (line_no, reason))?;
+                    file.queries.push(FileQuery::Generated {
+                        seed_index: record.seed_index,
+                        spec,
+                    });
+                    continue;
+                }
+                Err(e) => return Err((line_no, e.to_string())),
+            },
+        };
+        if let Some(mix) = mix {
+            file.mix = Some(mix.parse().map_err(|reason| (line_no, reason))?);
+        }
+        if query_seed.is_some() {
+            file.query_seed = query_seed;
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::common::error::Error;
+    use crate::generator::data_generator::{DataGeneratorConfig, UseCase};
+    use crate::generator::log_data_generator::LogConfig;
+    use crate::query::expected::ValueBounds;
+    use crate::query::generator::{QueryConfig, QueryGenerator};
+
+    fn workload(query_seed: u64) -> (QueryMix, Vec<QuerySpec>) {
+        let mix: QueryMix = "top_pages=3,distinct_users=1".parse().unwrap();
+        let query_config = QueryConfig {
+            mix: mix.clone(),
+            windows: vec![
+                (WindowSize::Fixed(Duration::from_secs(300)), 1),
+                (WindowSize::Full, 1),
+                (WindowSize::Kind, 1),
+            ],
+            recency: 1.0,
+            miss_fraction: 0.5,
+            query_seed,
+        };
+        let config = DataGeneratorConfig::new(
+            60 * 1_000_000,
+            "2023-01-01T00:00:00Z".to_string(),
+            "2023-01-02T00:00:00Z".to_string(),
+            StdRng::seed_from_u64(2),
+            UseCase::Log,
+        );
+        let mut generator =
+            QueryGenerator::new(&config, &LogConfig::new(10, 10), query_config).unwrap();
+        (mix, generator.workload(20).unwrap())
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let (mix, mut specs) = workload(2);
+        specs[0].expected = Some(ExpectedResult {
+            rows: 1,
+            column: 1,
+            values: vec![ValueBounds::exact(0.25)],
+        });
+        let mut out = Vec::new();
+        write_queries(&mut out, QueryFormat::Jsonl, Some(&mix), Some(2), &specs).unwrap();
+        let file = parse_queries(std::str::from_utf8(&out).unwrap(), QueryFormat::Jsonl).unwrap();
+        assert_eq!(file.mix, Some(mix));
+        assert_eq!(file.query_seed, Some(2));
+        let expected = specs
+            .into_iter()
+            .enumerate()
+            .map(|(seed_index, spec)| FileQuery::Generated { seed_index, spec })
+            .collect::<Vec<_>>();
+        assert_eq!(file.queries, expected);
+    }
+
+    #[test]
+    fn test_sql_round_trip() {
+        let (mix, mut specs) = workload(2);
+        specs[0].sql = "SELECT 1\nFROM web_logs".to_string();
+        let mut out = Vec::new();
+        write_queries(&mut out, QueryFormat::Sql, Some(&mix), Some(2), &specs).unwrap();
+        let text = format!("-- a comment\n\n{}", std::str::from_utf8(&out).unwrap());
+        let file = parse_queries(&text, QueryFormat::Sql).unwrap();
+        assert_eq!(file.mix, Some(mix));
+        assert_eq!(file.query_seed, Some(2));
+        assert_eq!(
+            file.queries[0],
+            FileQuery::Sql("SELECT 1 FROM web_logs".to_string())
+        );
+        assert_eq!(
+            file.queries[1..]
+                .iter()
+                .map(FileQuery::sql)
+                .collect::<Vec<_>>(),
+            specs[1..]
+                .iter()
+                .map(|spec| spec.sql.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(file.queries.iter().all(|query| query.spec().is_none()));
+    }
+
+    #[test]
+    fn test_invalid_query_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, specs) = workload(2);
+        let mut record = serde_json::to_value(QueryRecord::new(0, &specs[0])).unwrap();
+        record["kind"] = "country".into();
+        let path = dir.path().join("queries.jsonl");
+        std::fs::write(&path, format!("{{\"mix\": \"top_pages=1\"}}\n{}\n", record)).unwrap();
+        let err = read_queries(&path).unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidQueryFile { line_no: 2, .. }),
+            "{:?}",
+            err
+        );
+        assert!(
+            err.to_string().ends_with("unknown query kind \"country\""),
+            "{}",
+            err
+        );
+
+        let path = dir.path().join("queries.sql");
+        std::fs::write(&path, "SELECT 1;\n-- mix: top_pages\n").unwrap();
+        let err = read_queries(&path).unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidQueryFile { line_no: 2, .. }),
+            "{:?}",
+            err
+        );
+        std::fs::write(&path, "-- query_seed: -1\nSELECT 1;\n").unwrap();
+        let err = read_queries(&path).unwrap_err();
+        assert!(
+            err.to_string().ends_with("invalid query_seed \"-1\""),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_query_seed_reproducible() {
+        let jsonl = |query_seed| {
+            let (mix, specs) = workload(query_seed);
+            let mut out = Vec::new();
+            write_queries(
+                &mut out,
+                QueryFormat::Jsonl,
+                Some(&mix),
+                Some(query_seed),
+                &specs,
+            )
+            .unwrap();
+            out
+        };
+        assert_eq!(jsonl(11), jsonl(11));
+        assert_ne!(jsonl(11), jsonl(12));
+
+        // Without the seeds in the header the queries still differ
+        let (_, first) = workload(11);
+        let (_, second) = workload(12);
+        let sqls = |specs: &[QuerySpec]| {
+            specs
+                .iter()
+                .map(|spec| spec.sql.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_ne!(sqls(&first), sqls(&second));
+    }
+}
@@ -0,0 +1,783 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    fmt,
+    time::Duration,
+};
+
+use chrono::SecondsFormat;
+
+use crate::{
+    client::metrics::LatencyDistribution,
+    common::error::Error,
+    query::{
+        generator::{QueryKind, QueryMix, QuerySpec},
+        query_file::FileQuery,
+        runner::Warmup,
+    },
+};
+
+/// What running one query of a workload came to
+#[derive(Debug)]
+pub struct QueryOutcome {
+    // Position of the query in the run
+    pub index: usize,
+    // Worker that ran the query, from 0
+    pub worker: usize,
+    pub query: FileQuery,
+    pub latency: Duration,
+    // Unset for a query that failed
+    pub rows: Option<usize>,
+    pub error: Option<Error>,
+    // How the rows differ from the expected result of the query, for a
+    // query the file has one of
+    pub mismatch: Option<String>,
+}
+
+impl QueryOutcome {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Whether the query failed as it ran longer than its timeout
+    pub fn timed_out(&self) -> bool {
+        matches!(self.error, Some(Error::QueryTimeout { .. }))
+    }
+}
+
+/// What ran before the measured queries of a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarmupReport {
+    pub warmup: Warmup,
+    pub executed: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+}
+
+/// How the queries of an open-loop run kept to their schedule, see
+/// `RunnerOptions::target_qps`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpenLoopReport {
+    pub target_qps: f64,
+    // Queries that started more than `LATE_DISPATCH_SLACK` after they were
+    // due, waiting for a free worker or a scheduler behind
+    pub late: usize,
+    // Queries due while the backlog was full, counted but not run
+    pub dropped: usize,
+}
+
+/// Class of a query's window by its length, the data a query reads grows
+/// with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WindowClass {
+    // Under an hour
+    Minutes,
+    // An hour up to a day
+    Hours,
+    // A day or more
+    Days,
+}
+
+impl WindowClass {
+    pub fn of(length: Duration) -> Self {
+        match length.as_secs() {
+            secs if secs < 3600 => WindowClass::Minutes,
+            secs if secs < 24 * 3600 => WindowClass::Hours,
+            _ => WindowClass::Days,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowClass::Minutes => "minutes",
+            WindowClass::Hours => "hours",
+            WindowClass::Days => "days",
+        }
+    }
+}
+
+impl fmt::Display for WindowClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// One of the slowest queries of a run, kept to look into after it. Ordered
+/// by latency first.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SlowQuery {
+    pub latency: Duration,
+    // Position of the query in the run
+    pub index: usize,
+    // Name and placeholder values, empty for a query of a .sql file
+    pub name: String,
+    pub params: String,
+    pub sql: String,
+    pub failed: bool,
+}
+
+impl SlowQuery {
+    fn of(outcome: &QueryOutcome) -> Self {
+        let spec = outcome.query.spec();
+        SlowQuery {
+            latency: outcome.latency,
+            index: outcome.index,
+            name: spec.map(|spec| spec.name.clone()).unwrap_or_default(),
+            params: spec.map(describe_params).unwrap_or_default(),
+            sql: outcome.query.sql().to_string(),
+            failed: !outcome.is_ok(),
+        }
+    }
+}
+
+// Placeholder values of a generated query, lookups and buckets only for
+// queries with them
+fn describe_params(spec: &QuerySpec) -> String {
+    let params = &spec.params;
+    let mut described = format!(
+        "window {} to {}, page_id {}, user_id {}, limit {}",
+        params
+            .window
+            .start
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+        params.window.end.to_rfc3339_opts(SecondsFormat::Secs, true),
+        params.page_id,
+        params.user_id,
+        params.limit
+    );
+    if spec.lookup_hit.is_some() {
+        described.push_str(&format!(", log_id {}", params.log_id));
+    }
+    if spec.bucket_width.is_some() {
+        described.push_str(&format!(", bucket {}ms", params.bucket.as_millis()));
+    }
+    described
+}
+
+/// Slowest queries a report keeps
+pub const SLOWEST_KEPT: usize = 10;
+
+/// The `SLOWEST_KEPT` slowest queries recorded, on a heap of no more of them
+/// whose top is the fastest one, the one to make room for a slower query
+#[derive(Debug, Clone, Default)]
+pub struct SlowestQueries(BinaryHeap<Reverse<SlowQuery>>);
+
+impl SlowestQueries {
+    pub fn record(&mut self, outcome: &QueryOutcome) {
+        if self.0.len() >= SLOWEST_KEPT {
+            match self.0.peek() {
+                Some(Reverse(fastest)) if fastest.latency < outcome.latency => {
+                    self.0.pop();
+                }
+                _ => return,
+            }
+        }
+        self.0.push(Reverse(SlowQuery::of(outcome)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The queries kept, slowest first
+    pub fn to_vec(&self) -> Vec<SlowQuery> {
+        let mut slowest = self
+            .0
+            .iter()
+            .map(|Reverse(query)| query.clone())
+            .collect::<Vec<_>>();
+        slowest.sort_by(|a, b| b.cmp(a));
+        slowest
+    }
+}
+
+impl PartialEq for SlowestQueries {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_vec() == other.to_vec()
+    }
+}
+
+/// Summary of a query run, fed one outcome at a time while the run goes on
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryRunReport {
+    pub executed: usize,
+    pub failed: usize,
+    // Failed queries that ran longer than their timeout, the others failed
+    // with an error of the server or the connection
+    pub timeouts: usize,
+    // Queries of the file left out as the target may not support them
+    pub skipped: usize,
+    pub rows: u64,
+    pub elapsed: Duration,
+    // Workers queries ran on at once
+    pub concurrency: usize,
+    // Queries every worker ran, by worker id
+    pub workers: Vec<usize>,
+    // Failed queries included
+    pub latency: LatencyDistribution,
+    // Latencies of the queries of every kind, for files recording kinds
+    pub kinds: BTreeMap<String, LatencyDistribution>,
+    // Latencies of the queries by the length of their window, lookups left
+    // out as they read a log or two whatever their window
+    pub windows: BTreeMap<WindowClass, LatencyDistribution>,
+    // Latencies of lookups of generated logs and of logs that weren't, a
+    // miss may take another path through the server than a hit
+    pub lookup_hits: LatencyDistribution,
+    pub lookup_misses: LatencyDistribution,
+    // Latencies of bucketed aggregations by the width of the buckets, as
+    // the width of the interval or not
+    pub buckets: BTreeMap<String, LatencyDistribution>,
+    // Kinds whose share of the run is off the mix the file declares, empty
+    // when it's on or the file declares none
+    pub mix_mismatches: Vec<String>,
+    // Seed the queries of the file were generated with, for files recording
+    // it
+    pub query_seed: Option<u64>,
+    // Unset for a run without warmup, none of the other fields count it
+    pub warmup: Option<WarmupReport>,
+    // Set for a run at a target rate, its latencies count from when each
+    // query was due
+    pub open_loop: Option<OpenLoopReport>,
+    // Queries whose rows were compared with an expected result
+    pub checked: usize,
+    // The first of the checked queries that returned other rows, up to
+    // `WRONG_RESULTS_KEPT` of them
+    pub wrong_results: Vec<String>,
+    pub wrong_result_count: usize,
+    // Failed queries included
+    pub slowest: SlowestQueries,
+}
+
+/// Wrong results a report keeps the differences of
+pub const WRONG_RESULTS_KEPT: usize = 10;
+
+impl QueryRunReport {
+    pub fn record(&mut self, outcome: &QueryOutcome) {
+        self.executed += 1;
+        if self.workers.len() <= outcome.worker {
+            self.workers.resize(outcome.worker + 1, 0);
+        }
+        self.workers[outcome.worker] += 1;
+        if !outcome.is_ok() {
+            self.failed += 1;
+        }
+        if outcome.timed_out() {
+            self.timeouts += 1;
+        }
+        self.rows += outcome.rows.unwrap_or_default() as u64;
+        if outcome.is_ok()
+            && outcome
+                .query
+                .spec()
+                .is_some_and(|spec| spec.expected.is_some())
+        {
+            self.checked += 1;
+        }
+        if let Some(mismatch) = &outcome.mismatch {
+            self.wrong_result_count += 1;
+            if self.wrong_results.len() < WRONG_RESULTS_KEPT {
+                let name = outcome.query.spec().map_or("", |spec| spec.name.as_str());
+                self.wrong_results
+                    .push(format!("query {} {}: {}", outcome.index, name, mismatch));
+            }
+        }
+        self.latency.record(outcome.latency);
+        self.slowest.record(outcome);
+        if let Some(spec) = outcome.query.spec() {
+            self.kinds
+                .entry(spec.kind.name().to_string())
+                .or_default()
+                .record(outcome.latency);
+            match spec.lookup_hit {
+                Some(true) => self.lookup_hits.record(outcome.latency),
+                Some(false) => self.lookup_misses.record(outcome.latency),
+                None => self
+                    .windows
+                    .entry(WindowClass::of(spec.window().length()))
+                    .or_default()
+                    .record(outcome.latency),
+            }
+            if let Some(width) = spec.bucket_width {
+                self.buckets
+                    .entry(width.to_string())
+                    .or_default()
+                    .record(outcome.latency);
+            }
+        }
+    }
+
+    /// Queries of `kind` that ran
+    pub fn kind_count(&self, kind: &str) -> usize {
+        self.kinds
+            .get(kind)
+            .map_or(0, |latency| latency.count() as usize)
+    }
+
+    /// Compare the kinds run with `mix`. A kind's share may be off by what
+    /// drawing this many queries explains, four standard deviations of it.
+    pub fn check_mix(&mut self, mix: &QueryMix) {
+        let total = self
+            .kinds
+            .keys()
+            .map(|kind| self.kind_count(kind))
+            .sum::<usize>();
+        if total == 0 {
+            return;
+        }
+        self.mix_mismatches.clear();
+        // Kinds of the mix and the kinds run without being in it
+        let mut kinds = mix.0.iter().map(|(kind, _)| *kind).collect::<Vec<_>>();
+        let unmixed = self
+            .kinds
+            .keys()
+            .filter_map(|name| QueryKind::parse(name))
+            .filter(|kind| !kinds.contains(kind))
+            .collect::<Vec<_>>();
+        kinds.extend(unmixed);
+        for kind in kinds {
+            let expected = mix.share(kind);
+            let count = self.kind_count(kind.name());
+            let actual = count as f64 / total as f64;
+            let tolerance = 4.0 * (expected * (1.0 - expected) / total as f64).sqrt();
+            if (actual - expected).abs() > tolerance + f64::EPSILON {
+                self.mix_mismatches.push(format!(
+                    "{}: {:.1}% of the queries, the mix has {:.1}%",
+                    kind,
+                    actual * 100.0,
+                    expected * 100.0
+                ));
+            }
+        }
+    }
+
+    pub fn queries_per_sec(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.executed as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// The report as written by `run_queries --report-json`, latencies as
+    /// percentile tables
+    pub fn to_json(&self) -> serde_json::Value {
+        let kinds = self
+            .kinds
+            .iter()
+            .map(|(kind, latency)| (kind.clone(), serde_json::json!(latency.summary())))
+            .collect::<serde_json::Map<_, _>>();
+        let windows = self
+            .windows
+            .iter()
+            .map(|(class, latency)| (class.to_string(), serde_json::json!(latency.summary())))
+            .collect::<serde_json::Map<_, _>>();
+        let buckets = self
+            .buckets
+            .iter()
+            .map(|(width, latency)| (width.clone(), serde_json::json!(latency.summary())))
+            .collect::<serde_json::Map<_, _>>();
+        let slowest = self
+            .slowest
+            .to_vec()
+            .into_iter()
+            .map(|query| {
+                serde_json::json!({
+                    "latency_ms": query.latency.as_secs_f64() * 1000.0,
+                    "index": query.index,
+                    "name": query.name,
+                    "params": query.params,
+                    "sql": query.sql,
+                    "failed": query.failed,
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "executed": self.executed,
+            "failed": self.failed,
+            "timeouts": self.timeouts,
+            "errors": self.failed - self.timeouts,
+            "skipped": self.skipped,
+            "rows": self.rows,
+            "elapsed_secs": self.elapsed.as_secs_f64(),
+            "queries_per_sec": self.queries_per_sec(),
+            "concurrency": self.concurrency,
+            "query_seed": self.query_seed,
+            "workers": self.workers,
+            "latency": self.latency.summary(),
+            "kinds": kinds,
+            "windows": windows,
+            "lookup_hits": self.lookup_hits.summary(),
+            "lookup_misses": self.lookup_misses.summary(),
+            "buckets": buckets,
+            "mix_mismatches": self.mix_mismatches,
+            "checked": self.checked,
+            "wrong_result_count": self.wrong_result_count,
+            "wrong_results": self.wrong_results,
+            "slowest": slowest,
+            "warmup": self.warmup.as_ref().map(|warmup| serde_json::json!({
+                "config": warmup.warmup.to_string(),
+                "executed": warmup.executed,
+                "failed": warmup.failed,
+                "elapsed_secs": warmup.elapsed.as_secs_f64(),
+            })),
+            "open_loop": self.open_loop.as_ref().map(|open_loop| serde_json::json!({
+                "target_qps": open_loop.target_qps,
+                "achieved_qps": self.queries_per_sec(),
+                "late": open_loop.late,
+                "dropped": open_loop.dropped,
+            })),
+        })
+    }
+}
+
+impl fmt::Display for QueryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Ran {} queries in {:.3}s ({:.1} queries/s), {} failed, {} rows returned",
+            self.executed,
+            self.elapsed.as_secs_f64(),
+            self.queries_per_sec(),
+            self.failed,
+            self.rows
+        )?;
+        if self.timeouts > 0 {
+            writeln!(
+                f,
+                "  {} of the failed queries timed out, {} failed with an error",
+                self.timeouts,
+                self.failed - self.timeouts
+            )?;
+        }
+        if let Some(query_seed) = self.query_seed {
+            writeln!(f, "  Queries generated with query seed {}", query_seed)?;
+        }
+        if self.skipped > 0 {
+            writeln!(f, "  Skipped {} queries joining tables", self.skipped)?;
+        }
+        if let Some(warmup) = &self.warmup {
+            writeln!(
+                f,
+                "  Warmed up with {} queries in {:.3}s ({} failed), left out of the latencies",
+                warmup.executed,
+                warmup.elapsed.as_secs_f64(),
+                warmup.failed
+            )?;
+        }
+        if let Some(open_loop) = &self.open_loop {
+            writeln!(
+                f,
+                "  Open loop at {:.1} queries/s offered, {:.1} achieved, {} late and {} dropped",
+                open_loop.target_qps,
+                self.queries_per_sec(),
+                open_loop.late,
+                open_loop.dropped
+            )?;
+            writeln!(
+                f,
+                "  Latencies count from when each query was due, waiting for a worker included"
+            )?;
+        }
+        if self.concurrency > 1 {
+            // Queries of other workers compete for the server, latencies of
+            // runs are only comparable at the same concurrency
+            writeln!(
+                f,
+                "  {} workers at once, latencies include waiting on each other's queries",
+                self.concurrency
+            )?;
+            let workers = self
+                .workers
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>();
+            writeln!(f, "  Queries per worker: {}", workers.join(", "))?;
+        }
+        for mismatch in &self.mix_mismatches {
+            writeln!(f, "  Mix mismatch, {}", mismatch)?;
+        }
+        if self.checked > 0 {
+            writeln!(
+                f,
+                "Checked {} results, {} wrong",
+                self.checked, self.wrong_result_count
+            )?;
+            for wrong in &self.wrong_results {
+                writeln!(f, "  Wrong result of {}", wrong)?;
+            }
+        }
+        write!(f, "Latency: {}", self.latency.summary())?;
+        if !self.kinds.is_empty() {
+            write_table(
+                f,
+                "kind",
+                self.kinds.iter().map(|(kind, l)| (kind.as_str(), l)),
+            )?;
+        }
+        if !self.windows.is_empty() {
+            let windows = self
+                .windows
+                .iter()
+                .map(|(class, latency)| (class.name(), latency));
+            write_table(f, "window", windows)?;
+        }
+        if self.lookup_hits.count() + self.lookup_misses.count() > 0 {
+            write!(f, "\n  lookup hits: {}", self.lookup_hits.summary())?;
+            write!(f, "\n  lookup misses: {}", self.lookup_misses.summary())?;
+        }
+        for (width, latency) in &self.buckets {
+            write!(f, "\n  {} buckets: {}", width, latency.summary())?;
+        }
+        if !self.slowest.is_empty() {
+            write!(f, "\nSlowest queries:")?;
+        }
+        for query in self.slowest.to_vec() {
+            write!(
+                f,
+                "\n  {:.2}ms, query {} {}{}: {}",
+                query.latency.as_secs_f64() * 1000.0,
+                query.index,
+                query.name,
+                if query.failed { " (failed)" } else { "" },
+                query.sql
+            )?;
+            if !query.params.is_empty() {
+                write!(f, "\n    {}", query.params)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Latencies of every row, named in a column headed `column`
+fn write_table<'a>(
+    f: &mut fmt::Formatter<'_>,
+    column: &str,
+    rows: impl Iterator<Item = (&'a str, &'a LatencyDistribution)>,
+) -> fmt::Result {
+    write!(
+        f,
+        "\n  {:<20} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        column, "count", "mean", "p50", "p95", "p99", "max"
+    )?;
+    for (name, latency) in rows {
+        let summary = latency.summary();
+        write!(f, "\n  {:<20} {:>8}", name, summary.count)?;
+        for ms in [
+            summary.mean_ms,
+            summary.p50_ms,
+            summary.p95_ms,
+            summary.p99_ms,
+            summary.max_ms,
+        ] {
+            write!(f, " {:>8.2}ms", ms)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::common::error::RequestCancelledSnafu;
+    use crate::query::{
+        generator::{BucketWidth, QuerySpec, QueryWindow, WindowSize},
+        template::QueryParams,
+    };
+
+    fn outcome(kind: QueryKind) -> QueryOutcome {
+        let time = "2023-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        QueryOutcome {
+            index: 0,
+            worker: 0,
+            query: FileQuery::Generated {
+                seed_index: 0,
+                spec: QuerySpec {
+                    name: kind.name().to_string(),
+                    kind,
+                    sql: "SELECT 1".to_string(),
+                    params: QueryParams {
+                        window: QueryWindow {
+                            start: time,
+                            end: time,
+                        },
+                        page_id: 0,
+                        user_id: 0,
+                        limit: 10,
+                        log_id: 0,
+                        bucket: Duration::ZERO,
+                    },
+                    window_size: WindowSize::Kind,
+                    requires_join: false,
+                    lookup_hit: None,
+                    bucket_width: None,
+                    expected: None,
+                },
+            },
+            latency: Duration::from_millis(3),
+            rows: Some(1),
+            error: None,
+            mismatch: None,
+        }
+    }
+
+    #[test]
+    fn test_check_mix() {
+        let mix: QueryMix = "top_pages=3,error_rate=1".parse().unwrap();
+        let mut report = QueryRunReport::default();
+        for _ in 0..290 {
+            report.record(&outcome(QueryKind::TopPages));
+        }
+        for _ in 0..110 {
+            report.record(&outcome(QueryKind::ErrorRate));
+        }
+        report.check_mix(&mix);
+        assert!(
+            report.mix_mismatches.is_empty(),
+            "{:?}",
+            report.mix_mismatches
+        );
+
+        for _ in 0..100 {
+            report.record(&outcome(QueryKind::DistinctUsers));
+        }
+        report.check_mix(&mix);
+        assert_eq!(
+            report.mix_mismatches,
+            [
+                "top_pages: 58.0% of the queries, the mix has 75.0%",
+                "distinct_users: 20.0% of the queries, the mix has 0.0%"
+            ]
+        );
+        assert_eq!(report.executed, 500);
+        assert_eq!(report.kind_count("distinct_users"), 100);
+    }
+
+    #[test]
+    fn test_latency_by_kind() {
+        let mut report = QueryRunReport::default();
+        for (kind, millis) in [
+            (QueryKind::TopPages, 2),
+            (QueryKind::TopPages, 4),
+            (QueryKind::ErrorRate, 40),
+            (QueryKind::TopPages, 6),
+        ] {
+            let mut outcome = outcome(kind);
+            outcome.latency = Duration::from_millis(millis);
+            report.record(&outcome);
+        }
+        let mut lookup = outcome(QueryKind::LogLookup);
+        if let FileQuery::Generated { spec, .. } = &mut lookup.query {
+            spec.lookup_hit = Some(false);
+        }
+        report.record(&lookup);
+        assert_eq!(report.lookup_misses.count(), 1);
+        assert_eq!(report.lookup_hits.count(), 0);
+        assert_eq!(report.kind_count("log_lookup"), 1);
+        let mut buckets = outcome(QueryKind::RangeBuckets);
+        if let FileQuery::Generated { spec, .. } = &mut buckets.query {
+            spec.bucket_width = Some(BucketWidth::Prime);
+        }
+        report.record(&buckets);
+        assert_eq!(report.buckets["prime"].count(), 1);
+        assert!(report
+            .to_string()
+            .contains("\n  prime buckets: 1 | min 3.00ms"));
+        assert!(report
+            .to_string()
+            .contains("\n  lookup misses: 1 | min 3.00ms"));
+        let top_pages = report.kinds["top_pages"].summary();
+        assert_eq!(top_pages.count, 3);
+        assert_eq!(top_pages.mean_ms, 4.0);
+        assert_eq!(top_pages.max_ms, 6.0);
+        assert_eq!(report.latency.count(), 6);
+        assert_eq!(report.latency.summary().max_ms, 40.0);
+
+        let json = report.to_json();
+        assert_eq!(json["executed"], 6);
+        assert_eq!(json["latency"]["count"], 6);
+        assert_eq!(json["buckets"]["prime"]["count"], 1);
+        assert_eq!(json["lookup_misses"]["count"], 1);
+        assert_eq!(json["kinds"]["error_rate"]["p50_ms"], 40.0);
+        let row = format!(
+            "\n  {:<20} {:>8} {:>8}ms {:>8}ms",
+            "top_pages", 3, "4.00", "4.03"
+        );
+        assert!(report.to_string().contains(&row), "{}", report);
+    }
+
+    #[test]
+    fn test_breakdown_and_slowest() {
+        let mut report = QueryRunReport::default();
+        let kinds = [
+            (QueryKind::TopPages, 5 * 60),
+            (QueryKind::ErrorRate, 6 * 3600),
+            (QueryKind::DistinctUsers, 2 * 24 * 3600),
+        ];
+        // Latencies of 0ms to 59ms out of order, 37 and 60 have no common factor
+        for i in 0..60 {
+            let (kind, window_secs) = kinds[i % kinds.len()];
+            let mut outcome = outcome(kind);
+            outcome.index = i;
+            outcome.latency = Duration::from_millis(((i * 37) % 60) as u64);
+            if i == 55 {
+                outcome.error = Some(RequestCancelledSnafu {}.build());
+            }
+            if let FileQuery::Generated { spec, .. } = &mut outcome.query {
+                spec.params.window.end =
+                    spec.params.window.start + chrono::Duration::seconds(window_secs);
+            }
+            report.record(&outcome);
+        }
+        assert_eq!(report.kind_count("top_pages"), 20);
+        assert_eq!(report.kind_count("error_rate"), 20);
+        let windows = report
+            .windows
+            .iter()
+            .map(|(class, latency)| (*class, latency.count()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            windows,
+            [
+                (WindowClass::Minutes, 20),
+                (WindowClass::Hours, 20),
+                (WindowClass::Days, 20)
+            ]
+        );
+
+        let slowest = report.slowest.to_vec();
+        assert_eq!(slowest.len(), SLOWEST_KEPT);
+        let latencies = slowest
+            .iter()
+            .map(|query| query.latency.as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(latencies, (50..60).rev().collect::<Vec<_>>());
+        // 37 * 55 is 2035, 55ms
+        let failed = slowest.iter().find(|query| query.index == 55).unwrap();
+        assert_eq!(failed.latency, Duration::from_millis(55));
+        assert!(failed.failed);
+        assert_eq!(failed.name, "error_rate");
+        assert_eq!(
+            failed.params,
+            "window 2023-01-01T00:00:00Z to 2023-01-01T06:00:00Z, page_id 0, user_id 0, limit 10"
+        );
+        // Faster queries leave the slowest as they are
+        let before = report.slowest.clone();
+        report.record(&outcome(QueryKind::TopPages));
+        assert_eq!(report.slowest, before);
+
+        let json = report.to_json();
+        assert_eq!(json["windows"]["days"]["count"], 20);
+        assert_eq!(json["slowest"][0]["latency_ms"], 59.0);
+        assert_eq!(json["slowest"].as_array().unwrap().len(), SLOWEST_KEPT);
+        let text = report.to_string();
+        assert!(text.contains("\n  window "), "{}", text);
+        assert!(text.contains("\nSlowest queries:\n  59.00ms, query 47 distinct_users: SELECT 1"));
+        assert!(text.contains("\n  55.00ms, query 55 error_rate (failed): SELECT 1\n    window "));
+    }
+}
@@ -0,0 +1,167 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use snafu::ResultExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::common::error::{Result, WriteFileSnafu};
+use crate::query::report::QueryOutcome;
+
+/// Columns of a results CSV, a row per query run
+pub const RESULTS_CSV_HEADER: &str =
+    "timestamp,kind,window_seconds,worker_id,latency_ms,rows_returned,error";
+
+// Rows on their way to the writer, workers wait for room beyond them
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// A row of a results CSV
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultRow {
+    // When the query started, or was due in an open-loop run
+    pub timestamp: DateTime<Utc>,
+    // Empty for a query of a .sql file, as is the window
+    pub kind: String,
+    pub window_seconds: Option<u64>,
+    pub worker: usize,
+    pub latency: Duration,
+    // Unset for a query that failed
+    pub rows: Option<usize>,
+    pub error: bool,
+}
+
+impl ResultRow {
+    pub fn new(timestamp: DateTime<Utc>, outcome: &QueryOutcome) -> Self {
+        let spec = outcome.query.spec();
+        ResultRow {
+            timestamp,
+            kind: spec.map_or_else(String::new, |spec| spec.kind.name().to_string()),
+            window_seconds: spec.map(|spec| spec.window().length().as_secs()),
+            worker: outcome.worker,
+            latency: outcome.latency,
+            rows: outcome.rows,
+            error: !outcome.is_ok(),
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "{},{},{},{},{:.3},{},{}",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+            csv_field(&self.kind),
+            self.window_seconds
+                .map(|secs| secs.to_string())
+                .unwrap_or_default(),
+            self.worker,
+            self.latency.as_secs_f64() * 1000.0,
+            self.rows.map(|rows| rows.to_string()).unwrap_or_default(),
+            self.error
+        )
+    }
+}
+
+// `field` quoted when it holds a comma, quote or line break
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Writes the rows workers send it to a CSV file on a thread of its own. At
+/// most `CHANNEL_CAPACITY` rows wait to be written, whatever the length of
+/// the run.
+pub struct ResultsCsv {
+    sender: mpsc::Sender<ResultRow>,
+    writer: JoinHandle<Result<usize>>,
+}
+
+impl ResultsCsv {
+    /// Create `path` with the header line and start writing rows to it
+    pub fn create(path: &Path) -> Result<Self> {
+        let path = path.display().to_string();
+        let file = File::create(&path).context(WriteFileSnafu { path: &path })?;
+        let mut out = BufWriter::new(file);
+        writeln!(out, "{}", RESULTS_CSV_HEADER).context(WriteFileSnafu { path: &path })?;
+        let (sender, mut receiver) = mpsc::channel::<ResultRow>(CHANNEL_CAPACITY);
+        let writer = tokio::task::spawn_blocking(move || -> Result<usize> {
+            let mut written = 0;
+            while let Some(row) = receiver.blocking_recv() {
+                row.write(&mut out)
+                    .context(WriteFileSnafu { path: &path })?;
+                written += 1;
+            }
+            out.flush().context(WriteFileSnafu { path: &path })?;
+            Ok(written)
+        });
+        Ok(ResultsCsv { sender, writer })
+    }
+
+    /// Sender of the rows to write, writing ends once every one is dropped
+    pub fn sender(&self) -> mpsc::Sender<ResultRow> {
+        self.sender.clone()
+    }
+
+    /// Wait for the rows sent to be written, the senders handed out have to
+    /// be dropped first. The rows written, or the error that stopped the
+    /// writer.
+    pub async fn finish(self) -> Result<usize> {
+        drop(self.sender);
+        self.writer.await.expect("results writer panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        let csv = ResultsCsv::create(&path).unwrap();
+        let timestamp = "2023-01-01T00:00:00.25Z".parse::<DateTime<Utc>>().unwrap();
+        let sender = csv.sender();
+        let row = ResultRow {
+            timestamp,
+            kind: "top_pages".to_string(),
+            window_seconds: Some(3600),
+            worker: 2,
+            latency: Duration::from_micros(1500),
+            rows: Some(10),
+            error: false,
+        };
+        sender.send(row.clone()).await.unwrap();
+        let failed = ResultRow {
+            kind: "pages, \"by hits\"".to_string(),
+            window_seconds: None,
+            rows: None,
+            error: true,
+            ..row
+        };
+        sender.send(failed).await.unwrap();
+        drop(sender);
+        assert_eq!(csv.finish().await.unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "timestamp,kind,window_seconds,worker_id,latency_ms,rows_returned,error\n\
+             2023-01-01T00:00:00.250000Z,top_pages,3600,2,1.500,10,false\n\
+             2023-01-01T00:00:00.250000Z,\"pages, \"\"by hits\"\"\",,2,1.500,,true\n"
+        );
+
+        let err = ResultsCsv::create(&dir.path().join("missing/results.csv"))
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().starts_with("Failed to write file "),
+            "{}",
+            err
+        );
+    }
+}
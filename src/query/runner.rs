@@ -0,0 +1,1479 @@
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use log::debug;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use snafu::ResultExt;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+
+use crate::{
+    common::{
+        connector::{Connector, ConnectorOptions},
+        error::{
+            check_violations, Error, ErrorBudgetExceededSnafu, JoinTaskSnafu, QueryTimeoutSnafu,
+            Result,
+        },
+        rows::QueryRows,
+    },
+    query::{
+        compare::{CompareOptions, ComparisonReport, ResultMismatch},
+        generator::QueryKind,
+        query_file::{FileQuery, QueryFile},
+        report::{OpenLoopReport, QueryOutcome, QueryRunReport, WarmupReport},
+        results_csv::{ResultRow, ResultsCsv},
+    },
+    usql::error::statement_excerpt,
+};
+
+/// Runs the queries of a workload, the `Connector` of the target or a
+/// scripted one in tests. Workers share it, each query takes a connection of
+/// its own.
+pub trait QueryExecutor: Send + Sync {
+    /// Run `sql` and get the rows it returned
+    fn run(&self, sql: &str) -> impl Future<Output = Result<QueryRows>> + Send;
+
+    /// Run `sql` like `run`, failing with `Error::QueryTimeout` once it took
+    /// longer than `timeout`
+    fn run_with_timeout(
+        &self,
+        sql: &str,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<QueryRows>> + Send {
+        async move {
+            match tokio::time::timeout(timeout, self.run(sql)).await {
+                Ok(rows) => rows,
+                Err(_) => QueryTimeoutSnafu {
+                    timeout,
+                    statement: statement_excerpt(sql),
+                }
+                .fail(),
+            }
+        }
+    }
+}
+
+impl QueryExecutor for Connector {
+    async fn run(&self, sql: &str) -> Result<QueryRows> {
+        self.query(sql).await
+    }
+
+    // The connection of a query that timed out doesn't go back to the pool
+    async fn run_with_timeout(&self, sql: &str, timeout: Duration) -> Result<QueryRows> {
+        self.query_with_timeout(sql, timeout).await
+    }
+}
+
+/// Time an open-loop query may start after it was due before it counts as
+/// late, tokio's timer wakes the scheduler up a millisecond or so after the
+/// instant it asked for
+pub const LATE_DISPATCH_SLACK: Duration = Duration::from_millis(10);
+
+// Queries of an open-loop run waiting for a worker at most, a second's worth
+// at the target rate. Queries due with as many waiting are dropped, a target
+// the server can't keep up with doesn't queue them without bound.
+const OPEN_LOOP_BACKLOG: Duration = Duration::from_secs(1);
+
+/// Queries run before the measured ones, so these don't pay for cold caches
+/// and connections. Their latencies are left out of the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Warmup {
+    #[default]
+    None,
+    // This many of the queries of the run, from its first one again once
+    // all of them ran
+    Queries(usize),
+    // The queries of the run, over and over, until this long passed
+    Duration(Duration),
+}
+
+impl fmt::Display for Warmup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warmup::None => write!(f, "none"),
+            Warmup::Queries(count) => write!(f, "{} queries", count),
+            Warmup::Duration(duration) => write!(f, "{}s", duration.as_secs_f64()),
+        }
+    }
+}
+
+/// Which queries of the file run, how many run at once and what a failing
+/// one does to the run
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunnerOptions {
+    // Only run the first queries, after shuffling
+    pub limit: Option<usize>,
+    // Seed the queries are shuffled with, file order without it
+    pub shuffle: Option<u64>,
+    // Fail the run with the error of the first query that fails, instead of
+    // counting it and going on
+    pub stop_on_error: bool,
+    // Workers taking queries off the shared queue
+    pub concurrency: usize,
+    // Run before the queries, on as many workers
+    pub warmup: Warmup,
+    // Leave out the queries the target may not support, the ones joining
+    // tables
+    pub skip_unsupported: bool,
+    // CSV file to write a row per measured query to, as the run goes on
+    pub results_csv: Option<PathBuf>,
+    // Time a query may take before it's given up on and counted as timed
+    // out, unbounded without it
+    pub query_timeout: Option<Duration>,
+    // Timeouts of the generated queries of these kinds, instead of
+    // `query_timeout`
+    pub kind_timeouts: Vec<(QueryKind, Duration)>,
+    // Stop the run once more of its queries failed than this share of them,
+    // timeouts included. The warmup doesn't count.
+    pub max_error_rate: Option<f64>,
+    // Start the queries at this rate whether the ones before them finished
+    // or not, on up to `concurrency` workers, instead of each worker taking
+    // a query once its last one finished. Latencies then count from when a
+    // query was due, so a slow server doesn't lower the load it's measured
+    // under.
+    pub target_qps: Option<f64>,
+}
+
+impl Default for RunnerOptions {
+    fn default() -> Self {
+        RunnerOptions {
+            limit: None,
+            shuffle: None,
+            stop_on_error: false,
+            concurrency: 1,
+            warmup: Warmup::None,
+            skip_unsupported: false,
+            results_csv: None,
+            query_timeout: None,
+            kind_timeouts: Vec::new(),
+            max_error_rate: None,
+            target_qps: None,
+        }
+    }
+}
+
+impl RunnerOptions {
+    /// The queries of `file` to run, in the order they are taken
+    pub fn select(&self, file: &QueryFile) -> Vec<FileQuery> {
+        let mut queries = file.queries.clone();
+        if self.skip_unsupported {
+            queries.retain(|query| !query.requires_join());
+        }
+        if let Some(seed) = self.shuffle {
+            queries.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+        if let Some(limit) = self.limit {
+            queries.truncate(limit);
+        }
+        queries
+    }
+
+    /// Time `query` may take, the timeout of its kind if it has one
+    pub fn timeout_of(&self, query: &FileQuery) -> Option<Duration> {
+        query
+            .spec()
+            .and_then(|spec| {
+                self.kind_timeouts
+                    .iter()
+                    .find(|(kind, _)| *kind == spec.kind)
+                    .map(|(_, timeout)| *timeout)
+            })
+            .or(self.query_timeout)
+    }
+
+    /// Problems running with a pool of `connector_options`, every worker
+    /// needs a connection or workers wait for each other instead of the
+    /// server
+    pub fn violations(&self, connector_options: &ConnectorOptions) -> Vec<String> {
+        let mut violations = Vec::new();
+        if self.concurrency == 0 {
+            violations.push("concurrency must be at least 1".to_string());
+        }
+        if self.concurrency > connector_options.max_connections as usize {
+            violations.push(format!(
+                "concurrency {} needs as many connections, max_connections is {}",
+                self.concurrency, connector_options.max_connections
+            ));
+        }
+        let mut timeouts = self
+            .query_timeout
+            .iter()
+            .chain(self.kind_timeouts.iter().map(|(_, timeout)| timeout));
+        if timeouts.any(Duration::is_zero) {
+            violations.push("query timeouts must be longer than 0".to_string());
+        }
+        if let Some(target_qps) = self.target_qps {
+            if !(target_qps.is_finite() && target_qps > 0.0) {
+                violations.push(format!(
+                    "target_qps must be more than 0, got {}",
+                    target_qps
+                ));
+            }
+        }
+        if let Some(rate) = self.max_error_rate {
+            if !(0.0..=1.0).contains(&rate) {
+                violations.push(format!(
+                    "max_error_rate must be between 0 and 1, got {}",
+                    rate
+                ));
+            }
+        }
+        violations
+    }
+
+    pub fn validate(&self, connector_options: &ConnectorOptions) -> Result<()> {
+        check_violations(self.violations(connector_options))
+    }
+}
+
+// What the workers of a phase of the run, the warmup or the measured one,
+// share
+struct RunState {
+    queries: Vec<FileQuery>,
+    // Queries the workers took so far, the next one to take is this one
+    // modulo the number of queries
+    taken: Mutex<usize>,
+    // Queries to take, going round the queries when it's more of them
+    count: Option<usize>,
+    // No query is taken after it
+    until: Option<Instant>,
+    report: Mutex<QueryRunReport>,
+    // Error of the query that stopped the run with `stop_on_error`, no
+    // worker takes another query after it
+    stopped: Mutex<Option<Error>>,
+    // Where workers send the rows of the results CSV, if one is written
+    results: Option<mpsc::Sender<ResultRow>>,
+    // Stop the phase once more of its `count` queries failed than this
+    // share of them
+    max_error_rate: Option<f64>,
+}
+
+impl RunState {
+    fn new(queries: Vec<FileQuery>, count: Option<usize>, until: Option<Instant>) -> Self {
+        RunState {
+            queries,
+            taken: Mutex::new(0),
+            count,
+            until,
+            report: Mutex::new(QueryRunReport::default()),
+            stopped: Mutex::new(None),
+            results: None,
+            max_error_rate: None,
+        }
+    }
+
+    // The error stopping the phase once `report` has more failures than its
+    // error budget allows
+    fn check_error_budget(&self, report: &QueryRunReport) -> Option<Error> {
+        let max_error_rate = self.max_error_rate?;
+        let allowed = (max_error_rate * self.count? as f64).floor() as usize;
+        (report.failed > allowed).then(|| {
+            ErrorBudgetExceededSnafu {
+                failed: report.failed,
+                executed: report.executed,
+                max_error_rate,
+            }
+            .build()
+        })
+    }
+
+    // The next query with its position in the phase, `None` once the phase
+    // is over
+    fn take(&self) -> Option<(usize, FileQuery)> {
+        let mut taken = self.taken.lock().unwrap();
+        if self.queries.is_empty()
+            || self.count.is_some_and(|count| *taken >= count)
+            || self.until.is_some_and(|until| Instant::now() >= until)
+        {
+            return None;
+        }
+        let index = *taken;
+        *taken += 1;
+        Some((index, self.queries[index % self.queries.len()].clone()))
+    }
+}
+
+/// Run the queries of `file` on `options.concurrency` workers, each taking
+/// the next query once its last one finished, or as they are due at
+/// `options.target_qps`. Every query is recorded into
+/// the report as it finishes, and into the results CSV of `options` if
+/// there is one. The kinds run are checked against the mix the
+/// file declares. The warmup runs first, on the same workers, and only its
+/// counts make it into the report. Queries running longer than the timeout
+/// `options` gives them fail as timed out, and the run stops once more of
+/// them failed than `options.max_error_rate` allows.
+pub async fn run_queries<E: QueryExecutor + 'static>(
+    executor: Arc<E>,
+    file: &QueryFile,
+    options: &RunnerOptions,
+) -> Result<QueryRunReport> {
+    let queries = options.select(file);
+    let warmup = match options.warmup {
+        Warmup::None => None,
+        Warmup::Queries(count) => Some(RunState::new(queries.clone(), Some(count), None)),
+        Warmup::Duration(duration) => Some(RunState::new(
+            queries.clone(),
+            None,
+            Some(Instant::now() + duration),
+        )),
+    };
+    let warmup = match warmup {
+        Some(state) => {
+            let report = run_phase(executor.clone(), state, options).await?;
+            Some(WarmupReport {
+                warmup: options.warmup,
+                executed: report.executed,
+                failed: report.failed,
+                elapsed: report.elapsed,
+            })
+        }
+        None => None,
+    };
+    let count = queries.len();
+    let results_csv = options
+        .results_csv
+        .as_deref()
+        .map(ResultsCsv::create)
+        .transpose()?;
+    let mut state = RunState::new(queries, Some(count), None);
+    state.results = results_csv.as_ref().map(ResultsCsv::sender);
+    state.max_error_rate = options.max_error_rate;
+    let report = run_phase(executor, state, options).await;
+    // The workers are done and their senders dropped, the rows they sent
+    // are written whether the run stopped or not
+    if let Some(results_csv) = results_csv {
+        results_csv.finish().await?;
+    }
+    let mut report = report?;
+    report.warmup = warmup;
+    report.query_seed = file.query_seed;
+    let mut skipped_kinds = Vec::new();
+    if options.skip_unsupported {
+        for query in file.queries.iter().filter(|query| query.requires_join()) {
+            report.skipped += 1;
+            if let Some(spec) = query.spec() {
+                skipped_kinds.push(spec.kind);
+            }
+        }
+    }
+    if let Some(mix) = &file.mix {
+        // Kinds left out don't run, whatever their weight
+        let mut mix = mix.clone();
+        mix.0.retain(|(kind, _)| !skipped_kinds.contains(kind));
+        report.check_mix(&mix);
+    }
+    Ok(report)
+}
+
+// Run the queries of `state` on `options.concurrency` workers, at the
+// target rate of `options` if it has one. `Err` with the error that stopped
+// them.
+async fn run_phase<E: QueryExecutor + 'static>(
+    executor: Arc<E>,
+    state: RunState,
+    options: &RunnerOptions,
+) -> Result<QueryRunReport> {
+    let concurrency = options.concurrency.max(1);
+    let state = Arc::new(state);
+    let options = Arc::new(options.clone());
+    let start = Instant::now();
+    let open_loop = match options.target_qps {
+        Some(target_qps) => {
+            Some(run_open_loop(executor, state.clone(), options.clone(), target_qps).await?)
+        }
+        None => {
+            let mut workers = JoinSet::new();
+            for worker in 0..concurrency {
+                workers.spawn(run_worker(
+                    executor.clone(),
+                    state.clone(),
+                    options.clone(),
+                    worker,
+                ));
+            }
+            while let Some(result) = workers.join_next().await {
+                result.context(JoinTaskSnafu {
+                    task: "query worker",
+                })?;
+            }
+            None
+        }
+    };
+    if let Some(e) = state.stopped.lock().unwrap().take() {
+        return Err(e);
+    }
+    let mut report = std::mem::take(&mut *state.report.lock().unwrap());
+    report.elapsed = start.elapsed();
+    report.concurrency = concurrency;
+    report.open_loop = open_loop;
+    Ok(report)
+}
+
+// Take queries until the phase is over or the run stopped
+async fn run_worker<E: QueryExecutor>(
+    executor: Arc<E>,
+    state: Arc<RunState>,
+    options: Arc<RunnerOptions>,
+    worker: usize,
+) {
+    loop {
+        if state.stopped.lock().unwrap().is_some() {
+            return;
+        }
+        let Some((index, query)) = state.take() else {
+            return;
+        };
+        let started = Utc::now();
+        let timeout = options.timeout_of(&query);
+        let (outcome, _) = run_query(&*executor, worker, index, query, timeout).await;
+        record_outcome(&state, &options, started, outcome).await;
+    }
+}
+
+// Record `outcome` of a query started at `started` into the report and the
+// results CSV, and stop the run if it has to
+async fn record_outcome(
+    state: &RunState,
+    options: &RunnerOptions,
+    started: DateTime<Utc>,
+    outcome: QueryOutcome,
+) {
+    if let Some(results) = &state.results {
+        // Fails once the writer failed, the run returns its error
+        results.send(ResultRow::new(started, &outcome)).await.ok();
+    }
+    let over_budget = {
+        let mut report = state.report.lock().unwrap();
+        report.record(&outcome);
+        state.check_error_budget(&report)
+    };
+    if let Some(e) = over_budget {
+        state.stopped.lock().unwrap().get_or_insert(e);
+    }
+    if let Some(e) = outcome.error {
+        if options.stop_on_error {
+            state.stopped.lock().unwrap().get_or_insert(e);
+        }
+    }
+}
+
+// Schedule of an open-loop phase and what its queries share
+struct OpenLoop {
+    start: Instant,
+    start_time: DateTime<Utc>,
+    target_qps: f64,
+    // A permit per worker, held while a query runs
+    slots: Arc<Semaphore>,
+    // Ids of the workers without a query, one per free permit
+    idle_workers: Mutex<Vec<usize>>,
+    // Queries due that didn't get a worker yet
+    backlog: AtomicUsize,
+    late: AtomicUsize,
+}
+
+impl OpenLoop {
+    // When the query at `index` of the phase is due
+    fn due(&self, index: usize) -> (Instant, DateTime<Utc>) {
+        let offset = Duration::from_secs_f64(index as f64 / self.target_qps);
+        let offset_time = chrono::Duration::microseconds(offset.as_micros() as i64);
+        (self.start + offset, self.start_time + offset_time)
+    }
+}
+
+// Start the queries of `state` at `target_qps`, each one when it's due
+// whether the ones before it finished or not. A query waits for one of the
+// `options.concurrency` workers to be free, and is dropped when it's due with
+// `OPEN_LOOP_BACKLOG` of queries waiting already.
+async fn run_open_loop<E: QueryExecutor + 'static>(
+    executor: Arc<E>,
+    state: Arc<RunState>,
+    options: Arc<RunnerOptions>,
+    target_qps: f64,
+) -> Result<OpenLoopReport> {
+    let concurrency = options.concurrency.max(1);
+    let open_loop = Arc::new(OpenLoop {
+        start: Instant::now(),
+        start_time: Utc::now(),
+        target_qps,
+        slots: Arc::new(Semaphore::new(concurrency)),
+        idle_workers: Mutex::new((0..concurrency).rev().collect()),
+        backlog: AtomicUsize::new(0),
+        late: AtomicUsize::new(0),
+    });
+    let max_backlog = ((target_qps * OPEN_LOOP_BACKLOG.as_secs_f64()).ceil() as usize).max(1);
+    let mut dropped = 0;
+    let mut queries = JoinSet::new();
+    while state.stopped.lock().unwrap().is_none() {
+        let Some((index, query)) = state.take() else {
+            break;
+        };
+        let (due, _) = open_loop.due(index);
+        tokio::time::sleep_until(due.into()).await;
+        if open_loop.backlog.load(Ordering::SeqCst) >= max_backlog {
+            dropped += 1;
+            continue;
+        }
+        open_loop.backlog.fetch_add(1, Ordering::SeqCst);
+        queries.spawn(run_due_query(
+            executor.clone(),
+            state.clone(),
+            options.clone(),
+            open_loop.clone(),
+            index,
+            query,
+        ));
+    }
+    while let Some(result) = queries.join_next().await {
+        result.context(JoinTaskSnafu { task: "query" })?;
+    }
+    Ok(OpenLoopReport {
+        target_qps,
+        late: open_loop.late.load(Ordering::SeqCst),
+        dropped,
+    })
+}
+
+// Run the query at `index` of an open-loop phase once a worker is free, its
+// latency counted from when it was due
+async fn run_due_query<E: QueryExecutor>(
+    executor: Arc<E>,
+    state: Arc<RunState>,
+    options: Arc<RunnerOptions>,
+    open_loop: Arc<OpenLoop>,
+    index: usize,
+    query: FileQuery,
+) {
+    let (due, due_time) = open_loop.due(index);
+    let _slot = open_loop
+        .slots
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("worker slots closed");
+    open_loop.backlog.fetch_sub(1, Ordering::SeqCst);
+    if state.stopped.lock().unwrap().is_some() {
+        return;
+    }
+    if due.elapsed() > LATE_DISPATCH_SLACK {
+        open_loop.late.fetch_add(1, Ordering::SeqCst);
+    }
+    let worker = open_loop
+        .idle_workers
+        .lock()
+        .unwrap()
+        .pop()
+        .expect("an idle worker per free slot");
+    let timeout = options.timeout_of(&query);
+    let (mut outcome, _) = run_query(&*executor, worker, index, query, timeout).await;
+    outcome.latency = due.elapsed();
+    open_loop.idle_workers.lock().unwrap().push(worker);
+    record_outcome(&state, &options, due_time, outcome).await;
+}
+
+// Time `query`, given up on after `timeout` if there is one. A failure is
+// logged with the start of its SQL. The rows are returned along with the
+// outcome unless it failed.
+async fn run_query(
+    executor: &impl QueryExecutor,
+    worker: usize,
+    index: usize,
+    query: FileQuery,
+    timeout: Option<Duration>,
+) -> (QueryOutcome, Option<QueryRows>) {
+    let start = Instant::now();
+    let result = match timeout {
+        Some(timeout) => executor.run_with_timeout(query.sql(), timeout).await,
+        None => executor.run(query.sql()).await,
+    };
+    let latency = start.elapsed();
+    if let Err(e) = &result {
+        debug!(
+            "Query {} failed on worker {}: {}, SQL: {}",
+            index,
+            worker,
+            e,
+            statement_excerpt(query.sql())
+        );
+    }
+    let mismatch = match (
+        &result,
+        query.spec().and_then(|spec| spec.expected.as_ref()),
+    ) {
+        (Ok(rows), Some(expected)) => expected.check(rows).err(),
+        _ => None,
+    };
+    let (rows, error) = match result {
+        Ok(rows) => (Some(rows), None),
+        Err(e) => (None, Some(e)),
+    };
+    let outcome = QueryOutcome {
+        index,
+        worker,
+        query,
+        latency,
+        rows: rows.as_ref().map(|rows| rows.rows.len()),
+        error,
+        mismatch,
+    };
+    (outcome, rows)
+}
+
+// What the workers of a comparison share, the report of the left target is
+// the one of `run`
+struct CompareState {
+    run: RunState,
+    right: Mutex<QueryRunReport>,
+    comparison: Mutex<ComparisonReport>,
+}
+
+/// Run the queries of `file` on two targets and compare their results, see
+/// `CompareOptions::diff`. A worker runs each query on the left target and
+/// then on the right one, recording it into the report of either. Both
+/// reports span the whole run. Query timeouts apply, warmup, `stop_on_error`,
+/// the error budget, the target rate and the results CSV don't.
+pub async fn compare_queries<L, R>(
+    left: Arc<L>,
+    right: Arc<R>,
+    file: &QueryFile,
+    options: &RunnerOptions,
+    compare: CompareOptions,
+) -> ComparisonReport
+where
+    L: QueryExecutor + 'static,
+    R: QueryExecutor + 'static,
+{
+    let queries = options.select(file);
+    let count = queries.len();
+    let state = Arc::new(CompareState {
+        run: RunState::new(queries, Some(count), None),
+        right: Mutex::default(),
+        comparison: Mutex::default(),
+    });
+    let concurrency = options.concurrency.max(1);
+    let options = Arc::new(options.clone());
+    let start = Instant::now();
+    let mut workers = JoinSet::new();
+    for worker in 0..concurrency {
+        workers.spawn(compare_worker(
+            left.clone(),
+            right.clone(),
+            state.clone(),
+            options.clone(),
+            worker,
+            compare,
+        ));
+    }
+    while let Some(result) = workers.join_next().await {
+        result.expect("query worker panicked");
+    }
+    let elapsed = start.elapsed();
+    let mut comparison = std::mem::take(&mut *state.comparison.lock().unwrap());
+    comparison.left = std::mem::take(&mut *state.run.report.lock().unwrap());
+    comparison.right = std::mem::take(&mut *state.right.lock().unwrap());
+    for report in [&mut comparison.left, &mut comparison.right] {
+        report.elapsed = elapsed;
+        report.concurrency = concurrency;
+        report.query_seed = file.query_seed;
+    }
+    comparison
+}
+
+// Take queries until all of them ran on both targets
+async fn compare_worker<L: QueryExecutor, R: QueryExecutor>(
+    left: Arc<L>,
+    right: Arc<R>,
+    state: Arc<CompareState>,
+    options: Arc<RunnerOptions>,
+    worker: usize,
+    compare: CompareOptions,
+) {
+    while let Some((index, query)) = state.run.take() {
+        let timeout = options.timeout_of(&query);
+        let (left_outcome, left_rows) =
+            run_query(&*left, worker, index, query.clone(), timeout).await;
+        let (right_outcome, right_rows) =
+            run_query(&*right, worker, index, query.clone(), timeout).await;
+        let mismatch = compare_results(
+            &compare,
+            &query,
+            index,
+            result_of(&left_outcome, left_rows),
+            result_of(&right_outcome, right_rows),
+        );
+        state.run.report.lock().unwrap().record(&left_outcome);
+        state.right.lock().unwrap().record(&right_outcome);
+        if let Some(mismatch) = mismatch {
+            state.comparison.lock().unwrap().record(mismatch);
+        }
+    }
+}
+
+fn result_of(
+    outcome: &QueryOutcome,
+    rows: Option<QueryRows>,
+) -> std::result::Result<QueryRows, String> {
+    match (rows, &outcome.error) {
+        (Some(rows), _) => Ok(rows),
+        (None, error) => Err(error.as_ref().map(Error::to_string).unwrap_or_default()),
+    }
+}
+
+// The mismatch of the results of `query` on both targets, if any. `None` for
+// a query that failed on both, there is nothing to compare.
+fn compare_results(
+    compare: &CompareOptions,
+    query: &FileQuery,
+    index: usize,
+    left: std::result::Result<QueryRows, String>,
+    right: std::result::Result<QueryRows, String>,
+) -> Option<Option<ResultMismatch>> {
+    let diffs = match (&left, &right) {
+        (Ok(left), Ok(right)) => compare.diff(query.sql(), left, right),
+        (Err(_), Ok(_)) => vec!["failed on the left".to_string()],
+        (Ok(_), Err(_)) => vec!["failed on the right".to_string()],
+        (Err(_), Err(_)) => return None,
+    };
+    if diffs.is_empty() {
+        return Some(None);
+    }
+    Some(Some(ResultMismatch {
+        index,
+        name: query
+            .spec()
+            .map(|spec| spec.name.clone())
+            .unwrap_or_default(),
+        sql: query.sql().to_string(),
+        left,
+        right,
+        diffs,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::common::error::{Error, InvalidClientConfigSnafu};
+    use crate::common::rows::QueryValue;
+
+    // Answers every query with as many rows as the number in its SQL, after
+    // `delay`, and a second more for the queries containing "slow". Fails
+    // the queries containing "bad".
+    #[derive(Default)]
+    struct ScriptedExecutor {
+        delay: Duration,
+        ran: Mutex<Vec<String>>,
+    }
+
+    impl QueryExecutor for ScriptedExecutor {
+        async fn run(&self, sql: &str) -> Result<QueryRows> {
+            self.ran.lock().unwrap().push(sql.to_string());
+            tokio::time::sleep(self.delay).await;
+            if sql.contains("slow") {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            if sql.contains("bad") {
+                return InvalidClientConfigSnafu { msg: "bad query" }.fail();
+            }
+            let count = sql
+                .split_whitespace()
+                .find_map(|word| word.parse::<usize>().ok())
+                .unwrap_or_default();
+            Ok(QueryRows {
+                columns: vec!["n".to_string()],
+                rows: vec![vec![QueryValue::Int(1)]; count],
+            })
+        }
+    }
+
+    fn file(sqls: &[&str]) -> QueryFile {
+        QueryFile {
+            mix: None,
+            query_seed: None,
+            queries: sqls
+                .iter()
+                .map(|sql| FileQuery::Sql(sql.to_string()))
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_queries() {
+        let executor = Arc::new(ScriptedExecutor::default());
+        let mut file = file(&["SELECT 2", "SELECT bad", "SELECT 3"]);
+        file.query_seed = Some(7);
+        let report = run_queries(executor.clone(), &file, &RunnerOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.executed, 3);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.rows, 5);
+        assert_eq!(report.latency.count(), 3);
+        // The report echoes the seed the queries were generated with
+        assert_eq!(report.query_seed, Some(7));
+        assert_eq!(report.to_json()["query_seed"], 7);
+        assert!(report
+            .to_string()
+            .contains("\n  Queries generated with query seed 7\n"));
+
+        let options = RunnerOptions {
+            stop_on_error: true,
+            ..Default::default()
+        };
+        let err = run_queries(executor.clone(), &file, &options)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidClientConfig { .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(executor.ran.lock().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_query_timeouts() {
+        let executor = Arc::new(ScriptedExecutor::default());
+        let file = file(&["SELECT 1", "SELECT slow 2", "SELECT bad", "SELECT 3"]);
+        let options = RunnerOptions {
+            query_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let report = run_queries(executor.clone(), &file, &options)
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        // The slow query times out, the bad one fails with its error
+        assert_eq!(report.executed, 4);
+        assert_eq!(report.failed, 2);
+        assert_eq!(report.timeouts, 1);
+        assert_eq!(report.rows, 4);
+        assert_eq!(report.to_json()["timeouts"], 1);
+        assert_eq!(report.to_json()["errors"], 1);
+        assert!(report
+            .to_string()
+            .contains("\n  1 of the failed queries timed out, 1 failed with an error\n"));
+
+        let options = RunnerOptions {
+            stop_on_error: true,
+            ..options
+        };
+        let err = run_queries(executor, &file, &options).await.unwrap_err();
+        assert!(matches!(err, Error::QueryTimeout { .. }), "{:?}", err);
+        assert!(err.is_retryable());
+        assert!(err.to_string().contains("SQL: SELECT slow 2"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_open_loop() {
+        // Stalls the queries containing "stall", answers the others at once
+        struct StallingExecutor {
+            stall: Duration,
+            inner: ScriptedExecutor,
+        }
+
+        impl QueryExecutor for StallingExecutor {
+            async fn run(&self, sql: &str) -> Result<QueryRows> {
+                if sql.contains("stall") {
+                    tokio::time::sleep(self.stall).await;
+                }
+                self.inner.run(sql).await
+            }
+        }
+
+        let stalling = |millis| {
+            Arc::new(StallingExecutor {
+                stall: Duration::from_millis(millis),
+                inner: ScriptedExecutor::default(),
+            })
+        };
+        // A stalling query and `count - 1` quick ones
+        let stalled = |count| {
+            let mut sqls = vec!["SELECT stall 1".to_string()];
+            sqls.resize(count, "SELECT 1".to_string());
+            sqls
+        };
+        let sqls = stalled(10);
+        let file = file(&sqls.iter().map(String::as_str).collect::<Vec<_>>());
+        // Run one after the other, the queries after the stall wait for it
+        // without it showing in their latencies
+        let report = run_queries(stalling(200), &file, &RunnerOptions::default())
+            .await
+            .unwrap();
+        assert!(report.latency.summary().p50_ms < 50.0, "{}", report);
+        assert_eq!(report.open_loop, None);
+
+        // Due every 10ms, the queries after the stall queue up behind it and
+        // their latencies count the wait
+        let options = RunnerOptions {
+            target_qps: Some(100.0),
+            ..Default::default()
+        };
+        let report = run_queries(stalling(200), &file, &options).await.unwrap();
+        assert_eq!(report.executed, 10);
+        let latency = report.latency.summary();
+        assert!(latency.min_ms >= 100.0, "{:?}", latency);
+        assert!(latency.max_ms >= 200.0, "{:?}", latency);
+        let open_loop = report.open_loop.as_ref().unwrap();
+        assert_eq!(open_loop.target_qps, 100.0);
+        assert_eq!(open_loop.late, 9);
+        assert_eq!(open_loop.dropped, 0);
+        let json = report.to_json();
+        assert_eq!(json["open_loop"]["late"], 9);
+        assert!(json["open_loop"]["achieved_qps"].as_f64().unwrap() < 100.0);
+        assert!(report
+            .to_string()
+            .contains("\n  Open loop at 100.0 queries/s offered, "));
+
+        // A second's worth of queries waits at most, the ones due after
+        // them are dropped until the stall is over
+        let sqls = stalled(26);
+        let file = self::file(&sqls.iter().map(String::as_str).collect::<Vec<_>>());
+        let options = RunnerOptions {
+            target_qps: Some(20.0),
+            concurrency: 1,
+            ..Default::default()
+        };
+        let report = run_queries(stalling(1500), &file, &options).await.unwrap();
+        let open_loop = report.open_loop.as_ref().unwrap();
+        assert_eq!(open_loop.dropped, 5);
+        assert_eq!(open_loop.late, 20);
+        assert_eq!(report.executed, 21);
+    }
+
+    #[test]
+    fn test_kind_timeouts() {
+        use crate::generator::data_generator::{DataGeneratorConfig, UseCase};
+        use crate::generator::log_data_generator::LogConfig;
+        use crate::query::generator::{QueryConfig, QueryGenerator};
+
+        let config = DataGeneratorConfig::new(
+            60 * 1_000_000,
+            "2023-01-01T00:00:00Z".to_string(),
+            "2023-01-02T00:00:00Z".to_string(),
+            StdRng::seed_from_u64(0),
+            UseCase::Log,
+        );
+        let mut generator =
+            QueryGenerator::new(&config, &LogConfig::new(10, 10), QueryConfig::default()).unwrap();
+        let options = RunnerOptions {
+            query_timeout: Some(Duration::from_secs(5)),
+            kind_timeouts: vec![(QueryKind::TopPages, Duration::from_secs(30))],
+            ..Default::default()
+        };
+        for spec in generator.suite(1) {
+            let expected = match spec.kind {
+                QueryKind::TopPages => Duration::from_secs(30),
+                _ => Duration::from_secs(5),
+            };
+            let query = FileQuery::Generated {
+                seed_index: 0,
+                spec,
+            };
+            assert_eq!(options.timeout_of(&query), Some(expected));
+        }
+        let query = FileQuery::Sql("SELECT 1".to_string());
+        assert_eq!(options.timeout_of(&query), Some(Duration::from_secs(5)));
+        assert_eq!(RunnerOptions::default().timeout_of(&query), None);
+    }
+
+    #[tokio::test]
+    async fn test_error_budget() {
+        // Every tenth query fails
+        let sqls = (0..100)
+            .map(|i| {
+                if i % 10 == 9 {
+                    "SELECT bad".to_string()
+                } else {
+                    "SELECT 1".to_string()
+                }
+            })
+            .collect::<Vec<_>>();
+        let file = file(&sqls.iter().map(String::as_str).collect::<Vec<_>>());
+        // 5 failures of 100 queries are allowed, the sixth stops the run
+        let executor = Arc::new(ScriptedExecutor::default());
+        let options = RunnerOptions {
+            max_error_rate: Some(0.05),
+            ..Default::default()
+        };
+        let err = run_queries(executor.clone(), &file, &options)
+            .await
+            .unwrap_err();
+        match &err {
+            Error::ErrorBudgetExceeded {
+                failed, executed, ..
+            } => assert_eq!((*failed, *executed), (6, 60)),
+            e => panic!("{:?}", e),
+        }
+        assert_eq!(executor.ran.lock().unwrap().len(), 60);
+        assert!(!err.is_retryable());
+        assert_eq!(
+            err.to_string(),
+            "Stopped the run with 6 of 60 queries failed, over the error budget of 5.00%"
+        );
+
+        // Failures of the warmup don't count
+        let options = RunnerOptions {
+            max_error_rate: Some(0.1),
+            warmup: Warmup::Queries(100),
+            ..Default::default()
+        };
+        let report = run_queries(Arc::new(ScriptedExecutor::default()), &file, &options)
+            .await
+            .unwrap();
+        assert_eq!(report.executed, 100);
+        assert_eq!(report.failed, 10);
+    }
+
+    #[tokio::test]
+    async fn test_results_csv() {
+        use chrono::DateTime;
+
+        use crate::generator::data_generator::{DataGeneratorConfig, UseCase};
+        use crate::generator::log_data_generator::LogConfig;
+        use crate::query::generator::{QueryConfig, QueryGenerator, QueryKind};
+        use crate::query::results_csv::RESULTS_CSV_HEADER;
+
+        let config = DataGeneratorConfig::new(
+            60 * 1_000_000,
+            "2023-01-01T00:00:00Z".to_string(),
+            "2023-01-02T00:00:00Z".to_string(),
+            StdRng::seed_from_u64(0),
+            UseCase::Log,
+        );
+        let mut generator =
+            QueryGenerator::new(&config, &LogConfig::new(10, 10), QueryConfig::default()).unwrap();
+        let mut file = file(&["SELECT bad"]);
+        file.queries.extend(
+            generator
+                .suite(3)
+                .into_iter()
+                .enumerate()
+                .map(|(seed_index, spec)| FileQuery::Generated { seed_index, spec }),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.csv");
+        let options = RunnerOptions {
+            concurrency: 4,
+            warmup: Warmup::Queries(5),
+            results_csv: Some(path.clone()),
+            ..Default::default()
+        };
+        let report = run_queries(Arc::new(ScriptedExecutor::default()), &file, &options)
+            .await
+            .unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some(RESULTS_CSV_HEADER));
+        let rows = lines
+            .map(|line| line.split(',').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // A row per measured query, the warmup left out
+        assert_eq!(rows.len(), report.executed);
+        assert_eq!(rows.len(), 1 + 3 * QueryKind::ALL.len());
+        let mut failed = 0;
+        let mut returned = 0;
+        for row in &rows {
+            assert_eq!(row.len(), 7, "{:?}", row);
+            assert!(row[0].parse::<DateTime<Utc>>().is_ok(), "{:?}", row);
+            assert!(row[3].parse::<usize>().unwrap() < 4);
+            assert!(row[4].parse::<f64>().unwrap() >= 0.0);
+            match row[6] {
+                "true" => {
+                    failed += 1;
+                    assert_eq!(row[1..3], ["", ""]);
+                    assert_eq!(row[5], "");
+                }
+                "false" => {
+                    assert!(QueryKind::parse(row[1]).is_some(), "{:?}", row);
+                    assert!(row[2].parse::<u64>().unwrap() > 0);
+                    returned += row[5].parse::<u64>().unwrap();
+                }
+                flag => panic!("error flag {}", flag),
+            }
+        }
+        assert_eq!(failed, report.failed);
+        assert_eq!(returned, report.rows);
+    }
+
+    #[tokio::test]
+    async fn test_compare_queries() {
+        // Answers like `ScriptedExecutor`, with a row more for the queries
+        // containing a 3
+        #[derive(Default)]
+        struct DriftingExecutor(ScriptedExecutor);
+
+        impl QueryExecutor for DriftingExecutor {
+            async fn run(&self, sql: &str) -> Result<QueryRows> {
+                let mut rows = self.0.run(sql).await?;
+                if sql.contains('3') {
+                    rows.rows.push(vec![QueryValue::Int(1)]);
+                }
+                Ok(rows)
+            }
+        }
+
+        let file = file(&["SELECT 2", "SELECT bad", "SELECT 3", "SELECT 13"]);
+        let options = RunnerOptions {
+            concurrency: 2,
+            ..Default::default()
+        };
+        let comparison = compare_queries(
+            Arc::new(ScriptedExecutor::default()),
+            Arc::new(DriftingExecutor::default()),
+            &file,
+            &options,
+            CompareOptions::default(),
+        )
+        .await;
+        assert_eq!(comparison.left.executed, 4);
+        assert_eq!(comparison.left.failed, 1);
+        assert_eq!(comparison.right.executed, 4);
+        assert_eq!(comparison.right.rows, 2 + 4 + 14);
+        assert_eq!(comparison.right.concurrency, 2);
+        // The query failing on both isn't compared
+        assert_eq!(comparison.compared, 3);
+        assert_eq!(comparison.mismatch_count, 2);
+        let mut mismatches = comparison.mismatches.clone();
+        mismatches.sort_by_key(|mismatch| mismatch.index);
+        assert_eq!(mismatches[0].sql, "SELECT 3");
+        assert_eq!(mismatches[0].diffs, ["3 rows on the left, 4 on the right"]);
+        assert_eq!(mismatches[1].index, 3);
+        assert_eq!(mismatches[1].right.as_ref().unwrap().rows.len(), 14);
+    }
+
+    #[tokio::test]
+    async fn test_limit_and_shuffle() {
+        let sqls = (0..20).map(|i| format!("SELECT {}", i)).collect::<Vec<_>>();
+        let file = file(&sqls.iter().map(String::as_str).collect::<Vec<_>>());
+        let ran = |options: RunnerOptions| {
+            options
+                .select(&file)
+                .iter()
+                .map(|query| query.sql().to_string())
+                .collect::<Vec<_>>()
+        };
+        let limited = RunnerOptions {
+            limit: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(ran(limited.clone()), sqls[..5]);
+        let shuffled = RunnerOptions {
+            shuffle: Some(1),
+            ..limited
+        };
+        assert_eq!(ran(shuffled.clone()).len(), 5);
+        assert_ne!(ran(shuffled.clone()), sqls[..5]);
+        // The same seed gives the same order
+        assert_eq!(ran(shuffled.clone()), ran(shuffled));
+
+        let executor = Arc::new(ScriptedExecutor::default());
+        let options = RunnerOptions {
+            limit: Some(3),
+            ..Default::default()
+        };
+        let report = run_queries(executor.clone(), &file, &options)
+            .await
+            .unwrap();
+        assert_eq!(report.executed, 3);
+        assert_eq!(*executor.ran.lock().unwrap(), sqls[..3]);
+    }
+
+    #[tokio::test]
+    async fn test_skip_unsupported() {
+        let file = file(&[
+            "SELECT 1",
+            "SELECT 2 FROM web_logs w JOIN users u ON u.user_id = w.user_id",
+            "SELECT 'join' FROM `join`",
+            "SELECT 3 FROM web_logs w\nLEFT join pages p ON p.page_id = w.page_id",
+        ]);
+        let options = RunnerOptions {
+            skip_unsupported: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            options
+                .select(&file)
+                .iter()
+                .map(FileQuery::sql)
+                .collect::<Vec<_>>(),
+            ["SELECT 1", "SELECT 'join' FROM `join`"]
+        );
+        let report = run_queries(Arc::new(ScriptedExecutor::default()), &file, &options)
+            .await
+            .unwrap();
+        assert_eq!(report.executed, 2);
+        assert_eq!(report.skipped, 2);
+        assert!(report
+            .to_string()
+            .contains("Skipped 2 queries joining tables"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_workers() {
+        let executor = Arc::new(ScriptedExecutor {
+            delay: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let sqls = (0..40)
+            .map(|i| format!("SELECT {}", i % 3))
+            .collect::<Vec<_>>();
+        let file = file(&sqls.iter().map(String::as_str).collect::<Vec<_>>());
+        let options = RunnerOptions {
+            concurrency: 4,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let report = run_queries(executor.clone(), &file, &options)
+            .await
+            .unwrap();
+        // 40 queries of 10ms on 4 workers take about 100ms, not 400ms
+        assert!(
+            start.elapsed() < Duration::from_millis(300),
+            "{:?}",
+            start.elapsed()
+        );
+        assert_eq!(report.concurrency, 4);
+        assert_eq!(report.executed, 40);
+        assert_eq!(report.latency.count(), 40);
+        assert_eq!(report.rows, (0..40).map(|i| i % 3).sum::<u64>());
+        assert_eq!(report.workers.len(), 4);
+        assert_eq!(report.workers.iter().sum::<usize>(), 40);
+        assert!(
+            report.workers.iter().all(|&count| count >= 5),
+            "{:?}",
+            report.workers
+        );
+        // Every query ran exactly once
+        let mut ran = executor.ran.lock().unwrap().clone();
+        ran.sort();
+        let mut expected = sqls.clone();
+        expected.sort();
+        assert_eq!(ran, expected);
+
+        // A failure stops the other workers too
+        let executor = Arc::new(ScriptedExecutor {
+            delay: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let mut sqls = sqls;
+        sqls[0] = "SELECT bad".to_string();
+        let file = self::file(&sqls.iter().map(String::as_str).collect::<Vec<_>>());
+        let options = RunnerOptions {
+            stop_on_error: true,
+            ..options
+        };
+        assert!(run_queries(executor.clone(), &file, &options)
+            .await
+            .is_err());
+        assert!(executor.ran.lock().unwrap().len() < 10);
+    }
+
+    #[tokio::test]
+    async fn test_warmup() {
+        let executor = Arc::new(ScriptedExecutor::default());
+        let file = file(&["SELECT 1", "SELECT 2", "SELECT 3"]);
+        let options = RunnerOptions {
+            warmup: Warmup::Queries(5),
+            ..Default::default()
+        };
+        let report = run_queries(executor.clone(), &file, &options)
+            .await
+            .unwrap();
+        // The warmup goes round the queries, then the measured run starts
+        // from the first one
+        assert_eq!(
+            *executor.ran.lock().unwrap(),
+            [
+                "SELECT 1", "SELECT 2", "SELECT 3", "SELECT 1", "SELECT 2", "SELECT 1", "SELECT 2",
+                "SELECT 3"
+            ]
+        );
+        assert_eq!(report.executed, 3);
+        assert_eq!(report.rows, 6);
+        assert_eq!(report.latency.count(), 3);
+        let warmup = report.warmup.as_ref().unwrap();
+        assert_eq!(warmup.executed, 5);
+        assert_eq!(warmup.warmup, Warmup::Queries(5));
+        assert!(report.to_string().contains("Warmed up with 5 queries"));
+
+        let executor = Arc::new(ScriptedExecutor {
+            delay: Duration::from_millis(5),
+            ..Default::default()
+        });
+        let options = RunnerOptions {
+            concurrency: 2,
+            warmup: Warmup::Duration(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let report = run_queries(executor.clone(), &file, &options)
+            .await
+            .unwrap();
+        let warmup = report.warmup.as_ref().unwrap();
+        assert!(warmup.executed > 3, "{:?}", warmup);
+        assert_eq!(report.latency.count(), 3);
+        assert_eq!(report.workers.iter().sum::<usize>(), 3);
+        assert_eq!(
+            executor.ran.lock().unwrap().len(),
+            warmup.executed + report.executed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expected_results() {
+        use chrono::{DateTime, Utc};
+
+        use crate::query::expected::{ExpectedResult, ValueBounds};
+        use crate::query::generator::{QueryKind, QuerySpec, QueryWindow, WindowSize};
+        use crate::query::template::QueryParams;
+
+        let time = "2023-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let generated = |sql: &str, rows: usize| FileQuery::Generated {
+            seed_index: 0,
+            spec: QuerySpec {
+                name: "top_pages".to_string(),
+                kind: QueryKind::TopPages,
+                sql: sql.to_string(),
+                params: QueryParams {
+                    window: QueryWindow {
+                        start: time,
+                        end: time,
+                    },
+                    page_id: 0,
+                    user_id: 0,
+                    limit: 10,
+                    log_id: 0,
+                    bucket: Duration::ZERO,
+                },
+                window_size: WindowSize::Kind,
+                requires_join: false,
+                lookup_hit: None,
+                bucket_width: None,
+                expected: Some(ExpectedResult {
+                    rows,
+                    column: 0,
+                    values: vec![ValueBounds::exact(1.0)],
+                }),
+            },
+        };
+        let file = QueryFile {
+            mix: None,
+            query_seed: None,
+            queries: vec![
+                generated("SELECT 2", 2),
+                generated("SELECT 3", 2),
+                generated("SELECT bad", 1),
+                FileQuery::Sql("SELECT 4".to_string()),
+            ],
+        };
+        let report = run_queries(
+            Arc::new(ScriptedExecutor::default()),
+            &file,
+            &RunnerOptions::default(),
+        )
+        .await
+        .unwrap();
+        // Failed queries and queries without an expected result aren't
+        // checked, wrong results still count for the latencies
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.wrong_result_count, 1);
+        assert_eq!(
+            report.wrong_results,
+            ["query 1 top_pages: 3 rows, expected 2"]
+        );
+        assert_eq!(report.latency.count(), 4);
+        assert!(report
+            .to_string()
+            .contains("Checked 2 results, 1 wrong\n  Wrong result of query 1 top_pages"));
+    }
+
+    #[test]
+    fn test_runner_violations() {
+        let connector_options = ConnectorOptions {
+            max_connections: 4,
+            ..Default::default()
+        };
+        let options = RunnerOptions {
+            concurrency: 8,
+            ..Default::default()
+        };
+        assert_eq!(
+            options.violations(&connector_options),
+            ["concurrency 8 needs as many connections, max_connections is 4"]
+        );
+        let options = RunnerOptions {
+            concurrency: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            options.violations(&connector_options),
+            ["concurrency must be at least 1"]
+        );
+        let options = RunnerOptions {
+            query_timeout: Some(Duration::ZERO),
+            max_error_rate: Some(1.5),
+            target_qps: Some(0.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.violations(&connector_options),
+            [
+                "query timeouts must be longer than 0",
+                "target_qps must be more than 0, got 0",
+                "max_error_rate must be between 0 and 1, got 1.5"
+            ]
+        );
+        assert!(RunnerOptions::default()
+            .validate(&connector_options)
+            .is_ok());
+    }
+
+    // Runs the built-in suite against GreptimeDB's MySQL endpoint, with the
+    // log dataset of the default time range loaded
+    #[cfg(feature = "integration")]
+    #[tokio::test]
+    async fn test_run_builtin_suite() {
+        use crate::generator::data_generator::{DataGeneratorConfig, UseCase};
+        use crate::generator::log_data_generator::LogConfig;
+        use crate::query::generator::{QueryConfig, QueryGenerator, QueryKind};
+
+        let config = DataGeneratorConfig::new(
+            60 * 1_000_000,
+            "2021-01-01T00:00:00Z".to_string(),
+            "2021-01-02T00:00:00Z".to_string(),
+            StdRng::seed_from_u64(0),
+            UseCase::Log,
+        );
+        let mut generator =
+            QueryGenerator::new(&config, &LogConfig::new(1000, 100), QueryConfig::default())
+                .unwrap();
+        let file = QueryFile {
+            mix: None,
+            query_seed: None,
+            queries: generator
+                .suite(2)
+                .into_iter()
+                .enumerate()
+                .map(|(seed_index, spec)| FileQuery::Generated { seed_index, spec })
+                .collect(),
+        };
+        let connector = Connector::new("mysql://127.0.0.1:4002/public")
+            .await
+            .unwrap();
+        let report = run_queries(Arc::new(connector), &file, &RunnerOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.executed, file.queries.len());
+        assert_eq!(report.failed, 0, "{}", report);
+        assert_eq!(report.kinds.len(), QueryKind::ALL.len());
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use snafu::{ensure, OptionExt, ResultExt};
+
+use crate::common::error::{InvalidQueryTemplateSnafu, ReadFileSnafu, Result};
+use crate::query::generator::{time_literal, QueryKind, QueryWindow};
+
+/// Values a template can refer to as `{{name}}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    TimeStart,
+    TimeEnd,
+    PageId,
+    UserId,
+    Limit,
+    LogId,
+    Bucket,
+}
+
+impl Placeholder {
+    pub const ALL: [Placeholder; 7] = [
+        Placeholder::TimeStart,
+        Placeholder::TimeEnd,
+        Placeholder::PageId,
+        Placeholder::UserId,
+        Placeholder::Limit,
+        Placeholder::LogId,
+        Placeholder::Bucket,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Placeholder::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placeholder::TimeStart => "time_start",
+            Placeholder::TimeEnd => "time_end",
+            Placeholder::PageId => "page_id",
+            Placeholder::UserId => "user_id",
+            Placeholder::Limit => "limit",
+            Placeholder::LogId => "log_id",
+            Placeholder::Bucket => "bucket",
+        }
+    }
+}
+
+impl fmt::Display for Placeholder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Values of the placeholders of one query
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryParams {
+    pub window: QueryWindow,
+    pub page_id: i32,
+    pub user_id: i32,
+    pub limit: usize,
+    // Beyond the generated logs for a lookup meant to find nothing
+    pub log_id: i32,
+    // Width of the buckets of a query grouping by time
+    pub bucket: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    // Whether the placeholder is inside a string literal of the template
+    Placeholder(Placeholder, bool),
+}
+
+/// SQL with `{{name}}` placeholders, see `Placeholder`. Times are written as
+/// string literals, unless the placeholder is inside one already, where the
+/// value is written as it is with its quotes doubled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryTemplate {
+    pub name: String,
+    pub kind: QueryKind,
+    parts: Vec<Part>,
+}
+
+impl QueryTemplate {
+    /// Fails on a placeholder that isn't one of `Placeholder::ALL`
+    pub fn parse(name: &str, kind: QueryKind, text: &str) -> Result<Self> {
+        let invalid = |msg: String| InvalidQueryTemplateSnafu {
+            template: name,
+            msg,
+        };
+        let mut parts = Vec::new();
+        let mut in_literal = false;
+        let mut rest = text.trim().trim_end_matches(';').trim_end();
+        while let Some(open) = rest.find("{{") {
+            let text = &rest[..open];
+            in_literal ^= text.matches('\'').count() % 2 == 1;
+            parts.push(Part::Text(text.to_string()));
+            let close = rest[open..]
+                .find("}}")
+                .context(invalid("unclosed {{".to_string()))?;
+            let placeholder_name = rest[open + 2..open + close].trim();
+            let placeholder = Placeholder::parse(placeholder_name).context(invalid(format!(
+                "unknown placeholder {{{{{}}}}}, supported are {}",
+                placeholder_name,
+                Placeholder::ALL.map(|p| p.name()).join(", ")
+            )))?;
+            parts.push(Part::Placeholder(placeholder, in_literal));
+            rest = &rest[open + close + 2..];
+        }
+        parts.push(Part::Text(rest.to_string()));
+        ensure!(
+            !(in_literal ^ (rest.matches('\'').count() % 2 == 1)),
+            invalid("unterminated string literal".to_string())
+        );
+        Ok(QueryTemplate {
+            name: name.to_string(),
+            kind,
+            parts,
+        })
+    }
+
+    /// The placeholders the template refers to, in order
+    pub fn placeholders(&self) -> impl Iterator<Item = Placeholder> + '_ {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Placeholder(placeholder, _) => Some(*placeholder),
+            Part::Text(_) => None,
+        })
+    }
+
+    pub fn render(&self, params: &QueryParams) -> String {
+        let mut sql = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => sql.push_str(text),
+                Part::Placeholder(placeholder, in_literal) => {
+                    let time = match placeholder {
+                        Placeholder::TimeStart => Some(&params.window.start),
+                        Placeholder::TimeEnd => Some(&params.window.end),
+                        _ => None,
+                    };
+                    let value = match (placeholder, time) {
+                        (_, Some(time)) => time_literal(time),
+                        (Placeholder::PageId, _) => params.page_id.to_string(),
+                        (Placeholder::UserId, _) => params.user_id.to_string(),
+                        (Placeholder::LogId, _) => params.log_id.to_string(),
+                        (Placeholder::Bucket, _) => duration_literal(params.bucket),
+                        (_, _) => params.limit.to_string(),
+                    };
+                    if *in_literal {
+                        // The value goes into the template's literal,
+                        // without quotes of its own
+                        let value = value.trim_matches('\'');
+                        sql.push_str(&value.replace('\'', "''"));
+                    } else {
+                        sql.push_str(&value);
+                    }
+                }
+            }
+        }
+        sql
+    }
+}
+
+// Duration literal of GreptimeDB's range queries and intervals, in seconds or
+// milliseconds when it isn't whole seconds, e.g. '90s'
+fn duration_literal(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis % 1000 == 0 {
+        format!("'{}s'", millis / 1000)
+    } else {
+        format!("'{}ms'", millis)
+    }
+}
+
+/// Templates of a file, each one introduced by a `-- name: <name>` line. Text
+/// before the first of them is a template named after the file. Other lines
+/// starting with `--` are comments and left out.
+pub fn load_templates(path: &Path) -> Result<Vec<QueryTemplate>> {
+    let text = std::fs::read_to_string(path).context(ReadFileSnafu {
+        path: path.display().to_string(),
+    })?;
+    let file_name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut sections = vec![(file_name, String::new())];
+    for line in text.lines() {
+        match line.trim().strip_prefix("-- name:") {
+            Some(name) => sections.push((name.trim().to_string(), String::new())),
+            None if line.trim().starts_with("--") => {}
+            None => {
+                let body = &mut sections.last_mut().unwrap().1;
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+    }
+    sections
+        .into_iter()
+        .filter(|(_, body)| !body.trim().is_empty())
+        .map(|(name, body)| QueryTemplate::parse(&name, QueryKind::Custom, &body))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::common::error::Error;
+
+    fn params() -> QueryParams {
+        QueryParams {
+            window: QueryWindow {
+                start: "2023-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap(),
+                end: "2023-01-01T01:00:00Z".parse::<DateTime<Utc>>().unwrap(),
+            },
+            page_id: 3,
+            user_id: 7,
+            limit: 20,
+            log_id: 42,
+            bucket: Duration::from_millis(1500),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let template = QueryTemplate::parse(
+            "visits",
+            QueryKind::Custom,
+            "SELECT * FROM web_logs WHERE user_id = {{user_id}} AND page_id = {{ page_id }} \
+             AND log_id > {{log_id}} \
+             AND `timestamp` >= {{time_start}} AND `timestamp` < {{time_end}} LIMIT {{limit}};",
+        )
+        .unwrap();
+        assert_eq!(
+            template.placeholders().collect::<Vec<_>>(),
+            [
+                Placeholder::UserId,
+                Placeholder::PageId,
+                Placeholder::LogId,
+                Placeholder::TimeStart,
+                Placeholder::TimeEnd,
+                Placeholder::Limit
+            ]
+        );
+        assert_eq!(
+            template.render(&params()),
+            "SELECT * FROM web_logs WHERE user_id = 7 AND page_id = 3 AND log_id > 42 \
+             AND `timestamp` >= '2023-01-01T00:00:00Z' AND `timestamp` < '2023-01-01T01:00:00Z' \
+             LIMIT 20"
+        );
+    }
+
+    #[test]
+    fn test_render_in_literal() {
+        // Inside a literal the value is spliced in without quotes of its own,
+        // a doubled quote before the placeholder doesn't end the literal
+        let template = QueryTemplate::parse(
+            "urls",
+            QueryKind::Custom,
+            "SELECT '{{time_start}}' AS since, 'it''s page {{page_id}}' AS label, \
+             '{{ time_end }}''' AS quoted, INTERVAL '{{bucket}}' AS bucket, {{bucket}} AS width \
+             FROM pages WHERE page_id = {{page_id}}",
+        )
+        .unwrap();
+        assert_eq!(
+            template.render(&params()),
+            "SELECT '2023-01-01T00:00:00Z' AS since, 'it''s page 3' AS label, \
+             '2023-01-01T01:00:00Z''' AS quoted, INTERVAL '1500ms' AS bucket, '1500ms' AS width \
+             FROM pages WHERE page_id = 3"
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for (text, msg) in [
+            (
+                "SELECT * FROM error_logs WHERE trace_id = {{trace_id}}",
+                "unknown placeholder {{trace_id}}, supported are time_start, time_end, page_id, \
+                 user_id, limit, log_id, bucket",
+            ),
+            ("SELECT {{limit", "unclosed {{"),
+            ("SELECT 'a {{limit}}", "unterminated string literal"),
+        ] {
+            let err = QueryTemplate::parse("bad", QueryKind::Custom, text).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidQueryTemplate { .. }),
+                "{:?}",
+                err
+            );
+            assert_eq!(
+                err.to_string(),
+                format!("Invalid query template bad, {}", msg)
+            );
+        }
+    }
+
+    #[test]
+    fn test_load_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mine.sql");
+        std::fs::write(
+            &path,
+            "SELECT COUNT(*) FROM web_logs;\n\
+             -- name: by_user\n\
+             -- visits of one user\n\
+             SELECT * FROM web_logs\n  WHERE user_id = {{user_id}}\n\n\
+             -- name: empty\n",
+        )
+        .unwrap();
+        let templates = load_templates(&path).unwrap();
+        assert_eq!(
+            templates
+                .iter()
+                .map(|t| t.name.as_str())
+                .collect::<Vec<_>>(),
+            ["mine", "by_user"]
+        );
+        assert_eq!(
+            templates[1].render(&params()),
+            "SELECT * FROM web_logs\n  WHERE user_id = 7"
+        );
+    }
+}
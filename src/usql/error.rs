@@ -0,0 +1,178 @@
+use std::fmt;
+
+// Characters of a statement kept in errors, generated INSERT statements run
+// to megabytes
+const STATEMENT_EXCERPT_CHARS: usize = 200;
+
+/// What made usql fail a statement, told from what it printed to stderr.
+/// Carried by `Error::UsqlExecution`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsqlError {
+    // The database couldn't be reached
+    ConnectionRefused,
+    // The credentials or the privileges of the user were rejected
+    AccessDenied,
+    // The statement doesn't parse, `near` where the server stopped parsing
+    Syntax { near: Option<String> },
+    // usql exited or was never running
+    Exited,
+    // The statement failed otherwise, e.g. on a missing table
+    Statement,
+}
+
+impl UsqlError {
+    pub fn classify(stderr: &str) -> Self {
+        let lower = stderr.to_lowercase();
+        let any = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+        if lower.starts_with("usql is not running") {
+            UsqlError::Exited
+        } else if any(&[
+            "connection refused",
+            "no such host",
+            "i/o timeout",
+            "can't connect",
+            "could not connect",
+            "network is unreachable",
+        ]) {
+            UsqlError::ConnectionRefused
+        } else if any(&[
+            "access denied",
+            "authentication failed",
+            "permission denied",
+            "not authorized",
+            "unauthorized",
+        ]) {
+            UsqlError::AccessDenied
+        } else if any(&[
+            "syntax error",
+            "error in your sql syntax",
+            "sql parser error",
+        ]) {
+            UsqlError::Syntax {
+                near: syntax_error_position(stderr),
+            }
+        } else {
+            UsqlError::Statement
+        }
+    }
+}
+
+impl fmt::Display for UsqlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UsqlError::ConnectionRefused => f.write_str("connection refused"),
+            UsqlError::AccessDenied => f.write_str("access denied"),
+            UsqlError::Syntax { near: Some(near) } => write!(f, "syntax error near `{}`", near),
+            UsqlError::Syntax { near: None } => f.write_str("syntax error"),
+            UsqlError::Exited => f.write_str("usql exited"),
+            UsqlError::Statement => f.write_str("statement failed"),
+        }
+    }
+}
+
+// Quoted text after `near`, as printed by MySQL and Postgres, or the token
+// after `found:` of GreptimeDB's parser
+fn syntax_error_position(stderr: &str) -> Option<String> {
+    if let Some((_, rest)) = stderr.split_once("near ") {
+        let quote = rest.chars().next().filter(|c| matches!(c, '\'' | '"'))?;
+        let rest = &rest[quote.len_utf8()..];
+        return rest.find(quote).map(|end| rest[..end].to_string());
+    }
+    let (_, rest) = stderr.split_once("found: ")?;
+    let token = rest
+        .split(|c: char| c.is_whitespace())
+        .next()
+        .filter(|token| !token.is_empty())?;
+    Some(token.to_string())
+}
+
+/// The start of `sql` as reported in errors, truncated on a char boundary
+pub fn statement_excerpt(sql: &str) -> String {
+    let sql = sql.trim();
+    match sql.char_indices().nth(STATEMENT_EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}...", &sql[..end]),
+        None => sql.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            (
+                "error: dial tcp 127.0.0.1:4002: connect: connection refused",
+                UsqlError::ConnectionRefused,
+            ),
+            (
+                "error: dial tcp: lookup greptime: no such host",
+                UsqlError::ConnectionRefused,
+            ),
+            (
+                "error: mysql: 1045: Access denied for user 'bench'@'10.0.0.2' (using password: YES)",
+                UsqlError::AccessDenied,
+            ),
+            (
+                "error: pq: password authentication failed for user \"bench\"",
+                UsqlError::AccessDenied,
+            ),
+            (
+                "error: mysql: 1064: You have an error in your SQL syntax; check the manual that \
+                 corresponds to your MySQL server version for the right syntax to use near \
+                 'SELEC 1' at line 1",
+                UsqlError::Syntax {
+                    near: Some("SELEC 1".to_string()),
+                },
+            ),
+            (
+                "error: pq: syntax error at or near \"SELEC\"",
+                UsqlError::Syntax {
+                    near: Some("SELEC".to_string()),
+                },
+            ),
+            (
+                "error: mysql: 1815: Failed to parse query: SELEC 1, sql parser error: \
+                 Expected: an SQL statement, found: SELEC at Line: 1, Column: 1",
+                UsqlError::Syntax {
+                    near: Some("SELEC".to_string()),
+                },
+            ),
+            (
+                "error: syntax error",
+                UsqlError::Syntax { near: None },
+            ),
+            (
+                "usql is not running (output closed, exit status: 1): ",
+                UsqlError::Exited,
+            ),
+            (
+                "error: mysql: 4001: Table not found: greptime.public.cpu",
+                UsqlError::Statement,
+            ),
+        ];
+        for (stderr, expected) in cases {
+            assert_eq!(UsqlError::classify(stderr), expected, "{}", stderr);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let syntax = UsqlError::Syntax {
+            near: Some("SELEC".to_string()),
+        };
+        assert_eq!(syntax.to_string(), "syntax error near `SELEC`");
+        assert_eq!(UsqlError::AccessDenied.to_string(), "access denied");
+    }
+
+    #[test]
+    fn test_statement_excerpt() {
+        assert_eq!(statement_excerpt("  SELECT 1;\n"), "SELECT 1;");
+        let insert = format!("INSERT INTO t VALUES {}", "('é'),".repeat(100));
+        let excerpt = statement_excerpt(&insert);
+        assert_eq!(excerpt.chars().count(), STATEMENT_EXCERPT_CHARS + 3);
+        assert!(excerpt.starts_with("INSERT INTO t VALUES ('é')"));
+        assert!(excerpt.ends_with("..."));
+    }
+}
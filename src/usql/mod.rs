@@ -1 +1,4 @@
+pub mod error;
+pub mod output;
+pub mod script;
 pub mod usql;
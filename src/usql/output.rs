@@ -0,0 +1,325 @@
+use std::fmt;
+
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use serde_json::Value;
+use snafu::{OptionExt, ResultExt};
+
+use crate::common::error::{Result, UsqlJsonOutputSnafu, UsqlOutputSnafu, UsqlOutputValueSnafu};
+
+/// Format usql prints the results of queries in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    // Result sets separated by an empty line, each one a header and its rows
+    #[default]
+    Csv,
+    // One array of row objects per result set
+    Json,
+}
+
+impl OutputFormat {
+    /// usql flag selecting the format
+    pub fn flag(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "--csv",
+            OutputFormat::Json => "--json",
+        }
+    }
+}
+
+/// Result set of a query run through usql, values as usql printed them and
+/// nulls as empty strings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl QueryResult {
+    /// The last result set of `output`, the one of the last statement that
+    /// returned rows. Output without any result set is an empty result.
+    pub fn parse(format: OutputFormat, output: &str) -> Result<Self> {
+        let result_sets = match format {
+            OutputFormat::Csv => parse_csv(output)?,
+            OutputFormat::Json => parse_json(output)?,
+        };
+        Ok(result_sets.into_iter().last().unwrap_or_default())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Index of the column `name`
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column == name)
+    }
+
+    /// The only value of a result of one row and one column, e.g. a count
+    pub fn single_value(&self) -> Result<&str> {
+        match self.rows.as_slice() {
+            [row] if row.len() == 1 => Ok(&row[0]),
+            _ => UsqlOutputSnafu {
+                msg: format!(
+                    "expected a single value, got {} rows of {} columns",
+                    self.rows.len(),
+                    self.columns.len()
+                ),
+            }
+            .fail(),
+        }
+    }
+
+    pub fn single_i64(&self) -> Result<i64> {
+        let value = self.single_value()?;
+        value.trim().parse().ok().context(UsqlOutputValueSnafu {
+            value,
+            expected: "an integer",
+        })
+    }
+
+    pub fn single_f64(&self) -> Result<f64> {
+        let value = self.single_value()?;
+        value.trim().parse().ok().context(UsqlOutputValueSnafu {
+            value,
+            expected: "a number",
+        })
+    }
+}
+
+/// Rows a DML statement affected as usql reports it, e.g. `INSERT 3`, or
+/// `INSERT 0 3` for Postgres
+pub fn affected_rows_hint(output: &str) -> Option<usize> {
+    output.lines().rev().find_map(|line| {
+        let mut tokens = line.split_whitespace();
+        match tokens.next()? {
+            "INSERT" | "UPDATE" | "DELETE" => tokens.last()?.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+// Result sets of RFC 4180 CSV, separated by empty lines outside quotes. A
+// row of a single empty value reads as such a separator.
+fn parse_csv(output: &str) -> Result<Vec<QueryResult>> {
+    let mut result_sets = Vec::new();
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    // Whether the record has a field yet, tells an empty line apart
+    let mut in_record = false;
+    let mut quoted = false;
+    let mut chars = output.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => {
+                quoted = true;
+                in_record = true;
+            }
+            (false, ',') => {
+                record.push(std::mem::take(&mut field));
+                in_record = true;
+            }
+            (false, '\r') => {}
+            (false, '\n') if !in_record && field.is_empty() => {
+                if !records.is_empty() {
+                    result_sets.push(csv_result_set(std::mem::take(&mut records)));
+                }
+            }
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+                in_record = false;
+            }
+            (false, c) => {
+                field.push(c);
+                in_record = true;
+            }
+        }
+    }
+    if quoted {
+        return UsqlOutputSnafu {
+            msg: "unterminated quoted CSV field",
+        }
+        .fail();
+    }
+    if in_record || !field.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    if !records.is_empty() {
+        result_sets.push(csv_result_set(records));
+    }
+    Ok(result_sets)
+}
+
+fn csv_result_set(mut records: Vec<Vec<String>>) -> QueryResult {
+    let columns = records.remove(0);
+    QueryResult {
+        columns,
+        rows: records,
+    }
+}
+
+// Result sets of concatenated JSON arrays of row objects. An empty array
+// has no columns to tell.
+fn parse_json(output: &str) -> Result<Vec<QueryResult>> {
+    serde_json::Deserializer::from_str(output)
+        .into_iter::<Vec<OrderedRow>>()
+        .map(|rows| {
+            let rows = rows.context(UsqlJsonOutputSnafu {})?;
+            let columns = rows
+                .first()
+                .map(|row| row.0.iter().map(|(column, _)| column.clone()).collect())
+                .unwrap_or_default();
+            let rows = rows
+                .into_iter()
+                .map(|row| {
+                    row.0
+                        .into_iter()
+                        .map(|(_, value)| json_text(value))
+                        .collect()
+                })
+                .collect();
+            Ok(QueryResult { columns, rows })
+        })
+        .collect()
+}
+
+fn json_text(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        value => value.to_string(),
+    }
+}
+
+// Columns of a JSON row in the order usql printed them
+struct OrderedRow(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for OrderedRow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct RowVisitor;
+
+        impl<'de> Visitor<'de> for RowVisitor {
+            type Value = OrderedRow;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a row object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<OrderedRow, A::Error> {
+                let mut columns = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    columns.push(entry);
+                }
+                Ok(OrderedRow(columns))
+            }
+        }
+
+        deserializer.deserialize_map(RowVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        // Captured from `usql --csv`, a statement without rows prints nothing
+        let output = "host,cpu,note\n\
+                      a,1.5,\"says \"\"hi\"\", twice\"\n\
+                      b,,\"two\nlines\"\n";
+        let result = QueryResult::parse(OutputFormat::Csv, output).unwrap();
+        assert_eq!(result.columns, strings(&["host", "cpu", "note"]));
+        assert_eq!(
+            result.rows,
+            vec![
+                strings(&["a", "1.5", "says \"hi\", twice"]),
+                strings(&["b", "", "two\nlines"]),
+            ]
+        );
+        assert_eq!(result.column("cpu"), Some(1));
+    }
+
+    #[test]
+    fn test_parse_csv_statements() {
+        let output = "count(*)\n3\n\nhost\na\nb\n";
+        let result = QueryResult::parse(OutputFormat::Csv, output).unwrap();
+        assert_eq!(result.columns, strings(&["host"]));
+        assert_eq!(result.rows.len(), 2);
+
+        let count = QueryResult::parse(OutputFormat::Csv, "count(*)\n3\n").unwrap();
+        assert_eq!(count.single_i64().unwrap(), 3);
+
+        // A header alone is an empty result set, no output is no result set
+        let empty = QueryResult::parse(OutputFormat::Csv, "host,cpu\n").unwrap();
+        assert_eq!(empty.columns, strings(&["host", "cpu"]));
+        assert!(empty.is_empty());
+        assert_eq!(
+            QueryResult::parse(OutputFormat::Csv, "").unwrap(),
+            QueryResult::default()
+        );
+        assert!(QueryResult::parse(OutputFormat::Csv, "a\n\"open\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        // Captured from `usql --json`, one array per statement
+        let output = r#"[{"count(*)":2}]
+[{"ts":"2024-01-01 00:00:00","host":"a","cpu":1.5,"note":null},
+ {"ts":"2024-01-01 00:00:01","host":"b","cpu":2,"note":"x"}]
+"#;
+        let result = QueryResult::parse(OutputFormat::Json, output).unwrap();
+        assert_eq!(result.columns, strings(&["ts", "host", "cpu", "note"]));
+        assert_eq!(
+            result.rows,
+            vec![
+                strings(&["2024-01-01 00:00:00", "a", "1.5", ""]),
+                strings(&["2024-01-01 00:00:01", "b", "2", "x"]),
+            ]
+        );
+
+        let count = QueryResult::parse(OutputFormat::Json, r#"[{"count(*)":42}]"#).unwrap();
+        assert_eq!(count.single_i64().unwrap(), 42);
+        assert_eq!(count.single_f64().unwrap(), 42.0);
+        let empty = QueryResult::parse(OutputFormat::Json, "[]\n").unwrap();
+        assert!(empty.is_empty());
+        assert!(empty.single_i64().is_err());
+        assert!(QueryResult::parse(OutputFormat::Json, "[{").is_err());
+    }
+
+    #[test]
+    fn test_affected_rows_hint() {
+        assert_eq!(affected_rows_hint("INSERT 3\n"), Some(3));
+        assert_eq!(affected_rows_hint("INSERT 0 25\n"), Some(25));
+        assert_eq!(affected_rows_hint("DELETE 0\n"), Some(0));
+        assert_eq!(affected_rows_hint("count(*)\n3\n"), None);
+        assert_eq!(affected_rows_hint(""), None);
+    }
+
+    #[test]
+    fn test_single_value_errors() {
+        let result = QueryResult::parse(OutputFormat::Csv, "host\na\n").unwrap();
+        let err = result.single_i64().unwrap_err();
+        assert!(err.to_string().contains("\"a\""), "{}", err);
+        let result = QueryResult::parse(OutputFormat::Csv, "a,b\n1,2\n").unwrap();
+        assert!(result.single_value().is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use std::{fmt, time::Duration};
+
+use crate::common::error::{Error, Result};
+
+// Characters of a statement shown in a script report
+const STATEMENT_SUMMARY_CHARS: usize = 80;
+
+/// A statement of a script run by `Usql::execute_script`
+#[derive(Debug)]
+pub struct StatementReport {
+    // Position in the script, from 0
+    pub index: usize,
+    // The first characters of the statement
+    pub summary: String,
+    pub elapsed: Duration,
+    // What usql printed
+    pub result: Result<String>,
+}
+
+/// Statements of a script in order. A script stops at the first statement
+/// that fails, the ones after it aren't run.
+#[derive(Debug, Default)]
+pub struct ScriptReport {
+    pub statements: Vec<StatementReport>,
+    // Statements of the script, run or not
+    pub total: usize,
+}
+
+impl ScriptReport {
+    pub fn succeeded(&self) -> bool {
+        self.statements.iter().all(|s| s.result.is_ok())
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.statements.iter().map(|s| s.elapsed).sum()
+    }
+
+    /// Error of the statement that stopped the script
+    pub fn into_error(self) -> Option<Error> {
+        self.statements.into_iter().find_map(|s| s.result.err())
+    }
+}
+
+impl fmt::Display for ScriptReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for statement in &self.statements {
+            write!(
+                f,
+                "  [{}] {} in {:.3}s",
+                statement.index,
+                statement.summary,
+                statement.elapsed.as_secs_f64()
+            )?;
+            if let Err(e) = &statement.result {
+                write!(f, ", failed: {}", e)?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "Total: {} of {} statements in {:.3}s",
+            self.statements.iter().filter(|s| s.result.is_ok()).count(),
+            self.total,
+            self.elapsed().as_secs_f64()
+        )
+    }
+}
+
+/// The first characters of `statement` on one line
+pub(crate) fn summary(statement: &str) -> String {
+    let line = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(STATEMENT_SUMMARY_CHARS) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line,
+    }
+}
+
+/// Split a SQL script into its statements, without their terminating
+/// semicolons. Semicolons in quoted strings and identifiers and in comments
+/// don't end a statement, `--` and `/* */` comments are dropped.
+pub fn split_statements(script: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut statement = String::new();
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                statement.push(c);
+                // A doubled quote is an escaped one and reopens the quote
+                while let Some(q) = chars.next() {
+                    statement.push(q);
+                    if q == c && chars.peek() != Some(&c) {
+                        break;
+                    }
+                    if q == c {
+                        statement.push(chars.next().unwrap_or(c));
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        statement.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                statement.push(' ');
+            }
+            ';' => push_statement(&mut statements, &mut statement),
+            c => statement.push(c),
+        }
+    }
+    push_statement(&mut statements, &mut statement);
+    statements
+}
+
+fn push_statement(statements: &mut Vec<String>, statement: &mut String) {
+    let trimmed = statement.trim();
+    if !trimmed.is_empty() {
+        statements.push(trimmed.to_string());
+    }
+    statement.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_statements() {
+        let script = "-- schema; of the benchmark\n\
+                      CREATE TABLE t (a STRING, ts TIMESTAMP TIME INDEX);\n\
+                      \n\
+                      INSERT INTO t VALUES ('a;b', 1), ('it''s; fine', 2);\n\
+                      /* seed; data */ SELECT \"x;y\", `a;b` FROM t -- trailing; comment\n\
+                      ;;\n\
+                      SELECT 1";
+        assert_eq!(
+            split_statements(script),
+            vec![
+                "CREATE TABLE t (a STRING, ts TIMESTAMP TIME INDEX)",
+                "INSERT INTO t VALUES ('a;b', 1), ('it''s; fine', 2)",
+                "SELECT \"x;y\", `a;b` FROM t",
+                "SELECT 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_statements_edge_cases() {
+        assert!(split_statements("").is_empty());
+        assert!(split_statements(" -- only a comment;\n;").is_empty());
+        // Dashes inside a string aren't a comment, a lone dash is minus
+        assert_eq!(
+            split_statements("SELECT '--;', 2 - 1;"),
+            vec!["SELECT '--;', 2 - 1"]
+        );
+        // An unterminated string runs to the end of the script
+        assert_eq!(split_statements("SELECT 'a;b"), vec!["SELECT 'a;b"]);
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(summary("SELECT\n  1"), "SELECT 1");
+        let long = format!("INSERT INTO t VALUES {}", "(1),".repeat(40));
+        let summary = summary(&long);
+        assert_eq!(summary.chars().count(), STATEMENT_SUMMARY_CHARS + 3);
+        assert!(summary.ends_with("..."));
+    }
+}
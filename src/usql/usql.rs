@@ -1,36 +1,711 @@
 use std::{
-    io::Write,
-    process::{Command, Stdio},
+    io::{self, BufRead, BufReader, Read, Write},
+    iter,
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+use snafu::{ensure, IntoError, ResultExt};
+
+use super::{
+    error::{statement_excerpt, UsqlError},
+    output::{affected_rows_hint, OutputFormat, QueryResult},
+    script::{split_statements, summary, ScriptReport, StatementReport},
+};
+use crate::common::{
+    connection::ConnectionSpec,
+    error::{
+        Error, JoinTaskSnafu, ReadFileSnafu, Result, SpawnUsqlSnafu, UsqlExecutionSnafu,
+        UsqlNotFoundSnafu, UsqlTimeoutSnafu,
+    },
+    sql_executor::SqlExecutor,
+};
+
+/// Environment variable holding the path of usql, `usql` on the PATH by default
+pub const USQL_PATH_ENV: &str = "USQL_PATH";
+
+/// Time a statement may run before usql is killed
+pub const DEFAULT_STATEMENT_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Time usql is given to finish printing to stderr once it exited
+const EXIT_OUTPUT_WAIT: Duration = Duration::from_millis(100);
+
+/// Statements of a batch run by `Usql::execute_batch`
+#[derive(Debug)]
+pub struct BatchOutcome {
+    // One result per statement that ran, in order. A batch stops at the
+    // first statement that fails.
+    pub results: Vec<Result<String>>,
+    // One timing per result, transaction statements aren't timed
+    pub timings: Vec<StatementTiming>,
+    // The whole batch, transaction statements included
+    pub elapsed: Duration,
+}
+
+/// Time a statement of a batch took, from writing it to usql until usql
+/// printed its sentinel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementTiming {
+    // Position of the statement in the batch
+    pub statement_index: usize,
+    // Rows usql reported the statement affected, see `affected_rows_hint`
+    pub rows_hint: Option<usize>,
+    pub duration: Duration,
+}
+
+impl BatchOutcome {
+    pub fn succeeded(&self) -> bool {
+        self.results.iter().all(Result::is_ok)
+    }
+
+    /// Error of the statement that failed the batch
+    pub fn into_error(self) -> Option<Error> {
+        self.results.into_iter().find_map(Result::err)
+    }
+}
+
+/// A usql process running statements one after another over a single
+/// connection. Every statement is followed by `\echo` and `\warn` of a
+/// sentinel, its output is what usql prints to stdout before the sentinel and
+/// it failed when usql printed anything to stderr before the sentinel. The
+/// process is killed when the `Usql` is dropped or a statement times out.
 pub struct Usql {
-    command: Command,
+    child: Child,
+    // Closed once usql is gone
+    stdin: Option<ChildStdin>,
+    // Lines of stdout and stderr, read by threads so a statement can time out
+    // and neither pipe fills up
+    stdout: Receiver<String>,
+    stderr: Receiver<String>,
+    timeout: Duration,
+    // Format query results are printed in
+    format: OutputFormat,
+    // Statements executed so far, numbering the sentinels
+    statements: u64,
+}
+
+/// Path of usql, from `USQL_PATH_ENV` when set
+pub fn default_program() -> String {
+    std::env::var(USQL_PATH_ENV).unwrap_or_else(|_| "usql".to_string())
 }
 
 impl Usql {
-    pub fn new(command_args: &str) -> Self {
-        let mut command = Command::new("usql");
-        command.arg(command_args);
-        command.stdin(Stdio::piped());
-        command.stdout(Stdio::piped());
+    /// Print query results as CSV
+    pub fn new(command_args: &str) -> Result<Self> {
+        Self::with_format(command_args, OutputFormat::Csv)
+    }
+
+    pub fn with_format(command_args: &str, format: OutputFormat) -> Result<Self> {
+        Self::open(&default_program(), command_args, format)
+    }
+
+    /// Run usql installed at `program`
+    pub fn open(program: &str, command_args: &str, format: OutputFormat) -> Result<Self> {
+        Ok(Self::with_program(program, &[format.flag(), command_args])?.output_format(format))
+    }
+
+    /// Run usql installed at `program` connected to `spec`
+    pub fn connect(program: &str, spec: &ConnectionSpec, format: OutputFormat) -> Result<Self> {
+        Self::open(program, &spec.to_usql_dsn(), format)
+    }
+
+    /// Version of usql installed at `program`, failing with `UsqlNotFound`
+    /// when there's nothing to run so a missing install is told apart from an
+    /// unreachable database before any work is done
+    pub fn check(program: &str) -> Result<String> {
+        let output = Command::new(program)
+            .arg("--version")
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| spawn_error(program, e))?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        ensure!(
+            output.status.success(),
+            UsqlExecutionSnafu {
+                statement: "--version",
+                stderr: stderr.trim(),
+                kind: UsqlError::classify(&stderr),
+            }
+        );
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
 
-        Usql { command }
+    /// Run `program` with `args` instead of usql, for tests and custom installs
+    pub fn with_program(program: &str, args: &[&str]) -> Result<Self> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(program, e))?;
+        let stdin = child.stdin.take();
+        let stdout = read_lines(child.stdout.take().expect("stdout is piped"));
+        let stderr = read_lines(child.stderr.take().expect("stderr is piped"));
+
+        Ok(Usql {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            timeout: DEFAULT_STATEMENT_TIMEOUT,
+            format: OutputFormat::Csv,
+            statements: 0,
+        })
+    }
+
+    /// Time a statement may run, `DEFAULT_STATEMENT_TIMEOUT` by default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
     }
 
-    pub fn execute(&mut self, sql: &str) -> Result<String, String> {
-        let mut child = self.command.spawn().map_err(|e| e.to_string())?;
+    /// Format `query` parses, the one the program prints, CSV by default
+    pub fn output_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
 
+    /// Run `sql` and parse the result set of its last statement returning
+    /// rows, empty when none did
+    pub fn query(&mut self, sql: &str) -> Result<QueryResult> {
+        let output = self.execute(sql)?;
+        QueryResult::parse(self.format, &output)
+    }
+
+    /// Run `sql` and get what usql printed. Fails with what usql printed to
+    /// stderr when the statement failed or usql exited, and with
+    /// `UsqlTimeout` once usql was killed as the statement ran too long.
+    pub fn execute(&mut self, sql: &str) -> Result<String> {
+        let failed = |stderr: String| {
+            UsqlExecutionSnafu {
+                statement: statement_excerpt(sql),
+                kind: UsqlError::classify(&stderr),
+                stderr,
+            }
+            .fail()
+        };
+        let Some(stdin) = self.stdin.as_mut() else {
+            return failed("usql is not running".to_string());
+        };
+        self.statements += 1;
+        let sentinel = format!("__usql_done_{}__", self.statements);
+        // A statement left open would swallow the sentinel commands
+        let terminator = if sql.trim_end().ends_with(';') {
+            ""
+        } else {
+            ";"
+        };
+        let framed = format!(
+            "{}{}\n\\echo {}\n\\warn {}\n",
+            sql.trim_end(),
+            terminator,
+            sentinel,
+            sentinel
+        );
+        if let Err(e) = stdin
+            .write_all(framed.as_bytes())
+            .and_then(|_| stdin.flush())
         {
-            let stdin = child.stdin.as_mut().ok_or("Failed to open stdin")?;
-            stdin.write_all(sql.as_bytes()).map_err(|e| e.to_string())?;
+            return failed(self.exited(&e.to_string()));
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        let read = read_until(&self.stdout, &sentinel, deadline)
+            .and_then(|output| Ok((output, read_until(&self.stderr, &sentinel, deadline)?)));
+        let (output, errors) = match read {
+            Ok(read) => read,
+            Err(RecvTimeoutError::Timeout) => {
+                self.kill();
+                return UsqlTimeoutSnafu {
+                    statement: statement_excerpt(sql),
+                    timeout: self.timeout,
+                }
+                .fail();
+            }
+            Err(RecvTimeoutError::Disconnected) => return failed(self.exited("output closed")),
+        };
+        if !errors.is_empty() {
+            return failed(errors.join("\n"));
         }
+        Ok(output.into_iter().map(|line| line + "\n").collect())
+    }
 
-        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    /// Run `statements` in order until one fails. A transactional batch is
+    /// run between `BEGIN` and `COMMIT` and rolled back when a statement
+    /// fails, failing to begin or commit fails the batch.
+    pub fn execute_batch(
+        &mut self,
+        statements: &[String],
+        transactional: bool,
+    ) -> Result<BatchOutcome> {
+        let start = Instant::now();
+        if transactional {
+            self.execute("BEGIN")?;
+        }
+        let mut results = Vec::with_capacity(statements.len());
+        let mut timings = Vec::with_capacity(statements.len());
+        for (statement_index, statement) in statements.iter().enumerate() {
+            let statement_start = Instant::now();
+            let result = self.execute(statement);
+            timings.push(StatementTiming {
+                statement_index,
+                rows_hint: result.as_deref().ok().and_then(affected_rows_hint),
+                duration: statement_start.elapsed(),
+            });
+            let failed = result.is_err();
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        if transactional {
+            if results.last().is_some_and(Result::is_err) {
+                // The statement's error is the one reported, and nothing is
+                // left to roll back once usql is gone
+                if self.stdin.is_some() {
+                    let _ = self.execute("ROLLBACK");
+                }
+            } else {
+                self.execute("COMMIT")?;
+            }
+        }
+        Ok(BatchOutcome {
+            results,
+            timings,
+            elapsed: start.elapsed(),
+        })
+    }
 
-        if !output.status.success() {
-            return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    /// Run the statements of `script` one after another until one fails,
+    /// see `split_statements`
+    pub fn execute_script(&mut self, script: &str) -> ScriptReport {
+        let statements = split_statements(script);
+        let mut report = ScriptReport {
+            statements: Vec::with_capacity(statements.len()),
+            total: statements.len(),
+        };
+        for (index, statement) in statements.iter().enumerate() {
+            let start = Instant::now();
+            let result = self.execute(statement);
+            let failed = result.is_err();
+            report.statements.push(StatementReport {
+                index,
+                summary: summary(statement),
+                elapsed: start.elapsed(),
+                result,
+            });
+            if failed {
+                break;
+            }
         }
+        report
+    }
+
+    /// Run the statements of the SQL file at `path` like `execute_script`
+    pub fn execute_file(&mut self, path: &Path) -> Result<ScriptReport> {
+        let script = std::fs::read_to_string(path).context(ReadFileSnafu {
+            path: path.display().to_string(),
+        })?;
+        Ok(self.execute_script(&script))
+    }
+
+    // Why usql is gone, with what it printed to stderr before exiting
+    fn exited(&mut self, reason: &str) -> String {
+        self.stdin = None;
+        let status = match self.child.wait() {
+            Ok(status) => status.to_string(),
+            Err(e) => e.to_string(),
+        };
+        let stderr = iter::from_fn(|| self.stderr.recv_timeout(EXIT_OUTPUT_WAIT).ok())
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("usql is not running ({}, {}): {}", reason, status, stderr)
+    }
+
+    // Kill usql and reap it, one that already exited included
+    fn kill(&mut self) {
+        self.stdin = None;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// `Usql` as a `SqlExecutor`. usql blocks the calling thread on every
+/// statement, so statements run on tokio's blocking threads instead of a
+/// worker of the runtime.
+pub struct UsqlExecutor {
+    usql: Arc<Mutex<Usql>>,
+}
+
+impl UsqlExecutor {
+    pub fn new(usql: Usql) -> Self {
+        UsqlExecutor {
+            usql: Arc::new(Mutex::new(usql)),
+        }
+    }
+
+    // Run `f` on usql from a blocking thread
+    async fn run_blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Usql) -> Result<T> + Send + 'static,
+    {
+        let usql = Arc::clone(&self.usql);
+        tokio::task::spawn_blocking(move || f(&mut usql.lock().unwrap()))
+            .await
+            .context(JoinTaskSnafu { task: "usql" })?
+    }
+}
+
+impl SqlExecutor for UsqlExecutor {
+    fn name(&self) -> &'static str {
+        "usql"
+    }
+
+    async fn execute(&mut self, sql: &str) -> Result<String> {
+        let sql = sql.to_string();
+        self.run_blocking(move |usql| usql.execute(&sql)).await
+    }
+
+    async fn query(&mut self, sql: &str) -> Result<QueryResult> {
+        let sql = sql.to_string();
+        self.run_blocking(move |usql| usql.query(&sql)).await
+    }
+
+    async fn execute_batch(
+        &mut self,
+        statements: &[String],
+        transactional: bool,
+    ) -> Result<BatchOutcome> {
+        let statements = statements.to_vec();
+        self.run_blocking(move |usql| usql.execute_batch(&statements, transactional))
+            .await
+    }
+}
+
+impl Drop for Usql {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+// `UsqlNotFound` when there's no `program` to start
+fn spawn_error(program: &str, e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::NotFound => UsqlNotFoundSnafu { program }.build(),
+        _ => SpawnUsqlSnafu {}.into_error(e),
+    }
+}
+
+// Lines `pipe` yields, until it's closed
+fn read_lines(pipe: impl Read + Send + 'static) -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// Lines of `lines` before `sentinel`, unless it's missing by `deadline` or
+// the pipe closed first
+fn read_until(
+    lines: &Receiver<String>,
+    sentinel: &str,
+    deadline: Instant,
+) -> std::result::Result<Vec<String>, RecvTimeoutError> {
+    let mut read = Vec::new();
+    loop {
+        match lines.recv_timeout(deadline.saturating_duration_since(Instant::now()))? {
+            line if line == sentinel => return Ok(read),
+            line => read.push(line),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Usql {
+    /// A shell answering the sentinels like usql, running `on_statement` for
+    /// every other line it reads, with the line in `$line`
+    pub(crate) fn fake(on_statement: &str) -> Self {
+        let script = format!(
+            r#"while IFS= read -r line; do
+                case "$line" in
+                    '\echo '*) printf '%s\n' "${{line#* }}" ;;
+                    '\warn '*) printf '%s\n' "${{line#* }}" >&2 ;;
+                    *) {} ;;
+                esac
+            done"#,
+            on_statement
+        );
+        Usql::with_program("sh", &["-c", &script]).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::error::Error;
+
+    #[test]
+    fn test_statements_share_one_process() {
+        let mut usql = Usql::fake(r#"echo "$$ $line""#);
+        let first = usql.execute("SELECT 1").unwrap();
+        let second = usql.execute("SELECT 2;\n").unwrap();
+
+        // Terminated once, answered by the same process
+        let (pid, statement) = first.trim_end().split_once(' ').unwrap();
+        assert_eq!(statement, "SELECT 1;");
+        assert_eq!(second, format!("{} SELECT 2;\n", pid));
+    }
+
+    #[test]
+    fn test_statement_error() {
+        let mut usql = Usql::fake(
+            r#"case "$line" in
+                *fail*) echo "error: no such table" >&2 ;;
+                SELEC*) echo 'error: pq: syntax error at or near "SELEC"' >&2 ;;
+                *) echo ok ;;
+            esac"#,
+        );
+        let err = usql.execute("SELECT * FROM fail;").unwrap_err();
+        assert!(
+            matches!(&err, Error::UsqlExecution { stderr, .. } if stderr == "error: no such table"),
+            "{}",
+            err
+        );
+        assert_eq!(err.usql_error(), Some(&UsqlError::Statement));
+        let err = usql.execute("SELEC 1").unwrap_err();
+        assert_eq!(
+            err.usql_error(),
+            Some(&UsqlError::Syntax {
+                near: Some("SELEC".to_string())
+            })
+        );
+        assert!(
+            err.to_string()
+                .starts_with("Failed to execute `SELEC 1` via usql, syntax error near `SELEC`"),
+            "{}",
+            err
+        );
+        // The connection is still usable
+        assert_eq!(usql.execute("SELECT 1;").unwrap(), "ok\n");
+    }
+
+    #[test]
+    fn test_execute_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schema.sql");
+        std::fs::write(
+            &path,
+            "-- schema\nCREATE TABLE t (s STRING);\nINSERT INTO t VALUES ('a;b');\n\
+             SELECT * FROM fail;\nSELECT 1;\n",
+        )
+        .unwrap();
+        let mut usql = Usql::fake(
+            r#"case "$line" in
+                *fail*) echo "error: no such table" >&2 ;;
+                *) echo "$line" ;;
+            esac"#,
+        );
+
+        let report = usql.execute_file(&path).unwrap();
+        assert!(!report.succeeded());
+        assert_eq!(report.total, 4);
+        // Stopped at the failed statement
+        assert_eq!(report.statements.len(), 3);
+        assert_eq!(
+            report.statements[1].result.as_ref().unwrap(),
+            "INSERT INTO t VALUES ('a;b');\n"
+        );
+        assert_eq!(report.statements[2].index, 2);
+        assert_eq!(report.statements[2].summary, "SELECT * FROM fail");
+        let summary = report.to_string();
+        assert!(
+            summary.ends_with(&format!(
+                "Total: 2 of 4 statements in {:.3}s",
+                report.elapsed().as_secs_f64()
+            )),
+            "{}",
+            summary
+        );
+        assert!(summary.contains("[2] SELECT * FROM fail in"), "{}", summary);
+
+        let err = usql
+            .execute_file(&dir.path().join("missing.sql"))
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::ReadFile { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_process_exit() {
+        let mut usql = Usql::fake(r#"echo "connection lost" >&2; exit 3"#);
+        let err = usql.execute("SELECT 1;").unwrap_err();
+        assert_eq!(err.usql_error(), Some(&UsqlError::Exited));
+        let err = err.to_string();
+        assert!(err.contains("usql is not running"), "{}", err);
+        assert!(err.contains("exit status: 3"), "{}", err);
+        assert!(err.contains("connection lost"), "{}", err);
+        assert!(usql.execute("SELECT 2;").is_err());
+    }
+
+    #[test]
+    fn test_timeout_kills_usql() {
+        let mut usql = Usql::fake("sleep 30").with_timeout(Duration::from_millis(100));
+        let start = Instant::now();
+        let err = usql.execute("SELECT sleep(30);").unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(
+            matches!(&err, Error::UsqlTimeout { statement, .. } if statement == "SELECT sleep(30);"),
+            "{}",
+            err
+        );
+        // Killed and reaped
+        assert!(usql.child.try_wait().unwrap().is_some());
+        let err = usql.execute("SELECT 1;").unwrap_err().to_string();
+        assert!(err.contains("usql is not running"), "{}", err);
+    }
+
+    #[test]
+    fn test_execute_batch_framing() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdin = dir.path().join("stdin");
+        // Records every line it reads and fails statements mentioning `fail`
+        let script = format!(
+            r#"while IFS= read -r line; do
+                printf '%s\n' "$line" >> {}
+                case "$line" in
+                    '\echo '*) printf '%s\n' "${{line#* }}" ;;
+                    '\warn '*) printf '%s\n' "${{line#* }}" >&2 ;;
+                    *fail*) echo "error: rejected" >&2 ;;
+                    INSERT*) echo "${{line%;}}" ;;
+                esac
+            done"#,
+            stdin.display()
+        );
+        let mut usql = Usql::with_program("sh", &["-c", &script]).unwrap();
+        let statements = |sql: &[&str]| sql.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let outcome = usql
+            .execute_batch(&statements(&["INSERT 1;", "INSERT 2"]), true)
+            .unwrap();
+        assert!(outcome.succeeded());
+        assert_eq!(outcome.results.len(), 2);
+        // Timed apart from BEGIN and COMMIT, with the rows usql reported
+        assert_eq!(
+            outcome
+                .timings
+                .iter()
+                .map(|t| (t.statement_index, t.rows_hint))
+                .collect::<Vec<_>>(),
+            vec![(0, Some(1)), (1, Some(2))]
+        );
+        assert!(outcome.timings.iter().map(|t| t.duration).sum::<Duration>() <= outcome.elapsed);
+        let outcome = usql
+            .execute_batch(&statements(&["INSERT 3;", "fail;", "INSERT 4;"]), true)
+            .unwrap();
+        assert!(!outcome.succeeded());
+        assert_eq!(outcome.results.len(), 2);
+        assert_eq!(outcome.timings[1].rows_hint, None);
+        assert!(outcome
+            .into_error()
+            .unwrap()
+            .to_string()
+            .contains("rejected"));
+        usql.execute_batch(&statements(&["INSERT 5;"]), false)
+            .unwrap();
+
+        let framed = |n: usize, sql: &str| {
+            format!(
+                "{}\n\\echo __usql_done_{}__\n\\warn __usql_done_{}__\n",
+                sql, n, n
+            )
+        };
+        let expected = [
+            "BEGIN;",
+            "INSERT 1;",
+            "INSERT 2;",
+            "COMMIT;",
+            "BEGIN;",
+            "INSERT 3;",
+            "fail;",
+            "ROLLBACK;",
+            "INSERT 5;",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, sql)| framed(i + 1, sql))
+        .collect::<String>();
+        assert_eq!(std::fs::read_to_string(stdin).unwrap(), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let program = dir.path().join("usql");
+        std::fs::write(&program, "#!/bin/sh\necho 'usql 0.19.1'\n").unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            Usql::check(program.to_str().unwrap()).unwrap(),
+            "usql 0.19.1"
+        );
+
+        let missing = dir.path().join("missing");
+        let missing = missing.to_str().unwrap();
+        let err = Usql::check(missing).unwrap_err();
+        assert!(
+            matches!(&err, Error::UsqlNotFound { program, .. } if program == missing),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("--usql-path"), "{}", err);
+        let err = Usql::open(missing, "mysql://127.0.0.1:4002", OutputFormat::Csv)
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::UsqlNotFound { .. }), "{}", err);
+    }
+
+    #[test]
+    fn test_query() {
+        let mut usql = Usql::fake(
+            r#"case "$line" in
+                *count*) printf 'count(*)\n7\n' ;;
+                *json*) echo '[{"host":"a"}]' ;;
+            esac"#,
+        );
+        let result = usql.query("SELECT count(*) FROM cpu").unwrap();
+        assert_eq!(result.single_i64().unwrap(), 7);
+        // Statements printing nothing have no result set
+        assert!(usql.query("INSERT INTO cpu VALUES (1)").unwrap().is_empty());
+
+        let mut usql = usql.output_format(OutputFormat::Json);
+        let result = usql.query("SELECT 'json'").unwrap();
+        assert_eq!(result.columns, vec!["host".to_string()]);
+        assert_eq!(result.rows, vec![vec!["a".to_string()]]);
+    }
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_drop_kills_usql() {
+        let usql = Usql::fake("sleep 30");
+        let pid = usql.child.id();
+        drop(usql);
+        // Reaped, so no process or zombie is left behind
+        assert!(!std::path::Path::new(&format!("/proc/{}", pid)).exists());
     }
 }